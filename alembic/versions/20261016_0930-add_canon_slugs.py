@@ -0,0 +1,106 @@
+"""add_canon_slugs
+
+Revision ID: b5e1f0c2d4a7
+Revises: 3de32bb99a71
+Create Date: 2026-10-16 09:30:12.118402
+
+"""
+
+from collections.abc import Sequence
+
+import sqlalchemy as sa
+from alembic import op
+from sqlalchemy.dialects.postgresql import UUID
+
+# revision identifiers, used by Alembic.
+revision: str = "b5e1f0c2d4a7"
+down_revision: str | None = "3de32bb99a71"
+branch_labels: str | Sequence[str] | None = None
+depends_on: str | Sequence[str] | None = None
+
+
+def upgrade() -> None:
+    op.create_table(
+        "canon_slugs",
+        sa.Column("slug", sa.String(), nullable=False),
+        sa.Column("canon_id", UUID(as_uuid=True), nullable=False),
+        sa.Column(
+            "created_at", sa.DateTime(), server_default=sa.text("now()"), nullable=False
+        ),
+        sa.ForeignKeyConstraint(
+            ["canon_id"],
+            ["canons.id"],
+            name=op.f("fk_canon_slugs_canon_id_canons"),
+            ondelete="CASCADE",
+        ),
+        sa.PrimaryKeyConstraint("slug", name=op.f("pk_canon_slugs")),
+        sa.UniqueConstraint("canon_id", name=op.f("uq_canon_slugs_canon_id")),
+    )
+
+    # Slugs are derived from the canon name once and never rewritten, so public
+    # URLs stay stable; name collisions get a numeric suffix in creation order.
+    # NOTE: this was added manually to this script (not auto-generated)
+    op.execute(
+        """
+        CREATE FUNCTION assign_canon_slug(p_canon_id uuid, p_name text)
+        RETURNS text AS $$
+        DECLARE
+            base text;
+            candidate text;
+            n int := 1;
+        BEGIN
+            SELECT slug INTO candidate FROM canon_slugs WHERE canon_id = p_canon_id;
+            IF FOUND THEN
+                RETURN candidate;
+            END IF;
+
+            base := trim(BOTH '-' FROM regexp_replace(lower(p_name), '[^a-z0-9]+', '-', 'g'));
+            IF base = '' THEN
+                base := 'project';
+            END IF;
+
+            candidate := base;
+            WHILE EXISTS (SELECT 1 FROM canon_slugs WHERE slug = candidate) LOOP
+                n := n + 1;
+                candidate := base || '-' || n;
+            END LOOP;
+
+            INSERT INTO canon_slugs (slug, canon_id) VALUES (candidate, p_canon_id);
+            RETURN candidate;
+        END;
+        $$ LANGUAGE plpgsql;
+        """
+    )
+    op.execute(
+        """
+        CREATE FUNCTION canons_assign_slug() RETURNS trigger AS $$
+        BEGIN
+            PERFORM assign_canon_slug(NEW.id, NEW.name);
+            RETURN NEW;
+        END;
+        $$ LANGUAGE plpgsql;
+        """
+    )
+    op.execute(
+        """
+        CREATE TRIGGER trg_canons_assign_slug
+        AFTER INSERT ON canons
+        FOR EACH ROW EXECUTE FUNCTION canons_assign_slug();
+        """
+    )
+
+    # Backfill existing canons, oldest first so they keep the unsuffixed slug
+    op.execute(
+        """
+        SELECT assign_canon_slug(id, name)
+        FROM canons
+        ORDER BY created_at, id;
+        """
+    )
+
+
+def downgrade() -> None:
+    op.execute("DROP TRIGGER IF EXISTS trg_canons_assign_slug ON canons;")
+    op.execute("DROP FUNCTION IF EXISTS canons_assign_slug();")
+    op.execute("DROP FUNCTION IF EXISTS assign_canon_slug(uuid, text);")
+    op.drop_table("canon_slugs")
@@ -0,0 +1,50 @@
+"""add_canon_redirects
+
+Revision ID: 4c9a2e7d1b3f
+Revises: b5e1f0c2d4a7
+Create Date: 2026-10-16 10:15:47.602913
+
+"""
+
+from collections.abc import Sequence
+
+import sqlalchemy as sa
+from alembic import op
+from sqlalchemy.dialects.postgresql import UUID
+
+# revision identifiers, used by Alembic.
+revision: str = "4c9a2e7d1b3f"
+down_revision: str | None = "b5e1f0c2d4a7"
+branch_labels: str | Sequence[str] | None = None
+depends_on: str | Sequence[str] | None = None
+
+
+def upgrade() -> None:
+    # old_canon_id has no FK: the canon it names has been merged away. canon_id
+    # always points at a live canon, so when a surviving canon is itself merged the
+    # pipeline repoints its redirects before deleting it and chains never form.
+    op.create_table(
+        "canon_redirects",
+        sa.Column("old_canon_id", UUID(as_uuid=True), nullable=False),
+        sa.Column("canon_id", UUID(as_uuid=True), nullable=False),
+        sa.Column(
+            "created_at", sa.DateTime(), server_default=sa.text("now()"), nullable=False
+        ),
+        sa.ForeignKeyConstraint(
+            ["canon_id"],
+            ["canons.id"],
+            name=op.f("fk_canon_redirects_canon_id_canons"),
+        ),
+        sa.PrimaryKeyConstraint("old_canon_id", name=op.f("pk_canon_redirects")),
+    )
+    op.create_index(
+        op.f("ix_canon_redirects_canon_id"),
+        "canon_redirects",
+        ["canon_id"],
+        unique=False,
+    )
+
+
+def downgrade() -> None:
+    op.drop_index(op.f("ix_canon_redirects_canon_id"), table_name="canon_redirects")
+    op.drop_table("canon_redirects")
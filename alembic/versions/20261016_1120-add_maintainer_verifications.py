@@ -0,0 +1,62 @@
+"""add_maintainer_verifications
+
+Revision ID: 9d3b6f81ac20
+Revises: 4c9a2e7d1b3f
+Create Date: 2026-10-16 11:20:05.331877
+
+"""
+
+from collections.abc import Sequence
+
+import sqlalchemy as sa
+from alembic import op
+from sqlalchemy.dialects.postgresql import UUID
+
+# revision identifiers, used by Alembic.
+revision: str = "9d3b6f81ac20"
+down_revision: str | None = "4c9a2e7d1b3f"
+branch_labels: str | Sequence[str] | None = None
+depends_on: str | Sequence[str] | None = None
+
+
+def upgrade() -> None:
+    op.create_table(
+        "maintainer_verifications",
+        sa.Column(
+            "id",
+            UUID(as_uuid=True),
+            server_default=sa.text("uuid_generate_v4()"),
+            nullable=False,
+        ),
+        sa.Column("canon_id", UUID(as_uuid=True), nullable=False),
+        sa.Column("maintainer", sa.String(), nullable=False),
+        sa.Column("token", sa.String(), nullable=False),
+        sa.Column("verified_at", sa.DateTime(), nullable=True),
+        sa.Column("last_checked_at", sa.DateTime(), nullable=True),
+        sa.Column(
+            "created_at", sa.DateTime(), server_default=sa.text("now()"), nullable=False
+        ),
+        sa.ForeignKeyConstraint(
+            ["canon_id"],
+            ["canons.id"],
+            name=op.f("fk_maintainer_verifications_canon_id_canons"),
+            ondelete="CASCADE",
+        ),
+        sa.PrimaryKeyConstraint("id", name=op.f("pk_maintainer_verifications")),
+        sa.UniqueConstraint("canon_id", "maintainer", name="uq_canon_maintainer"),
+        sa.UniqueConstraint("token", name=op.f("uq_maintainer_verifications_token")),
+    )
+    op.create_index(
+        op.f("ix_maintainer_verifications_maintainer"),
+        "maintainer_verifications",
+        ["maintainer"],
+        unique=False,
+    )
+
+
+def downgrade() -> None:
+    op.drop_index(
+        op.f("ix_maintainer_verifications_maintainer"),
+        table_name="maintainer_verifications",
+    )
+    op.drop_table("maintainer_verifications")
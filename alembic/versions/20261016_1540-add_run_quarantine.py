@@ -0,0 +1,28 @@
+"""add_run_quarantine
+
+Revision ID: 6e1f0c4b7a92
+Revises: 9d3b6f81ac20
+Create Date: 2026-10-16 15:40:12.418806
+
+"""
+
+from collections.abc import Sequence
+
+import sqlalchemy as sa
+from alembic import op
+
+# revision identifiers, used by Alembic.
+revision: str = "6e1f0c4b7a92"
+down_revision: str | None = "9d3b6f81ac20"
+branch_labels: str | Sequence[str] | None = None
+depends_on: str | Sequence[str] | None = None
+
+
+def upgrade() -> None:
+    op.add_column(
+        "tea_rank_runs", sa.Column("quarantined_at", sa.DateTime(), nullable=True)
+    )
+
+
+def downgrade() -> None:
+    op.drop_column("tea_rank_runs", "quarantined_at")
@@ -0,0 +1,45 @@
+"""add_leaderboard_overrides
+
+Revision ID: b47d2e9c0f15
+Revises: 6e1f0c4b7a92
+Create Date: 2026-10-16 16:30:41.702315
+
+"""
+
+from collections.abc import Sequence
+
+import sqlalchemy as sa
+from alembic import op
+from sqlalchemy.dialects.postgresql import UUID
+
+# revision identifiers, used by Alembic.
+revision: str = "b47d2e9c0f15"
+down_revision: str | None = "6e1f0c4b7a92"
+branch_labels: str | Sequence[str] | None = None
+depends_on: str | Sequence[str] | None = None
+
+
+def upgrade() -> None:
+    # One override per canon: excluded when pinned_rank is null, else shown
+    # with pinned_rank, until expires_at (never when null)
+    op.create_table(
+        "leaderboard_overrides",
+        sa.Column("canon_id", UUID(as_uuid=True), nullable=False),
+        sa.Column("pinned_rank", sa.String(), nullable=True),
+        sa.Column("reason", sa.String(), nullable=False),
+        sa.Column("expires_at", sa.DateTime(), nullable=True),
+        sa.Column(
+            "created_at", sa.DateTime(), server_default=sa.text("now()"), nullable=False
+        ),
+        sa.ForeignKeyConstraint(
+            ["canon_id"],
+            ["canons.id"],
+            name=op.f("fk_leaderboard_overrides_canon_id_canons"),
+            ondelete="CASCADE",
+        ),
+        sa.PrimaryKeyConstraint("canon_id", name=op.f("pk_leaderboard_overrides")),
+    )
+
+
+def downgrade() -> None:
+    op.drop_table("leaderboard_overrides")
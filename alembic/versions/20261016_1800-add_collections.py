@@ -0,0 +1,48 @@
+"""add_collections
+
+Revision ID: 3c8e5f1a9d27
+Revises: b47d2e9c0f15
+Create Date: 2026-10-16 18:00:12.418903
+
+"""
+
+from collections.abc import Sequence
+
+import sqlalchemy as sa
+from alembic import op
+from sqlalchemy.dialects.postgresql import ARRAY, UUID
+
+# revision identifiers, used by Alembic.
+revision: str = "3c8e5f1a9d27"
+down_revision: str | None = "b47d2e9c0f15"
+branch_labels: str | Sequence[str] | None = None
+depends_on: str | Sequence[str] | None = None
+
+
+def upgrade() -> None:
+    # Named lists of canons kept by an API key (owner is the key's name), so
+    # callers can refer to them instead of resending the ids
+    op.create_table(
+        "collections",
+        sa.Column(
+            "id",
+            UUID(as_uuid=True),
+            server_default=sa.text("uuid_generate_v4()"),
+            nullable=False,
+        ),
+        sa.Column("owner", sa.String(), nullable=False),
+        sa.Column("name", sa.String(), nullable=False),
+        sa.Column("canon_ids", ARRAY(UUID(as_uuid=True)), nullable=False),
+        sa.Column(
+            "created_at", sa.DateTime(), server_default=sa.text("now()"), nullable=False
+        ),
+        sa.Column(
+            "updated_at", sa.DateTime(), server_default=sa.text("now()"), nullable=False
+        ),
+        sa.PrimaryKeyConstraint("id", name=op.f("pk_collections")),
+        sa.UniqueConstraint("owner", "name", name="uq_collection_owner_name"),
+    )
+
+
+def downgrade() -> None:
+    op.drop_table("collections")
@@ -0,0 +1,27 @@
+"""add_collection_webhooks
+
+Revision ID: 8d2f6a4c1e93
+Revises: 3c8e5f1a9d27
+Create Date: 2026-10-16 19:00:41.206517
+
+"""
+
+from collections.abc import Sequence
+
+import sqlalchemy as sa
+from alembic import op
+
+# revision identifiers, used by Alembic.
+revision: str = "8d2f6a4c1e93"
+down_revision: str | None = "3c8e5f1a9d27"
+branch_labels: str | Sequence[str] | None = None
+depends_on: str | Sequence[str] | None = None
+
+
+def upgrade() -> None:
+    # Where the collection's rank changes are posted after each run, if anywhere
+    op.add_column("collections", sa.Column("webhook_url", sa.String(), nullable=True))
+
+
+def downgrade() -> None:
+    op.drop_column("collections", "webhook_url")
@@ -0,0 +1,41 @@
+"""add_collection_webhook_secrets
+
+Revision ID: 5e1b9c7d3a48
+Revises: 8d2f6a4c1e93
+Create Date: 2026-10-16 20:00:27.553104
+
+"""
+
+from collections.abc import Sequence
+
+import sqlalchemy as sa
+from alembic import op
+
+# revision identifiers, used by Alembic.
+revision: str = "5e1b9c7d3a48"
+down_revision: str | None = "8d2f6a4c1e93"
+branch_labels: str | Sequence[str] | None = None
+depends_on: str | Sequence[str] | None = None
+
+
+def upgrade() -> None:
+    # Digests are signed with webhook_secret, which is only set once the
+    # webhook has echoed a challenge. After a rotation the previous secret
+    # keeps signing alongside it for a while, from webhook_secret_rotated_at.
+    op.add_column(
+        "collections", sa.Column("webhook_secret", sa.String(), nullable=True)
+    )
+    op.add_column(
+        "collections",
+        sa.Column("webhook_previous_secret", sa.String(), nullable=True),
+    )
+    op.add_column(
+        "collections",
+        sa.Column("webhook_secret_rotated_at", sa.DateTime(), nullable=True),
+    )
+
+
+def downgrade() -> None:
+    op.drop_column("collections", "webhook_secret_rotated_at")
+    op.drop_column("collections", "webhook_previous_secret")
+    op.drop_column("collections", "webhook_secret")
@@ -0,0 +1,50 @@
+"""add_latest_ranks
+
+Revision ID: 2a7c4e9f1b60
+Revises: 5e1b9c7d3a48
+Create Date: 2026-10-16 21:00:12.184620
+
+"""
+
+from collections.abc import Sequence
+
+import sqlalchemy as sa
+from alembic import op
+from sqlalchemy.dialects.postgresql import UUID
+
+# revision identifiers, used by Alembic.
+revision: str = "2a7c4e9f1b60"
+down_revision: str | None = "5e1b9c7d3a48"
+branch_labels: str | Sequence[str] | None = None
+depends_on: str | Sequence[str] | None = None
+
+
+def upgrade() -> None:
+    # Each canon's newest rank outside quarantined runs, and its newest
+    # positive one (null when it never had one). Rebuilt by the API whenever
+    # the latest run changes, so reads of current ranks skip tea_ranks.
+    op.create_table(
+        "latest_ranks",
+        sa.Column("canon_id", UUID(as_uuid=True), nullable=False),
+        sa.Column("tea_rank_run", sa.Integer(), nullable=False),
+        sa.Column("rank", sa.String(), nullable=False),
+        sa.Column("created_at", sa.DateTime(), nullable=False),
+        sa.Column("positive_tea_rank_run", sa.Integer(), nullable=True),
+        sa.Column("positive_rank", sa.String(), nullable=True),
+        sa.Column("positive_created_at", sa.DateTime(), nullable=True),
+        sa.ForeignKeyConstraint(
+            ["canon_id"],
+            ["canons.id"],
+            name=op.f("fk_latest_ranks_canon_id_canons"),
+            ondelete="CASCADE",
+        ),
+        sa.PrimaryKeyConstraint("canon_id", name=op.f("pk_latest_ranks")),
+    )
+    op.create_index(
+        op.f("ix_latest_ranks_tea_rank_run"), "latest_ranks", ["tea_rank_run"]
+    )
+
+
+def downgrade() -> None:
+    op.drop_index(op.f("ix_latest_ranks_tea_rank_run"), table_name="latest_ranks")
+    op.drop_table("latest_ranks")
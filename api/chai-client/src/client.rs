@@ -0,0 +1,860 @@
+use chai_types::fields::FieldsParams;
+use chai_types::filter::FilterParams;
+use chai_types::format::Timestamp;
+use chai_types::jobs::{Job, JobRequest};
+use chai_types::models::{
+    Collection, CollectionChanges, CollectionWebhook, CompactProject, CrossEcosystem,
+    DependencyConfusion, DependencyTree, ExportEntry, GraphStats, LeaderboardEntry,
+    LeaderboardOverride, Limits, MaintainerConcentration, MaintainerProjects, NewEntrants,
+    OrgLeaderboard, OrgPortfolio, Percentiles, Project, ProjectDependents, ProjectFunding,
+    ProjectMetrics, RankContributors, RankTrend, RunComparison, SearchResults, SimilarNames,
+    SourceHosts, StaleProjects, Status, Suspects, TableList, TablePage, TopDegree,
+    VerificationStatus, VerificationToken, Version,
+};
+use chai_types::requests::ProjectView;
+use chai_types::requests::{
+    AsOfParams, CheckNameParams, CollectionChangesParams, CollectionLeaderboardParams,
+    CollectionRequest, CollectionWebhookRequest, CompareParams, ContributorParams,
+    CrossEcosystemParams, CutoverRequest, DependencyConfusionRequest, DependencyTreeParams,
+    ExportParams, Format, InvalidateRequest, LeaderboardRequest, NewEntrantsParams,
+    OrgLeaderboardParams, OverrideRequest, PaginationParams, PercentileParams, PrecisionParams,
+    ProjectBatchRequest, ProjectExistsRequest, ProjectParams, RunWindowParams, SoftDeleteParams,
+    StaleParams, SuspectParams, TopDegreeParams,
+};
+use chai_types::sort::SortParams;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Method, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use url::Url;
+use uuid::Uuid;
+
+use crate::error::Error;
+
+const API_KEY_HEADER: &str = "X-API-Key";
+
+/// A CHAI API deployment. Cloning is cheap and shares the connection pool.
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base: Url,
+    api_key: Option<String>,
+}
+
+impl Client {
+    /// A client for the API at `base`, e.g. `https://api.chai.dev`. A path in
+    /// `base` is kept as a prefix of every endpoint.
+    pub fn new(base: &str) -> Result<Self, Error> {
+        let base = Url::parse(base).map_err(|e| Error::Url(e.to_string()))?;
+        if base.cannot_be_a_base() {
+            return Err(Error::Url(format!("{base} can't have a path")));
+        }
+        Ok(Self {
+            http: reqwest::Client::new(),
+            base,
+            api_key: None,
+        })
+    }
+
+    /// Sends `key` with every request, for keyed quotas and tiers or, with the
+    /// admin key, the admin endpoints
+    pub fn with_api_key(mut self, key: impl Into<String>) -> Self {
+        self.api_key = Some(key.into());
+        self
+    }
+
+    /// Uses `http` for requests, e.g. one with timeouts or a proxy set
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// The endpoint at `segments` under the base URL, percent-encoding each one
+    fn url(&self, segments: &[&str]) -> Url {
+        let mut url = self.base.clone();
+        url.path_segments_mut()
+            .expect("checked in Client::new")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    fn request(&self, method: Method, segments: &[&str]) -> RequestBuilder {
+        let request = self.http.request(method, self.url(segments));
+        match &self.api_key {
+            Some(key) => request.header(API_KEY_HEADER, key),
+            None => request,
+        }
+    }
+
+    /// A POST without a body still has to say it is JSON
+    fn post_empty(&self, segments: &[&str]) -> RequestBuilder {
+        self.request(Method::POST, segments)
+            .header(CONTENT_TYPE, "application/json")
+    }
+
+    async fn check(request: RequestBuilder) -> Result<Response, Error> {
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let body = response.text().await.unwrap_or_default();
+        Err(Error::from_body(status.as_u16(), &body))
+    }
+
+    async fn send<T: DeserializeOwned>(request: RequestBuilder) -> Result<T, Error> {
+        Ok(Self::check(request).await?.json().await?)
+    }
+
+    async fn send_text(request: RequestBuilder) -> Result<String, Error> {
+        Ok(Self::check(request).await?.text().await?)
+    }
+
+    // HEALTH
+
+    /// `GET /heartbeat`: succeeds while the API can reach its database
+    pub async fn heartbeat(&self) -> Result<String, Error> {
+        Self::send_text(self.request(Method::GET, &["heartbeat"])).await
+    }
+
+    /// `GET /status`: the API's health and how current its data is. A red
+    /// status is answered with `503`, so it comes back as an error.
+    pub async fn status(&self) -> Result<Status, Error> {
+        Self::send(self.request(Method::GET, &["status"])).await
+    }
+
+    pub async fn version(&self) -> Result<Version, Error> {
+        Self::send(self.request(Method::GET, &["version"])).await
+    }
+
+    /// The caller's own rate limit, without counting against it
+    pub async fn limits(&self) -> Result<Limits, Error> {
+        Self::send(self.request(Method::GET, &["limits"])).await
+    }
+
+    // TABLES
+
+    pub async fn list_tables(&self, pagination: &PaginationParams) -> Result<TableList, Error> {
+        Self::send(self.request(Method::GET, &["tables"]).query(pagination)).await
+    }
+
+    pub async fn get_table(
+        &self,
+        table: &str,
+        pagination: &PaginationParams,
+        sort: &SortParams,
+        filters: &FilterParams,
+        deleted: &SoftDeleteParams,
+    ) -> Result<TablePage, Error> {
+        let request = self
+            .request(Method::GET, &["tables", table])
+            .query(pagination)
+            .query(sort)
+            .query(filters)
+            .query(deleted);
+        Self::send(request).await
+    }
+
+    /// A row as an object keyed by column
+    pub async fn get_table_row(
+        &self,
+        table: &str,
+        id: Uuid,
+        deleted: &SoftDeleteParams,
+    ) -> Result<Value, Error> {
+        let id = id.to_string();
+        Self::send(
+            self.request(Method::GET, &["tables", table, &id])
+                .query(deleted),
+        )
+        .await
+    }
+
+    // LEADERBOARD AND RANKS
+
+    pub async fn leaderboard(
+        &self,
+        body: &LeaderboardRequest,
+        precision: &PrecisionParams,
+        sort: &SortParams,
+    ) -> Result<Vec<LeaderboardEntry>, Error> {
+        self.leaderboard_as_of(body, &AsOfParams::default(), precision, sort)
+            .await
+    }
+
+    /// [`Client::leaderboard`] with the ranks of an earlier run
+    pub async fn leaderboard_as_of(
+        &self,
+        body: &LeaderboardRequest,
+        as_of: &AsOfParams,
+        precision: &PrecisionParams,
+        sort: &SortParams,
+    ) -> Result<Vec<LeaderboardEntry>, Error> {
+        let request = self
+            .request(Method::POST, &["leaderboard"])
+            .query(as_of)
+            .query(precision)
+            .query(sort)
+            .json(body);
+        Self::send(request).await
+    }
+
+    /// [`Client::leaderboard`] with only `fields` of each entry
+    pub async fn leaderboard_fields(
+        &self,
+        body: &LeaderboardRequest,
+        fields: &FieldsParams,
+        precision: &PrecisionParams,
+        sort: &SortParams,
+    ) -> Result<Vec<Value>, Error> {
+        let request = self
+            .request(Method::POST, &["leaderboard"])
+            .query(fields)
+            .query(precision)
+            .query(sort)
+            .json(body);
+        Self::send(request).await
+    }
+
+    pub async fn new_entrants(
+        &self,
+        params: &NewEntrantsParams,
+        sort: &SortParams,
+    ) -> Result<NewEntrants, Error> {
+        let request = self
+            .request(Method::GET, &["leaderboard", "new"])
+            .query(params)
+            .query(sort);
+        Self::send(request).await
+    }
+
+    pub async fn rank_percentiles(&self, params: &PercentileParams) -> Result<Percentiles, Error> {
+        Self::send(
+            self.request(Method::GET, &["ranks", "percentiles"])
+                .query(params),
+        )
+        .await
+    }
+
+    pub async fn compare_runs(&self, params: &CompareParams) -> Result<RunComparison, Error> {
+        Self::send(
+            self.request(Method::GET, &["ranks", "compare"])
+                .query(params),
+        )
+        .await
+    }
+
+    /// Projects by the host of their source URL
+    pub async fn source_hosts(&self) -> Result<SourceHosts, Error> {
+        Self::send(self.request(Method::GET, &["stats", "source-hosts"])).await
+    }
+
+    /// Ranked projects without a release in `params.months`, a page at a time
+    pub async fn stale_report(
+        &self,
+        params: &StaleParams,
+        pagination: &PaginationParams,
+    ) -> Result<StaleProjects, Error> {
+        Self::send(
+            self.request(Method::GET, &["reports", "stale"])
+                .query(params)
+                .query(pagination),
+        )
+        .await
+    }
+
+    /// The projects with a source under `host/org`, e.g. `github.com` and
+    /// `tokio-rs`, a page at a time
+    pub async fn org(
+        &self,
+        host: &str,
+        org: &str,
+        pagination: &PaginationParams,
+    ) -> Result<OrgPortfolio, Error> {
+        let mut segments = vec!["org", host];
+        segments.extend(org.split('/'));
+        Self::send(self.request(Method::GET, &segments).query(pagination)).await
+    }
+
+    /// Organizations by the summed or best rank of their projects
+    pub async fn org_leaderboard(
+        &self,
+        params: &OrgLeaderboardParams,
+    ) -> Result<OrgLeaderboard, Error> {
+        Self::send(
+            self.request(Method::GET, &["leaderboard", "orgs"])
+                .query(params),
+        )
+        .await
+    }
+
+    /// The projects most depended on directly
+    pub async fn top_dependents(&self, params: &TopDegreeParams) -> Result<TopDegree, Error> {
+        Self::send(
+            self.request(Method::GET, &["stats", "top-dependents"])
+                .query(params),
+        )
+        .await
+    }
+
+    /// The projects with the most direct dependencies
+    pub async fn top_dependencies(&self, params: &TopDegreeParams) -> Result<TopDegree, Error> {
+        Self::send(
+            self.request(Method::GET, &["stats", "top-dependencies"])
+                .query(params),
+        )
+        .await
+    }
+
+    /// Dependency graph statistics as the server last computed them. Fails
+    /// with a 503 until the first computation after startup is done.
+    pub async fn graph_stats(&self) -> Result<GraphStats, Error> {
+        Self::send(self.request(Method::GET, &["stats", "graph"])).await
+    }
+
+    /// Projects in several package managers, by combination
+    pub async fn cross_ecosystem(
+        &self,
+        params: &CrossEcosystemParams,
+    ) -> Result<CrossEcosystem, Error> {
+        Self::send(
+            self.request(Method::GET, &["stats", "cross-ecosystem"])
+                .query(params),
+        )
+        .await
+    }
+
+    // PROJECTS
+
+    /// A project with everything the API knows about it. Merged projects
+    /// resolve to their survivor.
+    pub async fn project(&self, id: Uuid, precision: &PrecisionParams) -> Result<Project, Error> {
+        let id = id.to_string();
+        let params = full_view(precision);
+        Self::send(self.request(Method::GET, &["project", &id]).query(&params)).await
+    }
+
+    pub async fn project_by_slug(
+        &self,
+        slug: &str,
+        precision: &PrecisionParams,
+    ) -> Result<Project, Error> {
+        let params = full_view(precision);
+        Self::send(
+            self.request(Method::GET, &["project", "slug", slug])
+                .query(&params),
+        )
+        .await
+    }
+
+    /// Projects by id; ids of merged projects come back as their survivor,
+    /// marked with `redirected_from`
+    pub async fn projects(
+        &self,
+        ids: &[Uuid],
+        precision: &PrecisionParams,
+    ) -> Result<Vec<Project>, Error> {
+        self.project_batch(ids, None, &full_view(precision)).await
+    }
+
+    /// The projects of `since` whose rank was calculated after the
+    /// `tea_rank_calculated_at` given for them, for keeping a copy in sync
+    pub async fn modified_projects(
+        &self,
+        since: &BTreeMap<Uuid, Timestamp>,
+        precision: &PrecisionParams,
+    ) -> Result<Vec<Project>, Error> {
+        let ids: Vec<Uuid> = since.keys().copied().collect();
+        self.project_batch(&ids, Some(since), &full_view(precision))
+            .await
+    }
+
+    /// Like [`Client::projects`], with only `fields` of each; the aggregates
+    /// left out aren't computed
+    pub async fn project_fields(
+        &self,
+        ids: &[Uuid],
+        fields: &FieldsParams,
+        precision: &PrecisionParams,
+    ) -> Result<Vec<Value>, Error> {
+        let body = ProjectBatchRequest {
+            project_ids: ids.to_vec(),
+            if_modified_since: None,
+        };
+        let request = self
+            .request(Method::POST, &["project", "batch"])
+            .query(&full_view(precision))
+            .query(fields)
+            .json(&body);
+        Self::send(request).await
+    }
+
+    /// Like [`Client::projects`], with only ids, names, and ranks
+    pub async fn compact_projects(
+        &self,
+        ids: &[Uuid],
+        precision: &PrecisionParams,
+    ) -> Result<Vec<CompactProject>, Error> {
+        let params = ProjectParams {
+            precision: precision.precision,
+            view: ProjectView::Compact,
+            ..ProjectParams::default()
+        };
+        self.project_batch(ids, None, &params).await
+    }
+
+    async fn project_batch<T: DeserializeOwned>(
+        &self,
+        ids: &[Uuid],
+        since: Option<&BTreeMap<Uuid, Timestamp>>,
+        params: &ProjectParams,
+    ) -> Result<Vec<T>, Error> {
+        let body = ProjectBatchRequest {
+            project_ids: ids.to_vec(),
+            if_modified_since: since.cloned(),
+        };
+        let request = self
+            .request(Method::POST, &["project", "batch"])
+            .query(params)
+            .json(&body);
+        Self::send(request).await
+    }
+
+    /// Whether each of `ids` is a live canon, without fetching any of them
+    pub async fn projects_exist(&self, ids: &[Uuid]) -> Result<BTreeMap<Uuid, bool>, Error> {
+        let body = ProjectExistsRequest {
+            project_ids: ids.to_vec(),
+        };
+        Self::send(
+            self.request(Method::POST, &["project", "exists"])
+                .json(&body),
+        )
+        .await
+    }
+
+    /// The first page of projects whose name contains `name`
+    pub async fn search_projects(&self, name: &str) -> Result<SearchResults, Error> {
+        Self::send(self.request(Method::GET, &["project", "search", name])).await
+    }
+
+    /// Projects named like project `id`, at most `limit` of them
+    pub async fn similar_names(&self, id: Uuid, limit: Option<i64>) -> Result<SimilarNames, Error> {
+        let id = id.to_string();
+        Self::send(
+            self.request(Method::GET, &["project", &id, "similar-names"])
+                .query(&PaginationParams {
+                    limit,
+                    ..Default::default()
+                }),
+        )
+        .await
+    }
+
+    /// Projects named like `params.name`
+    pub async fn check_name(&self, params: &CheckNameParams) -> Result<SimilarNames, Error> {
+        Self::send(self.request(Method::GET, &["check-name"]).query(params)).await
+    }
+
+    /// Which internal package names a public registry already has
+    pub async fn dependency_confusion(
+        &self,
+        body: &DependencyConfusionRequest,
+    ) -> Result<DependencyConfusion, Error> {
+        Self::send(
+            self.request(Method::POST, &["analyze", "dependency-confusion"])
+                .json(body),
+        )
+        .await
+    }
+
+    pub async fn project_trend(
+        &self,
+        id: Uuid,
+        params: &RunWindowParams,
+    ) -> Result<RankTrend, Error> {
+        let id = id.to_string();
+        Self::send(
+            self.request(Method::GET, &["project", &id, "trend"])
+                .query(params),
+        )
+        .await
+    }
+
+    pub async fn project_metrics(
+        &self,
+        id: Uuid,
+        params: &RunWindowParams,
+    ) -> Result<ProjectMetrics, Error> {
+        let id = id.to_string();
+        Self::send(
+            self.request(Method::GET, &["project", &id, "metrics"])
+                .query(params),
+        )
+        .await
+    }
+
+    pub async fn rank_contributors(
+        &self,
+        id: Uuid,
+        params: &ContributorParams,
+    ) -> Result<RankContributors, Error> {
+        let id = id.to_string();
+        Self::send(
+            self.request(Method::GET, &["project", &id, "rank-contributors"])
+                .query(params),
+        )
+        .await
+    }
+
+    /// How a project's packages are spread over maintainers
+    pub async fn maintainer_concentration(
+        &self,
+        id: Uuid,
+    ) -> Result<MaintainerConcentration, Error> {
+        let id = id.to_string();
+        Self::send(self.request(Method::GET, &["project", &id, "maintainer-concentration"])).await
+    }
+
+    /// The projects a project depends on, nested `depth` levels deep
+    pub async fn dependency_tree(
+        &self,
+        id: Uuid,
+        depth: Option<i32>,
+    ) -> Result<DependencyTree, Error> {
+        let id = id.to_string();
+        Self::send(
+            self.request(Method::GET, &["project", &id, "dependencies"])
+                .query(&DependencyTreeParams { depth }),
+        )
+        .await
+    }
+
+    /// The projects depending directly on a project, best ranked first
+    pub async fn dependents(
+        &self,
+        id: Uuid,
+        pagination: &PaginationParams,
+    ) -> Result<ProjectDependents, Error> {
+        let id = id.to_string();
+        Self::send(
+            self.request(Method::GET, &["project", &id, "dependents"])
+                .query(pagination),
+        )
+        .await
+    }
+
+    /// Only served when the API runs with the `funding` feature
+    pub async fn project_funding(&self, id: Uuid) -> Result<ProjectFunding, Error> {
+        let id = id.to_string();
+        Self::send(self.request(Method::GET, &["project", &id, "funding"])).await
+    }
+
+    // EXPORTS
+
+    /// A run's full leaderboard
+    pub async fn export_leaderboard(&self, run: i32) -> Result<Vec<ExportEntry>, Error> {
+        let run = run.to_string();
+        Self::send(self.export_request(&run, Format::Json)).await
+    }
+
+    /// [`Client::export_leaderboard`] as CSV, with a header row
+    pub async fn export_leaderboard_csv(&self, run: i32) -> Result<String, Error> {
+        let run = run.to_string();
+        Self::send_text(self.export_request(&run, Format::Csv)).await
+    }
+
+    fn export_request(&self, run: &str, format: Format) -> RequestBuilder {
+        self.request(Method::GET, &["export", "leaderboard", run])
+            .query(&ExportParams { format })
+    }
+
+    // JOBS
+
+    /// Queues a job; poll [`Client::job`] until its status is finished
+    pub async fn submit_job(&self, job: &JobRequest) -> Result<Job, Error> {
+        Self::send(self.request(Method::POST, &["jobs"]).json(job)).await
+    }
+
+    pub async fn job(&self, id: Uuid) -> Result<Job, Error> {
+        let id = id.to_string();
+        Self::send(self.request(Method::GET, &["jobs", &id])).await
+    }
+
+    /// Cancels a job if it hasn't finished, and forgets it either way
+    pub async fn delete_job(&self, id: Uuid) -> Result<(), Error> {
+        let id = id.to_string();
+        Self::check(self.request(Method::DELETE, &["jobs", &id])).await?;
+        Ok(())
+    }
+
+    // COLLECTIONS
+
+    /// Saves a named list of projects for the client's API key
+    pub async fn create_collection(&self, body: &CollectionRequest) -> Result<Collection, Error> {
+        Self::send(self.request(Method::POST, &["collections"]).json(body)).await
+    }
+
+    /// The API key's collections, most recently changed first
+    pub async fn collections(&self) -> Result<Vec<Collection>, Error> {
+        Self::send(self.request(Method::GET, &["collections"])).await
+    }
+
+    pub async fn collection(&self, id: Uuid) -> Result<Collection, Error> {
+        let id = id.to_string();
+        Self::send(self.request(Method::GET, &["collections", &id])).await
+    }
+
+    /// Replaces a collection's name and projects
+    pub async fn put_collection(
+        &self,
+        id: Uuid,
+        body: &CollectionRequest,
+    ) -> Result<Collection, Error> {
+        let id = id.to_string();
+        Self::send(self.request(Method::PUT, &["collections", &id]).json(body)).await
+    }
+
+    pub async fn delete_collection(&self, id: Uuid) -> Result<(), Error> {
+        let id = id.to_string();
+        Self::check(self.request(Method::DELETE, &["collections", &id])).await?;
+        Ok(())
+    }
+
+    /// Posts the collection's changes to `url` after each run, once `url` has
+    /// echoed the server's challenge
+    pub async fn put_collection_webhook(
+        &self,
+        id: Uuid,
+        url: &str,
+    ) -> Result<CollectionWebhook, Error> {
+        let id = id.to_string();
+        Self::send(
+            self.request(Method::PUT, &["collections", &id, "webhook"])
+                .json(&CollectionWebhookRequest {
+                    url: url.to_string(),
+                }),
+        )
+        .await
+    }
+
+    /// A new signing secret for the collection's webhook; the old one keeps
+    /// signing digests for a day
+    pub async fn rotate_collection_webhook_secret(
+        &self,
+        id: Uuid,
+    ) -> Result<CollectionWebhook, Error> {
+        let id = id.to_string();
+        Self::send(self.request(Method::POST, &["collections", &id, "webhook", "secret"])).await
+    }
+
+    pub async fn delete_collection_webhook(&self, id: Uuid) -> Result<(), Error> {
+        let id = id.to_string();
+        Self::check(self.request(Method::DELETE, &["collections", &id, "webhook"])).await?;
+        Ok(())
+    }
+
+    /// A collection's projects, best ranked first
+    pub async fn collection_leaderboard(
+        &self,
+        id: Uuid,
+        params: &CollectionLeaderboardParams,
+    ) -> Result<Vec<LeaderboardEntry>, Error> {
+        let id = id.to_string();
+        Self::send(
+            self.request(Method::GET, &["collections", &id, "leaderboard"])
+                .query(params),
+        )
+        .await
+    }
+
+    /// How a collection's ranks moved between two runs
+    pub async fn collection_changes(
+        &self,
+        id: Uuid,
+        params: &CollectionChangesParams,
+    ) -> Result<CollectionChanges, Error> {
+        let id = id.to_string();
+        Self::send(
+            self.request(Method::GET, &["collections", &id, "changes"])
+                .query(params),
+        )
+        .await
+    }
+
+    // MAINTAINERS
+
+    /// Issues (or repeats) the token proving the key's owner maintains a project.
+    /// Needs an API key.
+    pub async fn request_verification(&self, id: Uuid) -> Result<VerificationToken, Error> {
+        let id = id.to_string();
+        Self::send(self.post_empty(&["project", &id, "verification"])).await
+    }
+
+    pub async fn verification(&self, id: Uuid) -> Result<VerificationStatus, Error> {
+        let id = id.to_string();
+        Self::send(self.request(Method::GET, &["project", &id, "verification"])).await
+    }
+
+    pub async fn maintainer_projects(
+        &self,
+        maintainer: &str,
+        precision: &PrecisionParams,
+    ) -> Result<MaintainerProjects, Error> {
+        Self::send(
+            self.request(Method::GET, &["maintainer", maintainer, "projects"])
+                .query(precision),
+        )
+        .await
+    }
+
+    // ADMIN: operational reports, returned as the API renders them
+
+    pub async fn usage(&self) -> Result<Value, Error> {
+        Self::send(self.request(Method::GET, &["admin", "usage"])).await
+    }
+
+    pub async fn table_usage(&self) -> Result<Value, Error> {
+        Self::send(self.request(Method::GET, &["admin", "table-usage"])).await
+    }
+
+    pub async fn invalidate_cache(&self, body: &InvalidateRequest) -> Result<Value, Error> {
+        Self::send(
+            self.request(Method::POST, &["admin", "cache", "invalidate"])
+                .json(body),
+        )
+        .await
+    }
+
+    pub async fn cache_stats(&self) -> Result<Value, Error> {
+        Self::send(self.request(Method::GET, &["admin", "cache", "stats"])).await
+    }
+
+    pub async fn pool_stats(&self) -> Result<Value, Error> {
+        Self::send(self.request(Method::GET, &["admin", "pool", "stats"])).await
+    }
+
+    pub async fn job_stats(&self) -> Result<Value, Error> {
+        Self::send(self.request(Method::GET, &["admin", "jobs"])).await
+    }
+
+    pub async fn schedule(&self) -> Result<Value, Error> {
+        Self::send(self.request(Method::GET, &["admin", "schedule"])).await
+    }
+
+    pub async fn run_checks(&self) -> Result<Value, Error> {
+        Self::send(self.request(Method::GET, &["admin", "runs", "check"])).await
+    }
+
+    pub async fn schema(&self) -> Result<Value, Error> {
+        Self::send(self.request(Method::GET, &["admin", "schema"])).await
+    }
+
+    pub async fn cutover_schema(&self, body: &CutoverRequest) -> Result<Value, Error> {
+        Self::send(
+            self.request(Method::POST, &["admin", "schema", "cutover"])
+                .json(body),
+        )
+        .await
+    }
+
+    /// Hides `run` from everything serving the latest run until
+    /// [`Client::clear_quarantine`]
+    pub async fn quarantine_run(&self, run: i32) -> Result<Value, Error> {
+        let run = run.to_string();
+        Self::send(self.post_empty(&["admin", "runs", &run, "quarantine"])).await
+    }
+
+    pub async fn clear_quarantine(&self, run: i32) -> Result<Value, Error> {
+        let run = run.to_string();
+        Self::send(self.request(Method::DELETE, &["admin", "runs", &run, "quarantine"])).await
+    }
+
+    /// Every leaderboard override, expired ones included
+    pub async fn overrides(&self) -> Result<Vec<LeaderboardOverride>, Error> {
+        Self::send(self.request(Method::GET, &["admin", "overrides"])).await
+    }
+
+    /// Excludes or pins a project on the leaderboard, replacing any override it had
+    pub async fn put_override(
+        &self,
+        id: Uuid,
+        body: &OverrideRequest,
+    ) -> Result<LeaderboardOverride, Error> {
+        let id = id.to_string();
+        Self::send(
+            self.request(Method::PUT, &["admin", "overrides", &id])
+                .json(body),
+        )
+        .await
+    }
+
+    pub async fn delete_override(&self, id: Uuid) -> Result<(), Error> {
+        let id = id.to_string();
+        Self::check(self.request(Method::DELETE, &["admin", "overrides", &id])).await?;
+        Ok(())
+    }
+
+    /// Canons that look like spam, placeholders or name squatting
+    pub async fn suspects(&self, params: &SuspectParams) -> Result<Suspects, Error> {
+        Self::send(
+            self.request(Method::GET, &["admin", "suspects"])
+                .query(params),
+        )
+        .await
+    }
+
+    /// The query plan of an endpoint's statement; `params` are the endpoint's
+    /// own plus `endpoint` itself
+    pub async fn explain(&self, params: &[(&str, &str)]) -> Result<Value, Error> {
+        Self::send(
+            self.request(Method::GET, &["admin", "explain"])
+                .query(params),
+        )
+        .await
+    }
+}
+
+fn full_view(precision: &PrecisionParams) -> ProjectParams {
+    ProjectParams {
+        precision: precision.precision,
+        view: ProjectView::Full,
+        ..ProjectParams::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endpoints_keep_the_base_path_and_encode_segments() {
+        let client = Client::new("https://example.com/chai/").unwrap();
+        assert_eq!(
+            client.url(&["project", "search", "a/b c"]).as_str(),
+            "https://example.com/chai/project/search/a%2Fb%20c"
+        );
+
+        let client = Client::new("https://example.com").unwrap();
+        assert_eq!(
+            client.url(&["leaderboard", "new"]).as_str(),
+            "https://example.com/leaderboard/new"
+        );
+        assert!(Client::new("mailto:chai@example.com").is_err());
+    }
+
+    #[test]
+    fn failures_report_the_problem_detail() {
+        let problem = r#"{"type":"about:blank","title":"Not Found","status":404,"detail":"No project","error":"No project"}"#;
+        let error = Error::from_body(404, problem);
+        assert_eq!(error.to_string(), "404: No project");
+        assert_eq!(error.status(), Some(404));
+
+        let error = Error::from_body(400, r#"{"error":"limit must be positive"}"#);
+        assert_eq!(error.to_string(), "400: limit must be positive");
+
+        let error = Error::from_body(500, "Failed to get database connection");
+        assert_eq!(error.to_string(), "500: Failed to get database connection");
+    }
+}
@@ -0,0 +1,65 @@
+use serde_json::Value;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The base URL doesn't parse or can't have a path
+    Url(String),
+    /// The request didn't complete, or the body wasn't the expected type
+    Http(reqwest::Error),
+    /// The API answered with a non-success status
+    Api {
+        status: u16,
+        /// The problem's `detail` (or the older `error`), else the raw body
+        message: String,
+        /// The body, when it was JSON
+        body: Option<Value>,
+    },
+}
+
+impl Error {
+    /// Reads a failed response. Most are RFC 9457 problems, some still only
+    /// `{"error": ...}`, and a few plain text.
+    pub(crate) fn from_body(status: u16, body: &str) -> Self {
+        let json: Option<Value> = serde_json::from_str(body).ok();
+        let message = json
+            .as_ref()
+            .and_then(|body| body.get("detail").or_else(|| body.get("error")))
+            .and_then(Value::as_str)
+            .map_or_else(|| body.trim().to_string(), str::to_string);
+        Error::Api {
+            status,
+            message,
+            body: json,
+        }
+    }
+
+    /// The response status, for errors the API answered
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Error::Api { status, .. } => Some(*status),
+            Error::Http(e) => e.status().map(|status| status.as_u16()),
+            Error::Url(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Url(e) => write!(f, "Invalid base URL: {e}"),
+            Error::Http(e) => write!(f, "Request failed: {e}"),
+            Error::Api {
+                status, message, ..
+            } => write!(f, "{status}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
@@ -0,0 +1,34 @@
+//! A typed async client for the CHAI API. Requests and responses use the
+//! types the server itself is built on, from `chai-types`, so they can't drift
+//! from what it accepts and returns; they are re-exported here so callers need
+//! only this crate.
+//!
+//! ```no_run
+//! use chai_client::requests::{LeaderboardRequest, PrecisionParams};
+//! use chai_client::sort::SortParams;
+//!
+//! # async fn run() -> Result<(), chai_client::Error> {
+//! let chai = chai_client::Client::new("https://api.chai.dev")?;
+//! let request = LeaderboardRequest {
+//!     project_ids: None,
+//!     limit: 10,
+//!     include_unranked: false,
+//!     name_prefix: None,
+//!     source_host: Some("github.com/rust-lang/*".to_string()),
+//! };
+//! for entry in chai
+//!     .leaderboard(&request, &PrecisionParams::default(), &SortParams::default())
+//!     .await?
+//! {
+//!     println!("{} {}", entry.tea_rank, entry.name);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod client;
+mod error;
+
+pub use chai_types::{format, jobs, models, requests, sort, tier};
+pub use client::Client;
+pub use error::Error;
@@ -0,0 +1,467 @@
+//! In-memory caches of rendered projects: entries and their expiry, lookup
+//! counters, invalidation, and snapshots that carry entries across restarts.
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::{fs, io};
+use uuid::Uuid;
+
+const TTL: Duration = Duration::from_secs(3600); // 1 hour
+
+#[derive(Clone)]
+pub struct ProjectCacheEntry {
+    pub data: Arc<Value>,
+    pub created_at: Instant,
+}
+
+impl ProjectCacheEntry {
+    pub fn new(data: Value) -> Self {
+        Self {
+            data: Arc::new(data),
+            created_at: Instant::now(),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.created_at.elapsed() > TTL
+    }
+
+    /// Whether an expired entry is still recent enough to serve while it refreshes
+    pub fn is_servable_stale(&self, max_staleness: Duration) -> bool {
+        self.created_at.elapsed() <= TTL + max_staleness
+    }
+}
+
+/// A cache invalidation target: `namespace:key`, where either part may be `*`.
+pub struct Target {
+    namespace: Option<String>,
+    key: Option<Uuid>,
+}
+
+impl Target {
+    /// Parses `*`, `project:*`, or `project:<uuid>`.
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let raw = raw.trim();
+        if raw == "*" {
+            return Ok(Self {
+                namespace: None,
+                key: None,
+            });
+        }
+
+        let (namespace, key) = raw
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid target '{raw}': expected 'namespace:key'"))?;
+        let namespace = match namespace {
+            "*" => None,
+            name => Some(name.to_string()),
+        };
+        let key = match key {
+            "*" => None,
+            key => Some(
+                Uuid::try_parse(key)
+                    .map_err(|_| format!("Invalid target '{raw}': key must be '*' or a UUID"))?,
+            ),
+        };
+        Ok(Self { namespace, key })
+    }
+}
+
+/// Age buckets reported by `/admin/cache/stats`, as (label, upper bound in seconds)
+const AGE_BUCKETS: [(&str, u64); 3] = [("under5m", 300), ("under15m", 900), ("under1h", 3600)];
+
+/// How a cache lookup was answered
+#[derive(Clone, Copy)]
+pub enum Lookup {
+    /// Fresh entry served
+    Hit,
+    /// Expired entry served while it refreshes
+    Stale,
+    /// Expired entry discarded and re-queried
+    Expired,
+    /// No entry
+    Miss,
+}
+
+/// Lookup counters for one cache namespace since startup
+#[derive(Default)]
+pub struct CacheCounters {
+    hits: AtomicU64,
+    stale_hits: AtomicU64,
+    expired: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CacheCounters {
+    pub fn record(&self, lookup: Lookup) {
+        let counter = match lookup {
+            Lookup::Hit => &self.hits,
+            Lookup::Stale => &self.stale_hits,
+            Lookup::Expired => &self.expired,
+            Lookup::Miss => &self.misses,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Lookups since startup by outcome
+    pub fn lookups(&self) -> [(&'static str, u64); 4] {
+        [
+            ("hit", self.hits.load(Ordering::Relaxed)),
+            ("stale", self.stale_hits.load(Ordering::Relaxed)),
+            ("expired", self.expired.load(Ordering::Relaxed)),
+            ("miss", self.misses.load(Ordering::Relaxed)),
+        ]
+    }
+
+    pub fn to_json(&self) -> Value {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let stale_hits = self.stale_hits.load(Ordering::Relaxed);
+        let expired = self.expired.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let lookups = hits + stale_hits + expired + misses;
+        let hit_rate = if lookups == 0 {
+            0.0
+        } else {
+            (hits + stale_hits) as f64 / lookups as f64
+        };
+        json!({
+            "hits": hits,
+            "staleHits": stale_hits,
+            "expired": expired,
+            "misses": misses,
+            "hitRate": hit_rate,
+        })
+    }
+}
+
+/// Counters for every in-memory cache namespace
+#[derive(Default)]
+pub struct CacheMetrics {
+    pub project: CacheCounters,
+    pub funding: CacheCounters,
+}
+
+impl CacheMetrics {
+    /// The counters of every namespace
+    pub fn namespaces(&self) -> [(&'static str, &CacheCounters); 2] {
+        [("project", &self.project), ("funding", &self.funding)]
+    }
+
+    fn get(&self, namespace: &str) -> Option<&CacheCounters> {
+        match namespace {
+            "project" => Some(&self.project),
+            "funding" => Some(&self.funding),
+            _ => None,
+        }
+    }
+}
+
+pub type Namespaces<'a> = [(&'static str, &'a DashMap<Uuid, ProjectCacheEntry>); 2];
+
+/// Every in-memory cache, by namespace
+pub fn namespaces<'a>(
+    project_cache: &'a DashMap<Uuid, ProjectCacheEntry>,
+    funding_cache: &'a DashMap<Uuid, ProjectCacheEntry>,
+) -> Namespaces<'a> {
+    [("project", project_cache), ("funding", funding_cache)]
+}
+
+/// Entry counts, approximate memory, lookup counters, and ages per namespace
+pub fn stats(caches: &Namespaces, metrics: &CacheMetrics) -> BTreeMap<&'static str, Value> {
+    caches
+        .iter()
+        .map(|(name, cache)| {
+            let mut memory_bytes = 0;
+            let mut ages: BTreeMap<&str, u64> = AGE_BUCKETS
+                .iter()
+                .map(|(label, _)| (*label, 0))
+                .chain([("overTtl", 0)])
+                .collect();
+            let mut oldest = 0;
+            for entry in cache.iter() {
+                // Serialized size plus the entry itself; allocator overhead is ignored
+                memory_bytes += serde_json::to_vec(entry.data.as_ref()).map_or(0, |v| v.len())
+                    + std::mem::size_of::<(Uuid, ProjectCacheEntry)>();
+                let age = entry.created_at.elapsed().as_secs();
+                oldest = oldest.max(age);
+                let bucket = AGE_BUCKETS
+                    .iter()
+                    .find(|(_, limit)| age < *limit)
+                    .map_or("overTtl", |(label, _)| label);
+                *ages.entry(bucket).or_default() += 1;
+            }
+
+            let mut stats = json!({
+                "entries": cache.len(),
+                "memoryBytes": memory_bytes,
+                "ageSeconds": { "oldest": oldest, "buckets": ages },
+            });
+            if let Some(counters) = metrics.get(name) {
+                stats["lookups"] = counters.to_json();
+            }
+            (*name, stats)
+        })
+        .collect()
+}
+
+/// Drops entries too old to be served even stale, which lookups would only
+/// discard, returning how many were dropped
+pub fn sweep(caches: &Namespaces, max_staleness: Duration) -> usize {
+    let mut swept = 0;
+    for (_, cache) in caches {
+        let before = cache.len();
+        cache.retain(|_, entry| entry.is_servable_stale(max_staleness));
+        swept += before.saturating_sub(cache.len());
+    }
+    swept
+}
+
+/// Evicts every entry matched by `targets`, returning counts per namespace.
+/// Unknown namespaces are rejected before anything is evicted.
+pub fn invalidate(
+    caches: &Namespaces,
+    targets: &[Target],
+) -> Result<BTreeMap<&'static str, usize>, String> {
+    for target in targets {
+        if let Some(namespace) = &target.namespace {
+            if !caches.iter().any(|(name, _)| name == namespace) {
+                let valid: Vec<&str> = caches.iter().map(|(name, _)| *name).collect();
+                return Err(format!(
+                    "Unknown cache namespace '{namespace}' (valid: {})",
+                    valid.join(", ")
+                ));
+            }
+        }
+    }
+
+    let mut evicted = BTreeMap::new();
+    for (name, cache) in caches {
+        let mut count = 0;
+        for target in targets {
+            if target.namespace.as_deref().is_some_and(|ns| ns != *name) {
+                continue;
+            }
+            match target.key {
+                Some(key) => count += usize::from(cache.remove(&key).is_some()),
+                None => {
+                    count += cache.len();
+                    cache.clear();
+                }
+            }
+        }
+        evicted.insert(*name, count);
+    }
+    Ok(evicted)
+}
+
+/// Splits `project_ids` into cached values and ids that must be queried. Expired
+/// entries within `max_staleness` are still returned, and their ids are listed as
+/// stale so the caller can refresh them in the background.
+pub fn get_cached_projects(
+    cache: Arc<DashMap<Uuid, ProjectCacheEntry>>,
+    project_ids: &[Uuid],
+    max_staleness: Duration,
+    counters: &CacheCounters,
+) -> (Vec<Arc<Value>>, Vec<Uuid>, Vec<Uuid>) {
+    let mut cached_projects = Vec::new();
+    let mut missing_ids = Vec::new();
+    let mut stale_ids = Vec::new();
+
+    for &project_id in project_ids {
+        if let Some(entry) = cache.get(&project_id) {
+            if !entry.is_expired() {
+                counters.record(Lookup::Hit);
+                cached_projects.push(entry.data.clone());
+                continue;
+            }
+            if entry.is_servable_stale(max_staleness) {
+                counters.record(Lookup::Stale);
+                cached_projects.push(entry.data.clone());
+                stale_ids.push(project_id);
+                continue;
+            }
+            counters.record(Lookup::Expired);
+        } else {
+            counters.record(Lookup::Miss);
+        }
+        missing_ids.push(project_id);
+    }
+
+    (cached_projects, missing_ids, stale_ids)
+}
+
+/// Caches each of `projects` under its `projectId`
+pub fn cache_projects(cache: &DashMap<Uuid, ProjectCacheEntry>, projects: &[Value]) {
+    for project in projects {
+        if let Some(project_id) = project.get("projectId").and_then(|v| v.as_str()) {
+            if let Ok(uuid) = Uuid::parse_str(project_id) {
+                cache.insert(uuid, ProjectCacheEntry::new(project.clone()));
+            } else {
+                log::warn!("Failed to parse project ID as UUID: {}", project_id);
+            }
+        } else {
+            log::warn!("No projectId found in project: {:?}", project);
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Snapshot {
+    saved_at: u64,
+    namespaces: BTreeMap<String, Vec<SnapshotEntry>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SnapshotEntry {
+    id: Uuid,
+    age_secs: u64,
+    data: Value,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Writes every cache to `path` as JSON, via a temporary file so a crash mid-write
+/// never leaves a truncated snapshot behind.
+pub fn save_snapshot(path: &Path, caches: &Namespaces) -> io::Result<usize> {
+    let mut total = 0;
+    let namespaces = caches
+        .iter()
+        .map(|(name, cache)| {
+            let entries: Vec<SnapshotEntry> = cache
+                .iter()
+                .map(|entry| SnapshotEntry {
+                    id: *entry.key(),
+                    age_secs: entry.created_at.elapsed().as_secs(),
+                    data: entry.data.as_ref().clone(),
+                })
+                .collect();
+            total += entries.len();
+            (name.to_string(), entries)
+        })
+        .collect();
+    let snapshot = Snapshot {
+        saved_at: now_secs(),
+        namespaces,
+    };
+
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_vec(&snapshot)?)?;
+    fs::rename(&tmp, path)?;
+    Ok(total)
+}
+
+/// Restores caches saved by [`save_snapshot`], keeping each entry's original age
+/// (including the downtime) and dropping any too old to be served.
+pub fn load_snapshot(
+    path: &Path,
+    caches: &Namespaces,
+    max_staleness: Duration,
+) -> io::Result<usize> {
+    let snapshot: Snapshot = serde_json::from_slice(&fs::read(path)?)?;
+    let downtime = now_secs().saturating_sub(snapshot.saved_at);
+    let now = Instant::now();
+
+    let mut total = 0;
+    for (name, entries) in snapshot.namespaces {
+        let Some((_, cache)) = caches.iter().find(|(n, _)| *n == name) else {
+            log::warn!("Skipping unknown cache namespace in snapshot: {name}");
+            continue;
+        };
+        for entry in entries {
+            let age = Duration::from_secs(entry.age_secs + downtime);
+            let Some(created_at) = now.checked_sub(age) else {
+                continue;
+            };
+            let restored = ProjectCacheEntry {
+                data: Arc::new(entry.data),
+                created_at,
+            };
+            if restored.is_servable_stale(max_staleness) {
+                cache.insert(entry.id, restored);
+                total += 1;
+            }
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cached(ids: &[Uuid]) -> DashMap<Uuid, ProjectCacheEntry> {
+        ids.iter()
+            .map(|id| (*id, ProjectCacheEntry::new(json!({ "projectId": id }))))
+            .collect()
+    }
+
+    fn targets(raw: &[&str]) -> Vec<Target> {
+        raw.iter().map(|raw| Target::parse(raw).unwrap()).collect()
+    }
+
+    #[test]
+    fn targets_parse_wildcards_and_reject_bad_keys() {
+        let id = Uuid::new_v4();
+        let all = Target::parse(" * ").unwrap();
+        assert_eq!((all.namespace, all.key), (None, None));
+        let namespace = Target::parse("project:*").unwrap();
+        assert_eq!(namespace.namespace.as_deref(), Some("project"));
+        assert_eq!(namespace.key, None);
+        let key = Target::parse(&format!("*:{id}")).unwrap();
+        assert_eq!((key.namespace, key.key), (None, Some(id)));
+
+        assert!(Target::parse("project").is_err());
+        assert!(Target::parse("project:not-a-uuid").is_err());
+    }
+
+    #[test]
+    fn targets_evict_what_they_match() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let (project, funding) = (cached(&[a, b]), cached(&[a]));
+        let caches = namespaces(&project, &funding);
+
+        // A key without a namespace is evicted from every namespace
+        let evicted = invalidate(&caches, &targets(&[&format!("*:{a}")])).unwrap();
+        assert_eq!(evicted, BTreeMap::from([("project", 1), ("funding", 1)]));
+        assert!(project.contains_key(&b));
+
+        funding.insert(a, ProjectCacheEntry::new(json!({})));
+        let evicted = invalidate(&caches, &targets(&["project:*"])).unwrap();
+        assert_eq!(evicted, BTreeMap::from([("project", 1), ("funding", 0)]));
+        assert!(project.is_empty());
+        assert_eq!(funding.len(), 1);
+
+        let evicted = invalidate(&caches, &targets(&["*"])).unwrap();
+        assert_eq!(evicted, BTreeMap::from([("project", 0), ("funding", 1)]));
+        assert!(funding.is_empty());
+    }
+
+    #[test]
+    fn unknown_namespaces_evict_nothing() {
+        let id = Uuid::new_v4();
+        let (project, funding) = (cached(&[id]), cached(&[id]));
+        let caches = namespaces(&project, &funding);
+
+        // Checked before the valid targets ahead of it are applied
+        let error = invalidate(&caches, &targets(&["*", "leaderboard:*"])).unwrap_err();
+        assert!(
+            error.contains("Unknown cache namespace 'leaderboard'"),
+            "{error}"
+        );
+        assert!(error.contains("project, funding"), "{error}");
+        assert_eq!((project.len(), funding.len()), (1, 1));
+    }
+}
@@ -0,0 +1,144 @@
+//! Running statements: timing them for debugging, fetching single rows, and
+//! reading the catalog of a schema.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{Client, Row};
+
+tokio::task_local! {
+    /// Statements run by the future inside [`collect_timings`]
+    static TIMINGS: RefCell<Vec<(String, Duration)>>;
+}
+
+/// Awaits a database call, recording it as `name` when it runs inside
+/// [`collect_timings`]. Elsewhere this is just `.await`.
+pub async fn timed<F: Future>(name: &str, query: F) -> F::Output {
+    let started = Instant::now();
+    let output = query.await;
+    let _ = TIMINGS.try_with(|timings| {
+        timings
+            .borrow_mut()
+            .push((name.to_string(), started.elapsed()))
+    });
+    output
+}
+
+/// Awaits `work`, returning its output along with every statement it [`timed`],
+/// in order
+pub async fn collect_timings<F: Future>(work: F) -> (F::Output, Vec<(String, Duration)>) {
+    TIMINGS
+        .scope(RefCell::new(Vec::new()), async move {
+            let output = work.await;
+            (output, TIMINGS.with(|timings| timings.take()))
+        })
+        .await
+}
+
+/// Why a single-row fetch didn't produce its row
+#[derive(Debug)]
+pub enum FetchError {
+    /// The query matched no rows
+    NotFound,
+    /// The query matched several rows where at most one was expected
+    TooManyRows {
+        count: usize,
+        context: String,
+    },
+    Db(tokio_postgres::Error),
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::NotFound => write!(f, "no rows returned"),
+            FetchError::TooManyRows { count, context } => {
+                write!(f, "expected at most one row for {context}, got {count}")
+            }
+            FetchError::Db(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl From<tokio_postgres::Error> for FetchError {
+    fn from(e: tokio_postgres::Error) -> Self {
+        FetchError::Db(e)
+    }
+}
+
+/// Runs a query expected to match at most one row. `context` names what was
+/// looked up, for the error raised when several rows come back.
+pub async fn fetch_optional(
+    client: &Client,
+    query: &str,
+    params: &[&(dyn ToSql + Sync)],
+    context: &str,
+) -> Result<Option<Row>, FetchError> {
+    let mut rows = timed(context, client.query(query, params)).await?;
+    match rows.len() {
+        0 => Ok(None),
+        1 => Ok(rows.pop()),
+        count => Err(FetchError::TooManyRows {
+            count,
+            context: context.to_string(),
+        }),
+    }
+}
+
+/// Runs a query expected to match exactly one row
+pub async fn fetch_one(
+    client: &Client,
+    query: &str,
+    params: &[&(dyn ToSql + Sync)],
+    context: &str,
+) -> Result<Row, FetchError> {
+    fetch_optional(client, query, params, context)
+        .await?
+        .ok_or(FetchError::NotFound)
+}
+
+/// The tables of a schema and their columns, as `/tables` serves them
+#[derive(PartialEq)]
+pub struct Catalog {
+    pub tables: Vec<String>,
+    /// Column names of every table, in ordinal order
+    pub columns: HashMap<String, Vec<String>>,
+}
+
+impl Catalog {
+    /// The tables of `schema`
+    pub async fn load(client: &Client, schema: &str) -> Result<Self, tokio_postgres::Error> {
+        let tables = client
+            .query(
+                "SELECT table_name FROM information_schema.tables
+                WHERE table_schema = $1
+                ORDER BY table_name",
+                &[&schema],
+            )
+            .await?
+            .into_iter()
+            .map(|row| row.get::<_, String>("table_name"))
+            .collect();
+
+        let rows = client
+            .query(
+                "SELECT table_name, column_name
+                FROM information_schema.columns
+                WHERE table_schema = $1
+                ORDER BY table_name, ordinal_position",
+                &[&schema],
+            )
+            .await?;
+        let mut columns: HashMap<String, Vec<String>> = HashMap::new();
+        for row in rows {
+            columns
+                .entry(row.get("table_name"))
+                .or_default()
+                .push(row.get("column_name"));
+        }
+        Ok(Self { tables, columns })
+    }
+}
@@ -0,0 +1,566 @@
+//! Walking the dependency graph between projects and matching packages to them.
+//!
+//! Functions taking `live` expect a SQL predicate over canons `c` that filters
+//! out soft-deleted rows, or `TRUE`.
+
+use chai_types::format::RowFormat;
+use chai_types::jobs::SbomPackage;
+use chai_types::models::{
+    DependencyConfusion, DependencyNode, DependencyTree, DependentProject, ExposedPackage,
+};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use tokio_postgres::Client;
+use uuid::Uuid;
+
+use crate::db::timed;
+use crate::models::to_json;
+use crate::sql::LatestRank;
+
+/// Projects reachable from `$1` over dependency edges in at most `$2` hops, each
+/// at its shortest distance
+pub fn closure_query(live: &str) -> String {
+    format!(
+        r#"
+        WITH RECURSIVE edges AS (
+            SELECT DISTINCT cp_from.canon_id AS dependent, cp_to.canon_id AS dependency
+            FROM legacy_dependencies ld
+            JOIN canon_packages cp_from ON cp_from.package_id = ld.package_id
+            JOIN canon_packages cp_to ON cp_to.package_id = ld.dependency_id
+            WHERE cp_from.canon_id <> cp_to.canon_id
+        ),
+        closure AS (
+            SELECT e.dependency AS canon_id, 1 AS depth
+            FROM edges e
+            WHERE e.dependent = $1
+            UNION
+            SELECT e.dependency, cl.depth + 1
+            FROM closure cl
+            JOIN edges e ON e.dependent = cl.canon_id
+            WHERE cl.depth < $2
+        )
+        SELECT c.id AS project_id, c.name, MIN(cl.depth) AS depth
+        FROM closure cl
+        JOIN canons c ON c.id = cl.canon_id
+        WHERE c.id <> $1 AND {live}
+        GROUP BY c.id, c.name
+        ORDER BY depth, c.name, c.id"#
+    )
+}
+
+/// Levels of a dependency tree returned by default
+pub const DEFAULT_TREE_DEPTH: i32 = 3;
+/// Most levels of a dependency tree one request can ask for
+pub const MAX_TREE_DEPTH: i32 = 10;
+/// Most nodes in one dependency tree; a project reached over several paths is
+/// repeated under each, so deep trees of popular projects grow quickly
+pub const MAX_TREE_NODES: usize = 10_000;
+
+/// The direct dependency edges of `$1` and of every project reachable from it
+/// in fewer than `$2` hops, with the dependency's name
+pub fn tree_edges_query(live: &str) -> String {
+    format!(
+        r#"
+        WITH RECURSIVE edges AS (
+            SELECT DISTINCT cp_from.canon_id AS dependent, cp_to.canon_id AS dependency
+            FROM legacy_dependencies ld
+            JOIN canon_packages cp_from ON cp_from.package_id = ld.package_id
+            JOIN canon_packages cp_to ON cp_to.package_id = ld.dependency_id
+            WHERE cp_from.canon_id <> cp_to.canon_id
+        ),
+        reach AS (
+            SELECT $1::uuid AS canon_id, 0 AS depth
+            UNION
+            SELECT e.dependency, r.depth + 1
+            FROM reach r
+            JOIN edges e ON e.dependent = r.canon_id
+            JOIN canons c ON c.id = e.dependency
+            WHERE r.depth + 1 < $2 AND {live}
+        )
+        SELECT DISTINCT e.dependent, e.dependency, c.name
+        FROM edges e
+        JOIN reach r ON r.canon_id = e.dependent
+        JOIN canons c ON c.id = e.dependency
+        WHERE {live}
+        ORDER BY c.name, e.dependency"#
+    )
+}
+
+/// Canons `c` with a package depending on a package of `$1`, other than `$1`
+/// itself
+const DEPENDENTS_FROM: &str = r#"
+        FROM (
+            SELECT DISTINCT cp_from.canon_id AS id
+            FROM canon_packages cp_to
+            JOIN legacy_dependencies ld ON ld.dependency_id = cp_to.package_id
+            JOIN canon_packages cp_from ON cp_from.package_id = ld.package_id
+            WHERE cp_to.canon_id = $1 AND cp_from.canon_id <> $1
+        ) d
+        JOIN canons c ON c.id = d.id"#;
+
+/// How many projects depend directly on `$1`
+pub fn count_dependents_query(live: &str) -> String {
+    format!("SELECT COUNT(*) AS count {DEPENDENTS_FROM} WHERE {live}")
+}
+
+/// `$3` of the projects depending directly on `$1` from `$4`, with their rank
+/// in run `$2`, the latest, best ranked first and unranked last
+pub fn dependents_query(live: &str) -> String {
+    let rank = LatestRank::of("c.id").in_run("$2", true).join("tr");
+    format!(
+        r#"
+        SELECT c.id AS project_id, c.name, tr.rank AS tea_rank
+        {DEPENDENTS_FROM}
+        {rank}
+        WHERE {live}
+        ORDER BY CAST(tr.rank AS NUMERIC) DESC NULLS LAST, c.name, c.id
+        LIMIT $3 OFFSET $4"#
+    )
+}
+
+/// The projects of each `($1[i], $2[i])` package manager and package name pair,
+/// with the 1-based `position` of the pair and the project's rank in run `$3`,
+/// which is the latest when `current`
+pub fn package_match_query(live: &str, current: bool) -> String {
+    let rank = LatestRank::of("c.id").in_run("$3", current).join("tr");
+    format!(
+        r#"
+        SELECT
+            input.position,
+            s.type AS package_manager,
+            p.name AS package,
+            c.id AS project_id,
+            c.name,
+            tr.rank AS tea_rank
+        FROM unnest($1::text[], $2::text[]) WITH ORDINALITY
+            AS input(package_manager, package, position)
+        JOIN sources s ON s.type = input.package_manager
+        JOIN package_managers pm ON pm.source_id = s.id
+        JOIN packages p ON p.package_manager_id = pm.id AND p.name = input.package
+        JOIN canon_packages cp ON cp.package_id = p.id
+        JOIN canons c ON c.id = cp.canon_id
+        {rank}
+        WHERE {live}
+        ORDER BY input.position, c.name"#
+    )
+}
+
+/// The public packages of each `($1[i], $2[i])` package manager and package
+/// name pair, with the 1-based `position` of the pair, the usernames that own
+/// the package, and its project's rank in run `$3`, the latest. Unlike
+/// [`package_match_query`], packages outside any live project still match.
+pub fn exposed_packages_query(live: &str) -> String {
+    let rank = LatestRank::of("c.id").in_run("$3", true).join("tr");
+    format!(
+        r#"
+        SELECT
+            input.position,
+            s.type AS package_manager,
+            p.name AS package,
+            c.id AS project_id,
+            c.name,
+            tr.rank AS tea_rank,
+            COALESCE(
+                (
+                    SELECT ARRAY_AGG(DISTINCT u.username ORDER BY u.username)
+                    FROM user_packages up
+                    JOIN users u ON u.id = up.user_id
+                    WHERE up.package_id = p.id
+                ),
+                '{{}}'
+            ) AS owners
+        FROM unnest($1::text[], $2::text[]) WITH ORDINALITY
+            AS input(package_manager, package, position)
+        JOIN sources s ON s.type = input.package_manager
+        JOIN package_managers pm ON pm.source_id = s.id
+        JOIN packages p ON p.package_manager_id = pm.id AND p.name = input.package
+        LEFT JOIN canon_packages cp ON cp.package_id = p.id
+        LEFT JOIN canons c ON c.id = cp.canon_id AND {live}
+        {rank}
+        ORDER BY input.position, p.name"#
+    )
+}
+
+/// The package managers among `managers` that CHAI doesn't know, deduplicated
+pub async fn unknown_package_managers(
+    client: &Client,
+    managers: &[&str],
+) -> Result<Vec<String>, tokio_postgres::Error> {
+    let mut managers = managers.to_vec();
+    managers.sort_unstable();
+    managers.dedup();
+    let rows = timed(
+        "known_package_managers",
+        client.query(
+            "SELECT type FROM sources WHERE type = ANY($1::text[])",
+            &[&managers],
+        ),
+    )
+    .await?;
+    let known: HashSet<String> = rows.iter().map(|row| row.get("type")).collect();
+    Ok(managers
+        .into_iter()
+        .filter(|manager| !known.contains(*manager))
+        .map(String::from)
+        .collect())
+}
+
+/// Which of `packages` a public registry already has, with ranks from `run`
+pub async fn exposed_packages(
+    client: &Client,
+    packages: &[SbomPackage],
+    run: Option<i32>,
+    live: &str,
+    format: RowFormat,
+) -> Result<DependencyConfusion<Value>, tokio_postgres::Error> {
+    let (managers, names): (Vec<&str>, Vec<&str>) = packages
+        .iter()
+        .map(|package| (package.package_manager.as_str(), package.name.as_str()))
+        .unzip();
+    let rows = timed(
+        "exposed_packages",
+        client.query(&exposed_packages_query(live), &[&managers, &names, &run]),
+    )
+    .await?;
+
+    let exposed: HashSet<i64> = rows.iter().map(|row| row.get("position")).collect();
+    let unclaimed = packages
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !exposed.contains(&(*i as i64 + 1)))
+        .map(|(_, package)| package.clone())
+        .collect();
+    Ok(DependencyConfusion {
+        run,
+        exposed: to_json::<ExposedPackage>(&rows, format),
+        unclaimed,
+    })
+}
+
+pub async fn count_dependents(
+    client: &Client,
+    id: Uuid,
+    live: &str,
+) -> Result<i64, tokio_postgres::Error> {
+    let row = timed(
+        "count_dependents",
+        client.query_one(&count_dependents_query(live), &[&id]),
+    )
+    .await?;
+    Ok(row.get("count"))
+}
+
+/// `limit` of the projects depending directly on `id` from `offset`, as
+/// [`DependentProject`]s with ranks from `run`
+pub async fn dependents(
+    client: &Client,
+    id: Uuid,
+    run: Option<i32>,
+    limit: i64,
+    offset: i64,
+    live: &str,
+    format: RowFormat,
+) -> Result<Vec<Value>, tokio_postgres::Error> {
+    let rows = timed(
+        "dependents",
+        client.query(&dependents_query(live), &[&id, &run, &limit, &offset]),
+    )
+    .await?;
+    Ok(to_json::<DependentProject>(&rows, format))
+}
+
+/// Project `id`'s dependencies nested `depth` levels deep, or `None` when it
+/// isn't a live project
+pub async fn dependency_tree(
+    client: &Client,
+    id: Uuid,
+    depth: i32,
+    live: &str,
+) -> Result<Option<DependencyTree>, tokio_postgres::Error> {
+    let query = format!("SELECT c.name FROM canons c WHERE c.id = $1 AND {live}");
+    let Some(root) = timed("dependency_tree_root", client.query_opt(&query, &[&id])).await? else {
+        return Ok(None);
+    };
+    let rows = timed(
+        "dependency_tree_edges",
+        client.query(&tree_edges_query(live), &[&id, &depth]),
+    )
+    .await?;
+
+    let mut edges: HashMap<Uuid, Vec<(Uuid, String)>> = HashMap::new();
+    for row in &rows {
+        edges
+            .entry(row.get("dependent"))
+            .or_default()
+            .push((row.get("dependency"), row.get("name")));
+    }
+    let mut tree = TreeBuilder {
+        edges: &edges,
+        depth: depth.max(0) as usize,
+        path: vec![id],
+        budget: MAX_TREE_NODES,
+        truncated: false,
+    };
+    let dependencies = tree.children(id);
+    Ok(Some(DependencyTree {
+        project_id: id,
+        name: root.get("name"),
+        depth,
+        truncated: tree.truncated,
+        dependencies,
+    }))
+}
+
+/// Expands dependency edges into nested nodes depth first, stopping at
+/// projects already on the path
+struct TreeBuilder<'a> {
+    edges: &'a HashMap<Uuid, Vec<(Uuid, String)>>,
+    depth: usize,
+    /// The root and the ancestors of the node being expanded
+    path: Vec<Uuid>,
+    /// Nodes left before the tree is truncated
+    budget: usize,
+    truncated: bool,
+}
+
+impl TreeBuilder<'_> {
+    fn children(&mut self, id: Uuid) -> Vec<DependencyNode> {
+        let Some(dependencies) = self.edges.get(&id) else {
+            return Vec::new();
+        };
+        let mut nodes = Vec::with_capacity(dependencies.len());
+        for (dependency, name) in dependencies {
+            if self.budget == 0 {
+                self.truncated = true;
+                break;
+            }
+            self.budget -= 1;
+
+            let cycle = self.path.contains(dependency);
+            let children = if cycle || self.path.len() >= self.depth {
+                Vec::new()
+            } else {
+                self.path.push(*dependency);
+                let children = self.children(*dependency);
+                self.path.pop();
+                children
+            };
+            nodes.push(DependencyNode {
+                project_id: *dependency,
+                name: name.clone(),
+                cycle,
+                children,
+            });
+        }
+        nodes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_project, test_client};
+
+    #[tokio::test]
+    async fn closure_follows_transitive_dependencies_once() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        let app = insert_project(&client, "app", "npm", &[]).await;
+        let web = insert_project(&client, "web", "npm", &[]).await;
+        let http = insert_project(&client, "http", "npm", &[]).await;
+        let bytes = insert_project(&client, "bytes", "npm", &[]).await;
+        for (dependent, dependency) in [
+            (app, web),
+            (web, http),
+            (app, http),
+            (http, bytes),
+            (bytes, app),
+        ] {
+            client
+                .execute(
+                    r#"
+                    INSERT INTO legacy_dependencies (package_id, dependency_id)
+                    SELECT cp_from.package_id, cp_to.package_id
+                    FROM canon_packages cp_from, canon_packages cp_to
+                    WHERE cp_from.canon_id = $1 AND cp_to.canon_id = $2"#,
+                    &[&dependent, &dependency],
+                )
+                .await
+                .unwrap();
+        }
+
+        let depths = |rows: Vec<tokio_postgres::Row>| -> Vec<(String, i32)> {
+            rows.iter()
+                .map(|row| (row.get("name"), row.get("depth")))
+                .collect()
+        };
+        let rows = client
+            .query(&closure_query("TRUE"), &[&app, &10i32])
+            .await
+            .unwrap();
+        assert_eq!(
+            depths(rows),
+            [
+                ("http".to_string(), 1),
+                ("web".to_string(), 1),
+                ("bytes".to_string(), 2)
+            ]
+        );
+
+        let rows = client
+            .query(&closure_query("TRUE"), &[&app, &1i32])
+            .await
+            .unwrap();
+        assert_eq!(depths(rows).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn dependency_tree_nests_levels_and_stops_at_cycles() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        let app = insert_project(&client, "app", "npm", &[]).await;
+        let web = insert_project(&client, "web", "npm", &[]).await;
+        let http = insert_project(&client, "http", "npm", &[]).await;
+        for (dependent, dependency) in [(app, web), (web, http), (http, app)] {
+            client
+                .execute(
+                    r#"
+                    INSERT INTO legacy_dependencies (package_id, dependency_id)
+                    SELECT cp_from.package_id, cp_to.package_id
+                    FROM canon_packages cp_from, canon_packages cp_to
+                    WHERE cp_from.canon_id = $1 AND cp_to.canon_id = $2"#,
+                    &[&dependent, &dependency],
+                )
+                .await
+                .unwrap();
+        }
+
+        let tree = dependency_tree(&client, app, 10, "TRUE")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!tree.truncated);
+        let [web_node] = &tree.dependencies[..] else {
+            panic!("expected one dependency, got {:?}", tree.dependencies);
+        };
+        assert_eq!((web_node.name.as_str(), web_node.cycle), ("web", false));
+        let http_node = &web_node.children[0];
+        assert_eq!(http_node.name, "http");
+        // http depends on app again, which is where the walk stops
+        let app_node = &http_node.children[0];
+        assert_eq!((app_node.project_id, app_node.cycle), (app, true));
+        assert!(app_node.children.is_empty());
+
+        let tree = dependency_tree(&client, app, 1, "TRUE")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(tree.dependencies[0].children.is_empty());
+        assert!(dependency_tree(&client, Uuid::new_v4(), 3, "TRUE")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn dependents_are_distinct_and_paginated() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        let http = insert_project(&client, "http", "npm", &[(1, "5")]).await;
+        let web = insert_project(&client, "web", "npm", &[(1, "9")]).await;
+        let cli = insert_project(&client, "cli", "npm", &[]).await;
+        // web depends on http twice over, through two of its packages
+        let web_extra = insert_project(&client, "web-extra", "npm", &[]).await;
+        client
+            .execute(
+                "UPDATE canon_packages SET canon_id = $1 WHERE canon_id = $2",
+                &[&web, &web_extra],
+            )
+            .await
+            .unwrap();
+        client
+            .execute(
+                r#"
+                INSERT INTO legacy_dependencies (package_id, dependency_id)
+                SELECT cp_from.package_id, cp_to.package_id
+                FROM canon_packages cp_from, canon_packages cp_to
+                WHERE cp_from.canon_id = ANY($1) AND cp_to.canon_id = $2"#,
+                &[&vec![web, cli, http], &http],
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(count_dependents(&client, http, "TRUE").await.unwrap(), 2);
+        let names = |values: Vec<Value>| -> Vec<String> {
+            values
+                .iter()
+                .map(|value| value["name"].as_str().unwrap().to_string())
+                .collect()
+        };
+        let page = dependents(&client, http, Some(1), 10, 0, "TRUE", RowFormat::default())
+            .await
+            .unwrap();
+        // Ranked first, and http's own packages don't make it a dependent
+        assert_eq!(names(page), ["web", "cli"]);
+        let page = dependents(&client, http, Some(1), 1, 1, "TRUE", RowFormat::default())
+            .await
+            .unwrap();
+        assert_eq!(names(page), ["cli"]);
+    }
+
+    #[tokio::test]
+    async fn exposed_packages_report_owners_and_unclaimed_names() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        let public = insert_project(&client, "acme-utils", "npm", &[(1, "12")]).await;
+        client
+            .execute(
+                r#"
+                WITH owner AS (
+                    INSERT INTO users (id, username, source_id)
+                    SELECT gen_random_uuid(), 'mallory', id FROM sources WHERE type = 'npm'
+                    RETURNING id
+                )
+                INSERT INTO user_packages (user_id, package_id)
+                SELECT owner.id, cp.package_id FROM owner, canon_packages cp
+                WHERE cp.canon_id = $1"#,
+                &[&public],
+            )
+            .await
+            .unwrap();
+
+        let package = |package_manager: &str, name: &str| SbomPackage {
+            package_manager: package_manager.to_string(),
+            name: name.to_string(),
+        };
+        let packages = [
+            package("npm", "acme-internal"),
+            package("npm", "acme-utils"),
+        ];
+        assert!(unknown_package_managers(&client, &["npm", "npm"])
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            unknown_package_managers(&client, &["corp", "npm"])
+                .await
+                .unwrap(),
+            ["corp"]
+        );
+
+        let report = exposed_packages(&client, &packages, Some(1), "TRUE", RowFormat::default())
+            .await
+            .unwrap();
+        assert_eq!(report.exposed.len(), 1);
+        assert_eq!(report.exposed[0]["package"], "acme-utils");
+        assert_eq!(report.exposed[0]["projectId"], public.to_string());
+        assert_eq!(report.exposed[0]["teaRank"], "12");
+        assert_eq!(report.exposed[0]["owners"], serde_json::json!(["mallory"]));
+        assert_eq!(report.unclaimed.len(), 1);
+        assert_eq!(report.unclaimed[0].name, "acme-internal");
+    }
+}
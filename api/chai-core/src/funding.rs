@@ -0,0 +1,110 @@
+use std::collections::{BTreeMap, BTreeSet};
+use url::Url;
+
+/// Funding channels keyed like GitHub's FUNDING.yml (`github`, `open_collective`, ...)
+pub type FundingLinks = BTreeMap<&'static str, BTreeSet<String>>;
+
+/// FUNDING.yml keys whose value is a bare account name on a known platform
+const PLATFORM_KEYS: &[&str] = &[
+    "github",
+    "open_collective",
+    "patreon",
+    "ko_fi",
+    "liberapay",
+    "buy_me_a_coffee",
+    "polar",
+    "thanks_dev",
+    "tidelift",
+];
+
+/// Collects funding channels from stored readmes and URLs.
+///
+/// Recognizes links to known funding platforms anywhere in the text as well as
+/// FUNDING.yml-style `key: value` lines. Everything is parsed locally; nothing
+/// here calls out to the network.
+pub fn extract_funding<'a>(texts: impl IntoIterator<Item = &'a str>) -> FundingLinks {
+    let mut links = FundingLinks::new();
+    for text in texts {
+        for line in text.lines() {
+            parse_funding_yml_line(line, &mut links);
+        }
+        for token in text.split(|c: char| c.is_whitespace() || "()<>[]\"'`,".contains(c)) {
+            if let Some((platform, account)) = classify_url(token) {
+                links.entry(platform).or_default().insert(account);
+            }
+        }
+    }
+    links
+}
+
+fn parse_funding_yml_line(line: &str, links: &mut FundingLinks) {
+    let Some((key, value)) = line.trim().split_once(':') else {
+        return;
+    };
+    let key = key.trim().to_ascii_lowercase();
+    let Some(platform) = PLATFORM_KEYS.iter().find(|k| **k == key) else {
+        return;
+    };
+
+    // `github: user` or `github: [user1, user2]`
+    let accounts = value.trim().trim_start_matches('[').trim_end_matches(']');
+    for account in accounts.split(',') {
+        let account = account.trim().trim_matches(|c| c == '"' || c == '\'');
+        if is_account_name(account) {
+            links
+                .entry(platform)
+                .or_default()
+                .insert(account.to_string());
+        }
+    }
+}
+
+fn classify_url(token: &str) -> Option<(&'static str, String)> {
+    let token = token.trim_end_matches(['.', ';', ':', '!', '?']);
+    let candidate = if token.contains("://") {
+        token.to_string()
+    } else if token.contains('.') && token.contains('/') {
+        format!("https://{token}")
+    } else {
+        return None;
+    };
+
+    let url = Url::parse(&candidate).ok()?;
+    let host = url
+        .host_str()?
+        .trim_start_matches("www.")
+        .to_ascii_lowercase();
+    let segments: Vec<&str> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+    let account = |index: usize| {
+        segments
+            .get(index)
+            .filter(|s| is_account_name(s))
+            .map(|s| s.to_string())
+    };
+
+    match host.as_str() {
+        "github.com" if segments.first() == Some(&"sponsors") => Some(("github", account(1)?)),
+        "opencollective.com" => Some(("open_collective", account(0)?)),
+        "patreon.com" => Some(("patreon", account(0)?)),
+        "ko-fi.com" => Some(("ko_fi", account(0)?)),
+        "liberapay.com" => Some(("liberapay", account(0)?)),
+        "buymeacoffee.com" => Some(("buy_me_a_coffee", account(0)?)),
+        "polar.sh" => Some(("polar", account(0)?)),
+        "thanks.dev" if segments.len() > 1 => Some(("thanks_dev", segments[1..].join("/"))),
+        "tidelift.com" if segments.len() > 1 => {
+            // tidelift.com/funding/github/<platform>/<package> or
+            // tidelift.com/subscription/pkg/<platform>-<package>
+            let package = segments.last()?;
+            Some(("tidelift", package.to_string()))
+        }
+        _ => None,
+    }
+}
+
+fn is_account_name(value: &str) -> bool {
+    !value.is_empty()
+        && value.len() <= 100
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+}
@@ -0,0 +1,520 @@
+//! The project dependency graph as a whole: how many edges it has, its cycles,
+//! its longest chains, and its hubs. Loading and walking it is too slow for a
+//! request, so the server computes [`graph_stats`] in the background and serves
+//! the last result.
+
+use chai_types::format::RowFormat;
+use chai_types::models::{DegreeProject, GraphHub, GraphProject, GraphStats};
+use chrono::Utc;
+use serde_json::Value;
+use std::collections::HashMap;
+use tokio_postgres::Client;
+use uuid::Uuid;
+
+use crate::db::timed;
+use crate::models::to_json;
+use crate::sql::LatestRank;
+
+/// How many projects the longest chain search starts from
+pub const DEPTH_SAMPLE: usize = 100;
+/// Hops after which a chain search stops, like the closure's depth limit
+pub const MAX_GRAPH_DEPTH: usize = 100;
+/// How many of the most connected projects are reported
+pub const GRAPH_HUBS: usize = 20;
+
+/// Not yet reached by Tarjan's search
+const UNVISITED: u32 = u32::MAX;
+
+/// Distinct dependency edges between two different live canons, as
+/// `dependent` and `dependency`
+pub fn graph_edges_query(live: &str) -> String {
+    format!(
+        r#"
+        WITH live AS MATERIALIZED (
+            SELECT c.id FROM canons c WHERE {live}
+        )
+        SELECT DISTINCT cp_from.canon_id AS dependent, cp_to.canon_id AS dependency
+        FROM legacy_dependencies ld
+        JOIN canon_packages cp_from ON cp_from.package_id = ld.package_id
+        JOIN canon_packages cp_to ON cp_to.package_id = ld.dependency_id
+        JOIN live l_from ON l_from.id = cp_from.canon_id
+        JOIN live l_to ON l_to.id = cp_to.canon_id
+        WHERE cp_from.canon_id <> cp_to.canon_id"#
+    )
+}
+
+/// Which way [`top_degree_query`] counts a project's edges
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Degree {
+    /// Projects depending on it directly
+    Dependents,
+    /// Projects it depends on directly
+    Dependencies,
+}
+
+/// The `$2` live canons with the most direct dependents or dependencies, with
+/// their rank in run `$1`, the latest
+pub fn top_degree_query(live: &str, degree: Degree) -> String {
+    let edges = graph_edges_query(live);
+    let rank = LatestRank::of("c.id").in_run("$1", true).join("tr");
+    let counted = match degree {
+        Degree::Dependents => "dependency",
+        Degree::Dependencies => "dependent",
+    };
+    format!(
+        r#"
+        SELECT c.id AS project_id, c.name, d.degree, tr.rank AS tea_rank
+        FROM (
+            SELECT e.{counted} AS canon_id, COUNT(*) AS degree
+            FROM ({edges}) e
+            GROUP BY 1
+        ) d
+        JOIN canons c ON c.id = d.canon_id
+        {rank}
+        ORDER BY d.degree DESC, c.name, c.id
+        LIMIT $2"#
+    )
+}
+
+/// Projects with at least one edge, and their dependencies in compressed rows:
+/// those of project `i` are `targets[offsets[i]..offsets[i + 1]]`
+pub struct DependencyGraph {
+    ids: Vec<Uuid>,
+    offsets: Vec<usize>,
+    targets: Vec<u32>,
+    dependents: Vec<u32>,
+}
+
+/// Strongly connected components of a [`DependencyGraph`]
+#[derive(Debug, PartialEq)]
+pub struct Components {
+    pub count: usize,
+    /// Components of more than one project, each a dependency cycle
+    pub cyclic: usize,
+    pub largest: usize,
+}
+
+/// State of [`DependencyGraph::components`]
+struct Tarjan<'a> {
+    graph: &'a DependencyGraph,
+    index: Vec<u32>,
+    low: Vec<u32>,
+    on_stack: Vec<bool>,
+    stack: Vec<u32>,
+    /// Each node being visited with the position of its next edge to follow
+    calls: Vec<(u32, usize)>,
+    next_index: u32,
+}
+
+impl Tarjan<'_> {
+    fn visit(&mut self, node: u32) {
+        self.index[node as usize] = self.next_index;
+        self.low[node as usize] = self.next_index;
+        self.next_index += 1;
+        self.on_stack[node as usize] = true;
+        self.stack.push(node);
+        self.calls.push((node, self.graph.offsets[node as usize]));
+    }
+}
+
+impl DependencyGraph {
+    /// Builds the graph of `(dependent, dependency)` edges, which must be
+    /// distinct
+    pub fn from_edges(edges: &[(Uuid, Uuid)]) -> Self {
+        let mut index = HashMap::new();
+        let mut ids = Vec::new();
+        let mut node = |id: Uuid| {
+            *index.entry(id).or_insert_with(|| {
+                ids.push(id);
+                (ids.len() - 1) as u32
+            })
+        };
+        let edges: Vec<(u32, u32)> = edges
+            .iter()
+            .map(|(from, to)| (node(*from), node(*to)))
+            .collect();
+
+        let mut offsets = vec![0; ids.len() + 1];
+        let mut dependents = vec![0; ids.len()];
+        for (from, to) in &edges {
+            offsets[*from as usize + 1] += 1;
+            dependents[*to as usize] += 1;
+        }
+        for i in 1..offsets.len() {
+            offsets[i] += offsets[i - 1];
+        }
+        let mut next = offsets.clone();
+        let mut targets = vec![0; edges.len()];
+        for (from, to) in edges {
+            targets[next[from as usize]] = to;
+            next[from as usize] += 1;
+        }
+        Self {
+            ids,
+            offsets,
+            targets,
+            dependents,
+        }
+    }
+
+    pub fn projects(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn edges(&self) -> usize {
+        self.targets.len()
+    }
+
+    fn dependencies(&self, node: u32) -> &[u32] {
+        &self.targets[self.offsets[node as usize]..self.offsets[node as usize + 1]]
+    }
+
+    /// Tarjan's algorithm, with an explicit stack so deep chains can't
+    /// overflow the thread's
+    pub fn components(&self) -> Components {
+        let mut search = Tarjan {
+            graph: self,
+            index: vec![UNVISITED; self.ids.len()],
+            low: vec![0; self.ids.len()],
+            on_stack: vec![false; self.ids.len()],
+            stack: Vec::new(),
+            calls: Vec::new(),
+            next_index: 0,
+        };
+        let mut components = Components {
+            count: 0,
+            cyclic: 0,
+            largest: 0,
+        };
+        for root in 0..self.ids.len() {
+            if search.index[root] != UNVISITED {
+                continue;
+            }
+            search.visit(root as u32);
+            while let Some(&(node, edge)) = search.calls.last() {
+                let v = node as usize;
+                if edge < self.offsets[v + 1] {
+                    search.calls.last_mut().expect("checked above").1 += 1;
+                    let next = self.targets[edge] as usize;
+                    if search.index[next] == UNVISITED {
+                        search.visit(next as u32);
+                    } else if search.on_stack[next] {
+                        search.low[v] = search.low[v].min(search.index[next]);
+                    }
+                    continue;
+                }
+
+                search.calls.pop();
+                if let Some(&(parent, _)) = search.calls.last() {
+                    let parent = parent as usize;
+                    search.low[parent] = search.low[parent].min(search.low[v]);
+                }
+                if search.low[v] == search.index[v] {
+                    let mut size = 0;
+                    while let Some(member) = search.stack.pop() {
+                        search.on_stack[member as usize] = false;
+                        size += 1;
+                        if member == node {
+                            break;
+                        }
+                    }
+                    components.count += 1;
+                    components.cyclic += usize::from(size > 1);
+                    components.largest = components.largest.max(size);
+                }
+            }
+        }
+        components
+    }
+
+    /// The longest of the shortest chains out of up to `sample` projects, cut
+    /// off at `max_depth` hops, dependent first, with how many projects were
+    /// searched. Searches start from projects nothing depends on, where chains
+    /// are longest, spread evenly over them by id so repeated runs agree.
+    pub fn longest_chain(&self, sample: usize, max_depth: usize) -> (usize, Vec<Uuid>) {
+        let has_dependencies = |node: &u32| !self.dependencies(*node).is_empty();
+        let mut starts: Vec<u32> = (0..self.ids.len() as u32)
+            .filter(|node| has_dependencies(node) && self.dependents[*node as usize] == 0)
+            .collect();
+        // With every project in a cycle, any may start a chain
+        if starts.is_empty() {
+            starts = (0..self.ids.len() as u32)
+                .filter(has_dependencies)
+                .collect();
+        }
+        starts.sort_unstable_by_key(|node| self.ids[*node as usize]);
+        let step = starts.len().div_ceil(sample.max(1)).max(1);
+        let starts: Vec<u32> = starts.into_iter().step_by(step).collect();
+
+        // Which search last reached each node, so nothing is cleared in between
+        let mut seen = vec![0; self.ids.len()];
+        let mut parent = vec![0; self.ids.len()];
+        let mut best: Vec<u32> = Vec::new();
+        for (search, start) in starts.iter().enumerate() {
+            let search = search + 1;
+            seen[*start as usize] = search;
+            let mut frontier = vec![*start];
+            let mut depth = 0;
+            let mut deepest = *start;
+            while !frontier.is_empty() && depth < max_depth {
+                let mut next = Vec::new();
+                for node in frontier {
+                    for dependency in self.dependencies(node) {
+                        if seen[*dependency as usize] != search {
+                            seen[*dependency as usize] = search;
+                            parent[*dependency as usize] = node;
+                            next.push(*dependency);
+                        }
+                    }
+                }
+                if let Some(first) = next.first() {
+                    depth += 1;
+                    deepest = *first;
+                }
+                frontier = next;
+            }
+            if depth + 1 > best.len() {
+                let mut chain = vec![deepest];
+                while *chain.last().expect("never empty") != *start {
+                    chain.push(parent[*chain.last().expect("never empty") as usize]);
+                }
+                chain.reverse();
+                best = chain;
+            }
+        }
+        let chain = best.iter().map(|node| self.ids[*node as usize]).collect();
+        (starts.len(), chain)
+    }
+
+    /// The `limit` projects with the most dependents and dependencies
+    /// together, as `(id, dependents, dependencies)`
+    pub fn hubs(&self, limit: usize) -> Vec<(Uuid, usize, usize)> {
+        let mut hubs: Vec<(Uuid, usize, usize)> = (0..self.ids.len() as u32)
+            .map(|node| {
+                (
+                    self.ids[node as usize],
+                    self.dependents[node as usize] as usize,
+                    self.dependencies(node).len(),
+                )
+            })
+            .collect();
+        hubs.sort_unstable_by(|a, b| (b.1 + b.2).cmp(&(a.1 + a.2)).then(a.0.cmp(&b.0)));
+        hubs.truncate(limit);
+        hubs
+    }
+}
+
+/// Loads the dependency graph between live canons
+pub async fn dependency_graph(
+    client: &Client,
+    live: &str,
+) -> Result<DependencyGraph, tokio_postgres::Error> {
+    let rows = timed("graph_edges", client.query(&graph_edges_query(live), &[])).await?;
+    let edges: Vec<(Uuid, Uuid)> = rows
+        .iter()
+        .map(|row| (row.get("dependent"), row.get("dependency")))
+        .collect();
+    Ok(DependencyGraph::from_edges(&edges))
+}
+
+/// At most `limit` canons with the highest `degree`, as [`DegreeProject`]s
+pub async fn top_degree(
+    client: &Client,
+    degree: Degree,
+    run: Option<i32>,
+    limit: i64,
+    live: &str,
+    format: RowFormat,
+) -> Result<Vec<Value>, tokio_postgres::Error> {
+    let rows = timed(
+        "top_degree",
+        client.query(&top_degree_query(live, degree), &[&run, &limit]),
+    )
+    .await?;
+    Ok(to_json::<DegreeProject>(&rows, format))
+}
+
+/// Edge, cycle, chain and hub statistics of the graph between live canons. The
+/// graph is walked on a blocking thread.
+pub async fn graph_stats(client: &Client, live: &str) -> Result<GraphStats, tokio_postgres::Error> {
+    let graph = dependency_graph(client, live).await?;
+    let (graph, components, (sampled, chain), hubs) = tokio::task::spawn_blocking(move || {
+        let components = graph.components();
+        let chain = graph.longest_chain(DEPTH_SAMPLE, MAX_GRAPH_DEPTH);
+        let hubs = graph.hubs(GRAPH_HUBS);
+        (graph, components, chain, hubs)
+    })
+    .await
+    .expect("graph statistics don't panic");
+
+    let ids: Vec<Uuid> = chain
+        .iter()
+        .chain(hubs.iter().map(|(id, _, _)| id))
+        .copied()
+        .collect();
+    let rows = timed(
+        "graph_names",
+        client.query("SELECT id, name FROM canons WHERE id = ANY($1)", &[&ids]),
+    )
+    .await?;
+    let names: HashMap<Uuid, String> = rows
+        .iter()
+        .map(|row| (row.get("id"), row.get("name")))
+        .collect();
+    let project = |id: Uuid| GraphProject {
+        project_id: id,
+        name: names.get(&id).cloned().unwrap_or_default(),
+    };
+
+    Ok(GraphStats {
+        computed_at: Utc::now(),
+        projects: graph.projects(),
+        edges: graph.edges(),
+        components: components.count,
+        cyclic_components: components.cyclic,
+        largest_component: components.largest,
+        depth_sample: sampled,
+        max_depth: chain.len().saturating_sub(1),
+        longest_chain: chain.into_iter().map(project).collect(),
+        hubs: hubs
+            .into_iter()
+            .map(|(id, dependents, dependencies)| GraphHub {
+                project_id: id,
+                name: names.get(&id).cloned().unwrap_or_default(),
+                dependents,
+                dependencies,
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_project, test_client};
+
+    fn ids(n: u128) -> Vec<Uuid> {
+        (1..=n).map(Uuid::from_u128).collect()
+    }
+
+    #[test]
+    fn graph_finds_cycles_chains_and_hubs() {
+        let id = ids(7);
+        // 1 -> 2 -> 3 -> 4, with 3 <-> 5 a cycle; 6 -> 4 and 7 alone with 6
+        let graph = DependencyGraph::from_edges(&[
+            (id[0], id[1]),
+            (id[1], id[2]),
+            (id[2], id[3]),
+            (id[2], id[4]),
+            (id[4], id[2]),
+            (id[5], id[3]),
+            (id[6], id[5]),
+        ]);
+        assert_eq!((graph.projects(), graph.edges()), (7, 7));
+        assert_eq!(
+            graph.components(),
+            Components {
+                count: 6,
+                cyclic: 1,
+                largest: 2,
+            }
+        );
+
+        let (sampled, chain) = graph.longest_chain(10, MAX_GRAPH_DEPTH);
+        assert_eq!(sampled, 2);
+        assert_eq!(chain, [id[0], id[1], id[2], id[3]]);
+        // The depth limit cuts chains short
+        assert_eq!(graph.longest_chain(10, 2).1.len(), 3);
+
+        assert_eq!(graph.hubs(1), [(id[2], 2, 2)]);
+    }
+
+    #[tokio::test]
+    async fn graph_stats_name_chain_and_hubs() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        let app = insert_project(&client, "app", "npm", &[]).await;
+        let lib = insert_project(&client, "lib", "npm", &[]).await;
+        let base = insert_project(&client, "base", "npm", &[]).await;
+        for (from, to) in [(app, lib), (lib, base)] {
+            client
+                .execute(
+                    r#"
+                    INSERT INTO legacy_dependencies (package_id, dependency_id)
+                    SELECT f.package_id, t.package_id
+                    FROM canon_packages f, canon_packages t
+                    WHERE f.canon_id = $1 AND t.canon_id = $2"#,
+                    &[&from, &to],
+                )
+                .await
+                .unwrap();
+        }
+
+        let stats = graph_stats(&client, "TRUE").await.unwrap();
+        assert_eq!((stats.projects, stats.edges), (3, 2));
+        assert_eq!((stats.components, stats.cyclic_components), (3, 0));
+        assert_eq!(stats.max_depth, 2);
+        let chain: Vec<&str> = stats
+            .longest_chain
+            .iter()
+            .map(|project| project.name.as_str())
+            .collect();
+        assert_eq!(chain, ["app", "lib", "base"]);
+        assert_eq!(stats.hubs.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn top_degree_counts_projects_not_packages() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        let openssl = insert_project(&client, "openssl", "homebrew", &[(1, "80")]).await;
+        let curl = insert_project(&client, "curl", "homebrew", &[(1, "60")]).await;
+        let git = insert_project(&client, "git", "homebrew", &[]).await;
+        for (from, to) in [(curl, openssl), (git, openssl), (git, curl), (git, curl)] {
+            client
+                .execute(
+                    r#"
+                    INSERT INTO legacy_dependencies (package_id, dependency_id)
+                    SELECT f.package_id, t.package_id
+                    FROM canon_packages f, canon_packages t
+                    WHERE f.canon_id = $1 AND t.canon_id = $2"#,
+                    &[&from, &to],
+                )
+                .await
+                .unwrap();
+        }
+
+        let client = &client;
+        let top = |degree| async move {
+            top_degree(client, degree, Some(1), 10, "TRUE", RowFormat::default())
+                .await
+                .unwrap()
+                .iter()
+                .map(|p| {
+                    let rank = p["teaRank"].as_str().map(str::to_string);
+                    (
+                        p["name"].as_str().unwrap().to_string(),
+                        p["degree"].as_i64().unwrap(),
+                        rank,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            top(Degree::Dependents).await,
+            [
+                ("openssl".to_string(), 2, Some("80".to_string())),
+                ("curl".to_string(), 1, Some("60".to_string())),
+            ]
+        );
+        assert_eq!(
+            top(Degree::Dependencies).await,
+            [
+                ("git".to_string(), 2, None),
+                ("curl".to_string(), 1, Some("60".to_string())),
+            ]
+        );
+    }
+}
@@ -0,0 +1,753 @@
+//! Ranked lists of projects: the top of a run, a chosen set of projects by
+//! rank, the newcomers of a run, and whole-run exports. The first two apply
+//! the leaderboard overrides curators set.
+//!
+//! Functions taking `live` expect a SQL predicate over canons `c` that filters
+//! out soft-deleted rows, or `TRUE`.
+
+use chai_types::format::RowFormat;
+use chai_types::models::{LeaderboardEntry, LeaderboardOverride, NewEntrant};
+use chai_types::sort::{Sort, SortValue};
+use chrono::{NaiveDate, NaiveDateTime};
+use serde_json::Value;
+use std::sync::Arc;
+use tokio_postgres::{Client, Row};
+use uuid::Uuid;
+
+use crate::db::timed;
+use crate::models::to_json;
+use crate::sql::{
+    first_source_url, has_source_url, source_urls, LatestRank, Params, CANON_BASE, HOMEPAGE,
+};
+
+/// The unquarantined run before `$1`, in a row that only exists if run `$1` does
+pub const PREVIOUS_RUN_QUERY: &str = r#"
+    SELECT (SELECT MAX(run) FROM tea_rank_runs WHERE run < $1 AND quarantined_at IS NULL)
+    FROM tea_rank_runs
+    WHERE run = $1"#;
+
+/// Fields `?sort` accepts on `/leaderboard/new`
+pub const NEW_ENTRANT_SORT_FIELDS: [&str; 4] = ["position", "rank", "name", "projectId"];
+
+/// Canons ranked in run `$1` but not in run `$2`, at most `$3`, by the position
+/// they entered at unless `sort` says otherwise
+pub fn new_entrants_query(live: &str, sort: Option<&Sort>) -> String {
+    let order = match sort {
+        Some(sort) => sort.order_by(|field| {
+            match field {
+                "rank" => "CAST(ranked.rank AS NUMERIC)",
+                "name" => "c.name",
+                "projectId" => "c.id",
+                _ => "ranked.position",
+            }
+            .to_string()
+        }),
+        None => "ranked.position".to_string(),
+    };
+    format!(
+        r#"
+        WITH ranked AS (
+            SELECT
+                tr.canon_id,
+                tr.rank,
+                ROW_NUMBER() OVER (ORDER BY CAST(tr.rank AS NUMERIC) DESC, tr.canon_id) AS position
+            FROM tea_ranks tr
+            JOIN canons c ON c.id = tr.canon_id
+            WHERE tr.tea_rank_run = $1
+            AND CAST(tr.rank AS NUMERIC) > 0
+            AND {live}
+        )
+        SELECT
+            c.id AS project_id,
+            c.name,
+            ranked.rank AS tea_rank,
+            ranked.position AS position
+        FROM ranked
+        JOIN canons c ON c.id = ranked.canon_id
+        WHERE NOT EXISTS (
+            SELECT 1
+            FROM tea_ranks prev
+            WHERE prev.canon_id = ranked.canon_id
+            AND prev.tea_rank_run = $2
+            AND CAST(prev.rank AS NUMERIC) > 0
+        )
+        ORDER BY {order}, ranked.position
+        LIMIT $3"#
+    )
+}
+
+/// The run before `run`, or `None` when `run` doesn't exist. The inner `None`
+/// means `run` is the first.
+pub async fn previous_run(
+    client: &Client,
+    run: i32,
+) -> Result<Option<Option<i32>>, tokio_postgres::Error> {
+    let row = timed(
+        "previous_run",
+        client.query_opt(PREVIOUS_RUN_QUERY, &[&run]),
+    )
+    .await?;
+    Ok(row.map(|row| row.get(0)))
+}
+
+/// At most `limit` canons ranked in `run` but not in `previous`, as [`NewEntrant`]s
+pub async fn new_entrants(
+    client: &Client,
+    run: i32,
+    previous: Option<i32>,
+    limit: i64,
+    live: &str,
+    sort: Option<&Sort>,
+    format: RowFormat,
+) -> Result<Vec<Value>, tokio_postgres::Error> {
+    let rows = timed(
+        "new_entrants",
+        client.query(&new_entrants_query(live, sort), &[&run, &previous, &limit]),
+    )
+    .await?;
+    Ok(to_json::<NewEntrant>(&rows, format))
+}
+
+/// Overrides in force, as a `WITH` list entry: canon `canon_id` is left out
+/// when `pinned_rank` is null, and shown with `pinned_rank` otherwise. A pin
+/// replaces a rank the canon has but doesn't rank an unranked canon.
+const ACTIVE_OVERRIDES: &str = r#"
+        overrides AS (
+            SELECT canon_id, pinned_rank
+            FROM leaderboard_overrides
+            WHERE expires_at IS NULL OR expires_at > now()
+        )"#;
+
+/// Fields `?fields` accepts on leaderboard endpoints, those of
+/// [`LeaderboardEntry`]
+pub const LEADERBOARD_ENTRY_FIELDS: [&str; 7] = [
+    "projectId",
+    "homepage",
+    "name",
+    "source",
+    "teaRank",
+    "teaRankCalculatedAt",
+    "packageManagers",
+];
+
+/// Fields of every leaderboard entry, however the entries were picked. Expects
+/// the canon as `c`, its rank row as `tr`, and its homepage and source URLs as
+/// `u_homepage` and `u_source`. `packageManagers` is null, for the caller to
+/// fill in from [`package_managers_by_canon`].
+///
+/// [`package_managers_by_canon`]: crate::projects::package_managers_by_canon
+fn leaderboard_fields() -> String {
+    format!(
+        r#"
+                {CANON_BASE},
+                u_source.url AS source,
+                COALESCE(tr.rank,'0') AS tea_rank,
+                tr.created_at AS tea_rank_calculated_at,
+                NULL::text[] AS package_managers"#
+    )
+}
+
+/// Leaderboard rows for `$1::uuid[]`, best rank first, at most `$2`, with each
+/// canon's latest rank, or with `as_of` its latest as of run `$3`, after
+/// overrides. Canons without a positive rank are left out unless
+/// `include_unranked`, in which case they come last with a `teaRank` of 0.
+pub fn leaderboard_projects_query(live: &str, include_unranked: bool, as_of: bool) -> String {
+    let mut params = Params::default();
+    let ids = params.placeholder();
+    let limit = params.placeholder();
+    let run = as_of.then(|| params.placeholder());
+    let fields = leaderboard_fields();
+    let source_urls = source_urls("c.id");
+    let latest_rank = match &run {
+        Some(run) => LatestRank::of("c.id").as_of(run),
+        None => LatestRank::of("c.id"),
+    }
+    .positive()
+    .pinned_by("overrides")
+    .join("tr");
+    let ranked = if include_unranked {
+        "TRUE"
+    } else {
+        "tr.canon_id IS NOT NULL"
+    };
+    format!(
+        r#"
+        WITH {ACTIVE_OVERRIDES}
+        SELECT *
+        FROM (
+            SELECT DISTINCT ON (c.id) {fields}
+            FROM canons c
+            {HOMEPAGE}
+            {source_urls}
+            LEFT JOIN overrides o ON o.canon_id = c.id
+            {latest_rank}
+            WHERE
+            c.id = ANY({ids}::uuid[])
+            AND (o.canon_id IS NULL OR o.pinned_rank IS NOT NULL)
+            AND {ranked}
+            AND {live}
+            ORDER BY c.id, tr.created_at DESC, u_source.url
+        ) sub
+        ORDER BY CAST(tea_rank AS NUMERIC) DESC NULLS LAST
+        LIMIT {limit}"#
+    )
+}
+
+/// At most `limit` of canons `ids`, best rank first, as [`LeaderboardEntry`]s
+/// ranked as of `run`, or as of now when `None`
+pub async fn leaderboard_projects(
+    client: &Client,
+    ids: &[Uuid],
+    limit: i64,
+    run: Option<i32>,
+    live: &str,
+    include_unranked: bool,
+    format: RowFormat,
+) -> Result<Vec<Value>, tokio_postgres::Error> {
+    let query = leaderboard_projects_query(live, include_unranked, run.is_some());
+    let rows = match run {
+        Some(run) => {
+            timed(
+                "leaderboard_projects",
+                client.query(&query, &[&ids, &limit, &run]),
+            )
+            .await?
+        }
+        None => {
+            timed(
+                "leaderboard_projects",
+                client.query(&query, &[&ids, &limit]),
+            )
+            .await?
+        }
+    };
+    Ok(to_json::<LeaderboardEntry>(&rows, format))
+}
+
+/// Fields `?sort` accepts on leaderboard endpoints
+pub const LEADERBOARD_SORT_FIELDS: [&str; 4] = ["rank", "name", "projectId", "calculatedAt"];
+
+/// SQL for a leaderboard sort field, over the aliases of [`leaderboard_fields`]
+fn leaderboard_sort_column(field: &str) -> String {
+    match field {
+        "rank" => "CAST(tr.rank AS NUMERIC)",
+        "name" => "c.name",
+        "calculatedAt" => "tr.created_at",
+        _ => "c.id",
+    }
+    .to_string()
+}
+
+/// In-memory counterpart of [`leaderboard_sort_column`] for built entries
+fn leaderboard_sort_value(field: &str, project: &Value) -> Option<SortValue> {
+    let text = |key: &str| {
+        project[key]
+            .as_str()
+            .map(|s| SortValue::Text(s.to_string()))
+    };
+    match field {
+        "rank" => Some(SortValue::Number(rank_of(project))),
+        "name" => text("name"),
+        "calculatedAt" => text("teaRankCalculatedAt"),
+        _ => text("projectId"),
+    }
+}
+
+/// Schemes a source URL under a host may be recorded with
+const SOURCE_SCHEMES: [&str; 6] = [
+    "https://",
+    "http://",
+    "https://www.",
+    "http://www.",
+    "git+https://",
+    "git://",
+];
+
+/// Narrows the top of a run to canons whose name starts with a prefix, or
+/// with a source URL under a host or organization, e.g. `github.com/rust-lang`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LeaderboardFilter {
+    /// `LIKE` pattern over canon names
+    name: Option<String>,
+    /// `LIKE` patterns, one per way of writing the source URL
+    sources: Option<Vec<String>>,
+}
+
+impl LeaderboardFilter {
+    /// A filter on `name_prefix` and `source_host` (`github.com`,
+    /// `github.com/rust-lang` or `github.com/rust-lang/*`), or why one of them
+    /// is not usable
+    pub fn new(name_prefix: Option<&str>, source_host: Option<&str>) -> Result<Self, String> {
+        let name = match name_prefix {
+            Some("") => return Err("namePrefix cannot be empty".to_string()),
+            Some(prefix) => Some(format!("{}%", escape_like(prefix))),
+            None => None,
+        };
+        let sources = source_host.map(source_patterns).transpose()?;
+        Ok(Self { name, sources })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.sources.is_none()
+    }
+}
+
+/// `value` matched literally by `LIKE`
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// `LIKE` prefixes for URLs under `pattern`, in each form a source URL takes:
+/// with any of [`SOURCE_SCHEMES`] or as an scp-style `git@host:path`
+pub(crate) fn source_patterns(pattern: &str) -> Result<Vec<String>, String> {
+    let trimmed = pattern.trim();
+    let unschemed = trimmed.split_once("://").map_or(trimmed, |(_, rest)| rest);
+    let prefix = unschemed.trim_end_matches('*').trim_end_matches('/');
+    let (host, path) = prefix.split_once('/').unwrap_or((prefix, ""));
+    let host = host.to_ascii_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host);
+    if host.is_empty() || prefix.contains('*') {
+        return Err(format!(
+            "Invalid sourceHost '{trimmed}': expected a host, optionally followed by a path \
+             such as github.com/rust-lang"
+        ));
+    }
+
+    let under = escape_like(&if path.is_empty() {
+        host.to_string()
+    } else {
+        format!("{host}/{path}")
+    });
+    let mut patterns: Vec<String> = SOURCE_SCHEMES
+        .iter()
+        .map(|scheme| format!("{scheme}{under}/%"))
+        .collect();
+    patterns.push(if path.is_empty() {
+        format!("git@{}:%", escape_like(host))
+    } else {
+        format!("git@{}:{}/%", escape_like(host), escape_like(path))
+    });
+    Ok(patterns)
+}
+
+/// The `$2` best-ranked canons of run `$1` after overrides, in the same shape
+/// as [`leaderboard_projects_query`]. Ranks are picked first so only the top
+/// rows are hydrated; a canon without a source URL gets a null `source`.
+/// `sort` reorders the picked rows, best first by default. `$3` and `$4` are
+/// the name and source patterns of a [`LeaderboardFilter`], unset when null.
+pub fn top_projects_query(live: &str, sort: Option<&Sort>) -> String {
+    let mut params = Params::default();
+    let run = params.placeholder();
+    let limit = params.placeholder();
+    let name = params.placeholder();
+    let sources = params.placeholder();
+    let fields = leaderboard_fields();
+    let has_source_url = has_source_url("c.id", Some(&sources));
+    let first_source_url = first_source_url("c.id");
+    let order = match sort {
+        Some(sort) => sort.order_by(leaderboard_sort_column),
+        None => "CAST(tr.rank AS NUMERIC) DESC".to_string(),
+    };
+    format!(
+        r#"
+        WITH {ACTIVE_OVERRIDES},
+        top AS (
+            SELECT r.canon_id, COALESCE(o.pinned_rank, r.rank) AS rank, r.created_at
+            FROM tea_ranks r
+            JOIN canons c ON c.id = r.canon_id
+            LEFT JOIN overrides o ON o.canon_id = r.canon_id
+            WHERE r.tea_rank_run = {run}
+            AND CAST(r.rank AS NUMERIC) > 0
+            AND (o.canon_id IS NULL OR o.pinned_rank IS NOT NULL)
+            AND ({name}::text IS NULL OR c.name LIKE {name})
+            AND ({sources}::text[] IS NULL OR {has_source_url})
+            AND {live}
+            ORDER BY CAST(COALESCE(o.pinned_rank, r.rank) AS NUMERIC) DESC, r.canon_id
+            LIMIT {limit}
+        )
+        SELECT {fields}
+        FROM top tr
+        JOIN canons c ON c.id = tr.canon_id
+        {HOMEPAGE}
+        {first_source_url}
+        ORDER BY {order}, c.id"#
+    )
+}
+
+/// The `limit` best-ranked canons of `run` that pass `filter`, as
+/// [`LeaderboardEntry`]s
+pub async fn top_projects(
+    client: &Client,
+    run: i32,
+    limit: i64,
+    filter: &LeaderboardFilter,
+    live: &str,
+    sort: Option<&Sort>,
+    format: RowFormat,
+) -> Result<Vec<Value>, tokio_postgres::Error> {
+    let rows = timed(
+        "top_projects",
+        client.query(
+            &top_projects_query(live, sort),
+            &[&run, &limit, &filter.name, &filter.sources],
+        ),
+    )
+    .await?;
+    Ok(to_json::<LeaderboardEntry>(&rows, format))
+}
+
+/// Every leaderboard override, expired ones included, as [`LeaderboardOverride`]s
+/// newest first; `id` narrows them to one canon
+pub async fn leaderboard_overrides(
+    client: &Client,
+    id: Option<Uuid>,
+    format: RowFormat,
+) -> Result<Vec<Value>, tokio_postgres::Error> {
+    let query = r#"
+        SELECT
+            lo.canon_id AS project_id,
+            c.name,
+            CASE WHEN lo.pinned_rank IS NULL THEN 'exclude' ELSE 'pin' END AS action,
+            lo.pinned_rank AS tea_rank,
+            lo.reason,
+            lo.expires_at,
+            COALESCE(lo.expires_at <= now(), FALSE) AS expired,
+            lo.created_at
+        FROM leaderboard_overrides lo
+        JOIN canons c ON c.id = lo.canon_id
+        WHERE $1::uuid IS NULL OR lo.canon_id = $1
+        ORDER BY lo.created_at DESC, lo.canon_id"#;
+    let rows = timed("leaderboard_overrides", client.query(query, &[&id])).await?;
+    Ok(to_json::<LeaderboardOverride>(&rows, format))
+}
+
+/// A leaderboard entry's rank; ranks are decimal strings, so compare them as floats
+pub fn rank_of(project: &Value) -> f64 {
+    project
+        .get("teaRank")
+        .and_then(|v| v.as_str())
+        .and_then(|s| s.parse::<f64>().ok())
+        .unwrap_or(0.0)
+}
+
+/// The `limit` best-ranked of `projects`, reordered by `sort` if one was asked
+/// for, like [`top_projects_query`] orders its page
+pub fn best_ranked(projects: Vec<Arc<Value>>, limit: i64, sort: Option<&Sort>) -> Vec<Value> {
+    let mut projects = projects;
+    projects.sort_by(|a, b| rank_of(b).total_cmp(&rank_of(a)));
+    projects.truncate(limit as usize);
+
+    if let Some(sort) = sort {
+        projects.sort_by(|a, b| sort.compare(a, b, |field, p| leaderboard_sort_value(field, p)));
+    }
+
+    // Arc<Value> doesn't implement Serialize, so hand back owned values
+    projects
+        .into_iter()
+        .map(|arc_val| (*arc_val).clone())
+        .collect()
+}
+
+pub async fn run_exists(client: &Client, run: i32) -> Result<bool, tokio_postgres::Error> {
+    let row = timed(
+        "run_exists",
+        client.query_opt("SELECT 1 FROM tea_rank_runs WHERE run = $1", &[&run]),
+    )
+    .await?;
+    Ok(row.is_some())
+}
+
+/// When `run` was created, or `None` when it doesn't exist
+pub async fn run_created_at(
+    client: &Client,
+    run: i32,
+) -> Result<Option<NaiveDateTime>, tokio_postgres::Error> {
+    let row = timed(
+        "run_created_at",
+        client.query_one(
+            "SELECT MIN(created_at) FROM tea_rank_runs WHERE run = $1",
+            &[&run],
+        ),
+    )
+    .await?;
+    Ok(row.get(0))
+}
+
+/// The last unquarantined run created on or before `day` (UTC), or `None`
+/// when there was none yet
+pub async fn run_as_of(
+    client: &Client,
+    day: NaiveDate,
+) -> Result<Option<i32>, tokio_postgres::Error> {
+    let row = timed(
+        "run_as_of",
+        client.query_one(
+            r#"
+            SELECT MAX(run) FROM tea_rank_runs
+            WHERE created_at < $1::date + 1 AND quarantined_at IS NULL"#,
+            &[&day],
+        ),
+    )
+    .await?;
+    Ok(row.get(0))
+}
+
+/// Every positively ranked project of `run`, best first, as [`ExportEntry`] rows
+///
+/// [`ExportEntry`]: chai_types::models::ExportEntry
+pub async fn leaderboard_rows(
+    client: &Client,
+    run: i32,
+) -> Result<Vec<Row>, tokio_postgres::Error> {
+    let query = r#"
+        SELECT
+            c.id AS project_id,
+            c.name,
+            tr.rank AS tea_rank
+        FROM tea_ranks tr
+        JOIN canons c ON c.id = tr.canon_id
+        WHERE tr.tea_rank_run = $1
+            AND CAST(tr.rank AS NUMERIC) > 0
+        ORDER BY CAST(tr.rank AS NUMERIC) DESC, c.id"#;
+    timed("leaderboard_export", client.query(query, &[&run])).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_project, test_client};
+    use serde_json::json;
+
+    const LEADERBOARD_SHAPE: [&str; 7] = [
+        "homepage",
+        "name",
+        "packageManagers",
+        "projectId",
+        "source",
+        "teaRank",
+        "teaRankCalculatedAt",
+    ];
+
+    fn keys(project: &Value) -> Vec<&str> {
+        let mut keys: Vec<&str> = project
+            .as_object()
+            .expect("leaderboard entries are objects")
+            .keys()
+            .map(String::as_str)
+            .collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    #[tokio::test]
+    async fn leaderboard_branches_return_identical_entries() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        let serde = insert_project(&client, "serde", "crates", &[(1, "12.5")]).await;
+        let tokio = insert_project(&client, "tokio", "crates", &[(1, "3.25")]).await;
+
+        let top = top_projects(
+            &client,
+            1,
+            10,
+            &LeaderboardFilter::default(),
+            "TRUE",
+            None,
+            RowFormat::default(),
+        )
+        .await
+        .unwrap();
+        let ids = vec![serde, tokio];
+        let filtered =
+            leaderboard_projects(&client, &ids, 10, None, "TRUE", false, RowFormat::default())
+                .await
+                .unwrap();
+
+        assert_eq!(top.len(), 2);
+        for project in top.iter().chain(&filtered) {
+            assert_eq!(keys(project), LEADERBOARD_SHAPE);
+        }
+        assert_eq!(top, filtered);
+        // Filled in by the caller from package_managers_by_canon
+        assert_eq!(top[0]["packageManagers"], Value::Null);
+        assert_eq!(top[0]["source"], json!("https://github.com/example/serde"));
+    }
+
+    #[tokio::test]
+    async fn top_projects_order_ranks_numerically() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        insert_project(&client, "small", "npm", &[(1, "9.5")]).await;
+        insert_project(&client, "large", "npm", &[(1, "10.25")]).await;
+        insert_project(&client, "unranked", "npm", &[(1, "0")]).await;
+        insert_project(&client, "other-run", "npm", &[(2, "99")]).await;
+
+        let rows = client
+            .query(
+                &top_projects_query("TRUE", None),
+                &[&1i32, &10i64, &None::<String>, &None::<Vec<String>>],
+            )
+            .await
+            .unwrap();
+        let names: Vec<String> = rows.iter().map(|row| row.get("name")).collect();
+        assert_eq!(names, ["large", "small"]);
+    }
+
+    #[tokio::test]
+    async fn top_projects_filter_by_name_prefix_and_source_host() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        insert_project(&client, "serde_json", "crates", &[(1, "3")]).await;
+        insert_project(&client, "serdexjson", "crates", &[(1, "2")]).await;
+        let moved = insert_project(&client, "tokio", "crates", &[(1, "1")]).await;
+        client
+            .execute(
+                r#"
+                UPDATE urls SET url = 'git@github.com:tokio-rs/tokio.git'
+                WHERE id IN (
+                    SELECT pu.url_id
+                    FROM canon_packages cp
+                    JOIN package_urls pu ON pu.package_id = cp.package_id
+                    WHERE cp.canon_id = $1
+                )"#,
+                &[&moved],
+            )
+            .await
+            .unwrap();
+
+        let names = |name: Option<&'static str>, source: Option<&'static str>| {
+            let client = &client;
+            async move {
+                let filter = LeaderboardFilter::new(name, source).unwrap();
+                top_projects(client, 1, 10, &filter, "TRUE", None, RowFormat::default())
+                    .await
+                    .unwrap()
+                    .iter()
+                    .map(|p| p["name"].as_str().unwrap().to_string())
+                    .collect::<Vec<_>>()
+            }
+        };
+        // `_` is literal, not a wildcard
+        assert_eq!(names(Some("serde_"), None).await, ["serde_json"]);
+        assert_eq!(
+            names(None, Some("https://GitHub.com/example/*")).await,
+            ["serde_json", "serdexjson"]
+        );
+        assert_eq!(
+            names(None, Some("github.com/exam")).await,
+            Vec::<String>::new()
+        );
+        assert_eq!(names(None, Some("github.com/tokio-rs")).await, ["tokio"]);
+        assert_eq!(
+            names(None, Some("www.github.com")).await,
+            ["serde_json", "serdexjson", "tokio"]
+        );
+        assert_eq!(
+            names(Some("serdex"), Some("github.com/example")).await,
+            ["serdexjson"]
+        );
+        assert!(LeaderboardFilter::new(None, Some("github.*/rust")).is_err());
+        assert!(LeaderboardFilter::new(Some(""), None).is_err());
+    }
+
+    #[tokio::test]
+    async fn unranked_projects_are_opt_in() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        let ranked = insert_project(&client, "ranked", "pypi", &[(1, "4.5")]).await;
+        let zero = insert_project(&client, "zero", "pypi", &[(1, "0")]).await;
+        let never = insert_project(&client, "never", "pypi", &[]).await;
+        let ids = vec![ranked, zero, never];
+
+        let names = |rows: Vec<tokio_postgres::Row>| -> Vec<String> {
+            rows.iter().map(|row| row.get("name")).collect()
+        };
+        let rows = client
+            .query(
+                &leaderboard_projects_query("TRUE", false, false),
+                &[&ids, &10i64],
+            )
+            .await
+            .unwrap();
+        assert_eq!(names(rows), ["ranked"]);
+
+        let projects =
+            leaderboard_projects(&client, &ids, 10, None, "TRUE", true, RowFormat::default())
+                .await
+                .unwrap();
+        assert_eq!(projects.len(), 3);
+        assert_eq!(projects[0]["name"], json!("ranked"));
+        for project in &projects[1..] {
+            assert_eq!(project["teaRank"], json!("0"));
+            assert_eq!(keys(project), LEADERBOARD_SHAPE);
+        }
+    }
+
+    #[tokio::test]
+    async fn overrides_exclude_and_pin_until_they_expire() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        let spam = insert_project(&client, "spam", "npm", &[(1, "50")]).await;
+        let pinned = insert_project(&client, "pinned", "npm", &[(1, "1.5")]).await;
+        let lapsed = insert_project(&client, "lapsed", "npm", &[(1, "40")]).await;
+        let plain = insert_project(&client, "plain", "npm", &[(1, "10")]).await;
+        client
+            .execute(
+                r#"
+                INSERT INTO leaderboard_overrides (canon_id, pinned_rank, reason, expires_at)
+                VALUES
+                    ($1, NULL, 'spam', NULL),
+                    ($2, '20', 'curated', now() + interval '1 day'),
+                    ($3, NULL, 'lapsed', now() - interval '1 day')"#,
+                &[&spam, &pinned, &lapsed],
+            )
+            .await
+            .unwrap();
+
+        let names = |projects: &[Value]| -> Vec<String> {
+            projects
+                .iter()
+                .map(|p| p["name"].as_str().unwrap().to_string())
+                .collect()
+        };
+        let top = top_projects(
+            &client,
+            1,
+            10,
+            &LeaderboardFilter::default(),
+            "TRUE",
+            None,
+            RowFormat::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(names(&top), ["lapsed", "pinned", "plain"]);
+        assert_eq!(top[1]["teaRank"], json!("20"));
+
+        let ids = vec![spam, pinned, lapsed, plain];
+        let filtered =
+            leaderboard_projects(&client, &ids, 10, None, "TRUE", false, RowFormat::default())
+                .await
+                .unwrap();
+        assert_eq!(filtered, top);
+
+        let overrides = leaderboard_overrides(&client, None, RowFormat::default())
+            .await
+            .unwrap();
+        assert_eq!(overrides.len(), 3);
+        let lapsed = overrides
+            .iter()
+            .find(|o| o["name"] == json!("lapsed"))
+            .unwrap();
+        assert_eq!(lapsed["expired"], json!(true));
+        assert_eq!(lapsed["action"], json!("exclude"));
+    }
+}
@@ -0,0 +1,22 @@
+//! The CHAI database as the API reads it, without the HTTP layer: SQL for every
+//! business query, the row-to-JSON conversion that fills the `chai-types`
+//! payloads, and the in-memory project caches. The `chai-api` binary wraps these in actix
+//! handlers; other front ends can run the same queries against their own
+//! `tokio_postgres` connections.
+
+pub mod cache;
+pub mod db;
+pub mod dependencies;
+pub mod funding;
+pub mod graph;
+pub mod leaderboard;
+pub mod models;
+pub mod orgs;
+pub mod projects;
+pub mod ranks;
+pub mod rows;
+pub mod sql;
+pub mod stats;
+pub mod suspects;
+#[cfg(any(test, feature = "test-support"))]
+pub mod test_support;
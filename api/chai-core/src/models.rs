@@ -0,0 +1,306 @@
+//! Building the [`chai_types::models`] payloads from query results. Queries
+//! select plain snake_case columns, and each payload reads its own.
+
+use chai_types::format::{Int8, RowFormat, Timestamp};
+use chai_types::models::{
+    ClosureEntry, CompactProject, CrossEcosystemProject, DegreeProject, DependentProject,
+    EcosystemCombination, ExportEntry, ExposedPackage, LeaderboardEntry, LeaderboardOverride,
+    MaintainerProject, MetricsPoint, NewEntrant, OrgProject, OrgRank, Project, RankContributor,
+    SbomMatch, SearchResult, SimilarName, StaleProject, Suspect,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+use crate::suspects::edit_distance;
+
+/// A struct built from one result row
+pub trait FromRow {
+    fn from_row(row: &Row, format: RowFormat) -> Self;
+}
+
+/// `rows` as `T` payloads, ready for caching and rank rounding
+pub fn to_json<T: FromRow + Serialize>(rows: &[Row], format: RowFormat) -> Vec<Value> {
+    rows.iter()
+        .map(|row| json!(T::from_row(row, format)))
+        .collect()
+}
+
+impl FromRow for LeaderboardEntry {
+    fn from_row(row: &Row, format: RowFormat) -> Self {
+        Self {
+            project_id: row.get("project_id"),
+            homepage: row.get("homepage"),
+            name: row.get("name"),
+            source: row.get("source"),
+            tea_rank: row.get("tea_rank"),
+            tea_rank_calculated_at: Timestamp(row.get("tea_rank_calculated_at"), format),
+            package_managers: row.get("package_managers"),
+            unranked: false,
+        }
+    }
+}
+
+impl FromRow for Project {
+    fn from_row(row: &Row, format: RowFormat) -> Self {
+        Self {
+            project_id: row.get("project_id"),
+            homepage: row.get("homepage"),
+            name: row.get("name"),
+            slug: row.get("slug"),
+            source: row.get("source"),
+            tea_rank: row.get("tea_rank"),
+            tea_rank_calculated_at: Timestamp(row.get("tea_rank_calculated_at"), format),
+            package_managers: row.get("package_managers"),
+            dependencies_count: Int8(row.get("dependencies_count"), format),
+            dependents_count: Int8(row.get("dependents_count"), format),
+            versions: row.get("versions"),
+            redirected_from: None,
+        }
+    }
+}
+
+impl FromRow for CompactProject {
+    fn from_row(row: &Row, _: RowFormat) -> Self {
+        Self {
+            project_id: row.get("project_id"),
+            name: row.get("name"),
+            tea_rank: row.get("tea_rank"),
+            redirected_from: None,
+        }
+    }
+}
+
+impl FromRow for SearchResult {
+    fn from_row(row: &Row, _: RowFormat) -> Self {
+        Self {
+            project_id: row.get("project_id"),
+            homepage: row.get("homepage"),
+            name: row.get("name"),
+            source: row.get("source"),
+            package_managers: row.get("package_managers"),
+        }
+    }
+}
+
+impl FromRow for MaintainerProject {
+    fn from_row(row: &Row, format: RowFormat) -> Self {
+        Self {
+            project_id: row.get("project_id"),
+            name: row.get("name"),
+            verified_at: Timestamp(row.get("verified_at"), format),
+            tea_rank: row.get("tea_rank"),
+            previous_tea_rank: row.get("previous_tea_rank"),
+            rank_change: row.get("rank_change"),
+            rank_trend: row.get("rank_trend"),
+            dependents_count: Int8(row.get("dependents_count"), format),
+        }
+    }
+}
+
+impl FromRow for LeaderboardOverride {
+    fn from_row(row: &Row, format: RowFormat) -> Self {
+        Self {
+            project_id: row.get("project_id"),
+            name: row.get("name"),
+            action: row.get("action"),
+            tea_rank: row.get("tea_rank"),
+            reason: row.get("reason"),
+            expires_at: Timestamp(row.get("expires_at"), format),
+            expired: row.get("expired"),
+            created_at: Timestamp(row.get("created_at"), format),
+        }
+    }
+}
+
+impl FromRow for Suspect {
+    fn from_row(row: &Row, _format: RowFormat) -> Self {
+        Self {
+            project_id: row.get("project_id"),
+            name: row.get("name"),
+            tea_rank: row.get("tea_rank"),
+            signals: row.get("signals"),
+            resembles: row
+                .get::<_, Option<Uuid>>("resembles_id")
+                .map(|project_id| {
+                    let name: String = row.get("resembles_name");
+                    SimilarName {
+                        project_id,
+                        edit_distance: edit_distance(row.get("name"), &name),
+                        name,
+                        tea_rank: row.get("resembles_rank"),
+                        similarity: row.get("similarity"),
+                    }
+                }),
+        }
+    }
+}
+
+/// Expects the name it was compared with as `compared_name`
+impl FromRow for SimilarName {
+    fn from_row(row: &Row, _format: RowFormat) -> Self {
+        let name: String = row.get("name");
+        Self {
+            project_id: row.get("project_id"),
+            edit_distance: edit_distance(&name, row.get("compared_name")),
+            name,
+            tea_rank: row.get("tea_rank"),
+            similarity: row.get("similarity"),
+        }
+    }
+}
+
+impl FromRow for NewEntrant {
+    fn from_row(row: &Row, format: RowFormat) -> Self {
+        Self {
+            project_id: row.get("project_id"),
+            name: row.get("name"),
+            tea_rank: row.get("tea_rank"),
+            position: Int8(row.get("position"), format),
+        }
+    }
+}
+
+impl FromRow for MetricsPoint {
+    fn from_row(row: &Row, format: RowFormat) -> Self {
+        Self {
+            run: row.get("run"),
+            calculated_at: Timestamp(row.get("calculated_at"), format),
+            tea_rank: row.get("tea_rank"),
+            dependents_count: Int8(row.get("dependents_count"), format),
+        }
+    }
+}
+
+impl FromRow for RankContributor {
+    fn from_row(row: &Row, format: RowFormat) -> Self {
+        Self {
+            project_id: row.get("project_id"),
+            name: row.get("name"),
+            tea_rank: row.get("tea_rank"),
+            dependency_count: Int8(row.get("dependency_count"), format),
+            contribution: row.get("contribution"),
+        }
+    }
+}
+
+impl FromRow for ExportEntry {
+    fn from_row(row: &Row, _: RowFormat) -> Self {
+        Self {
+            project_id: row.get("project_id"),
+            name: row.get("name"),
+            tea_rank: row.get("tea_rank"),
+        }
+    }
+}
+
+impl FromRow for ClosureEntry {
+    fn from_row(row: &Row, _: RowFormat) -> Self {
+        Self {
+            project_id: row.get("project_id"),
+            name: row.get("name"),
+            depth: row.get("depth"),
+        }
+    }
+}
+
+impl FromRow for EcosystemCombination {
+    fn from_row(row: &Row, format: RowFormat) -> Self {
+        Self {
+            package_managers: row.get("package_managers"),
+            count: Int8(row.get("count"), format),
+        }
+    }
+}
+
+impl FromRow for CrossEcosystemProject {
+    fn from_row(row: &Row, _: RowFormat) -> Self {
+        Self {
+            project_id: row.get("project_id"),
+            name: row.get("name"),
+            tea_rank: row.get("tea_rank"),
+            package_managers: row.get("package_managers"),
+        }
+    }
+}
+
+impl FromRow for DegreeProject {
+    fn from_row(row: &Row, format: RowFormat) -> Self {
+        Self {
+            project_id: row.get("project_id"),
+            name: row.get("name"),
+            degree: Int8(row.get("degree"), format),
+            tea_rank: row.get("tea_rank"),
+        }
+    }
+}
+
+impl FromRow for OrgRank {
+    fn from_row(row: &Row, format: RowFormat) -> Self {
+        Self {
+            host: row.get("host"),
+            org: row.get("org"),
+            projects: Int8(row.get("projects"), format),
+            rank_mass: row.get("rank_mass"),
+            top_rank: row.get("top_rank"),
+        }
+    }
+}
+
+impl FromRow for DependentProject {
+    fn from_row(row: &Row, _: RowFormat) -> Self {
+        Self {
+            project_id: row.get("project_id"),
+            name: row.get("name"),
+            tea_rank: row.get("tea_rank"),
+        }
+    }
+}
+
+impl FromRow for OrgProject {
+    fn from_row(row: &Row, _: RowFormat) -> Self {
+        Self {
+            project_id: row.get("project_id"),
+            name: row.get("name"),
+            source: row.get("source"),
+            tea_rank: row.get("tea_rank"),
+        }
+    }
+}
+
+impl FromRow for StaleProject {
+    fn from_row(row: &Row, format: RowFormat) -> Self {
+        Self {
+            project_id: row.get("project_id"),
+            name: row.get("name"),
+            tea_rank: row.get("tea_rank"),
+            last_release: Timestamp(row.get("last_release"), format),
+        }
+    }
+}
+
+impl FromRow for ExposedPackage {
+    fn from_row(row: &Row, _: RowFormat) -> Self {
+        Self {
+            package_manager: row.get("package_manager"),
+            package: row.get("package"),
+            project_id: row.get("project_id"),
+            name: row.get("name"),
+            tea_rank: row.get("tea_rank"),
+            owners: row.get("owners"),
+        }
+    }
+}
+
+impl FromRow for SbomMatch {
+    fn from_row(row: &Row, _: RowFormat) -> Self {
+        Self {
+            package_manager: row.get("package_manager"),
+            package: row.get("package"),
+            project_id: row.get("project_id"),
+            name: row.get("name"),
+            tea_rank: row.get("tea_rank"),
+        }
+    }
+}
@@ -0,0 +1,305 @@
+//! Projects grouped by the organization hosting their source, e.g.
+//! `github.com/tokio-rs`.
+//!
+//! Functions taking `live` expect a SQL predicate over canons `c` that filters
+//! out soft-deleted rows, or `TRUE`.
+
+use chai_types::format::RowFormat;
+use chai_types::models::{OrgProject, OrgRank};
+use chai_types::requests::OrgScore;
+use serde_json::Value;
+use tokio_postgres::Client;
+
+use crate::db::timed;
+use crate::leaderboard::source_patterns;
+use crate::models::to_json;
+use crate::sql::LatestRank;
+use crate::stats::KNOWN_FORGES;
+
+/// Canons `m` with a source URL matching any of `$2`, the first such URL as
+/// `source`, joined to their rank `tr` in run `$1`, the latest
+fn org_projects_from(live: &str) -> String {
+    let rank = LatestRank::of("m.id").in_run("$1", true).join("tr");
+    format!(
+        r#"
+        FROM (
+            SELECT c.id, c.name, MIN(u.url) AS source
+            FROM canons c
+            JOIN canon_packages cp ON cp.canon_id = c.id
+            JOIN package_urls pu ON pu.package_id = cp.package_id
+            JOIN urls u ON pu.url_id = u.id
+            JOIN url_types ut ON ut.id = u.url_type_id
+            WHERE ut.name = 'source' AND u.url LIKE ANY($2) AND {live}
+            GROUP BY c.id, c.name
+        ) m
+        {rank}"#
+    )
+}
+
+/// How many canons `$2` matches, how many are ranked, and their rank mass
+pub fn org_totals_query(live: &str) -> String {
+    format!(
+        r#"
+        SELECT
+            COUNT(*) AS projects,
+            COUNT(*) FILTER (WHERE CAST(tr.rank AS NUMERIC) > 0) AS ranked,
+            COALESCE(SUM(CAST(tr.rank AS NUMERIC)), 0)::float8 AS rank_mass
+        {}"#,
+        org_projects_from(live)
+    )
+}
+
+/// `$3` canons matching `$2` from `$4`, best ranked first and unranked last
+pub fn org_projects_query(live: &str) -> String {
+    format!(
+        r#"
+        SELECT m.id AS project_id, m.name, m.source, tr.rank AS tea_rank
+        {}
+        ORDER BY CAST(tr.rank AS NUMERIC) DESC NULLS LAST, m.name, m.id
+        LIMIT $3 OFFSET $4"#,
+        org_projects_from(live)
+    )
+}
+
+/// The `$3` organizations on the forges `$2` with the highest score, from the
+/// ranks of run `$1`, the latest. A canon counts under the organization of its
+/// first source URL, normalized as for [`source_hosts_query`]; only ranked
+/// canons count.
+///
+/// [`source_hosts_query`]: crate::stats::source_hosts_query
+pub fn org_ranks_query(live: &str, score: OrgScore) -> String {
+    let order = match score {
+        OrgScore::Sum => "rank_mass DESC, top_rank DESC",
+        OrgScore::Max => "top_rank DESC, rank_mass DESC",
+    };
+    let rank = LatestRank::of("s.id").in_run("$1", true).join("tr");
+    format!(
+        r#"
+        WITH sourced AS (
+            SELECT
+                c.id,
+                regexp_replace(
+                    lower(substring(src.url FROM '^(?:[a-z0-9+.-]+://)?(?:[^@/]+@)?([^/:?#]+)')),
+                    '^www\.',
+                    ''
+                ) AS host,
+                substring(
+                    src.url
+                    FROM '^(?:[a-z0-9+.-]+://)?(?:[^@/]+@)?[^/:?#]+(?::[0-9]+)?[/:]([^/?#]+)'
+                ) AS org
+            FROM canons c
+            JOIN LATERAL (
+                SELECT u.url
+                FROM canon_packages cp
+                JOIN package_urls pu ON pu.package_id = cp.package_id
+                JOIN urls u ON pu.url_id = u.id
+                JOIN url_types ut ON ut.id = u.url_type_id
+                WHERE cp.canon_id = c.id AND ut.name = 'source'
+                ORDER BY u.url
+                LIMIT 1
+            ) src ON TRUE
+            WHERE {live}
+        )
+        SELECT
+            s.host,
+            s.org,
+            COUNT(*) AS projects,
+            SUM(CAST(tr.rank AS NUMERIC))::float8 AS rank_mass,
+            MAX(CAST(tr.rank AS NUMERIC))::float8 AS top_rank
+        FROM sourced s
+        {rank}
+        WHERE s.host = ANY($2::text[]) AND s.org IS NOT NULL
+            AND CAST(tr.rank AS NUMERIC) > 0
+        GROUP BY s.host, s.org
+        ORDER BY {order}, s.host, s.org
+        LIMIT $3"#
+    )
+}
+
+/// The best `limit` organizations of `run` by `score`, as [`OrgRank`]s
+pub async fn org_ranks(
+    client: &Client,
+    run: Option<i32>,
+    score: OrgScore,
+    limit: i64,
+    live: &str,
+    format: RowFormat,
+) -> Result<Vec<Value>, tokio_postgres::Error> {
+    let rows = timed(
+        "org_ranks",
+        client.query(
+            &org_ranks_query(live, score),
+            &[&run, &KNOWN_FORGES.as_slice(), &limit],
+        ),
+    )
+    .await?;
+    Ok(to_json::<OrgRank>(&rows, format))
+}
+
+/// The canons of one organization, given as `host/org`
+pub struct Org {
+    pub host: String,
+    pub org: String,
+    patterns: Vec<String>,
+}
+
+impl Org {
+    /// The organization `org` on `host`, or why the pair is not usable
+    pub fn new(host: &str, org: &str) -> Result<Self, String> {
+        let host = host.trim().to_ascii_lowercase();
+        let host = host.strip_prefix("www.").unwrap_or(&host).to_string();
+        let org = org.trim_matches('/').to_string();
+        if host.is_empty() || host.contains('/') || org.is_empty() || org.contains('*') {
+            return Err(format!("Invalid organization '{host}/{org}'"));
+        }
+        let patterns = source_patterns(&format!("{host}/{org}"))?;
+        Ok(Self {
+            host,
+            org,
+            patterns,
+        })
+    }
+}
+
+/// Totals over the canons of an organization
+pub struct OrgTotals {
+    pub projects: i64,
+    pub ranked: i64,
+    pub rank_mass: f64,
+}
+
+pub async fn org_totals(
+    client: &Client,
+    org: &Org,
+    run: Option<i32>,
+    live: &str,
+) -> Result<OrgTotals, tokio_postgres::Error> {
+    let row = timed(
+        "org_totals",
+        client.query_one(&org_totals_query(live), &[&run, &org.patterns]),
+    )
+    .await?;
+    Ok(OrgTotals {
+        projects: row.get("projects"),
+        ranked: row.get("ranked"),
+        rank_mass: row.get("rank_mass"),
+    })
+}
+
+/// `limit` canons of `org` from `offset`, as [`OrgProject`]s
+pub async fn org_projects(
+    client: &Client,
+    org: &Org,
+    run: Option<i32>,
+    limit: i64,
+    offset: i64,
+    live: &str,
+    format: RowFormat,
+) -> Result<Vec<Value>, tokio_postgres::Error> {
+    let rows = timed(
+        "org_projects",
+        client.query(
+            &org_projects_query(live),
+            &[&run, &org.patterns, &limit, &offset],
+        ),
+    )
+    .await?;
+    Ok(to_json::<OrgProject>(&rows, format))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_project, test_client};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn org_projects_total_the_whole_org_and_page_by_rank() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        insert_project(&client, "serde", "crates", &[(1, "12.5")]).await;
+        insert_project(&client, "tokio", "crates", &[(1, "3.5")]).await;
+        insert_project(&client, "unranked", "crates", &[]).await;
+
+        let org = Org::new("WWW.GitHub.com", "example").unwrap();
+        assert_eq!(org.host, "github.com");
+        let totals = org_totals(&client, &org, Some(1), "TRUE").await.unwrap();
+        assert_eq!((totals.projects, totals.ranked), (3, 2));
+        assert_eq!(totals.rank_mass, 16.0);
+
+        let page = org_projects(&client, &org, Some(1), 2, 1, "TRUE", RowFormat::default())
+            .await
+            .unwrap();
+        assert_eq!(page.len(), 2);
+        assert_eq!(page[0]["name"], json!("tokio"));
+        assert_eq!(page[1]["name"], json!("unranked"));
+        assert_eq!(page[1]["teaRank"], json!(null));
+        assert_eq!(
+            page[1]["source"],
+            json!("https://github.com/example/unranked")
+        );
+
+        let other = Org::new("github.com", "exam").unwrap();
+        let totals = org_totals(&client, &other, Some(1), "TRUE").await.unwrap();
+        assert_eq!(totals.projects, 0);
+        assert!(Org::new("github.com", "*").is_err());
+    }
+
+    #[tokio::test]
+    async fn org_ranks_group_by_source_org() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        insert_project(&client, "serde", "crates", &[(1, "5")]).await;
+        insert_project(&client, "serde_json", "crates", &[(1, "4")]).await;
+        insert_project(&client, "unranked", "crates", &[(1, "0")]).await;
+        let tokio = insert_project(&client, "tokio", "crates", &[(1, "7")]).await;
+        client
+            .execute(
+                r#"
+                UPDATE urls SET url = 'git@www.GitHub.com:tokio-rs/tokio.git'
+                WHERE id IN (
+                    SELECT pu.url_id
+                    FROM canon_packages cp
+                    JOIN package_urls pu ON pu.package_id = cp.package_id
+                    WHERE cp.canon_id = $1
+                )"#,
+                &[&tokio],
+            )
+            .await
+            .unwrap();
+
+        let ranks = |score| {
+            let client = &client;
+            async move {
+                org_ranks(client, Some(1), score, 10, "TRUE", RowFormat::default())
+                    .await
+                    .unwrap()
+                    .iter()
+                    .map(|o| {
+                        let org = format!(
+                            "{}/{}",
+                            o["host"].as_str().unwrap(),
+                            o["org"].as_str().unwrap()
+                        );
+                        (org, o["projects"].clone(), o["rankMass"].as_f64().unwrap())
+                    })
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(
+            ranks(OrgScore::Sum).await,
+            [
+                ("github.com/example".to_string(), json!(2), 9.0),
+                ("github.com/tokio-rs".to_string(), json!(1), 7.0),
+            ]
+        );
+        let by_max: Vec<String> = ranks(OrgScore::Max)
+            .await
+            .into_iter()
+            .map(|o| o.0)
+            .collect();
+        assert_eq!(by_max, ["github.com/tokio-rs", "github.com/example"]);
+    }
+}
@@ -0,0 +1,905 @@
+//! Looking projects (canons) up: by id, slug, name, or maintainer, following
+//! the redirects merged canons leave behind.
+//!
+//! Functions taking `live` expect a SQL predicate over canons `c` that filters
+//! out soft-deleted rows, or `TRUE`.
+
+use chai_types::fields::Fields;
+use chai_types::format::{Int8, RowFormat, Timestamp};
+use chai_types::models::{
+    CompactProject, MaintainerConcentration, MaintainerProject, MaintainerShare, Project,
+    ProjectFunding, SearchResult, SearchResults,
+};
+use chai_types::requests::ProjectView;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use tokio_postgres::{Client, Row};
+use uuid::Uuid;
+
+use crate::db::{fetch_optional, timed, FetchError};
+use crate::funding::extract_funding;
+use crate::models::{to_json, FromRow};
+use crate::sql::{has_source_url, source_urls, LatestRank, Params, CANON_BASE, HOMEPAGE};
+
+/// Fields `?fields` accepts on project endpoints, those of [`Project`]
+pub const PROJECT_FIELDS: [&str; 11] = [
+    "projectId",
+    "homepage",
+    "name",
+    "slug",
+    "source",
+    "teaRank",
+    "teaRankCalculatedAt",
+    "packageManagers",
+    "dependenciesCount",
+    "dependentsCount",
+    "versions",
+];
+
+/// Matches `GET /project/search/{name}` returns
+pub const SEARCH_LIMIT: usize = 10;
+
+/// Canons `$1::uuid[]` that have a source URL, in `view`, one row each with
+/// its latest rank, or with `as_of` its latest as of run `$2`. Quarantined
+/// runs are skipped. The full view's aggregates are only computed when
+/// `fields` asks for them; the others read as placeholders. `packageManagers`
+/// is always null, for the caller to fill in from
+/// [`package_managers_by_canon`].
+pub fn project_query(
+    view: ProjectView,
+    fields: Option<&Fields>,
+    live: &str,
+    as_of: bool,
+) -> String {
+    let mut params = Params::default();
+    let ids = params.placeholder();
+    let run = as_of.then(|| params.placeholder());
+    let latest_rank = match &run {
+        Some(run) => LatestRank::of("c.id").as_of(run),
+        None => LatestRank::of("c.id"),
+    }
+    .join("tr_latest");
+
+    if view == ProjectView::Compact {
+        let has_source_url = has_source_url("c.id", None);
+        return format!(
+            r#"
+        SELECT
+            c.id AS project_id,
+            c.name,
+            COALESCE(tr_latest.rank, '0') AS tea_rank
+        FROM canons c
+        {latest_rank}
+        WHERE c.id = ANY({ids}::uuid[]) AND {live}
+        AND {has_source_url}
+        ORDER BY c.id"#
+        );
+    }
+
+    let wanted = |field: &str| fields.is_none_or(|fields| fields.contains(field));
+    let dependencies_count = if wanted("dependenciesCount") {
+        r#"(
+                SELECT COUNT(*)::bigint
+                FROM legacy_dependencies ld
+                JOIN canon_packages cp_out ON cp_out.package_id = ld.package_id
+                WHERE cp_out.canon_id = c.id
+                ) AS dependencies_count"#
+    } else {
+        "0::bigint AS dependencies_count"
+    };
+    let dependents_count = if wanted("dependentsCount") {
+        r#"(
+                SELECT COUNT(*)::bigint
+                FROM legacy_dependencies ld
+                JOIN canon_packages cp_in ON cp_in.package_id = ld.dependency_id
+                WHERE cp_in.canon_id = c.id
+                ) AS dependents_count"#
+    } else {
+        "0::bigint AS dependents_count"
+    };
+    let versions = if wanted("versions") {
+        r#"(
+                SELECT JSON_AGG(
+                    JSON_BUILD_OBJECT(
+                        'packageManager', s.type,
+                        'package', p3.name,
+                        'latestVersion', v.version
+                    )
+                    ORDER BY s.type, p3.name
+                )
+                FROM canon_packages cp3
+                JOIN packages p3           ON cp3.package_id = p3.id
+                JOIN package_managers pm3  ON p3.package_manager_id = pm3.id
+                JOIN sources s             ON pm3.source_id = s.id
+                LEFT JOIN LATERAL (
+                    SELECT v.version
+                    FROM versions v
+                    WHERE v.package_id = p3.id
+                    ORDER BY v.published_at DESC NULLS LAST, v.version DESC
+                    LIMIT 1
+                ) v ON TRUE
+                WHERE cp3.canon_id = c.id
+                ) AS versions"#
+    } else {
+        "NULL::json AS versions"
+    };
+
+    let source_urls = source_urls("b.project_id");
+    format!(
+        r#"
+        WITH base AS MATERIALIZED (
+            SELECT
+                {CANON_BASE},
+                cs.slug,
+                COALESCE(tr_latest.rank, '0') AS tea_rank,
+                tr_latest.created_at AS tea_rank_calculated_at,
+                NULL::text[] AS package_managers,
+                {dependencies_count},
+                {dependents_count},
+                {versions}
+            FROM canons c
+            {HOMEPAGE}
+            LEFT JOIN canon_slugs cs ON cs.canon_id = c.id
+            {latest_rank}
+            WHERE c.id = ANY({ids}::uuid[]) AND {live}
+        )
+        SELECT DISTINCT ON (b.project_id)
+            b.project_id,
+            b.homepage,
+            b.name,
+            b.slug,
+            u_source.url        AS source,
+            b.tea_rank,
+            b.tea_rank_calculated_at,
+            b.package_managers,
+            b.dependencies_count,
+            b.dependents_count,
+            b.versions
+        FROM base b
+        {source_urls}
+        ORDER BY b.project_id, u_source.url"#
+    )
+}
+
+/// A row of [`project_query`] as its `view` payload
+pub fn project_value(view: ProjectView, row: &Row, format: RowFormat) -> Value {
+    match view {
+        ProjectView::Compact => json!(CompactProject::from_row(row, format)),
+        ProjectView::Full => json!(Project::from_row(row, format)),
+    }
+}
+
+/// Canon `id` in `view` as of `run` (now when `None`), or `None` when it
+/// doesn't exist (or was merged away). Fields left out of `fields` come back
+/// as placeholders for the caller to drop.
+pub async fn fetch_project(
+    client: &Client,
+    id: Uuid,
+    view: ProjectView,
+    fields: Option<&Fields>,
+    run: Option<i32>,
+    live: &str,
+    format: RowFormat,
+) -> Result<Option<Value>, FetchError> {
+    let query = project_query(view, fields, live, run.is_some());
+    let ids = vec![id];
+    let context = format!("project '{id}'");
+    let row = match run {
+        Some(run) => fetch_optional(client, &query, &[&ids, &run], &context).await?,
+        None => fetch_optional(client, &query, &[&ids], &context).await?,
+    };
+    Ok(row.map(|row| project_value(view, &row, format)))
+}
+
+/// Canons `ids` in `view` as of `run` (now when `None`), in no particular
+/// order; missing ones are left out. Fields left out of `fields` come back as
+/// placeholders, as with [`fetch_project`].
+pub async fn fetch_projects(
+    client: &Client,
+    ids: &[Uuid],
+    view: ProjectView,
+    fields: Option<&Fields>,
+    run: Option<i32>,
+    live: &str,
+    format: RowFormat,
+) -> Result<Vec<Value>, tokio_postgres::Error> {
+    let query = project_query(view, fields, live, run.is_some());
+    let rows = match run {
+        Some(run) => timed("projects_batch", client.query(&query, &[&ids, &run])).await?,
+        None => timed("projects_batch", client.query(&query, &[&ids])).await?,
+    };
+    Ok(rows
+        .iter()
+        .map(|row| project_value(view, row, format))
+        .collect())
+}
+
+/// The `packageManagers` of every canon with packages, or only of `ids`, from
+/// one grouped query rather than one aggregate per project. Canons with the
+/// same package managers share one list.
+pub async fn package_managers_by_canon(
+    client: &Client,
+    ids: Option<&[Uuid]>,
+) -> Result<HashMap<Uuid, Arc<[String]>>, tokio_postgres::Error> {
+    let rows = timed(
+        "package_managers_by_canon",
+        client.query(
+            r#"
+        SELECT cp.canon_id, ARRAY_AGG(DISTINCT s.type) AS package_managers
+        FROM canon_packages cp
+        JOIN packages p           ON cp.package_id = p.id
+        JOIN package_managers pm  ON p.package_manager_id = pm.id
+        JOIN sources s            ON pm.source_id = s.id
+        WHERE $1::uuid[] IS NULL OR cp.canon_id = ANY($1)
+        GROUP BY cp.canon_id"#,
+            &[&ids],
+        ),
+    )
+    .await?;
+    let mut lists: HashSet<Arc<[String]>> = HashSet::new();
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let managers: Vec<String> = row.get("package_managers");
+            let list = match lists.get(managers.as_slice()) {
+                Some(list) => Arc::clone(list),
+                None => {
+                    let list: Arc<[String]> = managers.into();
+                    lists.insert(Arc::clone(&list));
+                    list
+                }
+            };
+            (row.get("canon_id"), list)
+        })
+        .collect())
+}
+
+/// The canon a slug (already lowercased) points at
+pub async fn canon_by_slug(
+    client: &Client,
+    slug: &str,
+) -> Result<Option<Uuid>, tokio_postgres::Error> {
+    let row = timed(
+        "project_slug",
+        client.query_opt("SELECT canon_id FROM canon_slugs WHERE slug = $1", &[&slug]),
+    )
+    .await?;
+    Ok(row.map(|row| row.get(0)))
+}
+
+/// Whether `id` is a live canon
+pub async fn canon_exists(
+    client: &Client,
+    id: Uuid,
+    live: &str,
+) -> Result<bool, tokio_postgres::Error> {
+    let query = format!("SELECT 1 FROM canons c WHERE c.id = $1 AND {live}");
+    let row = timed("canon_exists", client.query_opt(&query, &[&id])).await?;
+    Ok(row.is_some())
+}
+
+/// Which of `ids` are live canons
+pub async fn existing_canons(
+    client: &Client,
+    ids: &[Uuid],
+    live: &str,
+) -> Result<HashSet<Uuid>, tokio_postgres::Error> {
+    let query = format!("SELECT c.id FROM canons c WHERE c.id = ANY($1) AND {live}");
+    let rows = timed("existing_canons", client.query(&query, &[&ids])).await?;
+    Ok(rows.iter().map(|row| row.get(0)).collect())
+}
+
+/// Maps each merged canon id among `ids` to the canon that replaced it
+pub async fn resolve_redirects(
+    client: &Client,
+    ids: &[Uuid],
+) -> Result<HashMap<Uuid, Uuid>, tokio_postgres::Error> {
+    let rows = timed(
+        "canon_redirects",
+        client.query(
+            "SELECT old_canon_id, canon_id FROM canon_redirects WHERE old_canon_id = ANY($1)",
+            &[&ids],
+        ),
+    )
+    .await?;
+    Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+}
+
+/// Answers each requested id that was redirected with a copy of its survivor's
+/// payload carrying `redirectedFrom`, in request order
+pub fn embed_redirects(
+    projects: Vec<Value>,
+    requested: &[Uuid],
+    redirects: &HashMap<Uuid, Uuid>,
+) -> Vec<Value> {
+    if redirects.is_empty() {
+        return projects;
+    }
+
+    let by_id: HashMap<String, Value> = projects
+        .into_iter()
+        .filter_map(|project| {
+            let id = project.get("projectId")?.as_str()?.to_string();
+            Some((id, project))
+        })
+        .collect();
+
+    let mut seen = HashSet::new();
+    requested
+        .iter()
+        .filter(|id| seen.insert(**id))
+        .filter_map(|id| {
+            let target = redirects.get(id).unwrap_or(id);
+            let mut project = by_id.get(&target.to_string())?.clone();
+            if target != id {
+                project["redirectedFrom"] = json!(id);
+            }
+            Some(project)
+        })
+        .collect()
+}
+
+/// Drops the full-view `projects` whose `teaRankCalculatedAt` is no newer than
+/// the stamp `since` has for the id they were asked for by, which is
+/// `redirectedFrom` for survivors of a merge. Projects without a stamp, or
+/// without a rank date to compare, are kept.
+pub fn modified_since(projects: Vec<Value>, since: &BTreeMap<Uuid, Timestamp>) -> Vec<Value> {
+    projects
+        .into_iter()
+        .filter(|project| {
+            let requested = project
+                .get("redirectedFrom")
+                .or_else(|| project.get("projectId"))
+                .and_then(|id| id.as_str()?.parse::<Uuid>().ok());
+            let Some(Timestamp(Some(known), _)) = requested.and_then(|id| since.get(&id)) else {
+                return true;
+            };
+            let calculated = project
+                .get("teaRankCalculatedAt")
+                .and_then(|at| serde_json::from_value::<Timestamp>(at.clone()).ok());
+            match calculated {
+                Some(Timestamp(Some(calculated), _)) => calculated > *known,
+                _ => true,
+            }
+        })
+        .collect()
+}
+
+/// The first [`SEARCH_LIMIT`] canons with a source URL whose name contains `$1`
+/// (an `ILIKE` pattern), shortest name first, and one more to tell whether
+/// there are others
+pub fn search_query(live: &str) -> String {
+    let mut params = Params::default();
+    let name = params.placeholder();
+    let source_urls = source_urls("c.id");
+    format!(
+        r#"
+        SELECT *
+        FROM (
+            SELECT DISTINCT ON (c.id)
+                {CANON_BASE},
+                u_source.url AS source,
+                (
+                    SELECT ARRAY_AGG(DISTINCT s.type)
+                    FROM canon_packages cp2
+                    JOIN packages p2 ON cp2.package_id = p2.id
+                    JOIN package_managers pm2 ON p2.package_manager_id = pm2.id
+                    JOIN sources s ON pm2.source_id = s.id
+                    WHERE cp2.canon_id = c.id
+                ) AS package_managers
+            FROM canons c
+            {HOMEPAGE}
+            {source_urls}
+            WHERE (c.name ILIKE {name}) AND {live}
+            ORDER BY c.id
+        ) sub
+        ORDER BY LENGTH(name), name
+        LIMIT {};"#,
+        SEARCH_LIMIT + 1
+    )
+}
+
+/// The planner's estimate of the canons whose name contains `$1`, from the
+/// statistics it picks the trigram index with; nothing is counted
+pub fn search_estimate_query(live: &str) -> String {
+    format!("EXPLAIN (FORMAT JSON) SELECT 1 FROM canons c WHERE c.name ILIKE $1 AND {live}")
+}
+
+/// Canons whose name contains `name`, as [`SearchResult`]s. Only a page that
+/// fills up pays for an estimate of how many there are in all.
+pub async fn search_projects(
+    client: &Client,
+    name: &str,
+    live: &str,
+    format: RowFormat,
+) -> Result<SearchResults<Value>, tokio_postgres::Error> {
+    let wildcard = format!("%{name}%");
+    let rows = timed(
+        "projects_search",
+        client.query(&search_query(live), &[&wildcard]),
+    )
+    .await?;
+    let mut results = to_json::<SearchResult>(&rows, format);
+    let has_more = results.len() > SEARCH_LIMIT;
+    results.truncate(SEARCH_LIMIT);
+
+    let total_matches = if has_more {
+        let plan: Value = timed(
+            "projects_search_estimate",
+            client.query_one(&search_estimate_query(live), &[&wildcard]),
+        )
+        .await?
+        .get(0);
+        let estimate = plan[0]["Plan"]["Plan Rows"].as_f64().unwrap_or_default() as i64;
+        // The estimate can't be below what was already found
+        estimate.max(SEARCH_LIMIT as i64 + 1)
+    } else {
+        results.len() as i64
+    };
+    Ok(SearchResults {
+        name: name.to_string(),
+        has_more,
+        total_matches,
+        results,
+    })
+}
+
+/// Readmes of every package in canon `$1` plus all URLs attached to them
+const FUNDING_TEXTS_QUERY: &str = r#"
+    SELECT p.readme AS text
+    FROM canon_packages cp
+    JOIN packages p ON p.id = cp.package_id
+    WHERE cp.canon_id = $1 AND p.readme IS NOT NULL
+    UNION ALL
+    SELECT u.url
+    FROM canon_packages cp
+    JOIN package_urls pu ON pu.package_id = cp.package_id
+    JOIN urls u ON u.id = pu.url_id
+    WHERE cp.canon_id = $1
+    UNION ALL
+    SELECT u.url
+    FROM canons c
+    JOIN urls u ON u.id = c.url_id
+    WHERE c.id = $1"#;
+
+/// The funding links found for canon `id`, or `None` when it doesn't exist
+pub async fn project_funding(
+    client: &Client,
+    id: Uuid,
+    live: &str,
+) -> Result<Option<Value>, tokio_postgres::Error> {
+    let canon_query = format!("SELECT 1 FROM canons c WHERE c.id = $1 AND {live}");
+    if timed("funding_canon", client.query_opt(&canon_query, &[&id]))
+        .await?
+        .is_none()
+    {
+        return Ok(None);
+    }
+
+    let rows = timed("funding_texts", client.query(FUNDING_TEXTS_QUERY, &[&id])).await?;
+    let texts: Vec<String> = rows.iter().map(|row| row.get("text")).collect();
+    let funding = extract_funding(texts.iter().map(String::as_str));
+    Ok(Some(json!(ProjectFunding {
+        project_id: id,
+        has_funding: !funding.is_empty(),
+        funding: funding
+            .into_iter()
+            .map(|(platform, accounts)| (platform.to_string(), accounts))
+            .collect(),
+    })))
+}
+
+/// A maintainer's (`$1`) verified projects with their rank in the latest run
+/// compared against the run before it
+pub fn maintainer_projects_query(live: &str) -> String {
+    format!(
+        r#"
+        WITH live_runs AS (
+            SELECT run FROM tea_rank_runs WHERE quarantined_at IS NULL
+        ),
+        runs AS (
+            SELECT
+                MAX(run) AS latest,
+                MAX(run) FILTER (WHERE run < (SELECT MAX(run) FROM live_runs)) AS previous
+            FROM live_runs
+        )
+        SELECT
+            c.id AS project_id,
+            c.name,
+            mv.verified_at AS verified_at,
+            COALESCE(cur.rank, '0') AS tea_rank,
+            prev.rank AS previous_tea_rank,
+            (CAST(COALESCE(cur.rank, '0') AS NUMERIC)
+                - CAST(COALESCE(prev.rank, '0') AS NUMERIC))::float8 AS rank_change,
+            CASE
+                WHEN prev.rank IS NULL THEN 'new'
+                WHEN CAST(COALESCE(cur.rank, '0') AS NUMERIC) > CAST(prev.rank AS NUMERIC) THEN 'up'
+                WHEN CAST(COALESCE(cur.rank, '0') AS NUMERIC) < CAST(prev.rank AS NUMERIC) THEN 'down'
+                ELSE 'unchanged'
+            END AS rank_trend,
+            (
+                SELECT COUNT(*)::bigint
+                FROM legacy_dependencies ld
+                JOIN canon_packages cp_in ON cp_in.package_id = ld.dependency_id
+                WHERE cp_in.canon_id = c.id
+            ) AS dependents_count
+        FROM maintainer_verifications mv
+        JOIN canons c ON c.id = mv.canon_id
+        CROSS JOIN runs
+        LEFT JOIN tea_ranks cur ON cur.canon_id = c.id AND cur.tea_rank_run = runs.latest
+        LEFT JOIN tea_ranks prev ON prev.canon_id = c.id AND prev.tea_rank_run = runs.previous
+        WHERE mv.maintainer = $1 AND mv.verified_at IS NOT NULL AND {live}
+        ORDER BY CAST(COALESCE(cur.rank, '0') AS NUMERIC) DESC, c.name"#
+    )
+}
+
+/// A maintainer's verified projects, as [`MaintainerProject`]s
+pub async fn maintainer_projects(
+    client: &Client,
+    maintainer: &str,
+    live: &str,
+    format: RowFormat,
+) -> Result<Vec<Value>, tokio_postgres::Error> {
+    let rows = timed(
+        "maintainer_projects",
+        client.query(&maintainer_projects_query(live), &[&maintainer]),
+    )
+    .await?;
+    Ok(to_json::<MaintainerProject>(&rows, format))
+}
+
+/// The registry accounts maintaining any package of canon `$1`, with how many
+/// of its packages each maintains, most first
+const PACKAGE_MAINTAINERS: &str = r#"
+    SELECT u.username, s.type AS source, COUNT(DISTINCT cp.package_id) AS packages
+    FROM canon_packages cp
+    JOIN user_packages up ON up.package_id = cp.package_id
+    JOIN users u ON u.id = up.user_id
+    JOIN sources s ON s.id = u.source_id
+    WHERE cp.canon_id = $1
+    GROUP BY u.id, u.username, s.type
+    ORDER BY packages DESC, u.username, s.type"#;
+
+/// How many packages canon `$1` has, how many of them have a known maintainer,
+/// and its rank in run `$2`
+const PACKAGE_COUNTS: &str = r#"
+    SELECT
+        COUNT(*) AS packages,
+        COUNT(*) FILTER (
+            WHERE EXISTS (SELECT 1 FROM user_packages up WHERE up.package_id = cp.package_id)
+        ) AS maintained,
+        (
+            SELECT rank
+            FROM tea_ranks
+            WHERE tea_rank_run = $2 AND canon_id = $1
+            ORDER BY created_at DESC
+            LIMIT 1
+        ) AS tea_rank
+    FROM canon_packages cp
+    WHERE cp.canon_id = $1"#;
+
+/// How the packages of canon `id` are spread over maintainers, with its rank
+/// in `run`. The caller checks the canon exists.
+pub async fn maintainer_concentration(
+    client: &Client,
+    id: Uuid,
+    run: Option<i32>,
+    format: RowFormat,
+) -> Result<MaintainerConcentration, tokio_postgres::Error> {
+    let counts = timed(
+        "maintainer_concentration_counts",
+        client.query_one(PACKAGE_COUNTS, &[&id, &run]),
+    )
+    .await?;
+    let rows = timed(
+        "maintainer_concentration",
+        client.query(PACKAGE_MAINTAINERS, &[&id]),
+    )
+    .await?;
+
+    let packages: i64 = counts.get("packages");
+    let maintained: i64 = counts.get("maintained");
+    let maintainers: Vec<MaintainerShare> = rows
+        .iter()
+        .map(|row| {
+            let owned: i64 = row.get("packages");
+            MaintainerShare {
+                username: row.get("username"),
+                source: row.get("source"),
+                packages: Int8(owned, format),
+                share: owned as f64 / packages as f64,
+            }
+        })
+        .collect();
+    // Someone on every package can publish all of them alone
+    let controlled_by = maintainers
+        .iter()
+        .find(|maintainer| maintainer.packages.0 == packages)
+        .map(|maintainer| maintainer.username.clone());
+
+    Ok(MaintainerConcentration {
+        project_id: id,
+        run,
+        tea_rank: counts.get("tea_rank"),
+        packages: Int8(packages, format),
+        maintained_packages: Int8(maintained, format),
+        maintainer_count: maintainers.len(),
+        controlled_by,
+        maintainers,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ranks::rebuild_latest_ranks;
+    use crate::test_support::{insert_project, test_client};
+    use chai_types::fields::FieldsParams;
+
+    fn keys(project: &Value) -> Vec<&str> {
+        let mut keys: Vec<&str> = project
+            .as_object()
+            .expect("projects are objects")
+            .keys()
+            .map(String::as_str)
+            .collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    #[tokio::test]
+    async fn project_views_pick_their_fields() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        let older = insert_project(&client, "older", "npm", &[(1, "2.5"), (2, "3.5")]).await;
+        let newer = insert_project(&client, "newer", "npm", &[]).await;
+        client
+            .execute(
+                r#"
+                INSERT INTO versions (package_id, version, published_at)
+                SELECT cp.package_id, v.version, v.published_at
+                FROM canon_packages cp,
+                    (VALUES ('1.0.0', now() - interval '1 day'), ('1.1.0', now())) v(version, published_at)
+                WHERE cp.canon_id = $1"#,
+                &[&older],
+            )
+            .await
+            .unwrap();
+        let ids = vec![older, newer];
+
+        let rows = client
+            .query(
+                &project_query(ProjectView::Compact, None, "TRUE", false),
+                &[&ids],
+            )
+            .await
+            .unwrap();
+        let compact = to_json::<CompactProject>(&rows, RowFormat::default());
+        assert_eq!(compact.len(), 2);
+        for project in &compact {
+            assert_eq!(keys(project), ["name", "projectId", "teaRank"]);
+        }
+
+        // As of run 1, the rank run 2 gave isn't there yet
+        let as_of = client
+            .query_one(
+                &project_query(ProjectView::Compact, None, "TRUE", true),
+                &[&vec![older], &1i32],
+            )
+            .await
+            .unwrap();
+        assert_eq!(as_of.get::<_, String>("tea_rank"), "2.5");
+
+        let rows = client
+            .query(
+                &project_query(ProjectView::Full, None, "TRUE", false),
+                &[&ids],
+            )
+            .await
+            .unwrap();
+        let full = to_json::<Project>(&rows, RowFormat::default());
+        let older = full.iter().find(|p| p["name"] == json!("older")).unwrap();
+        assert_eq!(older["teaRank"], json!("3.5"));
+        assert_eq!(
+            older["versions"],
+            json!([{ "packageManager": "npm", "package": "older", "latestVersion": "1.1.0" }])
+        );
+        let newer = full.iter().find(|p| p["name"] == json!("newer")).unwrap();
+        assert_eq!(newer["versions"][0]["latestVersion"], Value::Null);
+
+        // Aggregates left out of `fields` aren't computed
+        let fields = FieldsParams::new(["name", "teaRank"])
+            .parse(&PROJECT_FIELDS, &[])
+            .unwrap();
+        let rows = client
+            .query(
+                &project_query(ProjectView::Full, fields.as_ref(), "TRUE", false),
+                &[&ids],
+            )
+            .await
+            .unwrap();
+        let sparse = to_json::<Project>(&rows, RowFormat::default());
+        assert_eq!(sparse.len(), 2);
+        for project in &sparse {
+            assert_eq!(project["packageManagers"], Value::Null);
+            assert_eq!(project["versions"], Value::Null);
+        }
+
+        // Quarantining run 2 falls back to the rank run 1 gave
+        client
+            .execute(
+                "UPDATE tea_rank_runs SET quarantined_at = now() WHERE run = 2",
+                &[],
+            )
+            .await
+            .unwrap();
+        rebuild_latest_ranks(&client).await.unwrap();
+        let quarantined = client
+            .query_one(
+                &project_query(ProjectView::Compact, None, "TRUE", false),
+                &[&vec![ids[0]]],
+            )
+            .await
+            .unwrap();
+        assert_eq!(quarantined.get::<_, String>("tea_rank"), "2.5");
+    }
+
+    #[tokio::test]
+    async fn package_managers_are_grouped_by_canon() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        let ids = [
+            insert_project(&client, "left-pad", "npm", &[]).await,
+            insert_project(&client, "right-pad", "npm", &[]).await,
+            insert_project(&client, "requests", "pypi", &[]).await,
+        ];
+        let grouped = package_managers_by_canon(&client, None).await.unwrap();
+        assert_eq!(&*grouped[&ids[0]], ["npm"]);
+        assert_eq!(&*grouped[&ids[2]], ["pypi"]);
+        assert!(Arc::ptr_eq(&grouped[&ids[0]], &grouped[&ids[1]]));
+
+        let some = package_managers_by_canon(&client, Some(&ids[2..]))
+            .await
+            .unwrap();
+        assert_eq!(some.len(), 1);
+        assert_eq!(&*some[&ids[2]], ["pypi"]);
+    }
+
+    #[test]
+    fn modified_since_keeps_newer_and_unstamped_projects() {
+        let (fresh, stale, merged, survivor, unstamped) = (
+            Uuid::from_u128(1),
+            Uuid::from_u128(2),
+            Uuid::from_u128(3),
+            Uuid::from_u128(4),
+            Uuid::from_u128(5),
+        );
+        let projects = vec![
+            json!({ "projectId": fresh, "teaRankCalculatedAt": "2024-12-27T08:00:00Z" }),
+            json!({ "projectId": stale, "teaRankCalculatedAt": "2024-12-20T08:00:00Z" }),
+            json!({
+                "projectId": survivor,
+                "redirectedFrom": merged,
+                "teaRankCalculatedAt": "2024-12-27T08:00:00",
+            }),
+            json!({ "projectId": unstamped, "teaRankCalculatedAt": null }),
+        ];
+        let stamp: Timestamp = serde_json::from_value(json!("2024-12-20T08:00:00Z")).unwrap();
+        let since = BTreeMap::from([
+            (fresh, stamp),
+            (stale, stamp),
+            (survivor, stamp),
+            (unstamped, stamp),
+        ]);
+
+        let kept: Vec<Value> = modified_since(projects, &since)
+            .into_iter()
+            .map(|project| project["projectId"].clone())
+            .collect();
+        // The survivor's stamp doesn't apply: it was asked for as `merged`
+        assert_eq!(kept, [json!(fresh), json!(survivor), json!(unstamped)]);
+    }
+
+    #[tokio::test]
+    async fn search_says_whether_there_are_more() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        for i in 0..SEARCH_LIMIT + 2 {
+            insert_project(&client, &format!("searchable-{i:02}"), "npm", &[]).await;
+        }
+        let page = search_projects(&client, "searchable", "TRUE", RowFormat::default())
+            .await
+            .unwrap();
+        assert_eq!(page.results.len(), SEARCH_LIMIT);
+        assert_eq!(page.results[0]["name"], "searchable-00");
+        assert!(page.has_more);
+        assert!(page.total_matches > SEARCH_LIMIT as i64);
+
+        let last = search_projects(&client, "searchable-1", "TRUE", RowFormat::default())
+            .await
+            .unwrap();
+        assert_eq!((last.has_more, last.total_matches), (false, 2));
+    }
+
+    #[tokio::test]
+    async fn maintainer_concentration_finds_sole_control() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        let id = insert_project(&client, "left-pad", "npm", &[(1, "7")]).await;
+        // A second package, maintained only by azer, and a third by no one
+        for name in ["left-pad-cli", "left-pad-docs"] {
+            client
+                .execute(
+                    r#"
+                    WITH pkg AS (
+                        INSERT INTO packages (id, package_manager_id, name)
+                        SELECT gen_random_uuid(), p.package_manager_id, $2
+                        FROM packages p JOIN canon_packages cp ON cp.package_id = p.id
+                        WHERE cp.canon_id = $1
+                        LIMIT 1
+                        RETURNING id
+                    )
+                    INSERT INTO canon_packages (canon_id, package_id) SELECT $1, id FROM pkg"#,
+                    &[&id, &name],
+                )
+                .await
+                .unwrap();
+        }
+        for (username, packages) in [
+            ("azer", &["left-pad", "left-pad-cli"][..]),
+            ("helper", &["left-pad"][..]),
+        ] {
+            client
+                .execute(
+                    r#"
+                    WITH account AS (
+                        INSERT INTO users (id, username, source_id)
+                        SELECT gen_random_uuid(), $1, id FROM sources WHERE type = 'npm'
+                        RETURNING id
+                    )
+                    INSERT INTO user_packages (user_id, package_id)
+                    SELECT account.id, p.id FROM account, packages p
+                    WHERE p.name = ANY($2)"#,
+                    &[&username, &packages],
+                )
+                .await
+                .unwrap();
+        }
+
+        let report = maintainer_concentration(&client, id, Some(1), RowFormat::default())
+            .await
+            .unwrap();
+        assert_eq!(report.tea_rank.as_deref(), Some("7"));
+        assert_eq!((report.packages.0, report.maintained_packages.0), (3, 2));
+        assert_eq!(report.maintainer_count, 2);
+        let shares: Vec<(&str, i64)> = report
+            .maintainers
+            .iter()
+            .map(|m| (m.username.as_str(), m.packages.0))
+            .collect();
+        assert_eq!(shares, [("azer", 2), ("helper", 1)]);
+        // Nobody maintains the docs package, so nobody controls everything
+        assert_eq!(report.controlled_by, None);
+
+        client
+            .execute(
+                r#"
+                DELETE FROM canon_packages cp
+                USING packages p
+                WHERE p.id = cp.package_id AND p.name = 'left-pad-docs'"#,
+                &[],
+            )
+            .await
+            .unwrap();
+        let report = maintainer_concentration(&client, id, Some(1), RowFormat::default())
+            .await
+            .unwrap();
+        assert_eq!(report.controlled_by.as_deref(), Some("azer"));
+        assert_eq!(report.maintainers[0].share, 1.0);
+    }
+}
@@ -0,0 +1,525 @@
+//! A project's rank across runs and where it comes from: run-wide percentiles,
+//! per-run history, run-to-run comparisons, and the dependents passing rank on
+//! to it, and the `latest_ranks` table current ranks are read from.
+
+use chai_types::format::RowFormat;
+use chai_types::models::{MetricsPoint, RankContributor};
+use serde_json::Value;
+use std::collections::HashMap;
+use tokio_postgres::{Client, GenericClient};
+use uuid::Uuid;
+
+use crate::db::timed;
+use crate::models::to_json;
+use crate::sql::LatestRank;
+
+/// Slopes within this fraction of the average rank per run count as stable
+const STABLE_SLOPE_RATIO: f64 = 0.01;
+
+/// The rank at each of `hundredths` (so 99.5 is 9950) among `run`'s positive
+/// ranks, or `None` when the run doesn't exist. Values are `None` when the run
+/// had no positive ranks.
+pub async fn rank_percentiles(
+    client: &Client,
+    run: i32,
+    hundredths: &[u32],
+) -> Result<Option<Vec<Option<f64>>>, tokio_postgres::Error> {
+    // Fractions rather than hundredths, as percentile_cont expects
+    let fractions: Vec<f64> = hundredths
+        .iter()
+        .map(|p| f64::from(*p) / 10_000.0)
+        .collect();
+    let query = r#"
+        SELECT
+            EXISTS (SELECT 1 FROM tea_rank_runs WHERE run = $1) AS run_exists,
+            (
+                SELECT percentile_cont($2::float8[])
+                    WITHIN GROUP (ORDER BY CAST(tr.rank AS float8))
+                FROM tea_ranks tr
+                WHERE tr.tea_rank_run = $1
+                AND CAST(tr.rank AS NUMERIC) > 0
+            ) AS percentiles"#;
+
+    let row = timed(
+        "rank_percentiles",
+        client.query_one(query, &[&run, &fractions]),
+    )
+    .await?;
+    if !row.get::<_, bool>("run_exists") {
+        return Ok(None);
+    }
+    // A run without positive ranks aggregates to NULL rather than an array
+    let values: Option<Vec<f64>> = row.get("percentiles");
+    Ok(Some(
+        (0..hundredths.len())
+            .map(|i| values.as_ref().map(|values| values[i]))
+            .collect(),
+    ))
+}
+
+/// Every canon's positive rank in `run`, or `None` when the run doesn't exist.
+/// Deleted canons are included, so comparisons see what the ranker wrote.
+pub async fn run_ranks(
+    client: &Client,
+    run: i32,
+) -> Result<Option<Vec<(Uuid, f64)>>, tokio_postgres::Error> {
+    ranks_in_run(client, run, None).await
+}
+
+/// Like [`run_ranks`], for `ids` only
+pub async fn run_ranks_of(
+    client: &Client,
+    run: i32,
+    ids: &[Uuid],
+) -> Result<Option<Vec<(Uuid, f64)>>, tokio_postgres::Error> {
+    ranks_in_run(client, run, Some(ids)).await
+}
+
+async fn ranks_in_run(
+    client: &Client,
+    run: i32,
+    ids: Option<&[Uuid]>,
+) -> Result<Option<Vec<(Uuid, f64)>>, tokio_postgres::Error> {
+    let exists = timed(
+        "run_exists",
+        client.query_one(
+            "SELECT EXISTS (SELECT 1 FROM tea_rank_runs WHERE run = $1)",
+            &[&run],
+        ),
+    )
+    .await?;
+    if !exists.get::<_, bool>(0) {
+        return Ok(None);
+    }
+
+    let query = r#"
+        SELECT canon_id, rank
+        FROM (
+            SELECT DISTINCT ON (canon_id) canon_id, CAST(rank AS float8) AS rank
+            FROM tea_ranks
+            WHERE tea_rank_run = $1 AND ($2::uuid[] IS NULL OR canon_id = ANY($2))
+            ORDER BY canon_id, created_at DESC
+        ) latest
+        WHERE rank > 0"#;
+    let rows = timed("run_ranks", client.query(query, &[&run, &ids])).await?;
+    Ok(Some(
+        rows.iter()
+            .map(|row| (row.get("canon_id"), row.get("rank")))
+            .collect(),
+    ))
+}
+
+/// Replaces `latest_ranks` with each canon's newest rank outside quarantined
+/// runs, and its newest positive one, returning how many canons have a rank.
+/// Callers own the transaction, so readers never see the table half built.
+pub async fn rebuild_latest_ranks(
+    client: &impl GenericClient,
+) -> Result<u64, tokio_postgres::Error> {
+    let query = r#"
+        INSERT INTO latest_ranks (
+            canon_id, tea_rank_run, rank, created_at,
+            positive_tea_rank_run, positive_rank, positive_created_at
+        )
+        SELECT
+            l.canon_id, l.tea_rank_run, l.rank, l.created_at,
+            p.tea_rank_run, p.rank, p.created_at
+        FROM (
+            SELECT DISTINCT ON (canon_id) canon_id, tea_rank_run, rank, created_at
+            FROM tea_ranks
+            WHERE tea_rank_run NOT IN (
+                SELECT run FROM tea_rank_runs WHERE quarantined_at IS NOT NULL
+            )
+            ORDER BY canon_id, created_at DESC
+        ) l
+        LEFT JOIN (
+            SELECT DISTINCT ON (canon_id) canon_id, tea_rank_run, rank, created_at
+            FROM tea_ranks
+            WHERE CAST(rank AS NUMERIC) > 0
+            AND tea_rank_run NOT IN (
+                SELECT run FROM tea_rank_runs WHERE quarantined_at IS NOT NULL
+            )
+            ORDER BY canon_id, created_at DESC
+        ) p ON p.canon_id = l.canon_id"#;
+    timed(
+        "latest_ranks_clear",
+        client.execute("DELETE FROM latest_ranks", &[]),
+    )
+    .await?;
+    timed("latest_ranks_rebuild", client.execute(query, &[])).await
+}
+
+pub struct RankChanges {
+    pub rose: usize,
+    pub fell: usize,
+    pub unchanged: usize,
+    /// Ranked in the later run only
+    pub new: usize,
+    /// Ranked in the earlier run only
+    pub dropped: usize,
+    pub mean_absolute_change: Option<f64>,
+    pub kendall_tau: Option<f64>,
+}
+
+/// How ranks moved from one run's `(canon, rank)` pairs to another's. Rises,
+/// falls, the mean change, and tau only cover canons ranked in both.
+pub fn rank_changes(from: &[(Uuid, f64)], to: &[(Uuid, f64)]) -> RankChanges {
+    let before: HashMap<Uuid, f64> = from.iter().copied().collect();
+    let mut pairs = Vec::new();
+    let (mut rose, mut fell, mut unchanged) = (0, 0, 0);
+    let mut total_change = 0.0;
+    for (id, rank) in to {
+        let Some(previous) = before.get(id) else {
+            continue;
+        };
+        let change = rank - previous;
+        if change > 0.0 {
+            rose += 1;
+        } else if change < 0.0 {
+            fell += 1;
+        } else {
+            unchanged += 1;
+        }
+        total_change += change.abs();
+        pairs.push((*previous, *rank));
+    }
+
+    let both = pairs.len();
+    RankChanges {
+        rose,
+        fell,
+        unchanged,
+        new: to.len() - both,
+        dropped: before.len() - both,
+        mean_absolute_change: (both > 0).then(|| total_change / both as f64),
+        kendall_tau: kendall_tau(&mut pairs),
+    }
+}
+
+/// Kendall's tau-b of `(x, y)` pairs, in O(n log n) by Knight's method: sort
+/// by `x`, then count the swaps a merge sort by `y` makes. `None` with fewer
+/// than two pairs or when either side is all ties.
+fn kendall_tau(pairs: &mut [(f64, f64)]) -> Option<f64> {
+    let n = pairs.len() as u64;
+    if n < 2 {
+        return None;
+    }
+    pairs.sort_unstable_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+
+    let all = n * (n - 1) / 2;
+    let x_ties = tied_pairs(pairs, |a, b| a.0 == b.0);
+    let joint_ties = tied_pairs(pairs, |a, b| a == b);
+    let mut ys: Vec<f64> = pairs.iter().map(|(_, y)| *y).collect();
+    let swaps = merge_swaps(&mut ys, &mut vec![0.0; pairs.len()]);
+    let y_ties = tied_pairs(&ys, |a, b| a == b);
+
+    let denominator = ((all - x_ties) as f64 * (all - y_ties) as f64).sqrt();
+    if denominator == 0.0 {
+        return None;
+    }
+    let numerator =
+        all as f64 - x_ties as f64 - y_ties as f64 + joint_ties as f64 - 2.0 * swaps as f64;
+    Some(numerator / denominator)
+}
+
+/// Pairs within runs of equal neighbours in sorted `values`
+fn tied_pairs<T>(values: &[T], equal: impl Fn(&T, &T) -> bool) -> u64 {
+    let mut ties = 0;
+    let mut run = 1u64;
+    for i in 1..=values.len() {
+        if i < values.len() && equal(&values[i - 1], &values[i]) {
+            run += 1;
+        } else {
+            ties += run * (run - 1) / 2;
+            run = 1;
+        }
+    }
+    ties
+}
+
+/// Sorts `values`, returning how many out-of-order pairs it had
+fn merge_swaps(values: &mut [f64], scratch: &mut [f64]) -> u64 {
+    let len = values.len();
+    if len < 2 {
+        return 0;
+    }
+    let mid = len / 2;
+    let mut swaps = merge_swaps(&mut values[..mid], &mut scratch[..mid])
+        + merge_swaps(&mut values[mid..], &mut scratch[mid..]);
+
+    let (mut i, mut j) = (0, mid);
+    for slot in scratch[..len].iter_mut() {
+        if j == len || (i < mid && values[i] <= values[j]) {
+            *slot = values[i];
+            i += 1;
+        } else {
+            *slot = values[j];
+            swaps += (mid - i) as u64;
+            j += 1;
+        }
+    }
+    values.copy_from_slice(&scratch[..len]);
+    swaps
+}
+
+/// Canon `id`'s rank in each of the last `runs` unquarantined runs as
+/// `(run, rank)`, oldest first, with no rank for runs it wasn't ranked in
+pub async fn rank_series(
+    client: &Client,
+    id: Uuid,
+    runs: i64,
+) -> Result<Vec<(i32, Option<String>)>, tokio_postgres::Error> {
+    // Most of the runs are past ones, so every rank is looked up in tea_ranks
+    let rank = LatestRank::of("$1").in_run("runs.run", false).join("tr");
+    let query = format!(
+        r#"
+        SELECT runs.run, tr.rank
+        FROM (
+            SELECT run FROM tea_rank_runs
+            WHERE quarantined_at IS NULL
+            ORDER BY run DESC
+            LIMIT $2
+        ) runs
+        {rank}
+        ORDER BY runs.run"#
+    );
+
+    let rows = timed("rank_trend", client.query(&query, &[&id, &runs])).await?;
+    Ok(rows
+        .iter()
+        .map(|row| (row.get("run"), row.get("rank")))
+        .collect())
+}
+
+pub struct Trend {
+    /// Least-squares change in rank per run
+    pub slope: f64,
+    pub moving_average: f64,
+    pub direction: &'static str,
+}
+
+/// Fits a line through the ranks a project had, one point per run. Runs the
+/// project wasn't ranked in are skipped rather than counted as zero, and
+/// fewer than two points give no trend.
+pub fn rank_trend(series: &[Option<f64>]) -> Option<Trend> {
+    let points: Vec<(f64, f64)> = series
+        .iter()
+        .enumerate()
+        .filter_map(|(i, rank)| rank.map(|rank| (i as f64, rank)))
+        .collect();
+    if points.len() < 2 {
+        return None;
+    }
+
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let covariance: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    let slope = covariance / variance;
+
+    let direction = if slope.abs() <= mean_y.abs() * STABLE_SLOPE_RATIO {
+        "stable"
+    } else if slope > 0.0 {
+        "rising"
+    } else {
+        "falling"
+    };
+
+    Some(Trend {
+        slope,
+        moving_average: mean_y,
+        direction,
+    })
+}
+
+/// Canon `id`'s rank and dependent count in each of the last `runs`
+/// unquarantined runs, as [`MetricsPoint`]s, oldest first
+pub async fn metrics(
+    client: &Client,
+    id: Uuid,
+    runs: i64,
+    format: RowFormat,
+) -> Result<Vec<Value>, tokio_postgres::Error> {
+    // Dependents aren't snapshotted per run, so a run's count is the dependency
+    // edges that existed when it was calculated. Edges removed since are not
+    // tracked and so never drop out of earlier runs.
+    let rank = LatestRank::of("$1").in_run("runs.run", false).join("tr");
+    let query = format!(
+        r#"
+        SELECT
+            runs.run,
+            runs.created_at AS calculated_at,
+            tr.rank AS tea_rank,
+            (
+                SELECT COUNT(*)::bigint
+                FROM legacy_dependencies ld
+                JOIN canon_packages cp ON cp.package_id = ld.dependency_id
+                WHERE cp.canon_id = $1 AND ld.created_at <= runs.created_at
+            ) AS dependents_count
+        FROM (
+            SELECT run, created_at FROM tea_rank_runs
+            WHERE quarantined_at IS NULL
+            ORDER BY run DESC
+            LIMIT $2
+        ) runs
+        {rank}
+        ORDER BY runs.run"#
+    );
+
+    let rows = timed("project_metrics", client.query(&query, &[&id, &runs])).await?;
+    Ok(to_json::<MetricsPoint>(&rows, format))
+}
+
+/// `run`'s split ratio and canon `id`'s rank in it, or `None` when the run
+/// doesn't exist
+pub async fn run_split(
+    client: &Client,
+    run: i32,
+    id: Uuid,
+) -> Result<Option<(f64, Option<String>)>, tokio_postgres::Error> {
+    let query = r#"
+        SELECT
+            CAST(r.split_ratio AS float8) AS split_ratio,
+            (
+                SELECT tr.rank
+                FROM tea_ranks tr
+                WHERE tr.tea_rank_run = r.run AND tr.canon_id = $2
+                ORDER BY tr.created_at DESC
+                LIMIT 1
+            ) AS rank
+        FROM tea_rank_runs r
+        WHERE r.run = $1"#;
+    let row = timed("run_split_ratio", client.query_opt(query, &[&run, &id])).await?;
+    Ok(row.map(|row| (row.get("split_ratio"), row.get("rank"))))
+}
+
+/// The `$4` direct dependents of canon `$1` sending it the most rank in run
+/// `$2`, whose split ratio is `$3`. The ranker doesn't record attribution, so
+/// the flow is re-derived from its model: a node keeps `split_ratio` of what
+/// reaches it and splits the rest evenly across its dependencies, so a
+/// dependent ranked `r` with `n` dependencies passed on
+/// `r / split_ratio * (1 - split_ratio) / n` to each. `current` when run `$2`
+/// is the latest.
+pub fn contributors_query(live: &str, current: bool) -> String {
+    let rank = LatestRank::of("c.id").in_run("$2", current).join("tr");
+    format!(
+        r#"
+        WITH edges AS (
+            SELECT DISTINCT cp_from.canon_id AS dependent, cp_to.canon_id AS dependency
+            FROM legacy_dependencies ld
+            JOIN canon_packages cp_from ON cp_from.package_id = ld.package_id
+            JOIN canon_packages cp_to ON cp_to.package_id = ld.dependency_id
+            WHERE cp_from.canon_id <> cp_to.canon_id
+        ),
+        dependents AS (
+            SELECT e.dependent, COUNT(*) AS dependencies
+            FROM edges e
+            WHERE e.dependent IN (SELECT dependent FROM edges WHERE dependency = $1)
+            GROUP BY e.dependent
+        )
+        SELECT
+            c.id AS project_id,
+            c.name,
+            tr.rank AS tea_rank,
+            d.dependencies AS dependency_count,
+            CAST(tr.rank AS float8) / $3 * (1 - $3) / d.dependencies AS contribution
+        FROM dependents d
+        JOIN canons c ON c.id = d.dependent
+        {rank}
+        WHERE CAST(tr.rank AS NUMERIC) > 0 AND {live}
+        ORDER BY contribution DESC, c.id
+        LIMIT $4"#
+    )
+}
+
+/// The top `limit` contributors to canon `id` in `run`, as [`RankContributor`]s.
+/// `live` filters canons `c`, and `current` says `run` is the latest.
+#[allow(clippy::too_many_arguments)]
+pub async fn rank_contributors(
+    client: &Client,
+    id: Uuid,
+    run: i32,
+    current: bool,
+    split_ratio: f64,
+    limit: i64,
+    live: &str,
+    format: RowFormat,
+) -> Result<Vec<Value>, tokio_postgres::Error> {
+    let rows = timed(
+        "rank_contributors",
+        client.query(
+            &contributors_query(live, current),
+            &[&id, &run, &split_ratio, &limit],
+        ),
+    )
+    .await?;
+    Ok(to_json::<RankContributor>(&rows, format))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_project, test_client};
+
+    #[test]
+    fn rank_changes_count_moves_and_correlate_orderings() {
+        let ids: Vec<Uuid> = (0..5).map(|i| Uuid::from_u128(i + 1)).collect();
+        let from = [(ids[0], 10.0), (ids[1], 8.0), (ids[2], 6.0), (ids[3], 4.0)];
+        let to = [(ids[0], 9.0), (ids[1], 8.0), (ids[2], 7.0), (ids[4], 1.0)];
+
+        let changes = rank_changes(&from, &to);
+        assert_eq!((changes.rose, changes.fell, changes.unchanged), (1, 1, 1));
+        assert_eq!((changes.new, changes.dropped), (1, 1));
+        assert_eq!(changes.mean_absolute_change, Some(2.0 / 3.0));
+        assert_eq!(changes.kendall_tau, Some(1.0));
+
+        // Reversed orderings, and ties, which tau-b discounts
+        let reversed: Vec<(Uuid, f64)> = from.iter().map(|(id, rank)| (*id, -rank)).collect();
+        assert_eq!(rank_changes(&from, &reversed).kendall_tau, Some(-1.0));
+        let tied = [(ids[0], 2.0), (ids[1], 1.0), (ids[2], 1.0), (ids[3], 0.5)];
+        let tau = rank_changes(&from, &tied).kendall_tau.unwrap();
+        assert!((tau - 5.0 / 30f64.sqrt()).abs() < 1e-12);
+
+        assert_eq!(rank_changes(&from[..1], &to[..1]).kendall_tau, None);
+        assert_eq!(rank_changes(&[], &to).mean_absolute_change, None);
+    }
+
+    #[tokio::test]
+    async fn latest_ranks_keep_the_newest_positive_rank_apart() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        let dropped = insert_project(&client, "dropped", "npm", &[(1, "3.5"), (2, "0")]).await;
+        let never = insert_project(&client, "never", "npm", &[(1, "0")]).await;
+        insert_project(&client, "unranked", "npm", &[]).await;
+
+        let latest = |id: Uuid| {
+            let client = &client;
+            async move {
+                let row = client
+                    .query_one(
+                        "SELECT tea_rank_run, rank, positive_tea_rank_run, positive_rank
+                        FROM latest_ranks WHERE canon_id = $1",
+                        &[&id],
+                    )
+                    .await
+                    .unwrap();
+                (
+                    row.get::<_, i32>(0),
+                    row.get::<_, String>(1),
+                    row.get::<_, Option<i32>>(2),
+                    row.get::<_, Option<String>>(3),
+                )
+            }
+        };
+        assert_eq!(
+            latest(dropped).await,
+            (2, "0".to_string(), Some(1), Some("3.5".to_string()))
+        );
+        assert_eq!(latest(never).await, (1, "0".to_string(), None, None));
+        assert_eq!(rebuild_latest_ranks(&client).await.unwrap(), 2);
+    }
+}
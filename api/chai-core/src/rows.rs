@@ -0,0 +1,202 @@
+//! Rendering rows as JSON, and rounding the ranks in rendered values.
+
+use chai_types::format::RowFormat;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde_json::{json, Value};
+use tokio_postgres::{types::Type, Column, Row};
+use uuid::Uuid;
+
+pub fn get_column_names(columns: &[Column]) -> Vec<String> {
+    columns.iter().map(|col| col.name().to_string()).collect()
+}
+
+pub fn convert_optional_to_json<T, E>(result: Result<Option<T>, E>) -> Value
+where
+    T: serde::Serialize,
+{
+    match result {
+        Ok(Some(val)) => json!(val),
+        _ => Value::Null,
+    }
+}
+
+pub fn rows_to_json(rows: &[Row], format: RowFormat) -> Vec<Value> {
+    rows.iter().map(|row| row_to_json(row, format)).collect()
+}
+
+pub fn row_to_json(row: &Row, format: RowFormat) -> Value {
+    let mut map = serde_json::Map::new();
+    for (i, column) in row.columns().iter().enumerate() {
+        map.insert(column.name().to_string(), column_to_json(row, i, format));
+    }
+    Value::Object(map)
+}
+
+/// Whether [`column_to_json`] reads `ty` itself; values of other types only
+/// come through when the query casts them to text
+pub fn renders_natively(ty: &Type) -> bool {
+    matches!(
+        *ty,
+        Type::INT2
+            | Type::INT4
+            | Type::INT8
+            | Type::FLOAT4
+            | Type::FLOAT8
+            | Type::BOOL
+            | Type::VARCHAR
+            | Type::TEXT
+            | Type::BPCHAR
+            | Type::TIMESTAMP
+            | Type::TIMESTAMPTZ
+            | Type::DATE
+            | Type::JSON
+            | Type::JSONB
+            | Type::UUID
+            | Type::TEXT_ARRAY
+            | Type::VARCHAR_ARRAY
+    )
+}
+
+/// The value of column `i` of `row`, as [`row_to_json`] renders it
+pub fn column_to_json(row: &Row, i: usize, format: RowFormat) -> Value {
+    match *row.columns()[i].type_() {
+        Type::INT2 => convert_optional_to_json(row.try_get::<_, Option<i16>>(i)),
+        Type::INT4 => convert_optional_to_json(row.try_get::<_, Option<i32>>(i)),
+        Type::INT8 => format.int8(row.try_get::<_, Option<i64>>(i).ok().flatten()),
+        Type::FLOAT4 => convert_optional_to_json(row.try_get::<_, Option<f32>>(i)),
+        Type::FLOAT8 => convert_optional_to_json(row.try_get::<_, Option<f64>>(i)),
+        Type::BOOL => convert_optional_to_json(row.try_get::<_, Option<bool>>(i)),
+        Type::VARCHAR | Type::TEXT | Type::BPCHAR => {
+            convert_optional_to_json(row.try_get::<_, Option<String>>(i))
+        }
+        Type::TIMESTAMP => {
+            format.timestamp(row.try_get::<_, Option<NaiveDateTime>>(i).ok().flatten())
+        }
+        Type::TIMESTAMPTZ => {
+            format.timestamptz(row.try_get::<_, Option<DateTime<Utc>>>(i).ok().flatten())
+        }
+        Type::DATE => convert_optional_to_json(row.try_get::<_, Option<NaiveDate>>(i)),
+        Type::JSON | Type::JSONB => {
+            convert_optional_to_json(row.try_get::<_, Option<serde_json::Value>>(i))
+        }
+        Type::UUID => convert_optional_to_json(row.try_get::<_, Option<Uuid>>(i)),
+        Type::TEXT_ARRAY | Type::VARCHAR_ARRAY => {
+            convert_optional_to_json(row.try_get::<_, Option<Vec<String>>>(i))
+        }
+        _ => {
+            // For unsupported types, try to convert to string
+            convert_optional_to_json(row.try_get::<_, Option<String>>(i))
+        }
+    }
+}
+
+/// Response fields holding rank values, rounded by `?precision=N`
+const RANK_FIELDS: [&str; 3] = ["teaRank", "previousTeaRank", "rankChange"];
+
+/// Rounds a decimal string to `places` digits, half away from zero, padding with
+/// zeros so every value has the same number of decimals (like Postgres
+/// `ROUND(numeric, places)`). Works on the digits themselves so stored ranks are
+/// never routed through a float.
+pub fn round_decimal(raw: &str, places: u32) -> Option<String> {
+    let raw = raw.trim();
+    let (negative, unsigned) = match raw.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, raw),
+    };
+    let (int_part, frac_part) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    let plain = !(int_part.is_empty() && frac_part.is_empty())
+        && int_part
+            .bytes()
+            .chain(frac_part.bytes())
+            .all(|b| b.is_ascii_digit());
+    if !plain {
+        // Exponent notation and the like
+        let value: f64 = raw.parse().ok().filter(|v: &f64| v.is_finite())?;
+        return Some(format!("{value:.*}", places as usize));
+    }
+
+    let places = places as usize;
+    let mut digits: Vec<u8> = int_part
+        .bytes()
+        .chain(
+            frac_part
+                .bytes()
+                .chain(std::iter::repeat(b'0'))
+                .take(places),
+        )
+        .collect();
+    if frac_part.as_bytes().get(places).is_some_and(|d| *d >= b'5') {
+        let mut carry = true;
+        for digit in digits.iter_mut().rev() {
+            if *digit == b'9' {
+                *digit = b'0';
+            } else {
+                *digit += 1;
+                carry = false;
+                break;
+            }
+        }
+        if carry {
+            digits.insert(0, b'1');
+        }
+    }
+
+    let split = digits.len() - places;
+    let int = std::str::from_utf8(&digits[..split])
+        .ok()?
+        .trim_start_matches('0');
+    let frac = std::str::from_utf8(&digits[split..]).ok()?;
+    let is_zero = int.is_empty() && frac.bytes().all(|d| d == b'0');
+
+    let mut rounded = String::with_capacity(digits.len() + 2);
+    if negative && !is_zero {
+        rounded.push('-');
+    }
+    rounded.push_str(if int.is_empty() { "0" } else { int });
+    if places > 0 {
+        rounded.push('.');
+        rounded.push_str(frac);
+    }
+    Some(rounded)
+}
+
+/// Rounds every rank field in `value` (an object, or an array of them) to
+/// `precision` decimals. String ranks stay strings; numeric ones stay numbers.
+pub fn round_ranks(value: &mut Value, precision: u32) {
+    match value {
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| round_ranks(item, precision)),
+        Value::Object(map) => {
+            for field in RANK_FIELDS {
+                let Some(rank) = map.get_mut(field) else {
+                    continue;
+                };
+                match rank {
+                    Value::String(raw) => {
+                        if let Some(rounded) = round_decimal(raw, precision) {
+                            *raw = rounded;
+                        }
+                    }
+                    Value::Number(number) => {
+                        if let Some(rounded) = round_decimal(&number.to_string(), precision)
+                            .and_then(|r| r.parse::<f64>().ok())
+                        {
+                            *rank = json!(rounded);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Rounds rank fields when the caller (or server default) asked for a precision
+pub fn with_precision(mut value: Value, precision: Option<u32>) -> Value {
+    if let Some(precision) = precision {
+        round_ranks(&mut value, precision);
+    }
+    value
+}
@@ -0,0 +1,336 @@
+//! SQL fragments the project, search and leaderboard queries share, so they
+//! read a canon's homepage, latest rank and source URLs the same way.
+//! Fragments only ever take placeholders from [`Params`], never values.
+//!
+//! Fragments expect canons as `c` unless they take the canon column.
+
+/// Numbers a query's placeholders in the order its values are bound
+#[derive(Default)]
+pub struct Params(usize);
+
+impl Params {
+    /// The next placeholder, `$1` first
+    pub fn placeholder(&mut self) -> String {
+        self.0 += 1;
+        format!("${}", self.0)
+    }
+}
+
+/// Each canon's id as `project_id`, its homepage URL and its name
+pub const CANON_BASE: &str = "c.id AS project_id, u_homepage.url AS homepage, c.name";
+
+/// Joins each canon to its homepage URL as `u_homepage`, for [`CANON_BASE`]
+pub const HOMEPAGE: &str = "JOIN urls u_homepage ON c.url_id = u_homepage.id";
+
+/// Each canon's latest rank that passes the filters set, joined with its
+/// `canon_id`, `rank` and `created_at`. Current ranks come from
+/// `latest_ranks`; only ranks as of an earlier run are looked up in
+/// `tea_ranks`. Ranks of quarantined runs are only picked when
+/// [`LatestRank::in_run`] asks for that run; canons without a rank get nulls.
+pub struct LatestRank<'a> {
+    canon: &'a str,
+    runs: Runs<'a>,
+    positive: bool,
+    pinned_by: Option<&'a str>,
+}
+
+/// The runs a [`LatestRank`] picks from
+enum Runs<'a> {
+    /// Every run
+    All,
+    /// This run and those before it
+    AsOf(&'a str),
+    /// This run only, which `latest_ranks` holds when `current`
+    In { run: &'a str, current: bool },
+}
+
+impl<'a> LatestRank<'a> {
+    /// The latest rank of the canon whose id is the column `canon`
+    pub fn of(canon: &'a str) -> Self {
+        Self {
+            canon,
+            runs: Runs::All,
+            positive: false,
+            pinned_by: None,
+        }
+    }
+
+    /// Only ranks from run `run` (an `int` placeholder) or before
+    pub fn as_of(mut self, run: &'a str) -> Self {
+        self.runs = Runs::AsOf(run);
+        self
+    }
+
+    /// Only the rank from run `run` (an `int` placeholder or column). When
+    /// `current`, `run` is the latest run, whose ranks `latest_ranks` holds.
+    pub fn in_run(mut self, run: &'a str, current: bool) -> Self {
+        self.runs = Runs::In { run, current };
+        self
+    }
+
+    /// Only ranks above zero
+    pub fn positive(mut self) -> Self {
+        self.positive = true;
+        self
+    }
+
+    /// With the `pinned_rank` of the `overrides` relation, by canon, in place
+    /// of the rank
+    pub fn pinned_by(mut self, overrides: &'a str) -> Self {
+        self.pinned_by = Some(overrides);
+        self
+    }
+
+    /// `LEFT JOIN` of the rank as `alias`
+    pub fn join(&self, alias: &str) -> String {
+        match self.runs {
+            Runs::All => self.join_latest(None, alias),
+            Runs::In { run, current: true } => self.join_latest(Some(run), alias),
+            Runs::AsOf(run) => self.join_ranks(
+                &format!(
+                    "r.tea_rank_run <= {run}
+                AND r.tea_rank_run NOT IN (
+                    SELECT run FROM tea_rank_runs WHERE quarantined_at IS NOT NULL
+                )"
+                ),
+                alias,
+            ),
+            Runs::In {
+                run,
+                current: false,
+            } => self.join_ranks(&format!("r.tea_rank_run = {run}"), alias),
+        }
+    }
+
+    fn join_latest(&self, run: Option<&str>, alias: &str) -> String {
+        let column = |name: &str| {
+            if self.positive {
+                format!("lr.positive_{name}")
+            } else {
+                format!("lr.{name}")
+            }
+        };
+        let (rank, created_at) = (column("rank"), column("created_at"));
+        let (pinned, pinned_join) = self.pinned(&rank, "lr");
+        let in_run = match run {
+            Some(run) => format!(" AND {} = {run}", column("tea_rank_run")),
+            None => String::new(),
+        };
+        format!(
+            r#"
+            LEFT JOIN (
+                SELECT lr.canon_id, {pinned} AS rank, {created_at} AS created_at
+                FROM latest_ranks lr{pinned_join}
+                WHERE {rank} IS NOT NULL{in_run}
+            ) {alias} ON {alias}.canon_id = {canon}"#,
+            canon = self.canon
+        )
+    }
+
+    /// The join through `tea_ranks` `r`, of the latest rank in the runs `runs`
+    /// picks
+    fn join_ranks(&self, runs: &str, alias: &str) -> String {
+        let (rank, pinned_join) = self.pinned("r.rank", "r");
+        let positive = if self.positive {
+            "\n                AND CAST(r.rank AS NUMERIC) > 0"
+        } else {
+            ""
+        };
+        format!(
+            r#"
+            LEFT JOIN LATERAL (
+                SELECT r.canon_id, {rank} AS rank, r.created_at
+                FROM tea_ranks r{pinned_join}
+                WHERE r.canon_id = {canon}{positive}
+                AND {runs}
+                ORDER BY r.created_at DESC
+                LIMIT 1
+            ) {alias} ON TRUE"#,
+            canon = self.canon
+        )
+    }
+
+    /// `rank` after any pin, and the join to the overrides of `ranks`' canons
+    fn pinned(&self, rank: &str, ranks: &str) -> (String, String) {
+        match self.pinned_by {
+            Some(overrides) => (
+                format!("COALESCE(pinned.pinned_rank, {rank})"),
+                format!(
+                    "\n                LEFT JOIN {overrides} pinned ON pinned.canon_id = {ranks}.canon_id"
+                ),
+            ),
+            None => (rank.to_string(), String::new()),
+        }
+    }
+}
+
+/// Joins the canon whose id is the column `canon` to each of its source URLs
+/// as `u_source`, so canons without one drop out
+pub fn source_urls(canon: &str) -> String {
+    format!(
+        r#"
+            JOIN canon_packages cp ON cp.canon_id = {canon}
+            JOIN package_urls pu ON pu.package_id = cp.package_id
+            JOIN urls u_source ON pu.url_id = u_source.id
+            JOIN url_types ut_source
+                ON ut_source.id = u_source.url_type_id AND ut_source.name = 'source'"#
+    )
+}
+
+/// Joins the canon whose id is the column `canon` to its first source URL as
+/// `u_source`, null for canons without one
+pub fn first_source_url(canon: &str) -> String {
+    format!(
+        r#"
+            LEFT JOIN LATERAL (
+                SELECT u.url
+                FROM canon_packages cp
+                JOIN package_urls pu ON pu.package_id = cp.package_id
+                JOIN urls u ON pu.url_id = u.id
+                JOIN url_types ut ON ut.id = u.url_type_id
+                WHERE cp.canon_id = {canon} AND ut.name = 'source'
+                ORDER BY u.url
+                LIMIT 1
+            ) u_source ON TRUE"#
+    )
+}
+
+/// Whether the canon whose id is the column `canon` has a source URL, one
+/// `LIKE` any of `patterns` (a `text[]` placeholder) when given
+pub fn has_source_url(canon: &str, patterns: Option<&str>) -> String {
+    let matching = match patterns {
+        Some(patterns) => format!(" AND u.url LIKE ANY({patterns})"),
+        None => String::new(),
+    };
+    format!(
+        r#"EXISTS (
+                SELECT 1
+                FROM canon_packages cp
+                JOIN package_urls pu ON pu.package_id = cp.package_id
+                JOIN urls u ON pu.url_id = u.id
+                JOIN url_types ut ON ut.id = u.url_type_id
+                WHERE cp.canon_id = {canon} AND ut.name = 'source'{matching}
+            )"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::leaderboard::{leaderboard_projects_query, top_projects_query};
+    use crate::projects::{project_query, search_query};
+    use crate::ranks::contributors_query;
+    use crate::test_support::test_client;
+    use chai_types::requests::ProjectView;
+    use tokio_postgres::types::Type;
+
+    #[test]
+    fn placeholders_are_numbered_in_binding_order() {
+        let mut params = Params::default();
+        assert_eq!(
+            [
+                params.placeholder(),
+                params.placeholder(),
+                params.placeholder()
+            ],
+            ["$1", "$2", "$3"]
+        );
+    }
+
+    #[test]
+    fn latest_rank_applies_only_the_filters_set() {
+        let plain = LatestRank::of("c.id").join("tr");
+        assert!(plain.contains("SELECT lr.canon_id, lr.rank AS rank, lr.created_at AS created_at"));
+        assert!(plain.contains("FROM latest_ranks lr\n"));
+        assert!(plain.ends_with(") tr ON tr.canon_id = c.id"));
+        assert!(!plain.contains("LATERAL"));
+        assert!(!plain.contains("pinned"));
+
+        let positive = LatestRank::of("b.id")
+            .positive()
+            .pinned_by("overrides")
+            .join("tr_latest");
+        assert!(positive.contains("COALESCE(pinned.pinned_rank, lr.positive_rank) AS rank"));
+        assert!(positive.contains("lr.positive_created_at AS created_at"));
+        assert!(positive.contains("LEFT JOIN overrides pinned ON pinned.canon_id = lr.canon_id"));
+        assert!(positive.contains("WHERE lr.positive_rank IS NOT NULL"));
+        assert!(positive.ends_with(") tr_latest ON tr_latest.canon_id = b.id"));
+
+        let as_of = LatestRank::of("b.id")
+            .as_of("$3")
+            .positive()
+            .pinned_by("overrides")
+            .join("tr_latest");
+        assert!(as_of.contains("COALESCE(pinned.pinned_rank, r.rank) AS rank"));
+        assert!(as_of.contains("FROM tea_ranks r\n"));
+        assert!(as_of.contains("WHERE r.canon_id = b.id"));
+        assert!(as_of.contains("AND CAST(r.rank AS NUMERIC) > 0"));
+        assert!(as_of.contains("AND r.tea_rank_run <= $3"));
+        assert!(as_of.contains("quarantined_at IS NOT NULL"));
+        assert!(as_of.ends_with(") tr_latest ON TRUE"));
+
+        let current = LatestRank::of("c.id").in_run("$1", true).join("tr");
+        assert!(current.contains("FROM latest_ranks lr\n"));
+        assert!(current.contains("WHERE lr.rank IS NOT NULL AND lr.tea_rank_run = $1\n"));
+        let current = LatestRank::of("c.id")
+            .in_run("$1", true)
+            .positive()
+            .join("tr");
+        assert!(current.contains("AND lr.positive_tea_rank_run = $1\n"));
+
+        let past = LatestRank::of("$1").in_run("runs.run", false).join("tr");
+        assert!(past.contains("FROM tea_ranks r\n"));
+        assert!(past.contains("WHERE r.canon_id = $1\n"));
+        assert!(past.contains("AND r.tea_rank_run = runs.run\n"));
+        assert!(!past.contains("quarantined_at"));
+        assert!(past.ends_with(") tr ON TRUE"));
+    }
+
+    #[test]
+    fn source_urls_take_the_canon_column() {
+        assert!(source_urls("b.id").contains("cp.canon_id = b.id"));
+        assert!(first_source_url("c.id").contains("cp.canon_id = c.id"));
+        assert!(!has_source_url("c.id", None).contains("LIKE"));
+        assert!(has_source_url("c.id", Some("$4")).contains("AND u.url LIKE ANY($4)\n"));
+    }
+
+    #[tokio::test]
+    async fn queries_take_their_values_in_binding_order() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        let params = |query: String| {
+            let client = &client;
+            async move { client.prepare(&query).await.unwrap().params().to_vec() }
+        };
+        for view in [ProjectView::Full, ProjectView::Compact] {
+            assert_eq!(
+                params(project_query(view, None, "TRUE", false)).await,
+                [Type::UUID_ARRAY]
+            );
+            assert_eq!(
+                params(project_query(view, None, "TRUE", true)).await,
+                [Type::UUID_ARRAY, Type::INT4]
+            );
+        }
+        assert_eq!(params(search_query("TRUE")).await, [Type::TEXT]);
+        assert_eq!(
+            params(leaderboard_projects_query("TRUE", true, false)).await,
+            [Type::UUID_ARRAY, Type::INT8]
+        );
+        assert_eq!(
+            params(leaderboard_projects_query("TRUE", true, true)).await,
+            [Type::UUID_ARRAY, Type::INT8, Type::INT4]
+        );
+        assert_eq!(
+            params(top_projects_query("TRUE", None)).await,
+            [Type::INT4, Type::INT8, Type::TEXT, Type::TEXT_ARRAY]
+        );
+        for current in [true, false] {
+            assert_eq!(
+                params(contributors_query("TRUE", current)).await,
+                [Type::UUID, Type::INT4, Type::FLOAT8, Type::INT8]
+            );
+        }
+    }
+}
@@ -0,0 +1,536 @@
+//! Aggregates over the whole catalog rather than one run or project. Functions
+//! taking `live` expect a SQL predicate over canons `c` that filters out
+//! soft-deleted rows, or `TRUE`.
+
+use chai_types::format::{Int8, RowFormat};
+use chai_types::models::{
+    CrossEcosystemProject, EcosystemCombination, SourceHost, SourceHosts, StaleProject,
+};
+use chrono::NaiveDateTime;
+use serde_json::Value;
+use tokio_postgres::Client;
+
+use crate::db::timed;
+use crate::models::{to_json, FromRow};
+use crate::sql::LatestRank;
+
+/// Canons with packages in more than one package manager, as a `WITH` list
+/// entry: `canon_id` and its sorted `package_managers`
+const MULTI_ECOSYSTEM: &str = r#"
+        multi AS MATERIALIZED (
+            SELECT
+                cp.canon_id,
+                ARRAY_AGG(DISTINCT s.type::text ORDER BY s.type::text) AS package_managers
+            FROM canon_packages cp
+            JOIN packages p ON p.id = cp.package_id
+            JOIN package_managers pm ON pm.id = p.package_manager_id
+            JOIN sources s ON s.id = pm.source_id
+            GROUP BY cp.canon_id
+            HAVING COUNT(DISTINCT s.type) > 1
+        )"#;
+
+/// How many live canons share each set of several package managers, most
+/// common first
+pub fn ecosystem_combinations_query(live: &str) -> String {
+    format!(
+        r#"
+        WITH {MULTI_ECOSYSTEM}
+        SELECT m.package_managers, COUNT(*) AS count
+        FROM multi m
+        JOIN canons c ON c.id = m.canon_id
+        WHERE {live}
+        GROUP BY m.package_managers
+        ORDER BY count DESC, m.package_managers"#
+    )
+}
+
+/// The `$3` best ranked canons in run `$1`, the latest, that are in several
+/// package managers, including all of `$2`
+pub fn cross_ecosystem_projects_query(live: &str) -> String {
+    let rank = LatestRank::of("c.id").in_run("$1", true).join("tr");
+    format!(
+        r#"
+        WITH {MULTI_ECOSYSTEM}
+        SELECT
+            c.id AS project_id,
+            c.name,
+            tr.rank AS tea_rank,
+            m.package_managers
+        FROM multi m
+        JOIN canons c ON c.id = m.canon_id
+        {rank}
+        WHERE m.package_managers @> $2::text[] AND {live}
+        ORDER BY CAST(COALESCE(tr.rank, '0') AS NUMERIC) DESC, c.name, c.id
+        LIMIT $3"#
+    )
+}
+
+pub async fn ecosystem_combinations(
+    client: &Client,
+    live: &str,
+    format: RowFormat,
+) -> Result<Vec<EcosystemCombination>, tokio_postgres::Error> {
+    let rows = timed(
+        "ecosystem_combinations",
+        client.query(&ecosystem_combinations_query(live), &[]),
+    )
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|row| EcosystemCombination::from_row(row, format))
+        .collect())
+}
+
+/// At most `limit` canons in several package managers, all of `managers`
+/// among them, as [`CrossEcosystemProject`]s
+pub async fn cross_ecosystem_projects(
+    client: &Client,
+    run: Option<i32>,
+    managers: &[&str],
+    limit: i64,
+    live: &str,
+    format: RowFormat,
+) -> Result<Vec<Value>, tokio_postgres::Error> {
+    let rows = timed(
+        "cross_ecosystem_projects",
+        client.query(
+            &cross_ecosystem_projects_query(live),
+            &[&run, &managers, &limit],
+        ),
+    )
+    .await?;
+    Ok(to_json::<CrossEcosystemProject>(&rows, format))
+}
+
+/// Live canons ranked in run `$1`, the latest, whose newest version was
+/// published more than `$3` months ago, optionally only those with a package
+/// in package manager `$2`, as a `FROM` and `WHERE` for the queries below.
+/// Releases on any of a canon's package managers count, and canons without a
+/// known release date are left out.
+fn stale_projects_from(live: &str) -> String {
+    let rank = LatestRank::of("c.id").in_run("$1", true).join("tr");
+    format!(
+        r#"
+        FROM (
+            SELECT
+                cp.canon_id,
+                MAX(v.published_at) AS last_release,
+                BOOL_OR(s.type = $2) AS in_ecosystem
+            FROM canon_packages cp
+            JOIN packages p ON p.id = cp.package_id
+            JOIN package_managers pm ON pm.id = p.package_manager_id
+            JOIN sources s ON s.id = pm.source_id
+            LEFT JOIN versions v ON v.package_id = p.id
+            GROUP BY cp.canon_id
+        ) a
+        JOIN canons c ON c.id = a.canon_id
+        {rank}
+        WHERE tr.canon_id IS NOT NULL
+        AND ($2::text IS NULL OR a.in_ecosystem)
+        AND a.last_release < now() - make_interval(months => $3)
+        AND {live}"#
+    )
+}
+
+/// How many canons [`stale_projects_query`] would list over all pages
+pub fn stale_projects_count_query(live: &str) -> String {
+    format!("SELECT COUNT(*) {}", stale_projects_from(live))
+}
+
+/// One page of stale canons, best ranked first: `$4` of them from offset `$5`
+pub fn stale_projects_query(live: &str) -> String {
+    format!(
+        r#"
+        SELECT c.id AS project_id, c.name, tr.rank AS tea_rank, a.last_release
+        {}
+        ORDER BY CAST(tr.rank AS NUMERIC) DESC, c.name, c.id
+        LIMIT $4 OFFSET $5"#,
+        stale_projects_from(live)
+    )
+}
+
+/// Source hosts reported on their own; the rest count as `self-hosted`
+pub const KNOWN_FORGES: [&str; 7] = [
+    "github.com",
+    "gitlab.com",
+    "codeberg.org",
+    "bitbucket.org",
+    "git.sr.ht",
+    "sourceforge.net",
+    "gitee.com",
+];
+
+/// Live canons grouped by the host of their source URL, with the sum of their
+/// ranks in run `$1`, the latest. Hosts outside `$2` are grouped as
+/// `self-hosted`, and canons without a source as a null `host`. Like the
+/// leaderboard, a canon with several source URLs counts under the first. Hosts
+/// are lowercased with the scheme, credentials, port and `www.` removed, so
+/// `git@github.com:a/b` and `git+https://www.github.com/a/b` agree.
+pub fn source_hosts_query(live: &str) -> String {
+    let rank = LatestRank::of("s.id").in_run("$1", true).join("tr");
+    format!(
+        r#"
+        WITH sourced AS (
+            SELECT
+                c.id,
+                regexp_replace(
+                    lower(substring(src.url FROM '^(?:[a-z0-9+.-]+://)?(?:[^@/]+@)?([^/:?#]+)')),
+                    '^www\.',
+                    ''
+                ) AS host
+            FROM canons c
+            LEFT JOIN LATERAL (
+                SELECT u.url
+                FROM canon_packages cp
+                JOIN package_urls pu ON pu.package_id = cp.package_id
+                JOIN urls u ON pu.url_id = u.id
+                JOIN url_types ut ON ut.id = u.url_type_id
+                WHERE cp.canon_id = c.id AND ut.name = 'source'
+                ORDER BY u.url
+                LIMIT 1
+            ) src ON TRUE
+            WHERE {live}
+        )
+        SELECT
+            CASE
+                WHEN s.host IS NULL THEN NULL
+                WHEN s.host = ANY($2::text[]) THEN s.host
+                ELSE 'self-hosted'
+            END AS host,
+            COUNT(*) AS projects,
+            COUNT(DISTINCT s.host) AS hosts,
+            COALESCE(SUM(CAST(tr.rank AS NUMERIC)), 0)::float8 AS rank_mass
+        FROM sourced s
+        {rank}
+        GROUP BY 1
+        ORDER BY projects DESC, host"#
+    )
+}
+
+/// Which canons count as stale: those ranked in `run` without a release in
+/// `months`, with a package in `ecosystem` if set
+pub struct StaleFilter<'a> {
+    pub run: Option<i32>,
+    pub ecosystem: Option<&'a str>,
+    pub months: i32,
+}
+
+pub async fn count_stale_projects(
+    client: &Client,
+    filter: &StaleFilter<'_>,
+    live: &str,
+) -> Result<i64, tokio_postgres::Error> {
+    let row = timed(
+        "stale_projects_count",
+        client.query_one(
+            &stale_projects_count_query(live),
+            &[&filter.run, &filter.ecosystem, &filter.months],
+        ),
+    )
+    .await?;
+    Ok(row.get(0))
+}
+
+/// `limit` stale canons from `offset`, as [`StaleProject`]s
+pub async fn stale_projects(
+    client: &Client,
+    filter: &StaleFilter<'_>,
+    limit: i64,
+    offset: i64,
+    live: &str,
+    format: RowFormat,
+) -> Result<Vec<Value>, tokio_postgres::Error> {
+    let rows = timed(
+        "stale_projects",
+        client.query(
+            &stale_projects_query(live),
+            &[
+                &filter.run,
+                &filter.ecosystem,
+                &filter.months,
+                &limit,
+                &offset,
+            ],
+        ),
+    )
+    .await?;
+    Ok(to_json::<StaleProject>(&rows, format))
+}
+
+/// Live canons by source host, with ranks from `run`
+pub async fn source_hosts(
+    client: &Client,
+    run: Option<i32>,
+    live: &str,
+    format: RowFormat,
+) -> Result<SourceHosts, tokio_postgres::Error> {
+    let rows = timed(
+        "source_hosts",
+        client.query(&source_hosts_query(live), &[&run, &KNOWN_FORGES.as_slice()]),
+    )
+    .await?;
+
+    let mut without_source = 0;
+    let mut hosts = Vec::new();
+    for row in &rows {
+        let projects: i64 = row.get("projects");
+        let Some(host) = row.get::<_, Option<String>>("host") else {
+            without_source = projects;
+            continue;
+        };
+        hosts.push(SourceHost {
+            host,
+            projects: Int8(projects, format),
+            hosts: Int8(row.get("hosts"), format),
+            rank_mass: row.get("rank_mass"),
+            rank_share: 0.0,
+        });
+    }
+    let total_mass: f64 = hosts.iter().map(|host| host.rank_mass).sum();
+    if total_mass > 0.0 {
+        for host in &mut hosts {
+            host.rank_share = host.rank_mass / total_mass;
+        }
+    }
+    Ok(SourceHosts {
+        run,
+        hosts,
+        without_source: Int8(without_source, format),
+    })
+}
+
+/// Each package manager with the last time one of its packages was written by
+/// the indexers, `None` when it has no packages. This reads every package, so
+/// it is computed in the background.
+pub async fn ecosystem_ingests(
+    client: &Client,
+) -> Result<Vec<(String, Option<NaiveDateTime>)>, tokio_postgres::Error> {
+    let rows = timed(
+        "ecosystem_ingests",
+        client.query(
+            r#"
+            SELECT s.type AS ecosystem, MAX(p.updated_at) AS last_ingest
+            FROM sources s
+            LEFT JOIN package_managers pm ON pm.source_id = s.id
+            LEFT JOIN packages p ON p.package_manager_id = pm.id
+            GROUP BY s.type
+            ORDER BY s.type"#,
+            &[],
+        ),
+    )
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|row| (row.get("ecosystem"), row.get("last_ingest")))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_project, test_client};
+
+    /// Adds a package on `source` to `canon`
+    async fn add_package(client: &Client, canon: uuid::Uuid, source: &str) {
+        client
+            .execute(
+                r#"
+                WITH src AS (
+                    INSERT INTO sources (id, type) VALUES (gen_random_uuid(), $2) RETURNING id
+                ), pm AS (
+                    INSERT INTO package_managers (id, source_id)
+                    SELECT gen_random_uuid(), id FROM src RETURNING id
+                ), pkg AS (
+                    INSERT INTO packages (id, package_manager_id, name)
+                    SELECT gen_random_uuid(), id, 'extra' FROM pm RETURNING id
+                )
+                INSERT INTO canon_packages (canon_id, package_id) SELECT $1, id FROM pkg"#,
+                &[&canon, &source],
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn cross_ecosystem_groups_canons_by_package_managers() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        let curl = insert_project(&client, "curl", "homebrew", &[(1, "50")]).await;
+        add_package(&client, curl, "debian").await;
+        let jq = insert_project(&client, "jq", "homebrew", &[(1, "70")]).await;
+        add_package(&client, jq, "debian").await;
+        let zlib = insert_project(&client, "zlib", "homebrew", &[(1, "90")]).await;
+        add_package(&client, zlib, "debian").await;
+        add_package(&client, zlib, "pkgx").await;
+        insert_project(&client, "left-pad", "npm", &[(1, "99")]).await;
+
+        let combinations = ecosystem_combinations(&client, "TRUE", RowFormat::default())
+            .await
+            .unwrap();
+        let counts: Vec<(Vec<String>, i64)> = combinations
+            .into_iter()
+            .map(|c| (c.package_managers, c.count.0))
+            .collect();
+        assert_eq!(
+            counts,
+            [
+                (vec!["debian".to_string(), "homebrew".to_string()], 2),
+                (
+                    vec![
+                        "debian".to_string(),
+                        "homebrew".to_string(),
+                        "pkgx".to_string()
+                    ],
+                    1
+                ),
+            ]
+        );
+
+        let names = |projects: Vec<Value>| -> Vec<String> {
+            projects
+                .iter()
+                .map(|p| p["name"].as_str().unwrap().to_string())
+                .collect()
+        };
+        let all = cross_ecosystem_projects(&client, Some(1), &[], 10, "TRUE", RowFormat::default())
+            .await
+            .unwrap();
+        assert_eq!(names(all), ["zlib", "jq", "curl"]);
+        let pkgx = cross_ecosystem_projects(
+            &client,
+            Some(1),
+            &["pkgx", "debian"],
+            10,
+            "TRUE",
+            RowFormat::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(names(pkgx), ["zlib"]);
+    }
+
+    #[tokio::test]
+    async fn source_hosts_normalize_urls_and_group_self_hosted() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        // Fixture projects have a https://github.com source
+        insert_project(&client, "tokio", "crates", &[(1, "30")]).await;
+        let serde = insert_project(&client, "serde", "crates", &[(1, "10")]).await;
+        let zlib = insert_project(&client, "zlib", "homebrew", &[(1, "40")]).await;
+        let git = insert_project(&client, "git", "homebrew", &[(1, "20")]).await;
+        let unsourced = insert_project(&client, "unsourced", "npm", &[]).await;
+        for (canon, url) in [
+            (serde, "git+https://www.GitHub.com/serde-rs/serde.git"),
+            (zlib, "https://git.zlib.example:8443/zlib"),
+            (git, "git@git.kernel.example:git/git.git"),
+        ] {
+            client
+                .execute(
+                    r#"
+                    UPDATE urls u SET url = $2
+                    FROM package_urls pu, canon_packages cp
+                    WHERE pu.url_id = u.id AND cp.package_id = pu.package_id
+                    AND cp.canon_id = $1"#,
+                    &[&canon, &url],
+                )
+                .await
+                .unwrap();
+        }
+        client
+            .execute(
+                r#"
+                DELETE FROM package_urls pu
+                USING canon_packages cp
+                WHERE cp.package_id = pu.package_id AND cp.canon_id = $1"#,
+                &[&unsourced],
+            )
+            .await
+            .unwrap();
+
+        let stats = source_hosts(&client, Some(1), "TRUE", RowFormat::default())
+            .await
+            .unwrap();
+        let hosts: Vec<(&str, i64, i64, f64)> = stats
+            .hosts
+            .iter()
+            .map(|h| (h.host.as_str(), h.projects.0, h.hosts.0, h.rank_mass))
+            .collect();
+        assert_eq!(
+            hosts,
+            [("github.com", 2, 1, 40.0), ("self-hosted", 2, 2, 60.0)]
+        );
+        assert_eq!(stats.hosts[1].rank_share, 0.6);
+        assert_eq!(stats.without_source.0, 1);
+    }
+
+    #[tokio::test]
+    async fn stale_projects_skip_recent_and_undated_releases() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        let serde = insert_project(&client, "serde", "crates", &[(1, "30")]).await;
+        let abandoned = insert_project(&client, "abandoned", "crates", &[(1, "20")]).await;
+        let leftpad = insert_project(&client, "left-pad", "npm", &[(1, "40")]).await;
+        insert_project(&client, "undated", "crates", &[(1, "50")]).await;
+        let unranked = insert_project(&client, "unranked", "crates", &[]).await;
+        for (canon, age) in [
+            (serde, "1 month"),
+            (abandoned, "3 years"),
+            (leftpad, "5 years"),
+            (unranked, "5 years"),
+        ] {
+            client
+                .execute(
+                    r#"
+                    INSERT INTO versions (id, package_id, version, published_at)
+                    SELECT gen_random_uuid(), package_id, '1.0.0', now() - $2::text::interval
+                    FROM canon_packages WHERE canon_id = $1"#,
+                    &[&canon, &age],
+                )
+                .await
+                .unwrap();
+        }
+
+        let filter = |ecosystem, months| StaleFilter {
+            run: Some(1),
+            ecosystem,
+            months,
+        };
+        let names = |projects: Vec<Value>| -> Vec<String> {
+            projects
+                .iter()
+                .map(|p| p["name"].as_str().unwrap().to_string())
+                .collect()
+        };
+        let all = stale_projects(
+            &client,
+            &filter(None, 24),
+            10,
+            0,
+            "TRUE",
+            RowFormat::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(names(all), ["left-pad", "abandoned"]);
+        let crates = stale_projects(
+            &client,
+            &filter(Some("crates"), 24),
+            10,
+            0,
+            "TRUE",
+            RowFormat::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(names(crates), ["abandoned"]);
+        assert_eq!(
+            count_stale_projects(&client, &filter(None, 48), "TRUE")
+                .await
+                .unwrap(),
+            1
+        );
+    }
+}
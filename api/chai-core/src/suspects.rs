@@ -0,0 +1,351 @@
+//! Spotting spam, placeholder and name-squatting canons. Functions taking
+//! `live` expect a SQL predicate over canons `c` that filters out soft-deleted
+//! rows, or `TRUE`.
+
+use chai_types::format::RowFormat;
+use chai_types::models::{SimilarName, Suspect};
+use serde_json::Value;
+use tokio_postgres::Client;
+use uuid::Uuid;
+
+use crate::db::timed;
+use crate::models::{to_json, FromRow};
+use crate::sql::LatestRank;
+
+/// How many of the best ranked projects lookalike names are checked against
+pub const GUARDED_PROJECTS: i64 = 100;
+
+/// The trigram similarity from which two different names count as lookalikes.
+/// It must stay above `pg_trgm.similarity_threshold` (0.3 by default), which
+/// the indexed `%` operator filters on first.
+pub const LOOKALIKE_SIMILARITY: f32 = 0.6;
+
+/// Canons with at least `$2` of three signals, most signals first: no source
+/// URL, no dependents, and a name within `$3` similarity of one of the `$4`
+/// best ranked canons of run `$1`, the latest, that outranks it. Canons already
+/// excluded from the leaderboard by a curator are left out, and `$5` caps the
+/// rows.
+pub fn suspects_query(live: &str) -> String {
+    let own_rank = LatestRank::of("c.id").in_run("$1", true).join("own");
+    let rank = LatestRank::of("s.id").in_run("$1", true).join("tr");
+    format!(
+        r#"
+        WITH top AS (
+            SELECT c.id, c.name, tr.rank
+            FROM tea_ranks tr
+            JOIN canons c ON c.id = tr.canon_id
+            WHERE tr.tea_rank_run = $1 AND {live}
+            ORDER BY CAST(tr.rank AS NUMERIC) DESC, c.id
+            LIMIT $4
+        ),
+        lookalikes AS (
+            SELECT DISTINCT ON (c.id)
+                c.id,
+                t.id AS resembles_id,
+                t.name AS resembles_name,
+                t.rank AS resembles_rank,
+                similarity(c.name, t.name) AS similarity
+            FROM top t
+            JOIN canons c ON c.name % t.name AND c.id <> t.id
+            {own_rank}
+            WHERE similarity(c.name, t.name) >= $3
+            AND (own.rank IS NULL OR CAST(own.rank AS NUMERIC) < CAST(t.rank AS NUMERIC))
+            AND {live}
+            ORDER BY c.id, similarity DESC, t.id
+        ),
+        flagged AS (
+            SELECT
+                c.id,
+                c.name,
+                NOT EXISTS (
+                    SELECT 1
+                    FROM canon_packages cp
+                    JOIN package_urls pu ON pu.package_id = cp.package_id
+                    JOIN urls u ON u.id = pu.url_id
+                    JOIN url_types ut ON ut.id = u.url_type_id
+                    WHERE cp.canon_id = c.id AND ut.name = 'source'
+                ) AS no_source,
+                NOT EXISTS (
+                    SELECT 1
+                    FROM legacy_dependencies ld
+                    JOIN canon_packages cp_in ON cp_in.package_id = ld.dependency_id
+                    JOIN canon_packages cp_out ON cp_out.package_id = ld.package_id
+                    WHERE cp_in.canon_id = c.id AND cp_out.canon_id <> c.id
+                ) AS no_dependents,
+                l.resembles_id,
+                l.resembles_name,
+                l.resembles_rank,
+                l.similarity
+            FROM canons c
+            LEFT JOIN lookalikes l ON l.id = c.id
+            WHERE {live}
+            AND NOT EXISTS (
+                SELECT 1
+                FROM leaderboard_overrides lo
+                WHERE lo.canon_id = c.id
+                AND lo.pinned_rank IS NULL
+                AND (lo.expires_at IS NULL OR lo.expires_at > now())
+            )
+        ),
+        counted AS (
+            SELECT
+                f.*,
+                f.no_source::int + f.no_dependents::int + (f.resembles_id IS NOT NULL)::int
+                    AS signal_count
+            FROM flagged f
+        )
+        SELECT
+            s.id AS project_id,
+            s.name,
+            tr.rank AS tea_rank,
+            ARRAY_REMOVE(ARRAY[
+                CASE WHEN s.no_source THEN 'noSource' END,
+                CASE WHEN s.no_dependents THEN 'noDependents' END,
+                CASE WHEN s.resembles_id IS NOT NULL THEN 'lookalike' END
+            ], NULL) AS signals,
+            s.resembles_id,
+            s.resembles_name,
+            s.resembles_rank,
+            s.similarity
+        FROM counted s
+        {rank}
+        WHERE s.signal_count >= $2
+        ORDER BY s.signal_count DESC, s.similarity DESC NULLS LAST, s.name, s.id
+        LIMIT $5"#
+    )
+}
+
+/// At most `limit` canons with `min_signals` or more signals, as [`Suspect`]s.
+/// Without a `run` there are no ranks, so nothing is flagged as a lookalike.
+pub async fn suspects(
+    client: &Client,
+    run: Option<i32>,
+    min_signals: i32,
+    limit: i64,
+    live: &str,
+    format: RowFormat,
+) -> Result<Vec<Value>, tokio_postgres::Error> {
+    let rows = timed(
+        "suspects",
+        client.query(
+            &suspects_query(live),
+            &[
+                &run,
+                &min_signals,
+                &LOOKALIKE_SIMILARITY,
+                &GUARDED_PROJECTS,
+                &limit,
+            ],
+        ),
+    )
+    .await?;
+    Ok(to_json::<Suspect>(&rows, format))
+}
+
+/// The most edits between two names that still count as similar, whatever
+/// their trigram similarity
+pub const MAX_EDITS: u32 = 2;
+
+/// How many trigram candidates [`similar_names`] checks before filtering
+const NAME_CANDIDATES: i64 = 500;
+
+/// Canons other than `$3` whose name shares enough trigrams with `$1` for the
+/// indexed `%` operator, most similar first, with their rank in run `$2`, the
+/// latest
+pub fn similar_names_query(live: &str) -> String {
+    let rank = LatestRank::of("c.id").in_run("$2", true).join("tr");
+    format!(
+        r#"
+        SELECT
+            c.id AS project_id,
+            c.name,
+            $1 AS compared_name,
+            tr.rank AS tea_rank,
+            similarity(c.name, $1) AS similarity
+        FROM canons c
+        {rank}
+        WHERE c.name % $1
+        AND c.id IS DISTINCT FROM $3
+        AND {live}
+        ORDER BY similarity DESC, c.name, c.id
+        LIMIT $4"#
+    )
+}
+
+/// Up to `limit` canons named like `name`, leaving out `exclude`: within
+/// [`MAX_EDITS`] edits or [`LOOKALIKE_SIMILARITY`], fewest edits first. Only
+/// names sharing some trigrams are considered, so very short names can miss
+/// typos that change most of their trigrams.
+pub async fn similar_names(
+    client: &Client,
+    name: &str,
+    exclude: Option<Uuid>,
+    run: Option<i32>,
+    limit: usize,
+    live: &str,
+    format: RowFormat,
+) -> Result<Vec<SimilarName>, tokio_postgres::Error> {
+    let rows = timed(
+        "similar_names",
+        client.query(
+            &similar_names_query(live),
+            &[&name, &run, &exclude, &NAME_CANDIDATES],
+        ),
+    )
+    .await?;
+    let mut matches: Vec<SimilarName> = rows
+        .iter()
+        .map(|row| SimilarName::from_row(row, format))
+        .filter(|m| m.edit_distance <= MAX_EDITS || m.similarity >= LOOKALIKE_SIMILARITY)
+        .collect();
+    matches.sort_by(|a, b| {
+        a.edit_distance
+            .cmp(&b.edit_distance)
+            .then(b.similarity.total_cmp(&a.similarity))
+            .then_with(|| a.name.cmp(&b.name))
+            .then(a.project_id.cmp(&b.project_id))
+    });
+    matches.truncate(limit);
+    Ok(matches)
+}
+
+/// The Levenshtein distance between `a` and `b`, ignoring case
+pub fn edit_distance(a: &str, b: &str) -> u32 {
+    let a: Vec<char> = a.chars().flat_map(char::to_lowercase).collect();
+    let b: Vec<char> = b.chars().flat_map(char::to_lowercase).collect();
+    let mut previous: Vec<u32> = (0..=b.len() as u32).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i as u32 + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let replace = previous[j] + u32::from(ca != cb);
+            current[j + 1] = replace.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_project, test_client};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn suspects_combine_signals() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        let react = insert_project(&client, "react", "npm", &[(1, "900")]).await;
+        let lodash = insert_project(&client, "lodash", "npm", &[(1, "800")]).await;
+        let reactt = insert_project(&client, "reactt", "npm", &[(1, "1")]).await;
+        let placeholder = insert_project(&client, "placeholder-pkg", "npm", &[]).await;
+        client
+            .execute(
+                r#"
+                DELETE FROM package_urls pu
+                USING canon_packages cp
+                WHERE cp.package_id = pu.package_id AND cp.canon_id = $1"#,
+                &[&placeholder],
+            )
+            .await
+            .unwrap();
+        // Something depends on lodash, so only lodash has dependents
+        client
+            .execute(
+                r#"
+                INSERT INTO legacy_dependencies (package_id, dependency_id)
+                SELECT dependent.package_id, dependency.package_id
+                FROM canon_packages dependent, canon_packages dependency
+                WHERE dependent.canon_id = $1 AND dependency.canon_id = $2"#,
+                &[&react, &lodash],
+            )
+            .await
+            .unwrap();
+
+        let found = suspects(&client, Some(1), 2, 10, "TRUE", RowFormat::default())
+            .await
+            .unwrap();
+        let names: Vec<&str> = found.iter().map(|s| s["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["reactt", "placeholder-pkg"]);
+        assert_eq!(found[0]["signals"], json!(["noDependents", "lookalike"]));
+        assert_eq!(found[0]["teaRank"], "1");
+        assert_eq!(found[0]["resembles"]["projectId"], react.to_string());
+        assert_eq!(found[1]["signals"], json!(["noSource", "noDependents"]));
+
+        // A curator's exclusion takes a canon off the list
+        client
+            .execute(
+                "INSERT INTO leaderboard_overrides (canon_id, reason) VALUES ($1, 'spam')",
+                &[&reactt],
+            )
+            .await
+            .unwrap();
+        let found = suspects(&client, Some(1), 2, 10, "TRUE", RowFormat::default())
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+
+        // One signal is enough when asked for: react has no dependents either
+        let found = suspects(&client, Some(1), 1, 10, "TRUE", RowFormat::default())
+            .await
+            .unwrap();
+        let names: Vec<&str> = found.iter().map(|s| s["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["placeholder-pkg", "react"]);
+    }
+
+    #[test]
+    fn edit_distance_counts_single_character_edits() {
+        assert_eq!(edit_distance("react", "react"), 0);
+        assert_eq!(edit_distance("React", "react"), 0);
+        assert_eq!(edit_distance("react", "reactt"), 1);
+        assert_eq!(edit_distance("lodash", "1odash"), 1);
+        assert_eq!(edit_distance("requests", "reqeusts"), 2);
+        assert_eq!(edit_distance("", "npm"), 3);
+    }
+
+    #[tokio::test]
+    async fn similar_names_match_by_edits_or_trigrams() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        let requests = insert_project(&client, "requests", "pypi", &[(1, "500")]).await;
+        insert_project(&client, "reqeusts", "pypi", &[]).await;
+        insert_project(&client, "py-requests", "pypi", &[(1, "90")]).await;
+        insert_project(&client, "python-requests-toolbelt", "pypi", &[]).await;
+        insert_project(&client, "numpy", "pypi", &[(1, "400")]).await;
+
+        let matches = similar_names(
+            &client,
+            "requests",
+            Some(requests),
+            Some(1),
+            10,
+            "TRUE",
+            RowFormat::default(),
+        )
+        .await
+        .unwrap();
+        let found: Vec<(&str, u32)> = matches
+            .iter()
+            .map(|m| (m.name.as_str(), m.edit_distance))
+            .collect();
+        // The toolbelt shares the trigrams but too few of them
+        assert_eq!(found, [("reqeusts", 2), ("py-requests", 3)]);
+        assert_eq!(matches[1].tea_rank.as_deref(), Some("90"));
+
+        let matches = similar_names(
+            &client,
+            "Requests",
+            None,
+            Some(1),
+            1,
+            "TRUE",
+            RowFormat::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(matches[0].project_id, requests);
+    }
+}
@@ -0,0 +1,197 @@
+//! Postgres fixtures for tests. [`test_client`] returns `None` when
+//! `DATABASE_URL` is unset, and tests that need a database skip themselves.
+
+use tokio_postgres::{Client, NoTls};
+use uuid::Uuid;
+
+use crate::ranks::rebuild_latest_ranks;
+
+/// Session-local copies of the tables the API queries, trimmed to the columns it
+/// reads. Temporary tables shadow real ones and vanish with the connection, so
+/// tests behave the same against an empty database or a populated one.
+const SCHEMA: &str = r#"
+    CREATE TEMP TABLE sources (id uuid PRIMARY KEY, type varchar NOT NULL);
+    CREATE TEMP TABLE package_managers (id uuid PRIMARY KEY, source_id uuid NOT NULL);
+    CREATE TEMP TABLE packages (
+        id uuid PRIMARY KEY,
+        package_manager_id uuid NOT NULL,
+        name text NOT NULL,
+        readme text
+    );
+    CREATE TEMP TABLE url_types (id uuid PRIMARY KEY, name text NOT NULL);
+    CREATE TEMP TABLE urls (id uuid PRIMARY KEY, url text NOT NULL, url_type_id uuid NOT NULL);
+    CREATE TEMP TABLE package_urls (
+        id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+        package_id uuid NOT NULL,
+        url_id uuid NOT NULL
+    );
+    CREATE TEMP TABLE canons (
+        id uuid PRIMARY KEY,
+        url_id uuid NOT NULL,
+        name text NOT NULL,
+        created_at timestamp NOT NULL DEFAULT now(),
+        updated_at timestamp NOT NULL DEFAULT now()
+    );
+    CREATE TEMP TABLE canon_slugs (canon_id uuid NOT NULL, slug text NOT NULL);
+    CREATE TEMP TABLE canon_redirects (
+        old_canon_id uuid PRIMARY KEY,
+        canon_id uuid NOT NULL,
+        created_at timestamp NOT NULL DEFAULT now()
+    );
+    CREATE TEMP TABLE versions (
+        id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+        package_id uuid NOT NULL,
+        version text NOT NULL,
+        published_at timestamp
+    );
+    CREATE TEMP TABLE canon_packages (
+        id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+        canon_id uuid NOT NULL,
+        package_id uuid NOT NULL
+    );
+    CREATE TEMP TABLE tea_rank_runs (
+        id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+        run integer NOT NULL,
+        split_ratio text NOT NULL DEFAULT '0.85',
+        created_at timestamp NOT NULL DEFAULT now(),
+        quarantined_at timestamp
+    );
+    CREATE TEMP TABLE tea_ranks (
+        id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+        tea_rank_run integer NOT NULL,
+        canon_id uuid NOT NULL,
+        rank varchar NOT NULL,
+        created_at timestamp NOT NULL DEFAULT now()
+    );
+    CREATE TEMP TABLE latest_ranks (
+        canon_id uuid PRIMARY KEY,
+        tea_rank_run integer NOT NULL,
+        rank varchar NOT NULL,
+        created_at timestamp NOT NULL,
+        positive_tea_rank_run integer,
+        positive_rank varchar,
+        positive_created_at timestamp
+    );
+    CREATE TEMP TABLE leaderboard_overrides (
+        canon_id uuid PRIMARY KEY,
+        pinned_rank varchar,
+        reason varchar NOT NULL,
+        expires_at timestamp,
+        created_at timestamp NOT NULL DEFAULT now()
+    );
+    CREATE TEMP TABLE users (
+        id uuid PRIMARY KEY,
+        username text NOT NULL,
+        source_id uuid NOT NULL
+    );
+    CREATE TEMP TABLE user_packages (
+        id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+        user_id uuid NOT NULL,
+        package_id uuid NOT NULL
+    );
+    CREATE TEMP TABLE maintainer_verifications (
+        id uuid PRIMARY KEY DEFAULT gen_random_uuid(),
+        canon_id uuid NOT NULL,
+        maintainer text NOT NULL,
+        token text NOT NULL UNIQUE,
+        verified_at timestamp,
+        last_checked_at timestamp,
+        created_at timestamp NOT NULL DEFAULT now(),
+        UNIQUE (canon_id, maintainer)
+    );
+    CREATE TEMP TABLE legacy_dependencies (
+        id serial PRIMARY KEY,
+        package_id uuid NOT NULL,
+        dependency_id uuid NOT NULL,
+        created_at timestamp NOT NULL DEFAULT now()
+    );
+    INSERT INTO url_types (id, name) VALUES
+        (gen_random_uuid(), 'homepage'),
+        (gen_random_uuid(), 'source');
+"#;
+
+/// A connection with the fixture schema loaded, or `None` without `DATABASE_URL`
+pub async fn test_client() -> Option<Client> {
+    let Ok(url) = std::env::var("DATABASE_URL") else {
+        eprintln!("DATABASE_URL not set, skipping database test");
+        return None;
+    };
+    let (client, connection) = tokio_postgres::connect(&url, NoTls)
+        .await
+        .expect("Failed to connect to DATABASE_URL");
+    tokio::spawn(connection);
+    create_fixture_tables(&client).await;
+    Some(client)
+}
+
+/// Loads the fixture schema on `client`, for connections opened some other way
+pub async fn create_fixture_tables(client: &Client) {
+    client
+        .batch_execute(SCHEMA)
+        .await
+        .expect("Failed to create fixture tables");
+}
+
+/// Inserts a canon with one package on `source` (e.g. "npm"), a homepage and a
+/// source URL, and a rank in each of `ranks` as `(run, rank)`, then rebuilds
+/// `latest_ranks`. Tests that quarantine a run rebuild it again themselves.
+pub async fn insert_project(
+    client: &Client,
+    name: &str,
+    source: &str,
+    ranks: &[(i32, &str)],
+) -> Uuid {
+    let canon_id = Uuid::new_v4();
+    let package_id = Uuid::new_v4();
+    client
+        .execute(
+            r#"
+            WITH src AS (
+                INSERT INTO sources (id, type) VALUES (gen_random_uuid(), $4) RETURNING id
+            ), pm AS (
+                INSERT INTO package_managers (id, source_id)
+                SELECT gen_random_uuid(), id FROM src RETURNING id
+            ), pkg AS (
+                INSERT INTO packages (id, package_manager_id, name)
+                SELECT $2, id, $3 FROM pm RETURNING id
+            ), homepage AS (
+                INSERT INTO urls (id, url, url_type_id)
+                SELECT gen_random_uuid(), 'https://' || $3 || '.example.org', id
+                FROM url_types WHERE name = 'homepage'
+                RETURNING id
+            ), source_url AS (
+                INSERT INTO urls (id, url, url_type_id)
+                SELECT gen_random_uuid(), 'https://github.com/example/' || $3, id
+                FROM url_types WHERE name = 'source'
+                RETURNING id
+            ), linked AS (
+                INSERT INTO package_urls (package_id, url_id)
+                SELECT $2, id FROM source_url
+            ), canon AS (
+                INSERT INTO canons (id, url_id, name) SELECT $1, id, $3 FROM homepage
+            )
+            INSERT INTO canon_packages (canon_id, package_id) VALUES ($1, $2)"#,
+            &[&canon_id, &package_id, &name, &source],
+        )
+        .await
+        .expect("Failed to insert project fixture");
+
+    for (run, rank) in ranks {
+        client
+            .execute(
+                r#"
+                WITH run AS (
+                    INSERT INTO tea_rank_runs (run)
+                    SELECT $1 WHERE NOT EXISTS (SELECT 1 FROM tea_rank_runs WHERE run = $1)
+                )
+                INSERT INTO tea_ranks (tea_rank_run, canon_id, rank) VALUES ($1, $2, $3)"#,
+                &[run, &canon_id, rank],
+            )
+            .await
+            .expect("Failed to insert rank fixture");
+    }
+    rebuild_latest_ranks(client)
+        .await
+        .expect("Failed to rebuild latest ranks");
+    canon_id
+}
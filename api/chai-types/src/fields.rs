@@ -0,0 +1,200 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// `?fields=name,teaRank,homepage`: the fields of each project to return.
+/// `projectId` is always returned. `?include=packageManagers` adds fields that
+/// are otherwise left out, either by `fields` or because they're opt-in.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct FieldsParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub fields: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub include: Option<String>,
+}
+
+/// A validated field selection
+#[derive(Debug, PartialEq)]
+pub struct Fields {
+    /// Fields to return, `projectId` first
+    requested: Vec<String>,
+    /// Fields of the payload that weren't asked for
+    omitted: Vec<String>,
+}
+
+impl FieldsParams {
+    /// Only `fields`
+    pub fn new<'a>(fields: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            fields: Some(fields.into_iter().collect::<Vec<_>>().join(",")),
+            include: None,
+        }
+    }
+
+    /// The requested fields, each checked against `fields`. Without `?fields`
+    /// that is all of them but the `opt_in` ones `?include` doesn't name;
+    /// `None` when that leaves none out.
+    pub fn parse(&self, fields: &[&str], opt_in: &[&str]) -> Result<Option<Fields>, String> {
+        let included = match self.include.as_deref() {
+            Some(raw) => listed(raw, "include", fields)?,
+            None => Vec::new(),
+        };
+        let mut requested = Vec::from(["projectId".to_string()]);
+        match self.fields.as_deref() {
+            Some(raw) => {
+                for field in listed(raw, "fields", fields)?.into_iter().chain(included) {
+                    if !requested.iter().any(|requested| requested == field) {
+                        requested.push(field.to_string());
+                    }
+                }
+            }
+            None => {
+                if opt_in.iter().all(|field| included.contains(field)) {
+                    return Ok(None);
+                }
+                requested = fields
+                    .iter()
+                    .filter(|field| !opt_in.contains(field) || included.contains(field))
+                    .map(|field| field.to_string())
+                    .collect();
+            }
+        }
+        let omitted = fields
+            .iter()
+            .filter(|field| !requested.iter().any(|requested| requested == *field))
+            .map(|field| field.to_string())
+            .collect();
+        Ok(Some(Fields { requested, omitted }))
+    }
+}
+
+/// The fields of a comma-separated `param`, each checked against `fields`
+fn listed<'a>(raw: &'a str, param: &str, fields: &[&str]) -> Result<Vec<&'a str>, String> {
+    raw.split(',')
+        .map(str::trim)
+        .map(|field| {
+            if field.is_empty() {
+                return Err(format!("{param} lists an empty field"));
+            }
+            if !fields.contains(&field) {
+                return Err(format!(
+                    "Unknown field '{field}' in {param} (expected any of: {})",
+                    fields.join(", ")
+                ));
+            }
+            Ok(field)
+        })
+        .collect()
+}
+
+impl Fields {
+    pub fn contains(&self, field: &str) -> bool {
+        self.requested.iter().any(|requested| requested == field)
+    }
+
+    /// Removes the fields that weren't asked for from `value`, or from each
+    /// of its items when it is an array. Keys outside the selectable fields,
+    /// like `redirectedFrom`, are kept.
+    pub fn select(&self, value: &mut Value) {
+        match value {
+            Value::Array(items) => items.iter_mut().for_each(|item| self.select(item)),
+            Value::Object(object) => {
+                object.retain(|key, _| !self.omitted.iter().any(|omitted| omitted == key))
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn fields_select_from_payloads() {
+        let fields = ["projectId", "name", "teaRank", "packageManagers"];
+        let selected = FieldsParams::new(["name", " teaRank", "name"])
+            .parse(&fields, &[])
+            .unwrap()
+            .unwrap();
+        assert!(selected.contains("projectId"));
+        assert!(selected.contains("teaRank"));
+        assert!(!selected.contains("packageManagers"));
+
+        let mut value = json!([{
+            "projectId": "x",
+            "name": "react",
+            "teaRank": "1.5",
+            "packageManagers": ["npm"],
+            "redirectedFrom": "y",
+        }]);
+        selected.select(&mut value);
+        assert_eq!(
+            value,
+            json!([{
+                "projectId": "x",
+                "name": "react",
+                "teaRank": "1.5",
+                "redirectedFrom": "y",
+            }])
+        );
+
+        assert_eq!(FieldsParams::default().parse(&fields, &[]), Ok(None));
+        for raw in ["", "name,,teaRank", "name,versions"] {
+            let params = FieldsParams {
+                fields: Some(raw.to_string()),
+                include: None,
+            };
+            assert!(params.parse(&fields, &[]).is_err(), "{raw}");
+            let params = FieldsParams {
+                fields: None,
+                include: Some(raw.to_string()),
+            };
+            assert!(params.parse(&fields, &[]).is_err(), "{raw}");
+        }
+    }
+
+    #[test]
+    fn opt_in_fields_need_including() {
+        let fields = ["projectId", "name", "teaRank", "packageManagers"];
+        let opt_in = ["packageManagers"];
+        let include = |include: Option<&str>| FieldsParams {
+            fields: None,
+            include: include.map(str::to_string),
+        };
+
+        let selected = include(None).parse(&fields, &opt_in).unwrap().unwrap();
+        assert!(selected.contains("teaRank"));
+        assert!(!selected.contains("packageManagers"));
+        assert_eq!(
+            include(Some("packageManagers")).parse(&fields, &opt_in),
+            Ok(None)
+        );
+        assert_eq!(include(None).parse(&fields, &[]), Ok(None));
+
+        // With `fields`, `include` only adds to them
+        let params = FieldsParams {
+            include: Some("packageManagers".to_string()),
+            ..FieldsParams::new(["name"])
+        };
+        let selected = params.parse(&fields, &opt_in).unwrap().unwrap();
+        assert!(selected.contains("packageManagers"));
+        assert!(!selected.contains("teaRank"));
+        let selected = FieldsParams::new(["packageManagers"])
+            .parse(&fields, &opt_in)
+            .unwrap()
+            .unwrap();
+        assert!(selected.contains("packageManagers"));
+    }
+}
@@ -0,0 +1,120 @@
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use serde::{Deserialize, Serialize};
+
+/// Most columns one request may filter on
+const MAX_FILTERS: usize = 8;
+
+/// `?filter[name]=react&filter[package_manager_id]=<uuid>`. Holds every query
+/// parameter; only the `filter[...]` ones are read.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(transparent)]
+pub struct FilterParams(pub BTreeMap<String, String>);
+
+#[derive(Debug, PartialEq)]
+pub struct Filter {
+    pub column: String,
+    pub value: String,
+}
+
+/// Validated equality filters, ANDed together
+#[derive(Debug, Default, PartialEq)]
+pub struct Filters(pub Vec<Filter>);
+
+impl FilterParams {
+    /// Filters requiring each `(column, value)`
+    pub fn new<'a>(filters: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        Self(
+            filters
+                .into_iter()
+                .map(|(column, value)| (format!("filter[{column}]"), value.to_string()))
+                .collect(),
+        )
+    }
+
+    /// The requested filters, with every column checked against `columns`
+    pub fn parse(&self, columns: &[&str]) -> Result<Filters, String> {
+        let mut filters = Vec::new();
+        for (key, value) in &self.0 {
+            let Some(rest) = key.strip_prefix("filter[") else {
+                continue;
+            };
+            let Some(column) = rest.strip_suffix(']') else {
+                return Err(format!(
+                    "Invalid filter '{key}' (expected 'filter[column]')"
+                ));
+            };
+            if !columns.contains(&column) {
+                return Err(format!(
+                    "Cannot filter by '{column}' (expected one of: {})",
+                    columns.join(", ")
+                ));
+            }
+            filters.push(Filter {
+                column: column.to_string(),
+                value: value.clone(),
+            });
+        }
+        if filters.len() > MAX_FILTERS {
+            return Err(format!("At most {MAX_FILTERS} filters are allowed"));
+        }
+        Ok(Filters(filters))
+    }
+}
+
+impl Filters {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// `AND <column> = $n` per filter, numbered from `$1` in order, each column
+    /// replaced by the SQL from `column`; empty without filters
+    pub fn conditions(&self, column: impl Fn(&str) -> String) -> String {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(i, filter)| format!(" AND {} = ${}", column(&filter.column), i + 1))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_compile_to_conditions() {
+        let columns = ["name", "package_manager_id"];
+        let filters = FilterParams::new([("name", "react"), ("package_manager_id", "x")])
+            .parse(&columns)
+            .unwrap();
+        assert_eq!(
+            filters.conditions(|column| format!("t.\"{column}\"")),
+            " AND t.\"name\" = $1 AND t.\"package_manager_id\" = $2"
+        );
+
+        // Other query parameters are left alone
+        let mut params = FilterParams::new([("name", "react")]);
+        params.0.insert("page".to_string(), "2".to_string());
+        assert_eq!(params.parse(&columns).unwrap().len(), 1);
+        assert!(FilterParams::default().parse(&columns).unwrap().is_empty());
+
+        for key in [
+            "filter[id]",
+            "filter[name",
+            "filter[name; DROP TABLE canons]",
+        ] {
+            let mut params = FilterParams::default();
+            params.0.insert(key.to_string(), "1".to_string());
+            assert!(params.parse(&columns).is_err(), "{key}");
+        }
+    }
+}
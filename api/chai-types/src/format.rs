@@ -0,0 +1,149 @@
+//! How values whose JSON shape is configurable are rendered: timestamps with
+//! or without an offset, and `bigint`s as numbers or strings.
+
+use alloc::string::{String, ToString};
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{json, Value};
+
+/// Largest integer a JavaScript number represents exactly (2^53 - 1)
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// How rows are rendered as JSON; fixed at startup so cached and fresh responses match
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RowFormat {
+    /// Render `bigint` values beyond ±2^53 - 1 as strings
+    pub bigint_as_string: bool,
+    /// Keep the old timestamp output: naive `TIMESTAMP`s without an offset
+    pub legacy_timestamps: bool,
+}
+
+impl RowFormat {
+    /// A `TIMESTAMP` column; stored values are UTC, so they are rendered as such
+    pub fn timestamp(self, value: Option<NaiveDateTime>) -> Value {
+        match value {
+            Some(naive) if !self.legacy_timestamps => self.timestamptz(Some(naive.and_utc())),
+            value => json!(value),
+        }
+    }
+
+    /// A `TIMESTAMPTZ` column as RFC 3339 in UTC, e.g. `2024-01-01T12:00:00.5Z`
+    pub fn timestamptz(self, value: Option<DateTime<Utc>>) -> Value {
+        match value {
+            Some(at) => Value::String(at.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+            None => Value::Null,
+        }
+    }
+
+    /// A `bigint` column, as a string when it is beyond JavaScript's safe range and
+    /// `bigint_as_string` is set
+    pub fn int8(self, value: Option<i64>) -> Value {
+        match value {
+            Some(v) if self.bigint_as_string && v.unsigned_abs() > MAX_SAFE_INTEGER => {
+                Value::String(v.to_string())
+            }
+            value => json!(value),
+        }
+    }
+}
+
+/// A `TIMESTAMP` column, rendered as [`RowFormat::timestamp`] does
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export, type = "string | null"))]
+pub struct Timestamp(pub Option<NaiveDateTime>, pub RowFormat);
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.1.timestamp(self.0).serialize(serializer)
+    }
+}
+
+/// Reads either rendering back, remembering which one it was
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let Some(raw) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(Timestamp(None, RowFormat::default()));
+        };
+        if let Ok(at) = DateTime::parse_from_rfc3339(&raw) {
+            return Ok(Timestamp(Some(at.naive_utc()), RowFormat::default()));
+        }
+        let naive = raw.parse().map_err(serde::de::Error::custom)?;
+        let legacy = RowFormat {
+            legacy_timestamps: true,
+            ..RowFormat::default()
+        };
+        Ok(Timestamp(Some(naive), legacy))
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl utoipa::PartialSchema for Timestamp {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        use utoipa::openapi::schema::{KnownFormat, ObjectBuilder, SchemaFormat, SchemaType, Type};
+        ObjectBuilder::new()
+            .schema_type(SchemaType::from_iter([Type::String, Type::Null]))
+            .format(Some(SchemaFormat::KnownFormat(KnownFormat::DateTime)))
+            .into()
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl utoipa::ToSchema for Timestamp {}
+
+/// A `bigint` column, rendered as [`RowFormat::int8`] does
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(
+    feature = "ts",
+    derive(ts_rs::TS),
+    ts(export, type = "number | string")
+)]
+pub struct Int8(pub i64, pub RowFormat);
+
+impl Serialize for Int8 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.1.int8(Some(self.0)).serialize(serializer)
+    }
+}
+
+/// Reads numbers and the string rendering of large values alike
+impl<'de> Deserialize<'de> for Int8 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Number(i64),
+            String(String),
+        }
+        match Raw::deserialize(deserializer)? {
+            Raw::Number(value) => Ok(Int8(value, RowFormat::default())),
+            Raw::String(raw) => {
+                let value = raw.parse().map_err(serde::de::Error::custom)?;
+                let format = RowFormat {
+                    bigint_as_string: true,
+                    ..RowFormat::default()
+                };
+                Ok(Int8(value, format))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl utoipa::PartialSchema for Int8 {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        use utoipa::openapi::schema::{
+            KnownFormat, ObjectBuilder, OneOfBuilder, SchemaFormat, Type,
+        };
+        OneOfBuilder::new()
+            .item(
+                ObjectBuilder::new()
+                    .schema_type(Type::Integer)
+                    .format(Some(SchemaFormat::KnownFormat(KnownFormat::Int64))),
+            )
+            .item(ObjectBuilder::new().schema_type(Type::String))
+            .into()
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl utoipa::ToSchema for Int8 {}
@@ -0,0 +1,196 @@
+//! Jobs as `POST /jobs` takes them and `GET /jobs/{id}` reports them. Queueing
+//! and running them is up to the server.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::tier::Tier;
+
+pub const DEFAULT_CLOSURE_DEPTH: i32 = 10;
+pub const MAX_CLOSURE_DEPTH: i32 = 50;
+pub const MAX_SBOM_PACKAGES: usize = 10_000;
+/// Every [`JobSpec::kind`]
+pub const JOB_KINDS: [&str; 3] = ["closure", "sbom", "export"];
+
+/// What a job computes, as submitted: `{"kind": "closure", "params": {...}}`
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "kind", content = "params", rename_all = "lowercase")]
+pub enum JobSpec {
+    /// Every project `projectId` depends on, directly or transitively
+    Closure(ClosureJob),
+    /// The projects and ranks behind an SBOM's packages
+    Sbom(SbomJob),
+    /// A run's full leaderboard, as `GET /export/leaderboard/{run}` returns it
+    Export(ExportJob),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ClosureJob {
+    pub project_id: Uuid,
+    pub max_depth: Option<i32>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SbomJob {
+    pub packages: Vec<SbomPackage>,
+    /// Run to rank against; the latest when unset
+    pub run: Option<i32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SbomPackage {
+    pub package_manager: String,
+    pub name: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ExportJob {
+    pub run: i32,
+}
+
+/// The body of `POST /jobs`
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct JobRequest {
+    #[serde(flatten)]
+    pub spec: JobSpec,
+    /// Defaults to [`JobSpec::default_priority`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub priority: Option<Priority>,
+}
+
+/// Queued jobs start in priority order; within a priority, higher tiers go
+/// first, then older jobs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl JobSpec {
+    /// Bulk exports yield to interactive analyses unless asked otherwise
+    pub fn default_priority(&self) -> Priority {
+        match self {
+            JobSpec::Export(_) => Priority::Low,
+            _ => Priority::Normal,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            JobSpec::Closure(_) => "closure",
+            JobSpec::Sbom(_) => "sbom",
+            JobSpec::Export(_) => "export",
+        }
+    }
+
+    /// Rejects parameters the job would only fail on later
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            JobSpec::Closure(job) => match job.max_depth {
+                Some(depth) if !(1..=MAX_CLOSURE_DEPTH).contains(&depth) => Err(format!(
+                    "maxDepth must be between 1 and {MAX_CLOSURE_DEPTH}"
+                )),
+                _ => Ok(()),
+            },
+            JobSpec::Sbom(job) if job.packages.is_empty() => {
+                Err("packages must not be empty".to_string())
+            }
+            JobSpec::Sbom(job) if job.packages.len() > MAX_SBOM_PACKAGES => Err(format!(
+                "At most {MAX_SBOM_PACKAGES} packages are allowed per job"
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Succeeded | JobStatus::Failed)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+    pub id: Uuid,
+    #[serde(flatten)]
+    pub spec: JobSpec,
+    /// Id of the submitter (`key:<name>` or `ip:<address>`), the only caller
+    /// besides admins who may read the job
+    pub caller: String,
+    #[serde(default)]
+    pub tier: Tier,
+    #[serde(default)]
+    pub priority: Priority,
+    pub status: JobStatus,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn specs_are_validated_on_submission() {
+        let spec: JobSpec = serde_json::from_value(json!({
+            "kind": "closure",
+            "params": {"projectId": Uuid::nil(), "maxDepth": 99},
+        }))
+        .unwrap();
+        assert!(spec.validate().is_err());
+
+        let spec: JobSpec =
+            serde_json::from_value(json!({"kind": "sbom", "params": {"packages": []}})).unwrap();
+        assert!(spec.validate().is_err());
+
+        assert!(serde_json::from_value::<JobSpec>(json!({"kind": "mine"})).is_err());
+    }
+}
@@ -0,0 +1,24 @@
+//! The requests and responses of the CHAI API, shared by the server, the Rust
+//! client, and browser front ends. Only `serde`, `serde_json`, `chrono`, and
+//! `uuid` are needed, all without `std`, so the crate builds for
+//! `wasm32-unknown-unknown` and other `no_std` targets with an allocator.
+//!
+//! With the `ts` feature (which needs `std`) every type also derives `ts_rs::TS`, and
+//! `cargo test -p chai-types --features ts` writes their TypeScript
+//! definitions to `bindings/`. With the `openapi` feature (which also needs
+//! `std`) they derive `utoipa::ToSchema`, and query parameters
+//! `utoipa::IntoParams`, for the server's OpenAPI spec.
+
+// The derived `TS` and `ToSchema` impls need `std`
+#![cfg_attr(not(any(feature = "ts", feature = "openapi")), no_std)]
+
+extern crate alloc;
+
+pub mod fields;
+pub mod filter;
+pub mod format;
+pub mod jobs;
+pub mod models;
+pub mod requests;
+pub mod sort;
+pub mod tier;
@@ -0,0 +1,1109 @@
+//! Response payloads of the endpoints. Each struct is the JSON contract of one
+//! kind of row, so field names are defined here once; `chai-core` builds them
+//! from query results. Envelopes around a list of rows take the row type as a
+//! parameter: the server fills them with rendered [`Value`]s, and clients read
+//! them back as the row structs.
+//!
+//! Everything deserializes too, so clients read responses with the same types
+//! the server writes them with.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec::Vec;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+
+use crate::format::{Int8, Timestamp};
+use crate::jobs::SbomPackage;
+use crate::requests::OrgScore;
+use crate::tier::{Tier, TierLimits};
+
+/// An entry of `POST /leaderboard`; also what the project cache holds
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardEntry {
+    pub project_id: Uuid,
+    pub homepage: String,
+    pub name: String,
+    pub source: Option<String>,
+    pub tea_rank: String,
+    pub tea_rank_calculated_at: Timestamp,
+    pub package_managers: Option<Vec<String>>,
+    /// Set on unranked projects, which are only returned with `includeUnranked`
+    #[serde(default, skip_serializing_if = "core::ops::Not::not")]
+    #[cfg_attr(feature = "ts", ts(as = "Option<bool>", optional))]
+    pub unranked: bool,
+}
+
+/// `GET /project/{id}` and `POST /project/batch` with `view=full`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Project {
+    pub project_id: Uuid,
+    pub homepage: String,
+    pub name: String,
+    pub slug: Option<String>,
+    pub source: String,
+    pub tea_rank: String,
+    pub tea_rank_calculated_at: Timestamp,
+    pub package_managers: Option<Vec<String>>,
+    pub dependencies_count: Int8,
+    pub dependents_count: Int8,
+    /// `[{packageManager, package, latestVersion}]`, built in SQL
+    pub versions: Option<Value>,
+    /// The merged canon a batch asked for, when this is its survivor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub redirected_from: Option<Uuid>,
+}
+
+/// The project endpoints with `view=compact`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct CompactProject {
+    pub project_id: Uuid,
+    pub name: String,
+    pub tea_rank: String,
+    /// The merged canon a batch asked for, when this is its survivor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub redirected_from: Option<Uuid>,
+}
+
+/// A project in the view its request asked for, which is how the project
+/// endpoints document their body
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(untagged)]
+pub enum ProjectBody {
+    Full(Project),
+    Compact(CompactProject),
+}
+
+/// A match of `GET /project/search/{name}`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult {
+    pub project_id: Uuid,
+    pub homepage: String,
+    pub name: String,
+    pub source: String,
+    pub package_managers: Option<Vec<String>>,
+}
+
+/// `GET /project/search/{name}`: the first page of matches, and whether there
+/// are more. `totalMatches` is exact on the last page and otherwise the
+/// planner's estimate of the canons named like `name`, so it is cheap enough
+/// to ask for on every keystroke.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResults<P = SearchResult> {
+    pub name: String,
+    pub has_more: bool,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub total_matches: i64,
+    pub results: Vec<P>,
+}
+
+/// A verified project of `GET /maintainer/{name}/projects`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct MaintainerProject {
+    pub project_id: Uuid,
+    pub name: String,
+    pub verified_at: Timestamp,
+    pub tea_rank: String,
+    pub previous_tea_rank: Option<String>,
+    pub rank_change: Option<f64>,
+    /// `new`, `up`, `down`, or `unchanged`
+    pub rank_trend: String,
+    pub dependents_count: Int8,
+}
+
+/// An entry of `GET /admin/overrides`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct LeaderboardOverride {
+    pub project_id: Uuid,
+    pub name: String,
+    /// `exclude` or `pin`
+    pub action: String,
+    /// The pinned rank; null for `exclude`
+    pub tea_rank: Option<String>,
+    pub reason: String,
+    pub expires_at: Timestamp,
+    /// Whether `expiresAt` has passed, so the override no longer applies
+    pub expired: bool,
+    pub created_at: Timestamp,
+}
+
+/// A project whose name is close to another's, by trigram similarity
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SimilarName {
+    pub project_id: Uuid,
+    pub name: String,
+    /// The project's rank in the latest run; null when unranked
+    pub tea_rank: Option<String>,
+    /// From 0 (no trigrams shared) to 1 (the same trigrams)
+    pub similarity: f32,
+    /// Characters to insert, delete or replace to get from one name to the
+    /// other, ignoring case
+    pub edit_distance: u32,
+}
+
+/// `GET /project/{id}/similar-names` and `GET /check-name`: projects named like
+/// `name`, closest first, with ranks from `run`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SimilarNames {
+    pub name: String,
+    pub run: Option<i32>,
+    pub matches: Vec<SimilarName>,
+}
+
+/// A project of `GET /admin/suspects`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Suspect {
+    pub project_id: Uuid,
+    pub name: String,
+    pub tea_rank: Option<String>,
+    /// Why the project looks suspicious: `noSource`, `noDependents` and
+    /// `lookalike`
+    pub signals: Vec<String>,
+    /// The top project its name resembles, for `lookalike`
+    pub resembles: Option<SimilarName>,
+}
+
+/// A project of `GET /leaderboard/new`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct NewEntrant {
+    pub project_id: Uuid,
+    pub name: String,
+    pub tea_rank: String,
+    /// Where the project entered the run's leaderboard, from 1
+    pub position: Int8,
+}
+
+/// One run of `GET /project/{id}/metrics`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct MetricsPoint {
+    pub run: i32,
+    pub calculated_at: Timestamp,
+    pub tea_rank: Option<String>,
+    pub dependents_count: Int8,
+}
+
+/// A dependent of `GET /project/{id}/rank-contributors`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct RankContributor {
+    pub project_id: Uuid,
+    pub name: String,
+    pub tea_rank: String,
+    pub dependency_count: Int8,
+    pub contribution: f64,
+}
+
+/// A row of `GET /export/leaderboard/{run}`, in JSON and CSV alike
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ExportEntry {
+    pub project_id: Uuid,
+    pub name: String,
+    pub tea_rank: String,
+}
+
+impl ExportEntry {
+    /// Field names as serialized, for CSV files without rows
+    pub const CSV_HEADER: [&'static str; 3] = ["projectId", "name", "teaRank"];
+}
+
+/// A dependency found by a `closure` job
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ClosureEntry {
+    pub project_id: Uuid,
+    pub name: String,
+    /// Fewest hops from the analyzed project, from 1
+    pub depth: i32,
+}
+
+/// A project in a `GET /project/{id}/dependencies` tree
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyNode {
+    pub project_id: Uuid,
+    pub name: String,
+    /// Already on the path from the root: the dependency closes a cycle and
+    /// its children are left out
+    pub cycle: bool,
+    /// Direct dependencies by name; empty at the depth limit
+    #[cfg_attr(feature = "openapi", schema(no_recursion))]
+    pub children: Vec<DependencyNode>,
+}
+
+/// `GET /project/{id}/dependencies`: the projects a project depends on, nested
+/// down to `depth` levels
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct DependencyTree {
+    pub project_id: Uuid,
+    pub name: String,
+    pub depth: i32,
+    /// Nodes past the size limit were left out
+    pub truncated: bool,
+    pub dependencies: Vec<DependencyNode>,
+}
+
+/// A project of `GET /project/{id}/dependents`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct DependentProject {
+    pub project_id: Uuid,
+    pub name: String,
+    /// Null when the project has no rank in the run
+    pub tea_rank: Option<String>,
+}
+
+/// `GET /project/{id}/dependents`: the projects depending directly on a
+/// project, best ranked first, a page at a time; ranks are from `run`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectDependents<P = DependentProject> {
+    pub project_id: Uuid,
+    pub run: Option<i32>,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub total_count: i64,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub page: i64,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub limit: i64,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub total_pages: i64,
+    pub dependents: Vec<P>,
+}
+
+/// A package of an `sbom` job that resolved to a project
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SbomMatch {
+    pub package_manager: String,
+    pub package: String,
+    pub project_id: Uuid,
+    pub name: String,
+    pub tea_rank: Option<String>,
+}
+
+/// An internal package name of `POST /analyze/dependency-confusion` that a
+/// public registry already has
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ExposedPackage {
+    pub package_manager: String,
+    pub package: String,
+    /// The project the public package belongs to; null when CHAI hasn't
+    /// grouped it into one
+    pub project_id: Option<Uuid>,
+    pub name: Option<String>,
+    pub tea_rank: Option<String>,
+    /// Registry accounts that publish the public package
+    pub owners: Vec<String>,
+}
+
+/// `POST /analyze/dependency-confusion`; ranks are from `run`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DependencyConfusion<E = ExposedPackage> {
+    pub run: Option<i32>,
+    /// Names taken publicly, in request order
+    pub exposed: Vec<E>,
+    /// Names no public registry has yet
+    pub unclaimed: Vec<SbomPackage>,
+}
+
+/// How many projects are in exactly the package managers of
+/// `package_managers`, for `GET /stats/cross-ecosystem`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct EcosystemCombination {
+    /// Sorted by name
+    pub package_managers: Vec<String>,
+    pub count: Int8,
+}
+
+/// A project of `GET /stats/cross-ecosystem`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct CrossEcosystemProject {
+    pub project_id: Uuid,
+    pub name: String,
+    pub tea_rank: Option<String>,
+    pub package_managers: Vec<String>,
+}
+
+/// `GET /stats/cross-ecosystem`: projects in more than one package manager.
+/// `total` and `combinations` cover all of them; `projects` lists the best
+/// ranked in `run` of those matching the request.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CrossEcosystem<P = CrossEcosystemProject> {
+    pub run: Option<i32>,
+    pub total: Int8,
+    /// Most common first
+    pub combinations: Vec<EcosystemCombination>,
+    pub projects: Vec<P>,
+}
+
+/// A project of `GET /reports/stale`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct StaleProject {
+    pub project_id: Uuid,
+    pub name: String,
+    pub tea_rank: String,
+    /// When its newest version on any package manager was published
+    pub last_release: Timestamp,
+}
+
+/// `GET /reports/stale`: ranked projects without a release in `months`, best
+/// ranked first, a page at a time; ranks are from `run`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct StaleProjects<P = StaleProject> {
+    pub run: Option<i32>,
+    pub ecosystem: Option<String>,
+    pub months: i32,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub total_count: i64,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub page: i64,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub limit: i64,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub total_pages: i64,
+    pub projects: Vec<P>,
+}
+
+/// A project of `GET /org/{host}/{org}`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct OrgProject {
+    pub project_id: Uuid,
+    pub name: String,
+    /// The first of its source URLs under the organization
+    pub source: String,
+    /// Null when the project has no rank in the run
+    pub tea_rank: Option<String>,
+}
+
+/// `GET /org/{host}/{org}`: every project with a source under the
+/// organization, best ranked first, a page at a time, with totals over all of
+/// them; ranks are from `run`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct OrgPortfolio<P = OrgProject> {
+    /// Lowercased, without `www.`
+    pub host: String,
+    pub org: String,
+    pub run: Option<i32>,
+    /// Ranked projects among `totalCount`
+    pub ranked_projects: Int8,
+    /// The sum of the projects' ranks
+    pub rank_mass: f64,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub total_count: i64,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub page: i64,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub limit: i64,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub total_pages: i64,
+    pub projects: Vec<P>,
+}
+
+/// An organization of `GET /leaderboard/orgs`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct OrgRank {
+    pub host: String,
+    pub org: String,
+    /// Its ranked projects
+    pub projects: Int8,
+    /// The sum of the projects' ranks
+    pub rank_mass: f64,
+    /// The rank of its best ranked project
+    pub top_rank: f64,
+}
+
+/// `GET /leaderboard/orgs`: organizations best first by `by`; ranks are from
+/// `run`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct OrgLeaderboard<O = OrgRank> {
+    pub run: Option<i32>,
+    pub by: OrgScore,
+    pub orgs: Vec<O>,
+}
+
+/// Projects whose source is on `host`, for `GET /stats/source-hosts`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SourceHost {
+    /// A known forge such as `github.com`, or `self-hosted` for every other host
+    pub host: String,
+    pub projects: Int8,
+    /// How many different hosts the row covers: 1 for a forge
+    pub hosts: Int8,
+    /// The sum of the projects' ranks
+    pub rank_mass: f64,
+    /// `rankMass` as a share of all projects with a source, from 0 to 1
+    pub rank_share: f64,
+}
+
+/// `GET /stats/source-hosts`; ranks are from `run`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct SourceHosts {
+    pub run: Option<i32>,
+    /// Most projects first
+    pub hosts: Vec<SourceHost>,
+    /// Projects without a source URL, left out of `hosts`
+    pub without_source: Int8,
+}
+
+/// A project of `GET /stats/top-dependents` or `GET /stats/top-dependencies`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct DegreeProject {
+    pub project_id: Uuid,
+    pub name: String,
+    /// Projects depending on it, or that it depends on, directly
+    pub degree: Int8,
+    pub tea_rank: Option<String>,
+}
+
+/// `GET /stats/top-dependents` and `GET /stats/top-dependencies`, highest
+/// degree first; ranks are from `run`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TopDegree<P = DegreeProject> {
+    pub run: Option<i32>,
+    pub projects: Vec<P>,
+}
+
+/// A project named in `GET /stats/graph`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct GraphProject {
+    pub project_id: Uuid,
+    pub name: String,
+}
+
+/// One of the most connected projects of `GET /stats/graph`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct GraphHub {
+    pub project_id: Uuid,
+    pub name: String,
+    /// Projects depending on it directly
+    pub dependents: usize,
+    /// Projects it depends on directly
+    pub dependencies: usize,
+}
+
+/// `GET /stats/graph`: the dependency graph between projects, as last computed
+/// in the background at `computedAt`. Only projects with a dependency or a
+/// dependent are part of it.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct GraphStats {
+    pub computed_at: DateTime<Utc>,
+    pub projects: usize,
+    pub edges: usize,
+    /// Strongly connected components
+    pub components: usize,
+    /// Components of more than one project, each a dependency cycle
+    pub cyclic_components: usize,
+    pub largest_component: usize,
+    /// How many projects the longest chain was searched from
+    pub depth_sample: usize,
+    /// Hops in `longestChain`
+    pub max_depth: usize,
+    /// The longest shortest path found, dependent first
+    pub longest_chain: Vec<GraphProject>,
+    /// Most direct dependents and dependencies together first
+    pub hubs: Vec<GraphHub>,
+}
+
+/// How `GET /status` rates the API or one part of its data, for status pages
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Health {
+    /// Serving current data
+    Green,
+    /// Serving, but some data is older than it should be or not checked yet
+    Yellow,
+    /// The database can't be reached
+    Red,
+}
+
+/// An ecosystem of `GET /status`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct EcosystemFreshness {
+    pub ecosystem: String,
+    /// When the indexers last wrote one of its packages
+    pub last_ingest: Timestamp,
+    pub age_hours: Option<f64>,
+    pub status: Health,
+}
+
+/// `GET /status`: whether the API is up and how current its data is
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    /// The worst of the parts below
+    pub status: Health,
+    pub version: String,
+    pub database: Health,
+    pub run: Option<i32>,
+    pub run_created_at: Timestamp,
+    pub run_age_hours: Option<f64>,
+    pub run_status: Health,
+    /// When `ecosystems` were last read; null until the first check finishes
+    pub ecosystems_checked_at: Option<DateTime<Utc>>,
+    pub ecosystems: Vec<EcosystemFreshness>,
+}
+
+/// `GET /version`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Version {
+    pub version: String,
+    /// Experimental features turned on
+    pub features: Vec<String>,
+}
+
+/// Snapshot of a caller's quota, as reported in headers and by `/limits`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Quota {
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub limit: u64,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub remaining: u64,
+    /// Unix timestamp (seconds) at which the current window resets
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub reset: u64,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub window_seconds: u64,
+}
+
+/// `GET /limits`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Limits {
+    /// `admin`, `key:<name>`, or `ip:<address>`
+    pub caller: String,
+    pub tier: Tier,
+    pub rate_limit: Quota,
+    /// What the caller's tier may ask for in one request
+    pub tier_limits: TierLimits,
+}
+
+/// `GET /tables`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TableList {
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub total_count: i64,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub page: i64,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub limit: i64,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub total_pages: i64,
+    pub data: Vec<String>,
+}
+
+/// `GET /tables/{table}`; rows are objects keyed by column
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TablePage {
+    pub table: String,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub total_count: i64,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub page: i64,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub limit: i64,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub total_pages: i64,
+    pub columns: Vec<String>,
+    pub data: Vec<Value>,
+    /// When paging by `cursor`, the cursor of the next page; absent on the
+    /// last one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub next_cursor: Option<String>,
+    /// Where to go instead, for tables superseded by a business endpoint
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub deprecation: Option<Value>,
+}
+
+/// `GET /leaderboard/new`; runs are null before the first run
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct NewEntrants<P = NewEntrant> {
+    pub run: Option<i32>,
+    pub previous_run: Option<i32>,
+    pub projects: Vec<P>,
+}
+
+/// `GET /admin/suspects`; `run` is the run ranks come from, null before the
+/// first run
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Suspects<S = Suspect> {
+    pub run: Option<i32>,
+    pub suspects: Vec<S>,
+}
+
+/// `GET /ranks/percentiles`: the rank at each percentile, keyed like `"99.5"`,
+/// or null when the run had no positive ranks
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Percentiles {
+    pub run: Option<i32>,
+    pub percentiles: BTreeMap<String, Option<f64>>,
+}
+
+/// `GET /ranks/compare`: how ranks moved between two runs. Rises, falls, the
+/// mean change, and tau cover projects ranked in both; tau is null with fewer
+/// than two of them.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct RunComparison {
+    pub from: i32,
+    pub to: i32,
+    pub rose: usize,
+    pub fell: usize,
+    pub unchanged: usize,
+    /// Ranked in `to` but not `from`
+    pub new: usize,
+    /// Ranked in `from` but not `to`
+    pub dropped: usize,
+    pub mean_absolute_change: Option<f64>,
+    /// Kendall's tau-b between the two orderings, from -1 to 1
+    pub kendall_tau: Option<f64>,
+}
+
+/// A named list of projects kept by an API key
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct Collection {
+    pub id: Uuid,
+    pub name: String,
+    pub project_ids: Vec<Uuid>,
+    pub created_at: Timestamp,
+    pub updated_at: Timestamp,
+    /// Where the collection's changes are posted after each run
+    pub webhook_url: Option<String>,
+}
+
+/// A collection's webhook with the secret its digests are signed with, only
+/// shown when the webhook is registered or its secret rotated
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionWebhook {
+    pub collection_id: Uuid,
+    pub url: String,
+    pub secret: String,
+    /// Until when digests are also signed with the secret this one replaced;
+    /// null when there is none
+    pub previous_secret_expires_at: Timestamp,
+}
+
+/// A project of a collection in the two runs `GET /collections/{id}/changes`
+/// compares; a rank is null when the project wasn't ranked in that run
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionChange {
+    pub project_id: Uuid,
+    pub from_rank: Option<f64>,
+    pub to_rank: Option<f64>,
+}
+
+/// `GET /collections/{id}/changes`, also posted to the collection's webhook
+/// after each run
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionChanges {
+    pub collection_id: Uuid,
+    /// The collection's movement, counted as `GET /ranks/compare` counts a run's
+    pub summary: RunComparison,
+    /// Every project of the collection, biggest move first
+    pub projects: Vec<CollectionChange>,
+}
+
+/// A project's rank in one run; null for runs it wasn't ranked in
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TrendPoint {
+    pub run: i32,
+    pub tea_rank: Option<String>,
+}
+
+/// `GET /project/{id}/trend`; the fit is null with fewer than two ranked runs
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct RankTrend<P = TrendPoint> {
+    pub project_id: Uuid,
+    pub runs: usize,
+    pub slope: Option<f64>,
+    pub moving_average: Option<f64>,
+    /// `rising`, `falling`, or `stable`
+    pub direction: Option<String>,
+    pub series: Vec<P>,
+}
+
+/// `GET /project/{id}/metrics`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectMetrics<P = MetricsPoint> {
+    pub project_id: Uuid,
+    pub points: Vec<P>,
+}
+
+/// `GET /project/{id}/rank-contributors`; run and rank are null before the
+/// first run
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct RankContributors<P = RankContributor> {
+    pub project_id: Uuid,
+    pub run: Option<i32>,
+    pub tea_rank: Option<String>,
+    pub contributors: Vec<P>,
+}
+
+/// `GET /maintainer/{name}/projects`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct MaintainerProjects<P = MaintainerProject> {
+    pub maintainer: String,
+    pub project_count: usize,
+    pub projects: Vec<P>,
+}
+
+/// A registry account maintaining packages of a project, for
+/// `GET /project/{id}/maintainer-concentration`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct MaintainerShare {
+    pub username: String,
+    /// The registry the account is on, e.g. `npm`
+    pub source: String,
+    /// The project's packages it maintains
+    pub packages: Int8,
+    /// `packages` as a share of all the project's packages, from 0 to 1
+    pub share: f64,
+}
+
+/// `GET /project/{id}/maintainer-concentration`: how many people could publish
+/// the project's packages. The rank is from `run`.
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct MaintainerConcentration {
+    pub project_id: Uuid,
+    pub run: Option<i32>,
+    pub tea_rank: Option<String>,
+    pub packages: Int8,
+    /// Packages with at least one known maintainer
+    pub maintained_packages: Int8,
+    pub maintainer_count: usize,
+    /// A maintainer of every package, who alone controls the project's
+    /// releases
+    pub controlled_by: Option<String>,
+    /// Most packages first
+    pub maintainers: Vec<MaintainerShare>,
+}
+
+/// `GET /project/{id}/funding`: channels keyed like GitHub's FUNDING.yml
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectFunding {
+    pub project_id: Uuid,
+    pub has_funding: bool,
+    pub funding: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// `POST /project/{id}/verification`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationToken {
+    pub project_id: Uuid,
+    pub maintainer: String,
+    pub token: String,
+    /// `pending` or `verified`
+    pub status: String,
+    pub verified_at: Timestamp,
+    pub instructions: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct VerifiedMaintainer {
+    pub maintainer: String,
+    pub verified_at: Timestamp,
+}
+
+/// `GET /project/{id}/verification`
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationStatus {
+    pub project_id: Uuid,
+    pub verified: bool,
+    pub verified_maintainers: Vec<VerifiedMaintainer>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::RowFormat;
+    use alloc::string::ToString;
+    use alloc::vec;
+    use chrono::NaiveDateTime;
+    use serde_json::json;
+
+    fn keys(value: &Value) -> Vec<&str> {
+        value
+            .as_object()
+            .expect("payloads are objects")
+            .keys()
+            .map(String::as_str)
+            .collect()
+    }
+
+    #[test]
+    fn leaderboard_entries_serialize_to_the_documented_contract() {
+        let calculated_at = NaiveDateTime::parse_from_str("2024-12-27 08:04:03.5", "%F %T%.f")
+            .expect("valid timestamp");
+        let entry = |format| LeaderboardEntry {
+            project_id: Uuid::nil(),
+            homepage: "https://example.com".to_string(),
+            name: "example".to_string(),
+            source: None,
+            tea_rank: "150".to_string(),
+            tea_rank_calculated_at: Timestamp(Some(calculated_at), format),
+            package_managers: Some(vec!["crates".to_string()]),
+            unranked: false,
+        };
+
+        let value = json!(entry(RowFormat::default()));
+        assert_eq!(
+            keys(&value),
+            [
+                "homepage",
+                "name",
+                "packageManagers",
+                "projectId",
+                "source",
+                "teaRank",
+                "teaRankCalculatedAt"
+            ]
+        );
+        assert_eq!(
+            value["teaRankCalculatedAt"],
+            json!("2024-12-27T08:04:03.500Z")
+        );
+
+        let legacy = RowFormat {
+            legacy_timestamps: true,
+            ..RowFormat::default()
+        };
+        assert_eq!(
+            json!(entry(legacy))["teaRankCalculatedAt"],
+            json!("2024-12-27T08:04:03.500")
+        );
+    }
+
+    #[test]
+    fn export_header_matches_serialized_fields() {
+        let entry = ExportEntry {
+            project_id: Uuid::nil(),
+            name: "example".to_string(),
+            tea_rank: "1".to_string(),
+        };
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.serialize(entry).unwrap();
+        let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+        assert_eq!(
+            csv.lines().next(),
+            Some(ExportEntry::CSV_HEADER.join(",").as_str())
+        );
+    }
+
+    #[test]
+    fn large_counts_follow_the_bigint_setting() {
+        let format = RowFormat {
+            bigint_as_string: true,
+            ..RowFormat::default()
+        };
+        assert_eq!(
+            json!(Int8(1 << 60, format)),
+            json!((1i64 << 60).to_string())
+        );
+        assert_eq!(json!(Int8(42, format)), json!(42));
+    }
+
+    #[test]
+    fn rendered_payloads_read_back() {
+        let calculated_at = NaiveDateTime::parse_from_str("2024-12-27 08:04:03.5", "%F %T%.f")
+            .expect("valid timestamp");
+        let legacy = RowFormat {
+            legacy_timestamps: true,
+            bigint_as_string: true,
+        };
+        for format in [RowFormat::default(), legacy] {
+            let point = MetricsPoint {
+                run: 3,
+                calculated_at: Timestamp(Some(calculated_at), format),
+                tea_rank: None,
+                dependents_count: Int8(1 << 60, format),
+            };
+            let rendered = json!(point);
+            let read: MetricsPoint = serde_json::from_value(rendered.clone()).unwrap();
+            assert_eq!(read.calculated_at.0, Some(calculated_at));
+            assert_eq!(read.dependents_count.0, 1 << 60);
+            assert_eq!(json!(read), rendered);
+        }
+    }
+}
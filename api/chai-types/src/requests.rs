@@ -0,0 +1,626 @@
+//! Query parameters and bodies the endpoints accept. The server deserializes
+//! them and clients serialize them, so unset options are left out of query
+//! strings rather than sent empty.
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::format::Timestamp;
+use crate::jobs::SbomPackage;
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct PaginationParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional, type = "number"))]
+    pub page: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional, type = "number"))]
+    pub limit: Option<i64>,
+    /// Table pages only: page by id from the `next_cursor` of the previous
+    /// page instead of by number, or from the start when empty
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub cursor: Option<String>,
+}
+
+/// `?include_deleted=true` shows soft-deleted rows; admin only
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct SoftDeleteParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub include_deleted: Option<bool>,
+}
+
+/// The body of `POST /leaderboard`: the top `limit` projects of the latest run,
+/// or the best ranked `limit` of `projectIds`. The top can be narrowed to names
+/// starting with `namePrefix` and to sources under `sourceHost`, e.g.
+/// `github.com/rust-lang/*`.
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct LeaderboardRequest {
+    #[serde(rename = "projectIds", skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub project_ids: Option<Vec<Uuid>>,
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub limit: i64,
+    #[serde(rename = "includeUnranked", default)]
+    pub include_unranked: bool,
+    #[serde(
+        rename = "namePrefix",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub name_prefix: Option<String>,
+    #[serde(
+        rename = "sourceHost",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub source_host: Option<String>,
+}
+
+/// `?as_of=2024-06-01`: ranks of the last run on or before that day (UTC)
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct AsOfParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub as_of: Option<NaiveDate>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct PrecisionParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub precision: Option<u32>,
+}
+
+/// How much of a project the project endpoints return
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectView {
+    /// Id, name, and rank, for lists
+    Compact,
+    /// Everything, including counts, URLs, package managers, and versions
+    #[default]
+    Full,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct ProjectParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub precision: Option<u32>,
+    #[serde(default)]
+    pub view: ProjectView,
+    /// The last run on or before this day (UTC) instead of the latest
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub as_of: Option<NaiveDate>,
+}
+
+/// `?depth=3`: how many levels of a dependency tree to return
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct DependencyTreeParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub depth: Option<i32>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct NewEntrantsParams {
+    #[serde(default)]
+    pub run: RunSelector,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional, type = "number"))]
+    pub limit: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub precision: Option<u32>,
+    /// The last run on or before this day (UTC) instead of `run`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub as_of: Option<NaiveDate>,
+}
+
+/// The body of `POST /project/batch`
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ProjectBatchRequest {
+    #[serde(rename = "projectIds")]
+    pub project_ids: Vec<Uuid>,
+    /// The `teaRankCalculatedAt` the caller already has for some of the ids;
+    /// those projects are only returned if their rank is newer
+    #[serde(
+        rename = "ifModifiedSince",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub if_modified_since: Option<BTreeMap<Uuid, Timestamp>>,
+}
+
+/// The body of `POST /project/exists`: canon ids to check without fetching them
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ProjectExistsRequest {
+    #[serde(rename = "projectIds")]
+    pub project_ids: Vec<Uuid>,
+}
+
+/// The body of `POST /analyze/dependency-confusion`: internal package names to
+/// look up in the public registries
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DependencyConfusionRequest {
+    pub packages: Vec<SbomPackage>,
+}
+
+/// A `run` query parameter: `latest` (the default) or a run number
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export, type = "string"))]
+#[serde(try_from = "String", into = "String")]
+pub enum RunSelector {
+    #[default]
+    Latest,
+    Run(i32),
+}
+
+impl TryFrom<String> for RunSelector {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if value == "latest" {
+            return Ok(RunSelector::Latest);
+        }
+        value
+            .parse()
+            .map(RunSelector::Run)
+            .map_err(|_| format!("Invalid run '{value}': expected 'latest' or a run number"))
+    }
+}
+
+impl From<RunSelector> for String {
+    fn from(selector: RunSelector) -> Self {
+        match selector {
+            RunSelector::Latest => "latest".to_string(),
+            RunSelector::Run(run) => run.to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl utoipa::PartialSchema for RunSelector {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        use utoipa::openapi::schema::{ObjectBuilder, Type};
+        ObjectBuilder::new()
+            .schema_type(Type::String)
+            .description(Some("`latest` or a run number"))
+            .into()
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl utoipa::ToSchema for RunSelector {}
+
+impl RunSelector {
+    /// The run number this selects, or `None` for `latest` before the first run
+    pub fn resolve(self, latest: Option<i32>) -> Option<i32> {
+        match self {
+            RunSelector::Latest => latest,
+            RunSelector::Run(run) => Some(run),
+        }
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct PercentileParams {
+    #[serde(default)]
+    pub run: RunSelector,
+    /// Comma-separated percentiles, e.g. `50,90,99.9`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub p: Option<String>,
+}
+
+/// The two runs `GET /ranks/compare` compares
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct CompareParams {
+    pub from: i32,
+    pub to: i32,
+}
+
+/// The body of `POST /collections` and `PUT /collections/{id}`
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CollectionRequest {
+    pub name: String,
+    #[serde(rename = "projectIds")]
+    pub project_ids: Vec<Uuid>,
+}
+
+/// The body of `PUT /collections/{id}/webhook`
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CollectionWebhookRequest {
+    pub url: String,
+}
+
+/// For `GET /collections/{id}/leaderboard`
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct CollectionLeaderboardParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional, type = "number"))]
+    pub limit: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub precision: Option<u32>,
+}
+
+/// The runs `GET /collections/{id}/changes` compares: by default the latest
+/// and the one before it
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct CollectionChangesParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub from: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub to: Option<i32>,
+}
+
+/// How many projects `GET /admin/suspects` returns, and how many signals each
+/// needs
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct SuspectParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional, type = "number"))]
+    pub limit: Option<i64>,
+    /// 1 to 3; defaults to 2
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub min_signals: Option<i32>,
+}
+
+/// The query of `GET /check-name`
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct CheckNameParams {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional, type = "number"))]
+    pub limit: Option<i64>,
+}
+
+/// `package_managers` narrows the projects of `GET /stats/cross-ecosystem` to
+/// those in all of a comma-separated list, e.g. `homebrew,debian`
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct CrossEcosystemParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub package_managers: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional, type = "number"))]
+    pub limit: Option<i64>,
+}
+
+/// For `GET /reports/stale`, besides [`PaginationParams`]: `months` without a
+/// release (24 by default), and the package manager to narrow to
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct StaleParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub ecosystem: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub months: Option<i32>,
+}
+
+/// For `GET /stats/top-dependents` and `GET /stats/top-dependencies`
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct TopDegreeParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional, type = "number"))]
+    pub limit: Option<i64>,
+}
+
+/// How `GET /leaderboard/orgs` scores an organization from its projects' ranks
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum OrgScore {
+    /// All its ranks added up: the most critical software overall
+    #[default]
+    Sum,
+    /// Its best ranked project
+    Max,
+}
+
+/// For `GET /leaderboard/orgs`
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct OrgLeaderboardParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional, type = "number"))]
+    pub limit: Option<i64>,
+    #[serde(default)]
+    pub by: OrgScore,
+}
+
+/// The last `runs` runs of a project's history
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct RunWindowParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional, type = "number"))]
+    pub runs: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub precision: Option<u32>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct ContributorParams {
+    #[serde(default)]
+    pub run: RunSelector,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional, type = "number"))]
+    pub limit: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub precision: Option<u32>,
+    /// The last run on or before this day (UTC) instead of `run`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub as_of: Option<NaiveDate>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, Default, PartialEq)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Json,
+    Csv,
+}
+
+impl Format {
+    pub const ALL: [Format; 2] = [Format::Json, Format::Csv];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Csv => "csv",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Csv => "text/csv; charset=utf-8",
+        }
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct ExportParams {
+    #[serde(default)]
+    pub format: Format,
+}
+
+/// `?format=csv` answers `GET /tables/{table}` and `POST /leaderboard` in CSV;
+/// without it the `Accept` header decides
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct FormatParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub format: Option<Format>,
+}
+
+/// The body of `POST /admin/cache/invalidate`
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct InvalidateRequest {
+    pub targets: Vec<String>,
+}
+
+/// What a leaderboard override does to its project
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum OverrideAction {
+    /// Leave the project off leaderboards
+    Exclude,
+    /// Show the project with `teaRank` in place of its own rank
+    Pin,
+}
+
+/// The body of `PUT /admin/overrides/{id}`
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct OverrideRequest {
+    pub action: OverrideAction,
+    /// The rank to show, for `pin`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub tea_rank: Option<String>,
+    pub reason: String,
+    /// When the override lapses; never when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// The body of `POST /admin/schema/cutover`
+#[derive(Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CutoverRequest {
+    /// A name from `DB_SCHEMAS`
+    pub target: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn run_selectors_round_trip_through_query_strings() {
+        for selector in [RunSelector::Latest, RunSelector::Run(42)] {
+            let raw = String::from(selector);
+            assert_eq!(RunSelector::try_from(raw), Ok(selector));
+        }
+        assert!(RunSelector::try_from("newest".to_string()).is_err());
+
+        // Unset options stay out of the query entirely
+        let params = json!(NewEntrantsParams {
+            limit: Some(5),
+            ..NewEntrantsParams::default()
+        });
+        assert_eq!(params, json!({ "run": "latest", "limit": 5 }));
+    }
+}
@@ -0,0 +1,254 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use serde::{Deserialize, Serialize};
+
+/// Most keys one `?sort` may list
+const MAX_SORT_KEYS: usize = 4;
+
+/// `?sort=rank:desc,name:asc&nulls=last`, or `?sort=created_at&order=desc`
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(
+    feature = "openapi",
+    derive(utoipa::ToSchema, utoipa::IntoParams),
+    into_params(parameter_in = Query)
+)]
+pub struct SortParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub sort: Option<String>,
+    /// Direction of the keys of `sort` that don't give their own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub order: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub nulls: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SortKey {
+    pub field: String,
+    pub descending: bool,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Nulls {
+    First,
+    Last,
+}
+
+/// A validated multi-key sort. Without an explicit `nulls`, nulls sort as
+/// Postgres does: last ascending, first descending.
+#[derive(Debug, PartialEq)]
+pub struct Sort {
+    pub keys: Vec<SortKey>,
+    pub nulls: Option<Nulls>,
+}
+
+/// A field value compared in memory; `None` stands for NULL
+#[derive(PartialEq, PartialOrd)]
+pub enum SortValue {
+    Number(f64),
+    Text(String),
+}
+
+impl SortParams {
+    /// The requested sort, with every field checked against `fields`. `None`
+    /// when the request has no `sort`.
+    pub fn parse(&self, fields: &[&str]) -> Result<Option<Sort>, String> {
+        let nulls = match self.nulls.as_deref().map(str::trim) {
+            None => None,
+            Some("first") => Some(Nulls::First),
+            Some("last") => Some(Nulls::Last),
+            Some(other) => {
+                return Err(format!(
+                    "Invalid nulls '{other}' (expected 'first' or 'last')"
+                ))
+            }
+        };
+        let order = match self.order.as_deref().map(str::trim) {
+            None => "asc",
+            Some(order @ ("asc" | "desc")) => order,
+            Some(other) => {
+                return Err(format!(
+                    "Invalid order '{other}' (expected 'asc' or 'desc')"
+                ))
+            }
+        };
+        let Some(raw) = self.sort.as_deref().filter(|raw| !raw.trim().is_empty()) else {
+            return match (nulls, &self.order) {
+                (Some(_), _) => Err("nulls requires a sort".to_string()),
+                (None, Some(_)) => Err("order requires a sort".to_string()),
+                (None, None) => Ok(None),
+            };
+        };
+
+        let mut keys: Vec<SortKey> = Vec::new();
+        for item in raw.split(',').map(str::trim) {
+            let (field, direction) = item.split_once(':').unwrap_or((item, order));
+            let descending = match direction {
+                "asc" => false,
+                "desc" => true,
+                _ => {
+                    return Err(format!(
+                        "Invalid direction '{direction}' for '{field}' (expected 'asc' or 'desc')"
+                    ))
+                }
+            };
+            if !fields.contains(&field) {
+                return Err(format!(
+                    "Cannot sort by '{field}' (expected one of: {})",
+                    fields.join(", ")
+                ));
+            }
+            if keys.iter().any(|key| key.field == field) {
+                return Err(format!("'{field}' is sorted on more than once"));
+            }
+            keys.push(SortKey {
+                field: field.to_string(),
+                descending,
+            });
+        }
+        if keys.len() > MAX_SORT_KEYS {
+            return Err(format!("At most {MAX_SORT_KEYS} sort keys are allowed"));
+        }
+        Ok(Some(Sort { keys, nulls }))
+    }
+}
+
+impl Sort {
+    /// `ORDER BY` items, each field replaced by the SQL from `column`
+    pub fn order_by(&self, column: impl Fn(&str) -> String) -> String {
+        self.keys
+            .iter()
+            .map(|key| {
+                let direction = if key.descending { "DESC" } else { "ASC" };
+                match self.nulls {
+                    Some(Nulls::First) => {
+                        format!("{} {direction} NULLS FIRST", column(&key.field))
+                    }
+                    Some(Nulls::Last) => format!("{} {direction} NULLS LAST", column(&key.field)),
+                    None => format!("{} {direction}", column(&key.field)),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Orders `a` and `b` the way [`Sort::order_by`] would, reading each field
+    /// through `value`
+    pub fn compare<T>(
+        &self,
+        a: &T,
+        b: &T,
+        value: impl Fn(&str, &T) -> Option<SortValue>,
+    ) -> Ordering {
+        for key in &self.keys {
+            let nulls_first = match self.nulls {
+                Some(nulls) => nulls == Nulls::First,
+                None => key.descending,
+            };
+            let ordering = match (value(&key.field, a), value(&key.field, b)) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) if nulls_first => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) if nulls_first => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => {
+                    let ordering = a.partial_cmp(&b).unwrap_or(Ordering::Equal);
+                    if key.descending {
+                        ordering.reverse()
+                    } else {
+                        ordering
+                    }
+                }
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn params(sort: &str, nulls: Option<&str>) -> SortParams {
+        SortParams {
+            sort: Some(sort.to_string()),
+            nulls: nulls.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sorts_compile_to_order_by() {
+        let sort = params("rank:desc,name", Some("last"))
+            .parse(&["rank", "name"])
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            sort.order_by(|field| format!("t.\"{field}\"")),
+            "t.\"rank\" DESC NULLS LAST, t.\"name\" ASC NULLS LAST"
+        );
+
+        for (sort, nulls) in [
+            ("rank:up", None),
+            ("id", None),
+            ("rank,rank:desc", None),
+            ("rank; DROP TABLE canons", None),
+            ("rank", Some("middle")),
+        ] {
+            assert!(
+                params(sort, nulls).parse(&["rank", "name"]).is_err(),
+                "{sort}"
+            );
+        }
+    }
+
+    #[test]
+    fn order_sets_the_default_direction() {
+        let sort = SortParams {
+            sort: Some("created_at,name:asc".to_string()),
+            order: Some("desc".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            sort.parse(&["created_at", "name"])
+                .unwrap()
+                .unwrap()
+                .order_by(|field| field.to_string()),
+            "created_at DESC, name ASC"
+        );
+
+        for (sort, order) in [(Some("name"), "down"), (None, "desc")] {
+            let params = SortParams {
+                sort: sort.map(str::to_string),
+                order: Some(order.to_string()),
+                ..Default::default()
+            };
+            assert!(params.parse(&["name"]).is_err(), "{order}");
+        }
+    }
+
+    #[test]
+    fn in_memory_sort_matches_postgres_nulls() {
+        let sort = params("rank:desc", None).parse(&["rank"]).unwrap().unwrap();
+        let mut ranks = vec![Some(1.0), None, Some(3.0)];
+        ranks.sort_by(|a, b| sort.compare(a, b, |_, rank| rank.map(SortValue::Number)));
+        assert_eq!(ranks, [None, Some(3.0), Some(1.0)]);
+
+        let sort = params("rank:desc", Some("last"))
+            .parse(&["rank"])
+            .unwrap()
+            .unwrap();
+        ranks.sort_by(|a, b| sort.compare(a, b, |_, rank| rank.map(SortValue::Number)));
+        assert_eq!(ranks, [Some(3.0), Some(1.0), None]);
+    }
+}
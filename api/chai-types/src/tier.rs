@@ -0,0 +1,57 @@
+use core::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+/// Service level of a caller. Keys are `free` unless `API_KEY_TIERS` says
+/// otherwise, anonymous callers are always `free`, and the admin key is
+/// `internal`.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Tier {
+    #[default]
+    Free,
+    Partner,
+    Internal,
+}
+
+impl Tier {
+    pub const ALL: [Tier; 3] = [Tier::Free, Tier::Partner, Tier::Internal];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Tier::Free => "free",
+            Tier::Partner => "partner",
+            Tier::Internal => "internal",
+        }
+    }
+}
+
+/// What one request from a tier may ask for, as `/limits` reports it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(export))]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "camelCase")]
+pub struct TierLimits {
+    /// Most entries a leaderboard returns
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub response_limit: i64,
+    /// Most project ids one batch lookup or leaderboard takes
+    #[cfg_attr(feature = "ts", ts(type = "number"))]
+    pub batch_size: usize,
+    /// Whether whole-run leaderboard exports are available
+    pub exports: bool,
+}
+
+impl FromStr for Tier {
+    type Err = ();
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        Tier::ALL
+            .into_iter()
+            .find(|tier| tier.as_str() == raw)
+            .ok_or(())
+    }
+}
@@ -0,0 +1,647 @@
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, web, HttpResponse, Responder};
+use chai_core::cache::Target;
+use chai_core::db::{timed, Catalog};
+use chai_core::leaderboard::{
+    leaderboard_projects_query, new_entrants_query, top_projects_query, LEADERBOARD_SORT_FIELDS,
+    NEW_ENTRANT_SORT_FIELDS, PREVIOUS_RUN_QUERY,
+};
+use chai_core::projects::maintainer_projects_query;
+use chai_core::suspects::suspects;
+use chai_types::models::Suspects;
+use chai_types::requests::{CutoverRequest, InvalidateRequest, RunSelector, SuspectParams};
+use chai_types::sort::{Sort, SortParams};
+use chrono::NaiveDateTime;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio_postgres::types::ToSql;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::auth::Admin;
+use crate::db::read_write;
+use crate::problem::{
+    database_error, database_problem, no_connection, not_found, problem, problem_with,
+};
+use crate::runs::{detect_latest_run, refresh_latest_ranks};
+
+/// Endpoints whose statements `/admin/explain` can plan
+const EXPLAINABLE: [&str; 3] = ["leaderboard", "leaderboard/new", "maintainer"];
+/// Upper bound on an explained statement, which really runs under ANALYZE
+const EXPLAIN_TIMEOUT: &str = "30s";
+
+/// Most suspects one `/admin/suspects` request returns
+const MAX_SUSPECTS: i64 = 1000;
+
+type SqlParams = Vec<Box<dyn ToSql + Sync + Send>>;
+
+#[utoipa::path(
+    tag = "admin",
+    security(("apiKey" = [])),
+    responses(
+        (
+            status = 200,
+            description = "Requests per caller and endpoint since startup",
+            body = Value
+        ),
+        (status = 403, description = "Not an admin API key"),
+    )
+)]
+#[get("/admin/usage")]
+pub async fn get_usage(_: Admin, data: web::Data<AppState>) -> impl Responder {
+    let callers = data.usage.summaries();
+    HttpResponse::Ok().json(json!({
+        "totalRequests": callers.iter().map(|c| c.requests).sum::<u64>(),
+        "totalBytesServed": callers.iter().map(|c| c.bytes_served).sum::<u64>(),
+        "callers": callers,
+        "routes": data.usage.route_sizes(),
+    }))
+}
+
+#[utoipa::path(
+    tag = "admin",
+    security(("apiKey" = [])),
+    responses(
+        (status = 200, description = "Requests per table since startup", body = Value),
+        (status = 403, description = "Not an admin API key"),
+    )
+)]
+#[get("/admin/table-usage")]
+pub async fn get_table_usage(_: Admin, data: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(json!({
+        "tables": data.usage.table_usage(&data.tables.borrow().tables),
+    }))
+}
+
+#[utoipa::path(
+    tag = "admin",
+    security(("apiKey" = [])),
+    responses(
+        (status = 200, description = "The evicted entries", body = Value),
+        (status = 400, description = "Invalid target"),
+        (status = 403, description = "Not an admin API key"),
+    )
+)]
+#[post("/admin/cache/invalidate")]
+pub async fn invalidate_cache(
+    _: Admin,
+    body: web::Json<InvalidateRequest>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    if body.targets.is_empty() {
+        return problem(StatusCode::BAD_REQUEST, "At least one target is required");
+    }
+
+    let targets = match body
+        .targets
+        .iter()
+        .map(|raw| Target::parse(raw))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(targets) => targets,
+        Err(e) => return problem(StatusCode::BAD_REQUEST, e),
+    };
+
+    match data.invalidate(&targets) {
+        Ok(evicted) => {
+            log::info!(target: "chai_api::audit", "Cache invalidated {:?}: {evicted:?}", body.targets);
+            HttpResponse::Ok().json(json!({
+                "evicted": evicted,
+                "total": evicted.values().sum::<usize>(),
+            }))
+        }
+        Err(e) => problem(StatusCode::BAD_REQUEST, e),
+    }
+}
+
+#[utoipa::path(
+    tag = "admin",
+    security(("apiKey" = [])),
+    responses(
+        (status = 200, description = "Entries, hits and misses per cache", body = Value),
+        (status = 403, description = "Not an admin API key"),
+    )
+)]
+#[get("/admin/cache/stats")]
+pub async fn get_cache_stats(_: Admin, data: web::Data<AppState>) -> impl Responder {
+    let exports = match data.export_cache.disk_usage().await {
+        Ok((objects, bytes)) => json!({
+            "objects": objects,
+            "diskBytes": bytes,
+            "lookups": data.export_cache.counters.to_json(),
+        }),
+        Err(e) => {
+            log::warn!("Failed to read export cache usage: {e}");
+            json!({ "lookups": data.export_cache.counters.to_json() })
+        }
+    };
+
+    HttpResponse::Ok().json(json!({
+        "namespaces": data.cache_stats(),
+        "exports": exports,
+    }))
+}
+
+#[utoipa::path(
+    tag = "admin",
+    security(("apiKey" = [])),
+    responses(
+        (status = 200, description = "Connections per database pool", body = Value),
+        (status = 403, description = "Not an admin API key"),
+    )
+)]
+#[get("/admin/pool/stats")]
+pub async fn get_pool_stats(_: Admin, data: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(json!({
+        "regions": data.pool.to_json(),
+        "recycled": data.recycles.to_json(),
+    }))
+}
+
+#[utoipa::path(
+    tag = "admin",
+    security(("apiKey" = [])),
+    responses(
+        (
+            status = 200,
+            description = "The configured schema targets and the active one",
+            body = Value
+        ),
+        (status = 403, description = "Not an admin API key"),
+    )
+)]
+#[get("/admin/schema")]
+pub async fn get_schema(_: Admin, data: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(data.schemas.to_json())
+}
+
+/// Switches every session to another `DB_SCHEMAS` target. The target must
+/// already hold tables; once it's active the table list is reloaded, caches
+/// and stored exports are dropped, and the latest run is read again.
+#[utoipa::path(
+    tag = "admin",
+    security(("apiKey" = [])),
+    responses(
+        (status = 200, description = "The newly active schema", body = Value),
+        (status = 400, description = "Unknown or empty target"),
+        (status = 403, description = "Not an admin API key"),
+        (status = 409, description = "A cutover is already running"),
+    )
+)]
+#[post("/admin/schema/cutover")]
+pub async fn cutover_schema(
+    _: Admin,
+    body: web::Json<CutoverRequest>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let Some(schema) = data.schemas.target(&body.target) else {
+        return problem_with(
+            StatusCode::BAD_REQUEST,
+            format!("Unknown schema target '{}'", body.target),
+            json!({ "valid_targets": data.schemas.names() }),
+        );
+    };
+    let _cutover = data.schemas.cutover.lock().await;
+
+    let client = match data.pool.primary().get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+    let catalog = match timed("schema_catalog", Catalog::load(&client, schema)).await {
+        Ok(catalog) => catalog,
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            return database_error(&e);
+        }
+    };
+    drop(client);
+    if catalog.tables.is_empty() {
+        return problem(
+            StatusCode::CONFLICT,
+            format!("Schema '{schema}' has no tables"),
+        );
+    }
+
+    data.schemas.activate(&body.target);
+    let tables = catalog.tables.len();
+    data.tables.send_replace(Arc::new(catalog));
+    let evicted = data
+        .invalidate(&[Target::parse("*").expect("'*' is a valid target")])
+        .unwrap_or_default();
+    data.percentile_cache.clear();
+    data.degree_cache.clear();
+    data.org_ranks.clear();
+    if let Err(e) = data.export_cache.clear().await {
+        log::error!("Failed to clear export cache after cutover: {e}");
+    }
+    detect_latest_run(&data.pool, &data.latest_run).await;
+    // The new schema's latest ranks may trail its runs even when the run is unchanged
+    let latest = *data.latest_run.borrow();
+    refresh_latest_ranks(&data.pool, latest).await;
+
+    log::info!(target: "chai_api::audit", "Schema cut over to {} ({schema})", body.target);
+    HttpResponse::Ok().json(json!({
+        "active": body.target,
+        "schema": schema,
+        "tables": tables,
+        "evicted": evicted,
+        "latestRun": *data.latest_run.borrow(),
+    }))
+}
+
+#[utoipa::path(
+    tag = "admin",
+    security(("apiKey" = [])),
+    responses(
+        (status = 200, description = "Jobs by status", body = Value),
+        (status = 403, description = "Not an admin API key"),
+    )
+)]
+#[get("/admin/jobs")]
+pub async fn get_job_stats(_: Admin, data: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(data.jobs.stats())
+}
+
+#[utoipa::path(
+    tag = "admin",
+    security(("apiKey" = [])),
+    responses(
+        (status = 200, description = "Maintenance tasks and their last runs", body = Value),
+        (status = 403, description = "Not an admin API key"),
+    )
+)]
+#[get("/admin/schedule")]
+pub async fn get_schedule(_: Admin, data: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(data.scheduler.to_json())
+}
+
+/// How the newest run compared with the one before it, and how many runs
+/// have raised alerts since startup
+#[utoipa::path(
+    tag = "admin",
+    security(("apiKey" = [])),
+    responses(
+        (status = 200, description = "The latest run check", body = Value),
+        (status = 403, description = "Not an admin API key"),
+    )
+)]
+#[get("/admin/runs/check")]
+pub async fn get_run_checks(_: Admin, data: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(data.run_alerts.to_json())
+}
+
+/// Outbound HTTP requests by host since startup, and how many are in flight
+#[utoipa::path(
+    tag = "admin",
+    security(("apiKey" = [])),
+    responses(
+        (status = 200, description = "Outbound requests by host", body = Value),
+        (status = 403, description = "Not an admin API key"),
+    )
+)]
+#[get("/admin/http/stats")]
+pub async fn get_http_stats(_: Admin, data: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(data.http.to_json())
+}
+
+/// Hides a run from every endpoint that serves the latest or previous run, so
+/// they fall back to the run before it until the quarantine is cleared
+#[utoipa::path(
+    tag = "admin",
+    params(("run" = i32, Path, description = "Run number")),
+    security(("apiKey" = [])),
+    responses(
+        (status = 200, description = "The run's quarantine and the latest run", body = Value),
+        (status = 403, description = "Not an admin API key"),
+        (status = 404, description = "No such run"),
+    )
+)]
+#[post("/admin/runs/{run}/quarantine")]
+pub async fn quarantine_run(
+    _: Admin,
+    run: web::Path<i32>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    set_quarantine(run.into_inner(), true, &data).await
+}
+
+#[utoipa::path(
+    tag = "admin",
+    params(("run" = i32, Path, description = "Run number")),
+    security(("apiKey" = [])),
+    responses(
+        (status = 200, description = "The run's quarantine and the latest run", body = Value),
+        (status = 403, description = "Not an admin API key"),
+        (status = 404, description = "No such run"),
+    )
+)]
+#[delete("/admin/runs/{run}/quarantine")]
+pub async fn clear_quarantine(
+    _: Admin,
+    run: web::Path<i32>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    set_quarantine(run.into_inner(), false, &data).await
+}
+
+/// Marks or clears `run`'s quarantine, then rebuilds the latest ranks, drops
+/// cached projects, whose ranks may come from it, and reads the latest run again
+async fn set_quarantine(run: i32, quarantined: bool, data: &AppState) -> HttpResponse {
+    let mut client = match data.pool.primary().get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+    let query = r#"
+        UPDATE tea_rank_runs
+        SET quarantined_at = CASE WHEN $2 THEN COALESCE(quarantined_at, now()) END
+        WHERE run = $1
+        RETURNING quarantined_at"#;
+    let update = async {
+        let transaction = read_write(&mut client).await?;
+        let rows = transaction.query(query, &[&run, &quarantined]).await?;
+        transaction.commit().await?;
+        Ok::<_, tokio_postgres::Error>(rows)
+    };
+    let quarantined_at: Option<NaiveDateTime> = match timed("quarantine_run", update).await {
+        Ok(rows) if rows.is_empty() => return not_found(format!("Run {run} not found")),
+        Ok(rows) => rows[0].get("quarantined_at"),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            return database_error(&e);
+        }
+    };
+    drop(client);
+
+    // Before the caches go, so they refill with the ranks left standing. Even a
+    // run older than the latest can hold some canons' latest rank.
+    refresh_latest_ranks(&data.pool, None).await;
+    let evicted = data
+        .invalidate(&[Target::parse("*").expect("'*' is a valid target")])
+        .unwrap_or_default();
+    detect_latest_run(&data.pool, &data.latest_run).await;
+
+    let action = if quarantined {
+        "quarantined"
+    } else {
+        "released from quarantine"
+    };
+    log::info!(target: "chai_api::audit", "Run {run} {action}");
+    HttpResponse::Ok().json(json!({
+        "run": run,
+        "quarantinedAt": quarantined_at.map(|at| at.and_utc()),
+        "latestRun": *data.latest_run.borrow(),
+        "evicted": evicted,
+    }))
+}
+
+/// Canons that look like spam, placeholders or name squatting, for curators to
+/// review: no source URL, no dependents, and a name close to a better ranked
+/// top project's
+#[utoipa::path(
+    tag = "admin",
+    params(SuspectParams),
+    security(("apiKey" = [])),
+    responses(
+        (status = 200, description = "OK", body = Suspects),
+        (status = 400, description = "Invalid parameters"),
+        (status = 403, description = "Not an admin API key"),
+    )
+)]
+#[get("/admin/suspects")]
+pub async fn list_suspects(
+    _: Admin,
+    params: web::Query<SuspectParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let limit = params.limit.unwrap_or(100).clamp(1, MAX_SUSPECTS);
+    let min_signals = params.min_signals.unwrap_or(2);
+    if !(1..=3).contains(&min_signals) {
+        return problem(
+            StatusCode::BAD_REQUEST,
+            format!("Invalid min_signals '{min_signals}': must be 1 to 3"),
+        );
+    }
+
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+    let run = *data.latest_run.borrow();
+    let live = data.not_deleted("canons", "c");
+    match suspects(&client, run, min_signals, limit, &live, data.row_format).await {
+        Ok(found) => HttpResponse::Ok().json(Suspects {
+            run,
+            suspects: found,
+        }),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+/// Parses the optional query parameter `name`
+fn explain_param<T: FromStr>(
+    params: &HashMap<String, String>,
+    name: &str,
+) -> Result<Option<T>, String> {
+    params
+        .get(name)
+        .map(|raw| raw.parse().map_err(|_| format!("Invalid {name} '{raw}'")))
+        .transpose()
+}
+
+/// The `sort`, `order`, and `nulls` query parameters, checked against `fields`
+fn explain_sort(
+    params: &HashMap<String, String>,
+    fields: &[&str],
+) -> Result<Option<Sort>, (StatusCode, String)> {
+    SortParams {
+        sort: params.get("sort").cloned(),
+        order: params.get("order").cloned(),
+        nulls: params.get("nulls").cloned(),
+    }
+    .parse(fields)
+    .map_err(|error| (StatusCode::BAD_REQUEST, error))
+}
+
+/// The statement `endpoint` would run for `params`, with its parameters. Errors
+/// carry the status to answer with.
+async fn explained_statement(
+    client: &deadpool_postgres::Client,
+    data: &AppState,
+    endpoint: &str,
+    params: &HashMap<String, String>,
+) -> Result<(String, SqlParams), (StatusCode, String)> {
+    let bad_request = |error: String| (StatusCode::BAD_REQUEST, error);
+    let live = data.not_deleted("canons", "c");
+    let limit: i64 = explain_param(params, "limit")
+        .map_err(bad_request)?
+        .unwrap_or(10);
+    let run = || -> Result<i32, String> {
+        let selector = match params.get("run") {
+            Some(raw) => RunSelector::try_from(raw.clone())?,
+            None => RunSelector::Latest,
+        };
+        selector
+            .resolve(*data.latest_run.borrow())
+            .ok_or_else(|| "No ranking runs yet".to_string())
+    };
+
+    match endpoint {
+        "leaderboard" => match params.get("projectIds") {
+            Some(raw) => {
+                let ids = raw
+                    .split(',')
+                    .map(|id| Uuid::try_parse(id.trim()))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| bad_request(format!("Invalid projectIds '{raw}'")))?;
+                let include_unranked = explain_param(params, "includeUnranked")
+                    .map_err(bad_request)?
+                    .unwrap_or(false);
+                Ok((
+                    leaderboard_projects_query(&live, include_unranked, false),
+                    vec![Box::new(ids), Box::new(limit)],
+                ))
+            }
+            None => Ok((
+                top_projects_query(
+                    &live,
+                    explain_sort(params, &LEADERBOARD_SORT_FIELDS)?.as_ref(),
+                ),
+                vec![
+                    Box::new(run().map_err(bad_request)?),
+                    Box::new(limit),
+                    Box::new(None::<String>),
+                    Box::new(None::<Vec<String>>),
+                ],
+            )),
+        },
+        "leaderboard/new" => {
+            let run = run().map_err(bad_request)?;
+            let previous: Option<i32> = match timed(
+                "previous_run",
+                client.query_opt(PREVIOUS_RUN_QUERY, &[&run]),
+            )
+            .await
+            {
+                Ok(Some(row)) => row.get(0),
+                Ok(None) => return Err(bad_request(format!("Run {run} not found"))),
+                Err(e) => {
+                    log::error!("Database query error: {e}");
+                    return Err(database_problem(&e));
+                }
+            };
+            Ok((
+                new_entrants_query(
+                    &live,
+                    explain_sort(params, &NEW_ENTRANT_SORT_FIELDS)?.as_ref(),
+                ),
+                vec![Box::new(run), Box::new(previous), Box::new(limit)],
+            ))
+        }
+        "maintainer" => {
+            let Some(name) = params.get("name") else {
+                return Err(bad_request(
+                    "maintainer requires a name parameter".to_string(),
+                ));
+            };
+            Ok((
+                maintainer_projects_query(&live),
+                vec![Box::new(name.clone())],
+            ))
+        }
+        _ => Err(bad_request(format!(
+            "Unknown endpoint '{endpoint}' (explainable: {})",
+            EXPLAINABLE.join(", ")
+        ))),
+    }
+}
+
+/// Runs `EXPLAIN (ANALYZE, FORMAT JSON)` on the statement an endpoint would
+/// execute for the given parameters, inside a rolled-back transaction
+#[utoipa::path(
+    tag = "admin",
+    params(("endpoint" = String, Query, description = "The endpoint whose statement to explain")),
+    security(("apiKey" = [])),
+    responses(
+        (status = 200, description = "The statement and its plan", body = Value),
+        (status = 400, description = "Unknown endpoint or missing parameters"),
+        (status = 403, description = "Not an admin API key"),
+    )
+)]
+#[get("/admin/explain")]
+pub async fn explain(
+    _: Admin,
+    params: web::Query<HashMap<String, String>>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let Some(endpoint) = params.get("endpoint") else {
+        return problem_with(
+            StatusCode::BAD_REQUEST,
+            "endpoint is required",
+            json!({ "explainable": EXPLAINABLE }),
+        );
+    };
+
+    let mut client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+
+    let (statement, statement_params) =
+        match explained_statement(&client, &data, endpoint, &params).await {
+            Ok(explained) => explained,
+            Err((status, error)) => return problem(status, error),
+        };
+    let statement_params: Vec<&(dyn ToSql + Sync)> = statement_params
+        .iter()
+        .map(|param| param.as_ref() as &(dyn ToSql + Sync))
+        .collect();
+
+    log::info!(target: "chai_api::audit", "Explaining {endpoint} with {:?}", params.0);
+
+    let result = async {
+        let transaction = client.transaction().await?;
+        transaction
+            .batch_execute(&format!(
+                "SET LOCAL statement_timeout = '{EXPLAIN_TIMEOUT}'"
+            ))
+            .await?;
+        let row = transaction
+            .query_one(
+                &format!("EXPLAIN (ANALYZE, FORMAT JSON) {statement}"),
+                &statement_params,
+            )
+            .await?;
+        transaction.rollback().await?;
+        Ok::<Value, tokio_postgres::Error>(row.get(0))
+    }
+    .await;
+
+    match result {
+        Ok(plan) => HttpResponse::Ok().json(json!({
+            "endpoint": endpoint,
+            "statement": statement.trim(),
+            "plan": plan,
+        })),
+        Err(e) => {
+            log::error!("Failed to explain {endpoint}: {e}");
+            database_error(&e)
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Checks run over a caller's own package list rather than a single project.
+
+use actix_web::http::StatusCode;
+use actix_web::{post, web, HttpResponse, Responder};
+use chai_core::dependencies::{exposed_packages, unknown_package_managers};
+use chai_types::models::DependencyConfusion;
+use chai_types::requests::DependencyConfusionRequest;
+use serde_json::json;
+
+use crate::app_state::AppState;
+use crate::problem::{database_error, no_connection, problem, problem_with};
+
+/// Most package names one dependency confusion check takes
+const MAX_PACKAGES: usize = 1000;
+
+/// Reports which internal package names are already taken in a public
+/// registry, and by whom: a build that resolves them from the public registry
+/// could install someone else's package
+#[utoipa::path(
+    tag = "analysis",
+    responses(
+        (status = 200, description = "OK", body = DependencyConfusion),
+        (status = 400, description = "Invalid request"),
+    )
+)]
+#[post("/analyze/dependency-confusion")]
+pub async fn analyze_dependency_confusion(
+    body: web::Json<DependencyConfusionRequest>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let packages = &body.packages;
+    if packages.is_empty() {
+        return problem(StatusCode::BAD_REQUEST, "No packages provided");
+    }
+    if packages.len() > MAX_PACKAGES {
+        return problem(
+            StatusCode::BAD_REQUEST,
+            format!("At most {MAX_PACKAGES} packages can be checked at once"),
+        );
+    }
+
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+
+    // An unknown package manager would report every name as unclaimed
+    let managers: Vec<&str> = packages
+        .iter()
+        .map(|package| package.package_manager.as_str())
+        .collect();
+    match unknown_package_managers(&client, &managers).await {
+        Ok(unknown) if !unknown.is_empty() => {
+            return problem_with(
+                StatusCode::BAD_REQUEST,
+                format!("Unknown package managers: {}", unknown.join(", ")),
+                json!({ "unknown": unknown }),
+            )
+        }
+        Ok(_) => {}
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            return database_error(&e);
+        }
+    }
+
+    let run = *data.latest_run.borrow();
+    let live = data.not_deleted("canons", "c");
+    match exposed_packages(&client, packages, run, &live, data.row_format).await {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
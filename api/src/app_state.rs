@@ -1,33 +1,113 @@
-use dashmap::DashMap;
-use deadpool_postgres::Pool;
-use serde_json::Value;
+use chai_core::cache::{CacheMetrics, ProjectCacheEntry};
+use chai_types::format::RowFormat;
+use dashmap::{DashMap, DashSet};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::Duration;
+use tokio::sync::Semaphore;
 use uuid::Uuid;
 
-const TTL: Duration = Duration::from_secs(3600); // 1 hour
+use crate::auth::ApiKeys;
+use crate::db::{RecycleCounters, Tables};
+use crate::export::ExportCache;
+use crate::features::Features;
+use crate::http_client::HttpClient;
+use crate::ip_filter::IpFilter;
+use crate::jobs::Jobs;
+use crate::metrics::Metrics;
+use crate::orgs::OrgRankCache;
+use crate::package_managers::PackageManagers;
+use crate::ranks::PercentileCache;
+use crate::rate_limit::RateLimiter;
+use crate::regions::Pools;
+use crate::run_alerts::RunAlerts;
+use crate::runs::LatestRun;
+use crate::scheduler::Scheduler;
+use crate::schemas::Schemas;
+use crate::stats::{DegreeCache, GraphCache};
+use crate::status::{IngestCache, StatusLimits};
+use crate::usage::UsageTracker;
 
-#[derive(Clone)]
-pub struct ProjectCacheEntry {
-    pub data: Arc<Value>,
-    pub created_at: Instant,
+const SOFT_DELETE_COLUMN: &str = "deleted_at";
+/// Stored ranks carry ~15 significant digits; more decimals add nothing
+const MAX_RANK_PRECISION: u32 = 15;
+
+pub struct AppState {
+    /// Reads use `pool.get()`; writes must use `pool.primary()`
+    pub pool: Arc<Pools>,
+    pub tables: Tables,
+    pub project_cache: Arc<DashMap<Uuid, ProjectCacheEntry>>,
+    pub funding_cache: Arc<DashMap<Uuid, ProjectCacheEntry>>,
+    /// Project ids with a background cache refresh in flight
+    pub refreshing: Arc<DashSet<Uuid>>,
+    pub cache_max_staleness: Duration,
+    pub cache_metrics: Arc<CacheMetrics>,
+    pub rank_precision: Option<u32>,
+    pub row_format: RowFormat,
+    pub latest_run: LatestRun,
+    pub run_alerts: Arc<RunAlerts>,
+    /// Every outbound HTTP request goes through this
+    pub http: Arc<HttpClient>,
+    pub schemas: Arc<Schemas>,
+    pub export_cache: Arc<ExportCache>,
+    pub jobs: Arc<Jobs>,
+    pub percentile_cache: Arc<PercentileCache>,
+    pub degree_cache: Arc<DegreeCache>,
+    pub org_ranks: Arc<OrgRankCache>,
+    pub graph_stats: Arc<GraphCache>,
+    pub ingests: Arc<IngestCache>,
+    pub package_managers: Arc<PackageManagers>,
+    pub status_limits: StatusLimits,
+    pub recycles: Arc<RecycleCounters>,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub api_keys: Arc<ApiKeys>,
+    pub usage: Arc<UsageTracker>,
+    pub metrics: Arc<Metrics>,
+    pub ip_filter: Arc<IpFilter>,
+    pub default_cache_control: Arc<str>,
+    pub features: Features,
+    pub scheduler: Arc<Scheduler>,
+    /// Point generic table reads at the business endpoints that replace them
+    pub table_deprecation_notices: bool,
+    /// Leave `packageManagers` out of projects unless `?include` asks for it
+    pub package_managers_opt_in: bool,
+    /// A permit per open `/tables/{table}/stream` response
+    pub table_streams: Arc<Semaphore>,
 }
 
-impl ProjectCacheEntry {
-    pub fn new(data: Value) -> Self {
-        Self {
-            data: Arc::new(data),
-            created_at: Instant::now(),
+impl AppState {
+    /// Decimals to round ranks to: the request's `?precision`, else the server
+    /// default, else none (ranks are returned as stored)
+    pub fn rank_precision(&self, requested: Option<u32>) -> Option<u32> {
+        requested
+            .or(self.rank_precision)
+            .map(|precision| precision.min(MAX_RANK_PRECISION))
+    }
+
+    /// Fields of projects returned only when `?include` names them
+    pub fn opt_in_fields(&self) -> &'static [&'static str] {
+        if self.package_managers_opt_in {
+            &["packageManagers"]
+        } else {
+            &[]
         }
     }
 
-    pub fn is_expired(&self) -> bool {
-        self.created_at.elapsed() > TTL
+    /// Whether `table` carries a `deleted_at` soft-delete column
+    pub fn is_soft_deletable(&self, table: &str) -> bool {
+        self.tables
+            .borrow()
+            .columns
+            .get(table)
+            .is_some_and(|columns| columns.iter().any(|c| c == SOFT_DELETE_COLUMN))
     }
-}
 
-pub struct AppState {
-    pub pool: Pool,
-    pub tables: Arc<Vec<String>>,
-    pub project_cache: Arc<DashMap<Uuid, ProjectCacheEntry>>,
+    /// SQL predicate excluding soft-deleted rows of `table` (referenced as
+    /// `alias`); always true for tables without soft deletes.
+    pub fn not_deleted(&self, table: &str, alias: &str) -> String {
+        if self.is_soft_deletable(table) {
+            format!("{alias}.{SOFT_DELETE_COLUMN} IS NULL")
+        } else {
+            "TRUE".to_string()
+        }
+    }
 }
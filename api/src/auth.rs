@@ -0,0 +1,358 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    error::InternalError,
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::Next,
+    web, Error, FromRequest, HttpMessage, HttpRequest, HttpResponse,
+};
+use chai_types::tier::{Tier, TierLimits};
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use subtle::ConstantTimeEq;
+
+use crate::app_state::AppState;
+use crate::config::{ignore_setting, Config};
+use crate::ip_filter::client_ip;
+use crate::problem::problem;
+
+const API_KEY_HEADER: &str = "X-API-Key";
+
+/// What every tier may ask for unless `TIER_*` settings say otherwise
+const DEFAULT_TIER_LIMITS: TierLimits = TierLimits {
+    response_limit: 1000,
+    batch_size: 1000,
+    exports: true,
+};
+
+/// The identity a request is attributed to for quotas and usage accounting.
+#[derive(Clone, Debug)]
+pub enum Caller {
+    Admin,
+    Key(String),
+    Anonymous(String),
+}
+
+impl Caller {
+    /// Stable identifier used as the key in per-caller bookkeeping.
+    pub fn id(&self) -> String {
+        match self {
+            Caller::Admin => "admin".to_string(),
+            Caller::Key(name) => format!("key:{name}"),
+            Caller::Anonymous(ip) => format!("ip:{ip}"),
+        }
+    }
+
+    pub fn is_admin(&self) -> bool {
+        matches!(self, Caller::Admin)
+    }
+}
+
+/// Registry of API keys, mapping each secret to the key's name.
+pub struct ApiKeys {
+    keys: HashMap<String, String>,
+    /// Tier by key name; keys not listed are `free`
+    tiers: HashMap<String, Tier>,
+    /// Limits by tier; tiers not listed get [`DEFAULT_TIER_LIMITS`]
+    limits: HashMap<Tier, TierLimits>,
+    admin_key: Option<String>,
+    /// Whether anonymous callers are told apart by proxy headers
+    trust_proxy_headers: bool,
+}
+
+impl ApiKeys {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            keys: config
+                .api_keys
+                .iter()
+                .map(|(name, secret)| (secret.clone(), name.clone()))
+                .collect(),
+            tiers: config
+                .api_key_tiers
+                .iter()
+                .filter_map(|(name, raw)| match raw.parse() {
+                    Ok(tier) => Some((name.clone(), tier)),
+                    Err(()) => {
+                        ignore_setting(format_args!(
+                            "Ignoring unknown tier {raw:?} for API key {name:?}"
+                        ));
+                        None
+                    }
+                })
+                .collect(),
+            limits: tier_limits(config),
+            admin_key: config.admin_api_key.clone(),
+            trust_proxy_headers: config.trust_proxy_headers,
+        }
+    }
+
+    pub fn tier(&self, caller: &Caller) -> Tier {
+        match caller {
+            Caller::Admin => Tier::Internal,
+            Caller::Key(name) => self.tiers.get(name).copied().unwrap_or_default(),
+            Caller::Anonymous(_) => Tier::Free,
+        }
+    }
+
+    /// What `tier` may ask for in one request
+    pub fn tier_limits(&self, tier: Tier) -> TierLimits {
+        self.limits
+            .get(&tier)
+            .copied()
+            .unwrap_or(DEFAULT_TIER_LIMITS)
+    }
+
+    /// What `caller`'s tier may ask for in one request
+    pub fn limits(&self, caller: &Caller) -> TierLimits {
+        self.tier_limits(self.tier(caller))
+    }
+
+    /// Resolves the caller from the presented key, falling back to the client
+    /// address for anonymous requests, as the IP filter resolves it. Unknown
+    /// keys are rejected.
+    pub fn identify(&self, req: &HttpRequest) -> Option<Caller> {
+        let Some(secret) = presented_key(req) else {
+            let ip = client_ip(req, self.trust_proxy_headers)
+                .map_or_else(|| "unknown".to_string(), |ip| ip.to_string());
+            return Some(Caller::Anonymous(ip));
+        };
+
+        let is_admin = self
+            .admin_key
+            .as_ref()
+            .is_some_and(|admin| bool::from(admin.as_bytes().ct_eq(secret.as_bytes())));
+        if is_admin {
+            return Some(Caller::Admin);
+        }
+        self.keys.get(&secret).cloned().map(Caller::Key)
+    }
+}
+
+/// Each tier's limits, with the `TIER_*` settings applied over
+/// [`DEFAULT_TIER_LIMITS`]
+fn tier_limits(config: &Config) -> HashMap<Tier, TierLimits> {
+    let mut limits: HashMap<Tier, TierLimits> = Tier::ALL
+        .into_iter()
+        .map(|tier| (tier, DEFAULT_TIER_LIMITS))
+        .collect();
+    let mut apply = |var: &str, raw: &str, update: &dyn Fn(&mut TierLimits)| match raw.parse() {
+        Ok(tier) => update(limits.get_mut(&tier).expect("every tier has limits")),
+        Err(()) => ignore_setting(format_args!("Ignoring {var} for unknown tier {raw:?}")),
+    };
+    for (tier, n) in &config.tier_response_limits {
+        apply("TIER_RESPONSE_LIMIT", tier, &|limits| {
+            limits.response_limit = (*n).max(1)
+        });
+    }
+    for (tier, n) in &config.tier_batch_sizes {
+        apply("TIER_BATCH_SIZE", tier, &|limits| {
+            limits.batch_size = (*n).max(1)
+        });
+    }
+    for (tier, exports) in &config.tier_exports {
+        apply("TIER_EXPORTS", tier, &|limits| limits.exports = *exports);
+    }
+    limits
+}
+
+fn presented_key(req: &HttpRequest) -> Option<String> {
+    if let Some(key) = req.headers().get(API_KEY_HEADER) {
+        return key.to_str().ok().map(ToOwned::to_owned);
+    }
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+}
+
+/// Returns the caller attached to the request by the `authenticate` middleware.
+pub fn caller(req: &HttpRequest) -> Caller {
+    req.extensions()
+        .get::<Caller>()
+        .cloned()
+        .unwrap_or_else(|| Caller::Anonymous("unknown".to_string()))
+}
+
+/// Name of the API key the request was made with, if any
+pub fn key_name(req: &HttpRequest) -> Option<String> {
+    match caller(req) {
+        Caller::Key(name) => Some(name),
+        _ => None,
+    }
+}
+
+pub fn require_admin(req: &HttpRequest) -> Option<HttpResponse> {
+    if caller(req).is_admin() {
+        None
+    } else {
+        Some(problem(
+            StatusCode::FORBIDDEN,
+            "This endpoint requires an admin API key",
+        ))
+    }
+}
+
+/// Proof the caller is an admin. Taken as a handler's first argument, it
+/// answers other callers with a 403 before the rest are extracted, so they
+/// can't probe the body, query, or path an admin endpoint accepts.
+pub struct Admin;
+
+impl FromRequest for Admin {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(match require_admin(req) {
+            None => Ok(Admin),
+            Some(response) => Err(InternalError::from_response("not an admin", response).into()),
+        })
+    }
+}
+
+pub async fn authenticate<B: MessageBody + 'static>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let Some(data) = req.app_data::<web::Data<AppState>>().cloned() else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+
+    match data.api_keys.identify(req.request()) {
+        Some(caller) => {
+            req.extensions_mut().insert(caller);
+            Ok(next.call(req).await?.map_into_left_body())
+        }
+        None => {
+            let response = problem(StatusCode::UNAUTHORIZED, "Invalid API key");
+            Ok(req.into_response(response).map_into_right_body())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::App;
+
+    use crate::admin::{invalidate_cache, list_suspects, quarantine_run};
+    use crate::overrides::put_override;
+    use crate::test_state::{test_state, UNREACHABLE_DATABASE_URL};
+
+    #[test]
+    fn tiers_get_their_configured_limits() {
+        let pairs = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        };
+        let keys = ApiKeys::from_config(&Config {
+            api_keys: pairs(&[("acme", "secret"), ("bulk", "other")]),
+            api_key_tiers: pairs(&[("bulk", "partner")]),
+            tier_response_limits: vec![("partner".into(), 5000), ("gold".into(), 9)],
+            tier_batch_sizes: vec![("free".into(), 100)],
+            tier_exports: vec![("free".into(), false)],
+            ..Config::from_env()
+        });
+
+        let free = keys.limits(&Caller::Key("acme".to_string()));
+        assert_eq!(
+            free,
+            TierLimits {
+                response_limit: 1000,
+                batch_size: 100,
+                exports: false,
+            }
+        );
+        assert_eq!(keys.limits(&Caller::Anonymous("ip".to_string())), free);
+        let partner = keys.limits(&Caller::Key("bulk".to_string()));
+        assert_eq!(partner.response_limit, 5000);
+        assert_eq!(partner.batch_size, 1000);
+        assert!(partner.exports);
+        assert_eq!(keys.limits(&Caller::Admin), DEFAULT_TIER_LIMITS);
+    }
+
+    #[test]
+    fn anonymous_callers_are_keyed_by_peer_unless_proxies_are_trusted() {
+        let keys = |trust_proxy_headers| ApiKeys {
+            keys: HashMap::from([("secret".to_string(), "acme".to_string())]),
+            tiers: HashMap::new(),
+            limits: HashMap::new(),
+            admin_key: Some("root".to_string()),
+            trust_proxy_headers,
+        };
+        let forwarded = || {
+            actix_web::test::TestRequest::default()
+                .peer_addr("192.0.2.1:4000".parse().unwrap())
+                .insert_header(("X-Forwarded-For", "198.51.100.9"))
+        };
+        let id = |keys: &ApiKeys, req: actix_web::test::TestRequest| {
+            keys.identify(&req.to_http_request())
+                .map(|caller| caller.id())
+        };
+
+        assert_eq!(
+            id(&keys(false), forwarded()).as_deref(),
+            Some("ip:192.0.2.1")
+        );
+        assert_eq!(
+            id(&keys(true), forwarded()).as_deref(),
+            Some("ip:198.51.100.9")
+        );
+        let keyed = |key: &str| forwarded().insert_header((API_KEY_HEADER, key));
+        assert_eq!(id(&keys(false), keyed("root")).as_deref(), Some("admin"));
+        assert_eq!(
+            id(&keys(false), keyed("secret")).as_deref(),
+            Some("key:acme")
+        );
+        assert_eq!(id(&keys(false), keyed("roo")), None);
+    }
+
+    #[actix_web::test]
+    async fn admin_routes_refuse_before_reading_the_request() {
+        let config = Config {
+            admin_api_key: Some("root".to_string()),
+            ..Config::from_env()
+        };
+        let state = test_state(&config, UNREACHABLE_DATABASE_URL);
+        let app = init_service(
+            App::new()
+                .app_data(state)
+                .wrap(from_fn(authenticate))
+                .configure(crate::extractors::configure)
+                .service(invalidate_cache)
+                .service(list_suspects)
+                .service(quarantine_run)
+                .service(put_override),
+        )
+        .await;
+        let requests = || {
+            [
+                TestRequest::post()
+                    .uri("/admin/cache/invalidate")
+                    .insert_header(("Content-Type", "application/json"))
+                    .set_payload("{"),
+                TestRequest::post().uri("/admin/cache/invalidate"),
+                TestRequest::get().uri("/admin/suspects?limit=many"),
+                TestRequest::post().uri("/admin/runs/latest/quarantine"),
+                TestRequest::put()
+                    .uri("/admin/overrides/not-a-uuid")
+                    .set_json(serde_json::json!({ "reason": 1 })),
+            ]
+        };
+
+        for request in requests() {
+            let response = call_service(&app, request.to_request()).await;
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+        for request in requests() {
+            let request = request.insert_header((API_KEY_HEADER, "root"));
+            let response = call_service(&app, request.to_request()).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        }
+    }
+}
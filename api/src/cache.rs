@@ -0,0 +1,41 @@
+use chai_core::cache::{invalidate, load_snapshot, namespaces, stats, sweep, Namespaces, Target};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+use crate::app_state::AppState;
+
+impl AppState {
+    pub fn caches(&self) -> Namespaces<'_> {
+        namespaces(&self.project_cache, &self.funding_cache)
+    }
+
+    /// Loads the snapshot at `path`, if there is one, logging the outcome
+    pub fn restore_caches(&self, path: &Path) {
+        match load_snapshot(path, &self.caches(), self.cache_max_staleness) {
+            Ok(count) => log::info!("Restored {count} cache entries from {}", path.display()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("Failed to restore cache snapshot {}: {e}", path.display()),
+        }
+    }
+
+    /// Entry counts, approximate memory, lookup counters, and ages per namespace
+    pub fn cache_stats(&self) -> BTreeMap<&'static str, Value> {
+        stats(&self.caches(), &self.cache_metrics)
+    }
+
+    /// Drops entries too old to be served even stale. One pass of the
+    /// `cache_sweep` maintenance task.
+    pub fn sweep_caches(&self) {
+        let swept = sweep(&self.caches(), self.cache_max_staleness);
+        if swept > 0 {
+            log::info!("Swept {swept} unservable cache entries");
+        }
+    }
+
+    /// Evicts every entry matched by `targets`, returning counts per namespace
+    pub fn invalidate(&self, targets: &[Target]) -> Result<BTreeMap<&'static str, usize>, String> {
+        invalidate(&self.caches(), targets)
+    }
+}
@@ -0,0 +1,564 @@
+//! `chai-api --check`: a startup self-test for deploy pipelines to run before
+//! traffic moves to a new instance. It validates the configuration, connects
+//! to the database, compares the active schema with the tables and columns the
+//! queries read, and sends one canary request to every business endpoint
+//! in-process, checking that each body reads as the `chai-types` type clients
+//! decode it with. Each step is reported on stdout; any failure makes the
+//! process exit non-zero.
+
+use actix_web::http::{Method, StatusCode};
+use actix_web::{test, App};
+use chai_types::models::{
+    CrossEcosystem, DependencyConfusion, DependencyTree, ExportEntry, LeaderboardEntry,
+    MaintainerConcentration, MaintainerProjects, NewEntrants, OrgLeaderboard, OrgPortfolio,
+    Percentiles, Project, ProjectDependents, ProjectFunding, ProjectMetrics, RankContributors,
+    RankTrend, SearchResults, SimilarNames, SourceHosts, StaleProjects, Status, TableList,
+    TablePage, TopDegree,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::env;
+use std::time::Instant;
+use url::{form_urlencoded, Url};
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::config::{ignored_settings, Config};
+use crate::features::Feature;
+use crate::{build_state, routes};
+
+/// The tables queries read, with the columns they rely on
+const EXPECTED_SCHEMA: &[(&str, &[&str])] = &[
+    ("sources", &["id", "type"]),
+    ("package_managers", &["id", "source_id"]),
+    ("packages", &["id", "package_manager_id", "name", "readme"]),
+    ("url_types", &["id", "name"]),
+    ("urls", &["id", "url", "url_type_id"]),
+    ("package_urls", &["package_id", "url_id"]),
+    (
+        "canons",
+        &["id", "url_id", "name", "created_at", "updated_at"],
+    ),
+    ("canon_slugs", &["canon_id", "slug"]),
+    ("canon_redirects", &["old_canon_id", "canon_id"]),
+    ("versions", &["package_id", "version", "published_at"]),
+    ("canon_packages", &["canon_id", "package_id"]),
+    (
+        "tea_rank_runs",
+        &["run", "split_ratio", "created_at", "quarantined_at"],
+    ),
+    (
+        "tea_ranks",
+        &["tea_rank_run", "canon_id", "rank", "created_at"],
+    ),
+    ("users", &["id", "username", "source_id"]),
+    ("user_packages", &["user_id", "package_id"]),
+    (
+        "legacy_dependencies",
+        &["package_id", "dependency_id", "created_at"],
+    ),
+    (
+        "maintainer_verifications",
+        &[
+            "canon_id",
+            "maintainer",
+            "token",
+            "verified_at",
+            "created_at",
+        ],
+    ),
+    (
+        "leaderboard_overrides",
+        &[
+            "canon_id",
+            "pinned_rank",
+            "reason",
+            "expires_at",
+            "created_at",
+        ],
+    ),
+    (
+        "collections",
+        &[
+            "id",
+            "owner",
+            "name",
+            "canon_ids",
+            "created_at",
+            "updated_at",
+            "webhook_url",
+            "webhook_secret",
+            "webhook_previous_secret",
+            "webhook_secret_rotated_at",
+        ],
+    ),
+];
+
+/// Whether a successful response's body matches its payload type
+type Contract = fn(&[u8]) -> Result<(), String>;
+
+fn reads_as<T: DeserializeOwned>(body: &[u8]) -> Result<(), String> {
+    serde_json::from_slice::<T>(body)
+        .map(drop)
+        .map_err(|e| format!("body doesn't match the client type: {e}"))
+}
+
+/// For plain-text responses
+fn any_body(_: &[u8]) -> Result<(), String> {
+    Ok(())
+}
+
+/// Stands in for a maintainer when no verification exists yet
+const CANARY_MAINTAINER: &str = "chai-check";
+
+/// Rows the canaries look up, taken from the latest ranking run
+struct Sample {
+    canon_id: Uuid,
+    name: String,
+    slug: Option<String>,
+    /// One of the canon's packages, as `(package manager, name)`
+    package: Option<(String, String)>,
+    maintainer: String,
+}
+
+/// Outcome of each step, printed as it completes
+#[derive(Default)]
+struct Report {
+    failures: usize,
+    total: usize,
+}
+
+impl Report {
+    fn record(&mut self, name: &str, started: Instant, result: Result<String, String>) {
+        self.total += 1;
+        let elapsed = started.elapsed().as_millis();
+        let (outcome, detail) = match result {
+            Ok(detail) => ("ok", detail),
+            Err(detail) => {
+                self.failures += 1;
+                ("FAIL", detail)
+            }
+        };
+        println!("{outcome:<4}  {elapsed:>5}ms  {name}: {detail}");
+    }
+}
+
+/// Runs every check against `config`, returning whether all of them passed
+pub async fn run(config: &Config) -> bool {
+    let mut report = Report::default();
+
+    // URLs first: building pools from a malformed one panics
+    let started = Instant::now();
+    if let Err(e) = check_database_urls(config) {
+        report.record("config", started, Err(e));
+        return finish(report);
+    }
+    let state = build_state(config).await;
+    // Building the state read every setting, so all ignored ones are counted
+    report.record("config", started, check_settings());
+    let state = match state {
+        Ok(state) => state,
+        Err(e) => {
+            report.record("database", started, Err(e));
+            return finish(report);
+        }
+    };
+    let regions = config.database_replicas.len() + 1;
+    report.record("database", started, Ok(format!("{regions} region(s)")));
+    if report.failures > 0 {
+        return finish(report);
+    }
+
+    let started = Instant::now();
+    let schema_ok = check_schema(&state);
+    report.record("schema", started, schema_ok.clone());
+    if schema_ok.is_err() {
+        return finish(report);
+    }
+
+    let started = Instant::now();
+    let sample = match load_sample(&state).await {
+        Ok(sample) => {
+            let detail = match &sample {
+                Some(sample) => format!("canon {}", sample.canon_id),
+                None => "no ranked projects, expecting 404s".to_string(),
+            };
+            report.record("sample", started, Ok(detail));
+            sample
+        }
+        Err(e) => {
+            report.record("sample", started, Err(e));
+            return finish(report);
+        }
+    };
+
+    let features = state.features.clone();
+    let app = test::init_service(
+        App::new()
+            .app_data(state.clone())
+            .configure(|cfg| routes(cfg, &features)),
+    )
+    .await;
+    for (method, uri, body, contract) in canaries(&state, sample.as_ref()) {
+        let name = format!("{method} {uri}");
+        let mut req = test::TestRequest::default()
+            .method(method)
+            .uri(&uri)
+            .insert_header(("content-type", "application/json"));
+        if let Some(body) = body {
+            req = req.set_json(body);
+        }
+        let started = Instant::now();
+        let response = test::call_service(&app, req.to_request()).await;
+        let status = response.status();
+        let body = test::read_body(response).await;
+        // Without sample rows, lookups legitimately find nothing
+        let result = if status.is_success() {
+            contract(&body)
+                .map(|()| status.to_string())
+                .map_err(|e| format!("{status}, but the {e}"))
+        } else if status == StatusCode::NOT_FOUND && sample.is_none() {
+            Ok(status.to_string())
+        } else {
+            Err(status.to_string())
+        };
+        report.record(&name, started, result);
+    }
+
+    finish(report)
+}
+
+fn finish(report: Report) -> bool {
+    let passed = report.failures == 0;
+    println!(
+        "{}: {} of {} checks passed",
+        if passed { "PASSED" } else { "FAILED" },
+        report.total - report.failures,
+        report.total
+    );
+    passed
+}
+
+/// Whether any setting was ignored as invalid or unknown
+fn check_settings() -> Result<String, String> {
+    match ignored_settings() {
+        0 => Ok("valid".to_string()),
+        ignored => Err(format!(
+            "{ignored} setting(s) ignored, see the warnings above"
+        )),
+    }
+}
+
+/// Whether the primary and every replica URL parse
+fn check_database_urls(config: &Config) -> Result<(), String> {
+    let primary = env::var("DATABASE_URL").map_err(|_| "DATABASE_URL is not set".to_string())?;
+    Url::parse(&primary).map_err(|e| format!("DATABASE_URL is invalid: {e}"))?;
+    for (region, url) in &config.database_replicas {
+        Url::parse(url).map_err(|e| format!("DATABASE_URLS entry {region} is invalid: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Whether the active schema has every table and column in [`EXPECTED_SCHEMA`]
+fn check_schema(state: &AppState) -> Result<String, String> {
+    let schema = state.schemas.schema();
+    let catalog = state.tables.borrow().clone();
+
+    let mut missing = Vec::new();
+    for (table, columns) in EXPECTED_SCHEMA {
+        let Some(present) = catalog.columns.get(*table) else {
+            missing.push(table.to_string());
+            continue;
+        };
+        missing.extend(
+            columns
+                .iter()
+                .filter(|column| !present.iter().any(|c| c == *column))
+                .map(|column| format!("{table}.{column}")),
+        );
+    }
+    if missing.is_empty() {
+        Ok(format!("{schema}: {} tables", catalog.tables.len()))
+    } else {
+        Err(format!("{schema} is missing {}", missing.join(", ")))
+    }
+}
+
+/// A ranked canon from the latest run, or `None` when nothing is ranked
+async fn load_sample(state: &AppState) -> Result<Option<Sample>, String> {
+    let Some(run) = *state.latest_run.borrow() else {
+        return Ok(None);
+    };
+    let client = state
+        .pool
+        .get()
+        .await
+        .map_err(|e| format!("Failed to get database connection: {e}"))?;
+    let row = client
+        .query_opt(
+            r#"
+            SELECT c.id, c.name,
+                (SELECT slug FROM canon_slugs s WHERE s.canon_id = c.id LIMIT 1) AS slug,
+                pkg.package_manager,
+                pkg.package,
+                (SELECT maintainer FROM maintainer_verifications LIMIT 1) AS maintainer
+            FROM tea_ranks tr
+            JOIN canons c ON c.id = tr.canon_id
+            LEFT JOIN LATERAL (
+                SELECT s.type AS package_manager, p.name AS package
+                FROM canon_packages cp
+                JOIN packages p ON p.id = cp.package_id
+                JOIN package_managers pm ON pm.id = p.package_manager_id
+                JOIN sources s ON s.id = pm.source_id
+                WHERE cp.canon_id = c.id
+                LIMIT 1
+            ) pkg ON TRUE
+            WHERE tr.tea_rank_run = $1
+            LIMIT 1"#,
+            &[&run],
+        )
+        .await
+        .map_err(|e| format!("Failed to pick a sample project: {e}"))?;
+    Ok(row.map(|row| Sample {
+        canon_id: row.get("id"),
+        name: row.get("name"),
+        slug: row.get("slug"),
+        package: row
+            .get::<_, Option<String>>("package_manager")
+            .zip(row.get::<_, Option<String>>("package")),
+        maintainer: row
+            .get::<_, Option<String>>("maintainer")
+            .unwrap_or_else(|| CANARY_MAINTAINER.to_string()),
+    }))
+}
+
+/// One request per business endpoint, as `(method, uri, JSON body, contract)`
+fn canaries(
+    state: &AppState,
+    sample: Option<&Sample>,
+) -> Vec<(Method, String, Option<Value>, Contract)> {
+    let id = sample.map_or(Uuid::nil(), |sample| sample.canon_id);
+    let name = sample.map_or(CANARY_MAINTAINER, |sample| sample.name.as_str());
+    let maintainer = sample.map_or(CANARY_MAINTAINER, |sample| sample.maintainer.as_str());
+    let run = state.latest_run.borrow().unwrap_or(0);
+
+    let mut canaries: Vec<(Method, String, Option<Value>, Contract)> = vec![
+        (Method::GET, "/heartbeat".to_string(), None, any_body),
+        (Method::GET, "/status".to_string(), None, reads_as::<Status>),
+        (
+            Method::GET,
+            "/tables".to_string(),
+            None,
+            reads_as::<TableList>,
+        ),
+        (
+            Method::GET,
+            "/tables/canons?limit=1".to_string(),
+            None,
+            reads_as::<TablePage>,
+        ),
+        (
+            Method::GET,
+            format!("/tables/canons/{id}"),
+            None,
+            reads_as::<Value>,
+        ),
+        (
+            Method::POST,
+            "/leaderboard".to_string(),
+            Some(json!({ "limit": 10 })),
+            reads_as::<Vec<LeaderboardEntry>>,
+        ),
+        (
+            Method::POST,
+            "/leaderboard".to_string(),
+            Some(json!({ "projectIds": [id], "limit": 1 })),
+            reads_as::<Vec<LeaderboardEntry>>,
+        ),
+        (
+            Method::POST,
+            "/leaderboard".to_string(),
+            Some(json!({ "limit": 10, "sourceHost": "github.com" })),
+            reads_as::<Vec<LeaderboardEntry>>,
+        ),
+        (
+            Method::GET,
+            "/leaderboard/new".to_string(),
+            None,
+            reads_as::<NewEntrants>,
+        ),
+        (
+            Method::GET,
+            "/leaderboard/orgs?limit=10".to_string(),
+            None,
+            reads_as::<OrgLeaderboard>,
+        ),
+        (
+            Method::GET,
+            "/ranks/percentiles".to_string(),
+            None,
+            reads_as::<Percentiles>,
+        ),
+        (
+            Method::GET,
+            "/stats/cross-ecosystem?limit=10".to_string(),
+            None,
+            reads_as::<CrossEcosystem>,
+        ),
+        (
+            Method::GET,
+            "/stats/source-hosts".to_string(),
+            None,
+            reads_as::<SourceHosts>,
+        ),
+        (
+            Method::GET,
+            "/reports/stale?limit=10".to_string(),
+            None,
+            reads_as::<StaleProjects>,
+        ),
+        (
+            Method::GET,
+            "/org/github.com/rust-lang?limit=10".to_string(),
+            None,
+            reads_as::<OrgPortfolio>,
+        ),
+        (
+            Method::GET,
+            "/stats/top-dependents?limit=10".to_string(),
+            None,
+            reads_as::<TopDegree>,
+        ),
+        (
+            Method::GET,
+            "/stats/top-dependencies?limit=10".to_string(),
+            None,
+            reads_as::<TopDegree>,
+        ),
+        (
+            Method::GET,
+            format!("/project/{id}"),
+            None,
+            reads_as::<Project>,
+        ),
+        (
+            Method::GET,
+            format!("/project/{id}/trend"),
+            None,
+            reads_as::<RankTrend>,
+        ),
+        (
+            Method::GET,
+            format!("/project/{id}/metrics"),
+            None,
+            reads_as::<ProjectMetrics>,
+        ),
+        (
+            Method::GET,
+            format!("/project/{id}/rank-contributors"),
+            None,
+            reads_as::<RankContributors>,
+        ),
+        (
+            Method::POST,
+            "/project/batch".to_string(),
+            Some(json!({ "projectIds": [id] })),
+            reads_as::<Vec<Project>>,
+        ),
+        (
+            Method::POST,
+            "/project/exists".to_string(),
+            Some(json!({ "projectIds": [id] })),
+            reads_as::<BTreeMap<Uuid, bool>>,
+        ),
+        (
+            Method::GET,
+            path(&["project", "search", name]),
+            None,
+            reads_as::<SearchResults>,
+        ),
+        (
+            Method::GET,
+            format!("/project/{id}/similar-names"),
+            None,
+            reads_as::<SimilarNames>,
+        ),
+        (
+            Method::GET,
+            format!("/project/{id}/maintainer-concentration"),
+            None,
+            reads_as::<MaintainerConcentration>,
+        ),
+        (
+            Method::GET,
+            format!("/project/{id}/dependencies?depth=2"),
+            None,
+            reads_as::<DependencyTree>,
+        ),
+        (
+            Method::GET,
+            format!("/project/{id}/dependents?limit=5"),
+            None,
+            reads_as::<ProjectDependents>,
+        ),
+        (
+            Method::GET,
+            format!(
+                "/check-name?name={}",
+                form_urlencoded::byte_serialize(name.as_bytes()).collect::<String>()
+            ),
+            None,
+            reads_as::<SimilarNames>,
+        ),
+        (
+            Method::GET,
+            path(&["maintainer", maintainer, "projects"]),
+            None,
+            reads_as::<MaintainerProjects>,
+        ),
+        (
+            Method::GET,
+            format!("/export/leaderboard/{run}"),
+            None,
+            reads_as::<Vec<ExportEntry>>,
+        ),
+    ];
+    if let Some(slug) = sample.and_then(|sample| sample.slug.as_deref()) {
+        canaries.push((
+            Method::GET,
+            path(&["project", "slug", slug]),
+            None,
+            reads_as::<Project>,
+        ));
+    }
+    // Without a known package manager the check is refused
+    if let Some((package_manager, package)) = sample.and_then(|sample| sample.package.as_ref()) {
+        canaries.push((
+            Method::POST,
+            "/analyze/dependency-confusion".to_string(),
+            Some(json!({
+                "packages": [{ "packageManager": package_manager, "name": package }]
+            })),
+            reads_as::<DependencyConfusion>,
+        ));
+    }
+    if state.features.is_enabled(Feature::Funding) {
+        canaries.push((
+            Method::GET,
+            format!("/project/{id}/funding"),
+            None,
+            reads_as::<ProjectFunding>,
+        ));
+    }
+    canaries
+}
+
+/// A path from `segments`, percent-encoding each one
+fn path(segments: &[&str]) -> String {
+    let mut url = Url::parse("http://check/").expect("valid base URL");
+    url.path_segments_mut()
+        .expect("base URL has a path")
+        .extend(segments);
+    url.path().to_string()
+}
@@ -0,0 +1,150 @@
+//! The `chai-api` command line. Without a subcommand the binary serves the API,
+//! as it always has; the others reuse the same state and queries for one-off
+//! jobs that don't need an HTTP server.
+
+use chai_core::cache::save_snapshot;
+use clap::{Args, Parser, Subcommand};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use crate::build_state;
+use crate::config::Config;
+use crate::export::prefill_leaderboard;
+use crate::handlers::warm_leaderboard;
+use crate::table_export::{dump_table, DumpFormat};
+
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Deprecated alias of `chai-api check`
+    #[arg(long, hide = true)]
+    check: bool,
+}
+
+impl Cli {
+    /// The subcommand to run, `serve` when none was given
+    pub fn command(self) -> Command {
+        match self.command {
+            Some(command) => command,
+            None if self.check => {
+                eprintln!("--check is deprecated; use `chai-api check`");
+                Command::Check
+            }
+            None => Command::Serve,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Serve the API (the default)
+    Serve,
+    /// Test the configuration, the database and every business endpoint, then exit
+    Check,
+    /// Prefill the export cache and the cache snapshot for the latest run
+    WarmCache,
+    /// Write every row of a table to a file or stdout
+    Export(ExportArgs),
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// Table to export, as `/tables` lists them
+    #[arg(long)]
+    table: String,
+    #[arg(long, value_enum, default_value = "json")]
+    format: DumpFormat,
+    /// File to write; stdout when omitted
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+    /// Include soft-deleted rows
+    #[arg(long)]
+    include_deleted: bool,
+}
+
+/// `chai-api warm-cache`: generates the latest run's leaderboard exports and,
+/// with `CACHE_SNAPSHOT_PATH` set, adds its top projects to the snapshot, so
+/// the next server start begins warm
+pub async fn warm_cache(config: &Config) -> Result<(), String> {
+    let state = build_state(config).await?;
+    let latest = *state.latest_run.borrow();
+    let Some(run) = latest else {
+        return Err("No ranking run found; nothing to warm".to_string());
+    };
+
+    let client = state
+        .pool
+        .get()
+        .await
+        .map_err(|e| format!("Failed to get database connection: {e}"))?;
+    let generated = prefill_leaderboard(&state, &client, run).await?;
+    if generated.is_empty() {
+        println!("Leaderboard exports of run {run} were already cached");
+    } else {
+        let formats: Vec<&str> = generated.iter().map(|format| format.extension()).collect();
+        println!(
+            "Cached leaderboard exports of run {run} as {}",
+            formats.join(", ")
+        );
+    }
+
+    let Some(path) = &config.cache_snapshot_path else {
+        println!("CACHE_SNAPSHOT_PATH is not set, so no cache snapshot was written");
+        return Ok(());
+    };
+    state.restore_caches(path);
+    let warmed = warm_leaderboard(&state).await;
+    let saved = save_snapshot(path, &state.caches())
+        .map_err(|e| format!("Failed to save cache snapshot {}: {e}", path.display()))?;
+    println!(
+        "Warmed {warmed} leaderboard projects; saved {saved} cache entries to {}",
+        path.display()
+    );
+    Ok(())
+}
+
+/// `chai-api export`: dumps a table without going through the HTTP server
+pub async fn export(config: &Config, args: &ExportArgs) -> Result<(), String> {
+    let state = build_state(config).await?;
+    // Written beside the target and renamed once complete, so a failed export
+    // never leaves a truncated file in its place
+    let partial = args
+        .output
+        .as_ref()
+        .map(|path| path.with_extension(format!("tmp-{}", std::process::id())));
+    let out: Box<dyn Write + Send> = match &partial {
+        Some(partial) => {
+            Box::new(BufWriter::new(File::create(partial).map_err(|e| {
+                format!("Failed to create {}: {e}", partial.display())
+            })?))
+        }
+        None => Box::new(BufWriter::new(io::stdout())),
+    };
+
+    let written = dump_table(&state, &args.table, args.include_deleted, args.format, out).await;
+    match (written, &partial, &args.output) {
+        (Ok(rows), Some(partial), Some(output)) => {
+            std::fs::rename(partial, output)
+                .map_err(|e| format!("Failed to write {}: {e}", output.display()))?;
+            eprintln!(
+                "Exported {rows} rows of {} to {}",
+                args.table,
+                output.display()
+            );
+            Ok(())
+        }
+        (Ok(rows), ..) => {
+            eprintln!("Exported {rows} rows of {}", args.table);
+            Ok(())
+        }
+        (Err(e), partial, _) => {
+            if let Some(partial) = partial {
+                let _ = std::fs::remove_file(partial);
+            }
+            Err(e)
+        }
+    }
+}
@@ -0,0 +1,890 @@
+//! Named collections of projects (watchlists) kept per API key, so callers can
+//! ask for a collection's leaderboard or rank changes without resending its
+//! ids every time. A collection with a webhook is sent its changes after each
+//! run, signed with the webhook's secret.
+
+use actix_web::http::header::LOCATION;
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse, Responder};
+use chai_core::db::timed;
+use chai_core::leaderboard::{leaderboard_projects, previous_run, LEADERBOARD_ENTRY_FIELDS};
+use chai_core::projects::existing_canons;
+use chai_core::ranks::{rank_changes, run_ranks_of};
+use chai_core::rows::with_precision;
+use chai_types::fields::FieldsParams;
+use chai_types::format::{RowFormat, Timestamp};
+use chai_types::models::{
+    Collection, CollectionChange, CollectionChanges, CollectionWebhook, LeaderboardEntry,
+    RunComparison,
+};
+use chai_types::requests::{
+    CollectionChangesParams, CollectionLeaderboardParams, CollectionRequest,
+    CollectionWebhookRequest,
+};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use tokio_postgres::error::SqlState;
+use tokio_postgres::{Client, Row};
+use url::Url;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::auth::key_name;
+use crate::db::read_write;
+use crate::extractors::PathUuid;
+use crate::http_client::HttpClient;
+use crate::problem::{database_error, no_connection, not_found, problem, problem_with};
+use crate::regions::Pools;
+use crate::run_alerts::post_webhook;
+
+/// Most projects one collection holds, as many as one leaderboard request takes
+const MAX_COLLECTION_PROJECTS: usize = 1000;
+/// Longest collection name
+const MAX_NAME_LENGTH: usize = 100;
+
+const COLLECTION_COLUMNS: &str = "id, name, canon_ids, created_at, updated_at, webhook_url";
+/// How long the secret a rotation replaced keeps signing digests, so receivers
+/// can switch over without dropping any
+const PREVIOUS_SECRET_GRACE: &str = "interval '24 hours'";
+
+/// Collections are kept per API key, so callers without one get a 401
+fn unauthorized() -> HttpResponse {
+    problem(StatusCode::UNAUTHORIZED, "Collections require an API key")
+}
+
+fn collection(row: &Row, format: RowFormat) -> Collection {
+    Collection {
+        id: row.get("id"),
+        name: row.get("name"),
+        project_ids: row.get("canon_ids"),
+        created_at: Timestamp(row.get("created_at"), format),
+        updated_at: Timestamp(row.get("updated_at"), format),
+        webhook_url: row.get("webhook_url"),
+    }
+}
+
+fn webhook(id: Uuid, row: &Row, format: RowFormat) -> CollectionWebhook {
+    CollectionWebhook {
+        collection_id: id,
+        url: row.get("webhook_url"),
+        secret: row.get("webhook_secret"),
+        previous_secret_expires_at: Timestamp(row.get("previous_secret_expires_at"), format),
+    }
+}
+
+/// `webhook_url`, `webhook_secret`, and `previous_secret_expires_at`
+fn webhook_columns() -> String {
+    format!(
+        "webhook_url, webhook_secret, \
+        CASE WHEN webhook_secret_rotated_at > now() - {PREVIOUS_SECRET_GRACE} \
+            THEN webhook_secret_rotated_at + {PREVIOUS_SECRET_GRACE} \
+        END AS previous_secret_expires_at"
+    )
+}
+
+/// A new webhook signing secret, with 244 random bits
+fn new_secret() -> String {
+    format!(
+        "whsec_{}{}",
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    )
+}
+
+fn collection_not_found(id: Uuid) -> HttpResponse {
+    not_found(format!("No collection found with id '{id}'"))
+}
+
+/// Checks `body`, returning its trimmed name and its ids without repeats
+fn validate(body: &CollectionRequest) -> Result<(String, Vec<Uuid>), String> {
+    let name = body.name.trim();
+    if name.is_empty() {
+        return Err("A collection needs a name".to_string());
+    }
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err(format!(
+            "Collection names are at most {MAX_NAME_LENGTH} characters"
+        ));
+    }
+    let mut seen = HashSet::new();
+    let ids: Vec<Uuid> = body
+        .project_ids
+        .iter()
+        .copied()
+        .filter(|id| seen.insert(*id))
+        .collect();
+    if ids.len() > MAX_COLLECTION_PROJECTS {
+        return Err(format!(
+            "A collection holds at most {MAX_COLLECTION_PROJECTS} projects"
+        ));
+    }
+    Ok((name.to_string(), ids))
+}
+
+/// Validates `body` and checks its ids are live canons, answering with the
+/// 400 or database error when not
+async fn checked_request(
+    client: &Client,
+    body: &CollectionRequest,
+    data: &AppState,
+) -> Result<(String, Vec<Uuid>), HttpResponse> {
+    let (name, ids) = validate(body).map_err(|error| problem(StatusCode::BAD_REQUEST, error))?;
+    let existing = existing_canons(client, &ids, &data.not_deleted("canons", "c"))
+        .await
+        .map_err(|e| {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        })?;
+    let unknown: Vec<Uuid> = ids
+        .iter()
+        .filter(|id| !existing.contains(id))
+        .copied()
+        .collect();
+    if !unknown.is_empty() {
+        return Err(problem_with(
+            StatusCode::BAD_REQUEST,
+            "Unknown project IDs",
+            json!({ "unknown": unknown }),
+        ));
+    }
+    Ok((name, ids))
+}
+
+fn name_taken(name: &str) -> HttpResponse {
+    problem(
+        StatusCode::CONFLICT,
+        format!("A collection named '{name}' already exists"),
+    )
+}
+
+/// The caller's collection `id`, or the 404 or database error to answer with
+async fn owned_collection(
+    client: &Client,
+    id: Uuid,
+    owner: &str,
+    format: RowFormat,
+) -> Result<Collection, HttpResponse> {
+    let query =
+        format!("SELECT {COLLECTION_COLUMNS} FROM collections WHERE id = $1 AND owner = $2");
+    match timed("collection", client.query_opt(&query, &[&id, &owner])).await {
+        Ok(Some(row)) => Ok(collection(&row, format)),
+        Ok(None) => Err(collection_not_found(id)),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            Err(database_error(&e))
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "collections",
+    responses(
+        (status = 201, description = "Collection created", body = Collection),
+        (status = 401, description = "No API key"),
+        (status = 400, description = "Invalid collection"),
+    )
+)]
+#[post("/collections")]
+pub async fn create_collection(
+    req: HttpRequest,
+    body: web::Json<CollectionRequest>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let Some(owner) = key_name(&req) else {
+        return unauthorized();
+    };
+    let mut client = match data.pool.primary().get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+    let (name, ids) = match checked_request(&client, &body, &data).await {
+        Ok(checked) => checked,
+        Err(response) => return response,
+    };
+
+    let insert_query = format!(
+        "INSERT INTO collections (owner, name, canon_ids) VALUES ($1, $2, $3) \
+        RETURNING {COLLECTION_COLUMNS}"
+    );
+    let insert = async {
+        let transaction = read_write(&mut client).await?;
+        let row = transaction
+            .query_one(&insert_query, &[&owner, &name, &ids])
+            .await?;
+        transaction.commit().await?;
+        Ok::<_, tokio_postgres::Error>(row)
+    };
+    match timed("create_collection", insert).await {
+        Ok(row) => {
+            let collection = collection(&row, data.row_format);
+            HttpResponse::Created()
+                .insert_header((LOCATION, format!("/collections/{}", collection.id)))
+                .json(collection)
+        }
+        Err(e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => name_taken(&name),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+/// The caller's collections, most recently changed first
+#[utoipa::path(
+    tag = "collections",
+    responses(
+        (status = 200, description = "OK", body = Vec<Collection>),
+        (status = 401, description = "No API key"),
+    )
+)]
+#[get("/collections")]
+pub async fn list_collections(req: HttpRequest, data: web::Data<AppState>) -> impl Responder {
+    let Some(owner) = key_name(&req) else {
+        return unauthorized();
+    };
+    let client = match data.pool.primary().get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+    let query = format!(
+        "SELECT {COLLECTION_COLUMNS} FROM collections WHERE owner = $1 \
+        ORDER BY updated_at DESC, name"
+    );
+    match timed("collections", client.query(&query, &[&owner])).await {
+        Ok(rows) => HttpResponse::Ok().json(
+            rows.iter()
+                .map(|row| collection(row, data.row_format))
+                .collect::<Vec<_>>(),
+        ),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "collections",
+    params(("id" = Uuid, Path, description = "Collection id")),
+    responses(
+        (status = 200, description = "OK", body = Collection),
+        (status = 401, description = "No API key"),
+        (status = 404, description = "No such collection"),
+    )
+)]
+#[get("/collections/{id}")]
+pub async fn get_collection(
+    req: HttpRequest,
+    id: PathUuid,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let PathUuid(id) = id;
+    let Some(owner) = key_name(&req) else {
+        return unauthorized();
+    };
+    let client = match data.pool.primary().get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+    match owned_collection(&client, id, &owner, data.row_format).await {
+        Ok(collection) => HttpResponse::Ok().json(collection),
+        Err(response) => response,
+    }
+}
+
+/// Replaces a collection's name and projects
+#[utoipa::path(
+    tag = "collections",
+    params(("id" = Uuid, Path, description = "Collection id")),
+    responses(
+        (status = 200, description = "OK", body = Collection),
+        (status = 401, description = "No API key"),
+        (status = 400, description = "Invalid collection"),
+        (status = 404, description = "No such collection"),
+    )
+)]
+#[put("/collections/{id}")]
+pub async fn put_collection(
+    req: HttpRequest,
+    id: PathUuid,
+    body: web::Json<CollectionRequest>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let PathUuid(id) = id;
+    let Some(owner) = key_name(&req) else {
+        return unauthorized();
+    };
+    let mut client = match data.pool.primary().get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+    let (name, ids) = match checked_request(&client, &body, &data).await {
+        Ok(checked) => checked,
+        Err(response) => return response,
+    };
+
+    let update_query = format!(
+        "UPDATE collections SET name = $3, canon_ids = $4, updated_at = now() \
+        WHERE id = $1 AND owner = $2 RETURNING {COLLECTION_COLUMNS}"
+    );
+    let update = async {
+        let transaction = read_write(&mut client).await?;
+        let row = transaction
+            .query_opt(&update_query, &[&id, &owner, &name, &ids])
+            .await?;
+        transaction.commit().await?;
+        Ok::<_, tokio_postgres::Error>(row)
+    };
+    match timed("put_collection", update).await {
+        Ok(Some(row)) => HttpResponse::Ok().json(collection(&row, data.row_format)),
+        Ok(None) => collection_not_found(id),
+        Err(e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => name_taken(&name),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "collections",
+    params(("id" = Uuid, Path, description = "Collection id")),
+    responses(
+        (status = 204, description = "Collection deleted"),
+        (status = 401, description = "No API key"),
+        (status = 404, description = "No such collection"),
+    )
+)]
+#[delete("/collections/{id}")]
+pub async fn delete_collection(
+    req: HttpRequest,
+    id: PathUuid,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let PathUuid(id) = id;
+    let Some(owner) = key_name(&req) else {
+        return unauthorized();
+    };
+    let mut client = match data.pool.primary().get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+    let remove = async {
+        let transaction = read_write(&mut client).await?;
+        let deleted = transaction
+            .execute(
+                "DELETE FROM collections WHERE id = $1 AND owner = $2",
+                &[&id, &owner],
+            )
+            .await?;
+        transaction.commit().await?;
+        Ok::<_, tokio_postgres::Error>(deleted)
+    };
+    match timed("delete_collection", remove).await {
+        Ok(0) => collection_not_found(id),
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+/// The collection's projects as `POST /leaderboard` would rank them, from the
+/// latest run
+#[utoipa::path(
+    tag = "collections",
+    params(
+        ("id" = Uuid, Path, description = "Collection id"),
+        CollectionLeaderboardParams,
+        FieldsParams,
+    ),
+    responses(
+        (status = 200, description = "OK", body = Vec<LeaderboardEntry>),
+        (status = 401, description = "No API key"),
+        (status = 400, description = "Invalid fields"),
+        (status = 404, description = "No such collection"),
+    )
+)]
+#[get("/collections/{id}/leaderboard")]
+pub async fn get_collection_leaderboard(
+    req: HttpRequest,
+    id: PathUuid,
+    params: web::Query<CollectionLeaderboardParams>,
+    fields: web::Query<FieldsParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let PathUuid(id) = id;
+    let Some(owner) = key_name(&req) else {
+        return unauthorized();
+    };
+    let fields = match fields.parse(&LEADERBOARD_ENTRY_FIELDS, data.opt_in_fields()) {
+        Ok(fields) => fields,
+        Err(error) => return problem(StatusCode::BAD_REQUEST, error),
+    };
+    let limit = params
+        .limit
+        .unwrap_or(MAX_COLLECTION_PROJECTS as i64)
+        .clamp(1, MAX_COLLECTION_PROJECTS as i64);
+    let client = match data.pool.primary().get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+    let collection = match owned_collection(&client, id, &owner, data.row_format).await {
+        Ok(collection) => collection,
+        Err(response) => return response,
+    };
+
+    let live = data.not_deleted("canons", "c");
+    match leaderboard_projects(
+        &client,
+        &collection.project_ids,
+        limit,
+        None,
+        &live,
+        false,
+        data.row_format,
+    )
+    .await
+    {
+        Ok(mut projects) => {
+            if let Err(e) = data.package_managers.hydrate(&client, &mut projects).await {
+                log::error!("Database query error: {e}");
+                return database_error(&e);
+            }
+            let mut json = Value::Array(projects);
+            if let Some(fields) = &fields {
+                fields.select(&mut json);
+            }
+            let precision = data.rank_precision(params.precision);
+            HttpResponse::Ok().json(with_precision(json, precision))
+        }
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+/// How the collection's ranks moved between two runs: the same summary as
+/// `GET /ranks/compare`, plus each project's ranks
+#[utoipa::path(
+    tag = "collections",
+    params(
+        ("id" = Uuid, Path, description = "Collection id"),
+        CollectionChangesParams,
+    ),
+    responses(
+        (status = 200, description = "OK", body = CollectionChanges),
+        (status = 401, description = "No API key"),
+        (status = 400, description = "Invalid runs"),
+        (status = 404, description = "No such collection or run"),
+    )
+)]
+#[get("/collections/{id}/changes")]
+pub async fn get_collection_changes(
+    req: HttpRequest,
+    id: PathUuid,
+    params: web::Query<CollectionChangesParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let PathUuid(id) = id;
+    let Some(owner) = key_name(&req) else {
+        return unauthorized();
+    };
+    let client = match data.pool.primary().get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+    let collection = match owned_collection(&client, id, &owner, data.row_format).await {
+        Ok(collection) => collection,
+        Err(response) => return response,
+    };
+
+    let latest = *data.latest_run.borrow();
+    let Some(to) = params.to.or(latest) else {
+        return not_found("No ranking runs yet".to_string());
+    };
+    let from = match params.from {
+        Some(from) => from,
+        None => match previous_run(&client, to).await {
+            Ok(Some(Some(previous))) => previous,
+            Ok(Some(None)) => {
+                return problem(
+                    StatusCode::BAD_REQUEST,
+                    format!("Run {to} is the first run; pass from to compare it"),
+                )
+            }
+            Ok(None) => return not_found(format!("Run {to} not found")),
+            Err(e) => {
+                log::error!("Database query error: {e}");
+                return database_error(&e);
+            }
+        },
+    };
+
+    match collection_changes(&client, id, &collection.project_ids, from, to).await {
+        Ok(Ok(changes)) => HttpResponse::Ok().json(changes),
+        Ok(Err(run)) => not_found(format!("Run {run} not found")),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+/// How the ranks of collection `id`'s projects `ids` moved from run `from` to
+/// run `to`, or the run that doesn't exist
+async fn collection_changes(
+    client: &Client,
+    id: Uuid,
+    ids: &[Uuid],
+    from: i32,
+    to: i32,
+) -> Result<Result<CollectionChanges, i32>, tokio_postgres::Error> {
+    let mut ranks = Vec::with_capacity(2);
+    for run in [from, to] {
+        match run_ranks_of(client, run, ids).await? {
+            Some(run_ranks) => ranks.push(run_ranks),
+            None => return Ok(Err(run)),
+        }
+    }
+
+    let changes = rank_changes(&ranks[0], &ranks[1]);
+    let before: HashMap<Uuid, f64> = ranks[0].iter().copied().collect();
+    let after: HashMap<Uuid, f64> = ranks[1].iter().copied().collect();
+    let mut projects: Vec<CollectionChange> = ids
+        .iter()
+        .map(|id| CollectionChange {
+            project_id: *id,
+            from_rank: before.get(id).copied(),
+            to_rank: after.get(id).copied(),
+        })
+        .collect();
+    // Projects entering or leaving the ranking move by their whole rank
+    let moved = |change: &CollectionChange| {
+        (change.to_rank.unwrap_or(0.0) - change.from_rank.unwrap_or(0.0)).abs()
+    };
+    projects.sort_by(|a, b| moved(b).total_cmp(&moved(a)));
+
+    Ok(Ok(CollectionChanges {
+        collection_id: id,
+        summary: RunComparison {
+            from,
+            to,
+            rose: changes.rose,
+            fell: changes.fell,
+            unchanged: changes.unchanged,
+            new: changes.new,
+            dropped: changes.dropped,
+            mean_absolute_change: changes.mean_absolute_change,
+            kendall_tau: changes.kendall_tau,
+        },
+        projects,
+    }))
+}
+
+/// Posts a one-off challenge to `url` and expects it echoed back, so a webhook
+/// is only registered by whoever answers at its URL
+async fn challenge_webhook(http: &HttpClient, url: &Url, id: Uuid) -> Result<(), String> {
+    let challenge = Uuid::new_v4().simple().to_string();
+    let request = http.post(url.as_str()).json(&json!({
+        "type": "challenge",
+        "collectionId": id,
+        "challenge": challenge,
+    }));
+    let response = http
+        .send(request)
+        .await
+        .map_err(|e| e.to_string())?
+        .error_for_status()
+        .map_err(|e| e.to_string())?;
+    let answer: Value = response
+        .json()
+        .await
+        .map_err(|_| "the response wasn't JSON".to_string())?;
+    if answer.get("challenge").and_then(Value::as_str) == Some(challenge.as_str()) {
+        Ok(())
+    } else {
+        Err("the response didn't echo the challenge".to_string())
+    }
+}
+
+/// Subscribes a collection's webhook to its changes after each run, replacing
+/// any webhook it had. The URL must first echo a challenge; the response
+/// carries the secret digests are signed with, which a replaced webhook keeps.
+#[utoipa::path(
+    tag = "collections",
+    params(("id" = Uuid, Path, description = "Collection id")),
+    responses(
+        (status = 200, description = "OK", body = CollectionWebhook),
+        (status = 401, description = "No API key"),
+        (status = 400, description = "Invalid URL or failed challenge"),
+        (status = 404, description = "No such collection"),
+    )
+)]
+#[put("/collections/{id}/webhook")]
+pub async fn put_collection_webhook(
+    req: HttpRequest,
+    id: PathUuid,
+    body: web::Json<CollectionWebhookRequest>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let PathUuid(id) = id;
+    let Some(owner) = key_name(&req) else {
+        return unauthorized();
+    };
+    // Digests are posted from the server, so only to public HTTPS endpoints
+    let url = match Url::parse(body.url.trim()) {
+        Ok(url) if url.scheme() == "https" && url.host_str().is_some() => url,
+        _ => return problem(StatusCode::BAD_REQUEST, "url must be an absolute https URL"),
+    };
+    if let Err(reason) = data.http.egress().check_resolved(&url).await {
+        return problem(
+            StatusCode::BAD_REQUEST,
+            format!("url is not allowed: {reason}"),
+        );
+    }
+    let mut client = match data.pool.primary().get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+
+    // Only challenge endpoints on behalf of the collection's owner
+    let owned = timed(
+        "collection_owner",
+        client.query_opt(
+            "SELECT 1 FROM collections WHERE id = $1 AND owner = $2",
+            &[&id, &owner],
+        ),
+    )
+    .await;
+    match owned {
+        Ok(Some(_)) => {}
+        Ok(None) => return collection_not_found(id),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            return database_error(&e);
+        }
+    }
+    if let Err(e) = challenge_webhook(&data.http, &url, id).await {
+        return problem(
+            StatusCode::BAD_REQUEST,
+            format!("The webhook failed its challenge: {e}"),
+        );
+    }
+
+    let update_query = format!(
+        "UPDATE collections SET webhook_url = $3, \
+            webhook_secret = COALESCE(webhook_secret, $4), updated_at = now() \
+        WHERE id = $1 AND owner = $2 RETURNING {}",
+        webhook_columns()
+    );
+    let update = async {
+        let transaction = read_write(&mut client).await?;
+        let row = transaction
+            .query_opt(&update_query, &[&id, &owner, &url.as_str(), &new_secret()])
+            .await?;
+        transaction.commit().await?;
+        Ok::<_, tokio_postgres::Error>(row)
+    };
+    match timed("put_collection_webhook", update).await {
+        Ok(Some(row)) => HttpResponse::Ok().json(webhook(id, &row, data.row_format)),
+        Ok(None) => collection_not_found(id),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+/// Replaces a webhook's signing secret. Digests are signed with both secrets
+/// for the next 24 hours so receivers can switch over.
+#[utoipa::path(
+    tag = "collections",
+    params(("id" = Uuid, Path, description = "Collection id")),
+    responses(
+        (status = 200, description = "OK", body = CollectionWebhook),
+        (status = 401, description = "No API key"),
+        (status = 404, description = "No such webhook"),
+    )
+)]
+#[post("/collections/{id}/webhook/secret")]
+pub async fn rotate_collection_webhook_secret(
+    req: HttpRequest,
+    id: PathUuid,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let PathUuid(id) = id;
+    let Some(owner) = key_name(&req) else {
+        return unauthorized();
+    };
+    let mut client = match data.pool.primary().get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+
+    let rotate_query = format!(
+        "UPDATE collections SET webhook_previous_secret = webhook_secret, \
+            webhook_secret = $3, webhook_secret_rotated_at = now(), updated_at = now() \
+        WHERE id = $1 AND owner = $2 AND webhook_secret IS NOT NULL RETURNING {}",
+        webhook_columns()
+    );
+    let rotate = async {
+        let transaction = read_write(&mut client).await?;
+        let row = transaction
+            .query_opt(&rotate_query, &[&id, &owner, &new_secret()])
+            .await?;
+        transaction.commit().await?;
+        Ok::<_, tokio_postgres::Error>(row)
+    };
+    match timed("rotate_collection_webhook_secret", rotate).await {
+        Ok(Some(row)) => HttpResponse::Ok().json(webhook(id, &row, data.row_format)),
+        Ok(None) => not_found(format!("No collection with a webhook found with id '{id}'")),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "collections",
+    params(("id" = Uuid, Path, description = "Collection id")),
+    responses(
+        (status = 204, description = "Webhook removed"),
+        (status = 401, description = "No API key"),
+        (status = 404, description = "No such webhook"),
+    )
+)]
+#[delete("/collections/{id}/webhook")]
+pub async fn delete_collection_webhook(
+    req: HttpRequest,
+    id: PathUuid,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let PathUuid(id) = id;
+    let Some(owner) = key_name(&req) else {
+        return unauthorized();
+    };
+    let mut client = match data.pool.primary().get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+    let remove = async {
+        let transaction = read_write(&mut client).await?;
+        let updated = transaction
+            .execute(
+                "UPDATE collections SET webhook_url = NULL, webhook_secret = NULL, \
+                    webhook_previous_secret = NULL, webhook_secret_rotated_at = NULL, \
+                    updated_at = now() \
+                WHERE id = $1 AND owner = $2",
+                &[&id, &owner],
+            )
+            .await?;
+        transaction.commit().await?;
+        Ok::<_, tokio_postgres::Error>(updated)
+    };
+    match timed("delete_collection_webhook", remove).await {
+        Ok(0) => collection_not_found(id),
+        Ok(_) => HttpResponse::NoContent().finish(),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+/// Posts each collection with a webhook its changes from the run before `run`
+/// to `run`, signed with its secret and any secret still in its grace period.
+/// A first run has nothing to compare with and sends nothing. Webhooks
+/// registered before secrets existed have never answered a challenge and are
+/// skipped until registered again.
+pub async fn send_digests(pool: &Pools, http: &HttpClient, run: i32) {
+    let sent = async {
+        let collections = pool.primary().get().await.map_err(|e| e.to_string())?;
+        let webhooks_query = format!(
+            "SELECT id, canon_ids, webhook_url, webhook_secret, \
+                CASE WHEN webhook_secret_rotated_at > now() - {PREVIOUS_SECRET_GRACE} \
+                    THEN webhook_previous_secret \
+                END AS previous_secret \
+            FROM collections \
+            WHERE webhook_url IS NOT NULL AND webhook_secret IS NOT NULL"
+        );
+        let rows = timed(
+            "collection_webhooks",
+            collections.query(&webhooks_query, &[]),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        if rows.is_empty() {
+            return Ok(0);
+        }
+
+        let client = pool.get().await.map_err(|e| e.to_string())?;
+        let Some(Some(previous)) = previous_run(&client, run)
+            .await
+            .map_err(|e| e.to_string())?
+        else {
+            return Ok(0);
+        };
+        let mut delivered = 0;
+        for row in &rows {
+            let id: Uuid = row.get("id");
+            let ids: Vec<Uuid> = row.get("canon_ids");
+            let url: String = row.get("webhook_url");
+            let secret: String = row.get("webhook_secret");
+            let previous_secret: Option<String> = row.get("previous_secret");
+            let changes = match collection_changes(&client, id, &ids, previous, run).await {
+                Ok(Ok(changes)) => changes,
+                // The run was replaced while the digests were going out
+                Ok(Err(_)) => return Ok(delivered),
+                Err(e) => return Err(e.to_string()),
+            };
+            let secrets: Vec<&str> = [Some(secret.as_str()), previous_secret.as_deref()]
+                .into_iter()
+                .flatten()
+                .collect();
+            match post_webhook(http, &url, &changes, &secrets).await {
+                Ok(()) => delivered += 1,
+                Err(e) => log::warn!("Failed to post collection {id} digest for run {run}: {e}"),
+            }
+        }
+        Ok::<_, String>(delivered)
+    };
+    match sent.await {
+        Ok(0) => {}
+        Ok(delivered) => log::info!("Posted {delivered} collection digests for run {run}"),
+        Err(e) => log::error!("Failed to send collection digests for run {run}: {e}"),
+    }
+}
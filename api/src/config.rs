@@ -0,0 +1,352 @@
+use ipnet::IpNet;
+use std::env;
+use std::fmt;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// Runtime settings read from the environment at startup.
+pub struct Config {
+    pub rate_limit_requests: u64,
+    pub rate_limit_window: Duration,
+    /// `(name, secret)` pairs from `API_KEYS=name:secret,name:secret`
+    pub api_keys: Vec<(String, String)>,
+    pub admin_api_key: Option<String>,
+    /// `(name, tier)` pairs from `API_KEY_TIERS=name:tier,...`
+    pub api_key_tiers: Vec<(String, String)>,
+    /// Most leaderboard entries per tier, from `TIER_RESPONSE_LIMIT=tier:n,...`
+    pub tier_response_limits: Vec<(String, i64)>,
+    /// Most ids per batch per tier, from `TIER_BATCH_SIZE=tier:n,...`
+    pub tier_batch_sizes: Vec<(String, usize)>,
+    /// Whether each tier may export, from `TIER_EXPORTS=tier:bool,...`
+    pub tier_exports: Vec<(String, bool)>,
+    pub usage_flush_interval: Duration,
+    pub ip_allowlist: Vec<IpNet>,
+    pub ip_denylist: Vec<IpNet>,
+    pub admin_ip_allowlist: Vec<IpNet>,
+    /// Use `X-Forwarded-For`/`Forwarded` for the client address (only behind a trusted proxy)
+    pub trust_proxy_headers: bool,
+    /// `Cache-Control` sent when a handler doesn't set its own
+    pub default_cache_control: String,
+    pub verification_interval: Duration,
+    /// Experimental features to turn on, from `FEATURES=a,b`
+    pub features: Vec<String>,
+    /// Deprecated alias of `FEATURES=funding`
+    pub enable_funding: bool,
+    /// How long past its TTL a cached project may still be served while it refreshes
+    pub cache_max_staleness: Duration,
+    /// Where caches are saved on shutdown and restored from on startup
+    pub cache_snapshot_path: Option<PathBuf>,
+    /// Directory holding generated run-scoped exports
+    pub export_cache_dir: PathBuf,
+    /// Directory holding job records and results
+    pub job_dir: PathBuf,
+    /// Jobs that may run at once
+    pub job_workers: usize,
+    /// Unfinished jobs accepted before submissions are turned away
+    pub job_queue_limit: usize,
+    /// How long a job may run before it is failed
+    pub job_timeout: Duration,
+    /// How long finished jobs are kept
+    pub job_retention: Duration,
+    /// Per-kind overrides of `job_retention`, from `JOB_RETENTION=kind:secs,...`
+    pub job_retention_by_kind: Vec<(String, Duration)>,
+    /// Most jobs each tier may run at once, from `JOB_TIER_CONCURRENCY=tier:n,...`
+    pub job_tier_concurrency: Vec<(String, usize)>,
+    /// Decimals rank values are rounded to when a request doesn't pass `?precision`
+    pub rank_precision: Option<u32>,
+    /// Serialize `bigint` values outside JavaScript's safe integer range as strings
+    pub bigint_as_string: bool,
+    /// Return `TIMESTAMP` columns in the old offset-less format
+    pub legacy_timestamps: bool,
+    /// How often `tea_rank_runs` is checked for a new run
+    pub run_poll_interval: Duration,
+    /// Where alerts about anomalous runs are posted
+    pub run_alert_webhook_url: Option<String>,
+    /// Largest relative change in ranked projects between runs without an alert
+    pub run_alert_count_change: f64,
+    /// Largest relative change in mean rank between runs without an alert
+    pub run_alert_mean_change: f64,
+    /// Largest share of the top 100 replaced between runs without an alert
+    pub run_alert_top_churn: f64,
+    /// How long an outbound HTTP request may take, retries aside
+    pub http_timeout: Duration,
+    /// Times a transient outbound failure is retried
+    pub http_retries: u32,
+    /// Outbound requests that may be in flight at once
+    pub http_max_in_flight: usize,
+    /// Outbound requests one host may receive per `http_host_rate_window`
+    pub http_host_rate_limit: u64,
+    pub http_host_rate_window: Duration,
+    /// Hosts outbound requests may reach, from `OUTBOUND_HOST_ALLOWLIST=host,*.domain`;
+    /// empty allows any
+    pub outbound_host_allowlist: Vec<String>,
+    /// Let outbound requests reach loopback, private, and other non-public addresses
+    pub outbound_allow_private: bool,
+    /// Age past which `/status` reports the latest run as stale
+    pub status_run_max_age: Duration,
+    /// Age past which `/status` reports an ecosystem's last ingest as stale
+    pub status_ingest_max_age: Duration,
+    /// Start database sessions read-only so only explicit read-write transactions can write
+    pub db_read_only: bool,
+    /// Age after which a pooled connection is replaced at its next checkout
+    pub db_max_connection_lifetime: Option<Duration>,
+    /// Checkouts after which a pooled connection is replaced
+    pub db_max_connection_uses: Option<usize>,
+    /// Region label of `DATABASE_URL`, the primary
+    pub database_region: String,
+    /// Read replicas as `(region, url)`, from `DATABASE_URLS`
+    pub database_replicas: Vec<(String, String)>,
+    /// Blue/green schema targets as `(name, schema)`, from `DB_SCHEMAS=name:schema,...`
+    pub db_schemas: Vec<(String, String)>,
+    /// Target sessions start in; the first of `db_schemas` when unset
+    pub db_active_schema: Option<String>,
+    /// How often every database region's latency is probed
+    pub db_probe_interval: Duration,
+    /// Maintenance task intervals, from `MAINTENANCE_SCHEDULE=task:secs,...`
+    pub maintenance_schedule: Vec<(String, Duration)>,
+    /// Mark `/tables` reads of tables with dedicated endpoints as deprecated
+    pub table_deprecation_notices: bool,
+    /// Leave `packageManagers` out of projects unless `?include` asks for it
+    pub package_managers_opt_in: bool,
+    /// `/tables/{table}/stream` responses that may be open at once, each
+    /// holding a database connection
+    pub table_streams_max: usize,
+    /// Responses larger than this are logged as oversized
+    pub response_size_warn_bytes: Option<u64>,
+    /// Report per-statement timings in `X-Debug-Timings`; ignored in release builds
+    pub debug_sql: bool,
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        Self {
+            rate_limit_requests: env_or("RATE_LIMIT_REQUESTS", 600).max(1),
+            rate_limit_window: Duration::from_secs(env_or("RATE_LIMIT_WINDOW_SECS", 60).max(1)),
+            api_keys: parse_api_keys(&env::var("API_KEYS").unwrap_or_default()),
+            admin_api_key: env::var("ADMIN_API_KEY").ok().filter(|key| !key.is_empty()),
+            api_key_tiers: parse_named("API_KEY_TIERS", "name:tier"),
+            tier_response_limits: parse_named("TIER_RESPONSE_LIMIT", "tier:n"),
+            tier_batch_sizes: parse_named("TIER_BATCH_SIZE", "tier:n"),
+            tier_exports: parse_named("TIER_EXPORTS", "tier:bool"),
+            usage_flush_interval: Duration::from_secs(
+                env_or("USAGE_FLUSH_INTERVAL_SECS", 300).max(1),
+            ),
+            ip_allowlist: parse_cidrs("IP_ALLOWLIST"),
+            ip_denylist: parse_cidrs("IP_DENYLIST"),
+            admin_ip_allowlist: parse_cidrs("ADMIN_IP_ALLOWLIST"),
+            trust_proxy_headers: env_or("TRUST_PROXY_HEADERS", false),
+            default_cache_control: env::var("DEFAULT_CACHE_CONTROL")
+                .unwrap_or_else(|_| "no-store".to_string()),
+            verification_interval: Duration::from_secs(
+                env_or("VERIFICATION_INTERVAL_SECS", 600).max(1),
+            ),
+            features: env::var("FEATURES")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect(),
+            enable_funding: env_or("ENABLE_FUNDING_ENDPOINT", false),
+            cache_max_staleness: Duration::from_secs(env_or("CACHE_MAX_STALENESS_SECS", 900)),
+            cache_snapshot_path: env::var("CACHE_SNAPSHOT_PATH")
+                .ok()
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            export_cache_dir: env::var("EXPORT_CACHE_DIR")
+                .ok()
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .unwrap_or_else(|| env::temp_dir().join("chai-exports")),
+            job_dir: env::var("JOB_DIR")
+                .ok()
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from)
+                .unwrap_or_else(|| env::temp_dir().join("chai-jobs")),
+            job_workers: env_or("JOB_WORKERS", 2).max(1),
+            job_queue_limit: env_or("JOB_QUEUE_LIMIT", 100).max(1),
+            job_timeout: Duration::from_secs(env_or("JOB_TIMEOUT_SECS", 900).max(1)),
+            job_retention: Duration::from_secs(env_or("JOB_RETENTION_SECS", 86_400)),
+            job_retention_by_kind: parse_named::<u64>("JOB_RETENTION", "kind:secs")
+                .into_iter()
+                .map(|(kind, secs)| (kind, Duration::from_secs(secs)))
+                .collect(),
+            job_tier_concurrency: parse_named("JOB_TIER_CONCURRENCY", "tier:n"),
+            rank_precision: env::var("RANK_PRECISION").ok().and_then(|raw| {
+                raw.parse()
+                    .map_err(|_| {
+                        ignore_setting(format_args!(
+                            "Ignoring invalid value for RANK_PRECISION: {raw:?}"
+                        ))
+                    })
+                    .ok()
+            }),
+            bigint_as_string: env_or("BIGINT_AS_STRING", false),
+            legacy_timestamps: env_or("LEGACY_TIMESTAMPS", false),
+            run_poll_interval: Duration::from_secs(env_or("RUN_POLL_INTERVAL_SECS", 60).max(1)),
+            run_alert_webhook_url: env::var("RUN_ALERT_WEBHOOK_URL")
+                .ok()
+                .filter(|url| !url.is_empty()),
+            run_alert_count_change: env_or("RUN_ALERT_COUNT_CHANGE", 0.2f64).max(0.0),
+            run_alert_mean_change: env_or("RUN_ALERT_MEAN_CHANGE", 0.2f64).max(0.0),
+            run_alert_top_churn: env_or("RUN_ALERT_TOP_CHURN", 0.5f64).max(0.0),
+            http_timeout: Duration::from_secs(env_or("HTTP_TIMEOUT_SECS", 10).max(1)),
+            http_retries: env_or("HTTP_RETRIES", 2u32).min(5),
+            http_max_in_flight: env_or("HTTP_MAX_IN_FLIGHT", 32usize).max(1),
+            http_host_rate_limit: env_or("HTTP_HOST_RATE_LIMIT", 60).max(1),
+            http_host_rate_window: Duration::from_secs(
+                env_or("HTTP_HOST_RATE_WINDOW_SECS", 60).max(1),
+            ),
+            outbound_host_allowlist: env::var("OUTBOUND_HOST_ALLOWLIST")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|host| !host.is_empty())
+                .map(str::to_string)
+                .collect(),
+            outbound_allow_private: env_or("OUTBOUND_ALLOW_PRIVATE", false),
+            status_run_max_age: Duration::from_secs(
+                env_or("STATUS_RUN_MAX_AGE_HOURS", 192u64).max(1) * 3600,
+            ),
+            status_ingest_max_age: Duration::from_secs(
+                env_or("STATUS_INGEST_MAX_AGE_HOURS", 48u64).max(1) * 3600,
+            ),
+            db_read_only: env_or("DB_READ_ONLY", true),
+            db_max_connection_lifetime: Some(env_or("DB_MAX_CONNECTION_LIFETIME_SECS", 1800))
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs),
+            db_max_connection_uses: Some(env_or("DB_MAX_CONNECTION_USES", 10_000))
+                .filter(|uses| *uses > 0),
+            database_region: env::var("DATABASE_REGION")
+                .ok()
+                .filter(|region| !region.is_empty())
+                .unwrap_or_else(|| "primary".to_string()),
+            database_replicas: parse_replicas(&env::var("DATABASE_URLS").unwrap_or_default()),
+            db_schemas: parse_named("DB_SCHEMAS", "name:schema"),
+            db_active_schema: env::var("DB_ACTIVE_SCHEMA")
+                .ok()
+                .filter(|name| !name.is_empty()),
+            db_probe_interval: Duration::from_secs(env_or("DB_PROBE_INTERVAL_SECS", 30).max(1)),
+            maintenance_schedule: parse_named::<u64>("MAINTENANCE_SCHEDULE", "task:secs")
+                .into_iter()
+                .map(|(task, secs)| (task, Duration::from_secs(secs)))
+                .collect(),
+            table_deprecation_notices: env_or("TABLE_DEPRECATION_NOTICES", false),
+            package_managers_opt_in: env_or("PACKAGE_MANAGERS_OPT_IN", false),
+            table_streams_max: env_or("TABLE_STREAMS_MAX", 2usize),
+            response_size_warn_bytes: Some(env_or("RESPONSE_SIZE_WARN_BYTES", 5_000_000))
+                .filter(|bytes| *bytes > 0),
+            debug_sql: env_or("DEBUG_SQL", false),
+        }
+    }
+}
+
+/// Settings ignored so far because they didn't parse or name anything known
+static IGNORED_SETTINGS: AtomicUsize = AtomicUsize::new(0);
+
+/// Logs that a setting is being ignored, counting it for `--check`
+pub fn ignore_setting(message: fmt::Arguments) {
+    log::warn!("{message}");
+    IGNORED_SETTINGS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// How many settings [`ignore_setting`] has reported
+pub fn ignored_settings() -> usize {
+    IGNORED_SETTINGS.load(Ordering::Relaxed)
+}
+
+fn env_or<T: FromStr>(name: &str, default: T) -> T {
+    match env::var(name) {
+        Ok(raw) => raw.parse().unwrap_or_else(|_| {
+            ignore_setting(format_args!("Ignoring invalid value for {name}: {raw:?}"));
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+fn parse_api_keys(raw: &str) -> Vec<(String, String)> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| match entry.split_once(':') {
+            Some((name, secret)) if !name.is_empty() && !secret.is_empty() => {
+                Some((name.to_string(), secret.to_string()))
+            }
+            _ => {
+                ignore_setting(format_args!(
+                    "Ignoring malformed API_KEYS entry (expected name:secret)"
+                ));
+                None
+            }
+        })
+        .collect()
+}
+
+/// `name:value,name:value` pairs from the variable `var`, skipping (and
+/// logging) entries that don't match `format` or whose value doesn't parse
+fn parse_named<T: FromStr>(var: &str, format: &str) -> Vec<(String, T)> {
+    env::var(var)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| match entry.split_once(':') {
+            Some((name, value)) if !name.is_empty() => match value.parse() {
+                Ok(value) => Some((name.to_string(), value)),
+                Err(_) => {
+                    ignore_setting(format_args!("Ignoring invalid {var} entry {entry:?}"));
+                    None
+                }
+            },
+            _ => {
+                ignore_setting(format_args!(
+                    "Ignoring malformed {var} entry (expected {format})"
+                ));
+                None
+            }
+        })
+        .collect()
+}
+
+/// Parses `region=url` pairs, comma-separated
+fn parse_replicas(raw: &str) -> Vec<(String, String)> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| match entry.split_once('=') {
+            Some((region, url)) if !region.is_empty() && !url.is_empty() => {
+                Some((region.to_string(), url.to_string()))
+            }
+            _ => {
+                ignore_setting(format_args!(
+                    "Ignoring malformed DATABASE_URLS entry (expected region=url)"
+                ));
+                None
+            }
+        })
+        .collect()
+}
+
+/// Parses a comma-separated list of CIDR blocks; bare addresses are treated as
+/// single-host networks.
+fn parse_cidrs(name: &str) -> Vec<IpNet> {
+    env::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            entry
+                .parse::<IpNet>()
+                .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| {
+                    ignore_setting(format_args!("Ignoring invalid {name} entry: {entry:?}"))
+                })
+                .ok()
+        })
+        .collect()
+}
@@ -0,0 +1,182 @@
+//! CSV answers to endpoints that answer in JSON by default. Records are
+//! encoded a chunk at a time as the body is sent, rather than all at once.
+
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::http::header::Accept;
+use actix_web::web::Bytes;
+use actix_web::{web, HttpMessage, HttpRequest};
+use chai_core::rows::column_to_json;
+use chai_types::format::RowFormat;
+use chai_types::requests::{Format, FormatParams};
+use serde_json::Value;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio_postgres::Row;
+
+/// Records encoded per chunk of the body
+const CHUNK_RECORDS: usize = 256;
+
+/// The format to answer in: `?format` when given, else whichever of JSON and
+/// CSV `Accept` ranks first, else JSON
+pub fn response_format(req: &HttpRequest) -> Result<Format, String> {
+    let params =
+        web::Query::<FormatParams>::from_query(req.query_string()).map_err(|e| e.to_string())?;
+    if let Some(format) = params.format {
+        return Ok(format);
+    }
+    Ok(req
+        .get_header::<Accept>()
+        .into_iter()
+        .flat_map(|accept| accept.ranked())
+        .find_map(|mime| match mime.essence_str() {
+            "text/csv" => Some(Format::Csv),
+            "application/json" => Some(Format::Json),
+            _ => None,
+        })
+        .unwrap_or_default())
+}
+
+/// A CSV body: `header`, then every record of `records`
+pub struct CsvBody<I> {
+    header: Option<Vec<String>>,
+    records: I,
+}
+
+impl<I: Iterator<Item = Vec<String>>> CsvBody<I> {
+    pub fn new(header: Vec<String>, records: I) -> Self {
+        Self {
+            header: Some(header),
+            records,
+        }
+    }
+
+    /// The next chunk of records, empty once they have all been sent
+    fn next_chunk(&mut self) -> io::Result<Vec<u8>> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        if let Some(header) = self.header.take() {
+            writer.write_record(header)?;
+        }
+        for record in self.records.by_ref().take(CHUNK_RECORDS) {
+            writer.write_record(record)?;
+        }
+        writer.into_inner().map_err(|e| e.into_error())
+    }
+}
+
+impl<I: Iterator<Item = Vec<String>> + Unpin> MessageBody for CsvBody<I> {
+    type Error = io::Error;
+
+    fn size(&self) -> BodySize {
+        BodySize::Stream
+    }
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        Poll::Ready(match self.get_mut().next_chunk() {
+            Ok(chunk) if chunk.is_empty() => None,
+            Ok(chunk) => Some(Ok(Bytes::from(chunk))),
+            Err(e) => Some(Err(e)),
+        })
+    }
+}
+
+/// `rows` under their `columns`, each cell as `/tables` renders it in JSON
+pub fn rows_body(
+    columns: Vec<String>,
+    rows: Vec<Row>,
+    format: RowFormat,
+) -> CsvBody<impl Iterator<Item = Vec<String>> + Unpin> {
+    CsvBody::new(
+        columns,
+        rows.into_iter().map(move |row| record(&row, format)),
+    )
+}
+
+/// `columns` of each object of the array `values`
+pub fn values_body(
+    columns: Vec<String>,
+    values: Value,
+) -> CsvBody<impl Iterator<Item = Vec<String>> + Unpin> {
+    let values = match values {
+        Value::Array(values) => values,
+        other => vec![other],
+    };
+    let header = columns.clone();
+    CsvBody::new(
+        header,
+        values.into_iter().map(move |mut value| {
+            columns
+                .iter()
+                .map(|column| cell(value[column].take()))
+                .collect()
+        }),
+    )
+}
+
+/// Every cell of `row`
+pub fn record(row: &Row, format: RowFormat) -> Vec<String> {
+    (0..row.len())
+        .map(|i| cell(column_to_json(row, i, format)))
+        .collect()
+}
+
+/// A rendered value as a cell: strings without quotes, arrays and objects as
+/// JSON, and nulls empty
+fn cell(value: Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s,
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::to_bytes;
+    use actix_web::test::TestRequest;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn values_are_sent_as_csv_records() {
+        let values = json!((0..300)
+            .map(|i| json!({ "name": format!("p{i}"), "tags": ["a", "b"], "rank": null }))
+            .collect::<Vec<_>>());
+        let columns = ["name", "tags", "rank"].map(String::from).to_vec();
+        let body = to_bytes(values_body(columns, values)).await.unwrap();
+        let csv = String::from_utf8(body.to_vec()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 301);
+        assert_eq!(lines[0], "name,tags,rank");
+        assert_eq!(lines[1], r#"p0,"[""a"",""b""]","#);
+
+        let header = ["name"].map(String::from).to_vec();
+        let body = to_bytes(values_body(header, json!([]))).await.unwrap();
+        assert_eq!(body, "name\n");
+    }
+
+    #[test]
+    fn format_follows_the_query_then_accept() {
+        let format = |uri, accept| {
+            let mut req = TestRequest::with_uri(uri);
+            if let Some(accept) = accept {
+                req = req.insert_header(("Accept", accept));
+            }
+            response_format(&req.to_http_request())
+        };
+        assert_eq!(format("/t?format=csv", None), Ok(Format::Csv));
+        assert_eq!(format("/t?format=json", Some("text/csv")), Ok(Format::Json));
+        assert_eq!(format("/t", None), Ok(Format::Json));
+        assert!(format("/t?format=xml", None).is_err());
+
+        assert_eq!(format("/t", Some("text/csv")), Ok(Format::Csv));
+        let preferred = "application/json;q=0.5, text/csv";
+        assert_eq!(format("/t", Some(preferred)), Ok(Format::Csv));
+        let preferred = "text/html, application/json, text/csv;q=0.9";
+        assert_eq!(format("/t", Some(preferred)), Ok(Format::Json));
+        assert_eq!(format("/t", Some("*/*")), Ok(Format::Json));
+    }
+}
@@ -1,12 +1,65 @@
-use deadpool_postgres::{Config, Pool, Runtime};
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+    Error,
+};
+use chai_core::db::{collect_timings, Catalog};
+use deadpool_postgres::{
+    Config, Hook, HookError, HookErrorCause, ManagerConfig, Pool, PoolConfig, RecyclingMethod,
+    Runtime, Timeouts,
+};
+use serde_json::{json, Value};
 use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio_postgres::{Client, NoTls};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio_postgres::NoTls;
 use url::Url;
 
-pub async fn create_pool() -> Pool {
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let db_url = Url::parse(&database_url).expect("Invalid database URL");
+use crate::schemas::Schemas;
+
+pub const DEBUG_TIMINGS_HEADER: HeaderName = HeaderName::from_static("x-debug-timings");
+
+/// Reports every statement the request ran, in order, as
+/// `X-Debug-Timings: name;dur=1.234, ...` with durations in milliseconds;
+/// requests that ran none get no header.
+/// Only installed when `DEBUG_SQL` is set in a debug build.
+pub async fn debug_timings(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let (res, timings) = collect_timings(next.call(req)).await;
+    let mut res = res?;
+    if timings.is_empty() {
+        return Ok(res);
+    }
+    let header = timings
+        .iter()
+        .map(|(name, elapsed)| format!("{name};dur={:.3}", elapsed.as_secs_f64() * 1000.0))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if let Ok(value) = HeaderValue::from_str(&header) {
+        res.headers_mut().insert(DEBUG_TIMINGS_HEADER, value);
+    }
+    Ok(res)
+}
+
+/// Bounds opening or recycling a connection, so an unreachable database fails
+/// fast enough for reads to move to another region
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Re-applied every time a pooled connection is checked out, so a stray `SET`
+/// can't leave a session writable for the next request
+const READ_ONLY_SQL: &str = "SET default_transaction_read_only = on";
+
+/// Pool settings for `database_url`. With `read_only`, sessions start read-only
+/// and are reset to read-only at each checkout; writes then need an explicit
+/// read-write transaction (see [`read_write`]).
+pub fn pool_config(database_url: &str, read_only: bool) -> Config {
+    let db_url = Url::parse(database_url).expect("Invalid database URL");
 
     let mut config = Config::new();
     config.host = db_url.host_str().map(ToOwned::to_owned);
@@ -14,29 +67,248 @@ pub async fn create_pool() -> Pool {
     config.user = Some(db_url.username().to_owned());
     config.password = db_url.password().map(ToOwned::to_owned);
     config.dbname = db_url.path().strip_prefix('/').map(ToOwned::to_owned);
+    config.pool = Some(PoolConfig {
+        timeouts: Timeouts {
+            create: Some(CONNECT_TIMEOUT),
+            recycle: Some(CONNECT_TIMEOUT),
+            wait: None,
+        },
+        ..PoolConfig::default()
+    });
+    if read_only {
+        config.options = Some("-c default_transaction_read_only=on".to_string());
+        config.manager = Some(ManagerConfig {
+            recycling_method: RecyclingMethod::Custom(READ_ONLY_SQL.to_string()),
+        });
+    }
+    config
+}
+
+/// Connections retired at checkout instead of being reused
+#[derive(Default)]
+pub struct RecycleCounters {
+    lifetime: AtomicU64,
+    uses: AtomicU64,
+    cutover: AtomicU64,
+}
+
+impl RecycleCounters {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "lifetime": self.lifetime.load(Ordering::Relaxed),
+            "uses": self.uses.load(Ordering::Relaxed),
+            "cutover": self.cutover.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// When pooled connections are replaced. Long-lived backends grow their memory
+/// (plan and catalog caches, plus each statement `prepare_cached` keeps), so
+/// connections are retired once they reach either limit; `None` disables it.
+/// Connections opened before a schema cutover are retired as well.
+pub struct Recycling {
+    pub max_lifetime: Option<Duration>,
+    pub max_uses: Option<usize>,
+    pub counters: Arc<RecycleCounters>,
+    /// The schema new connections are pointed at
+    pub schemas: Arc<Schemas>,
+}
+
+/// Builds a pool from `config` that retires connections per `recycling`
+pub fn build_pool(config: &Config, recycling: &Recycling) -> Pool {
+    let max_lifetime = recycling.max_lifetime;
+    let max_uses = recycling.max_uses;
+    let counters = Arc::clone(&recycling.counters);
+    let schemas = Arc::clone(&recycling.schemas);
+
+    // Failing the hook with `Continue` drops the connection and checks out
+    // another (or opens a fresh one)
+    let stale = Arc::clone(&schemas);
+    let retire = Hook::sync_fn(move |_, metrics| {
+        let (counter, reason) = if stale.is_stale(metrics.created) {
+            (&counters.cutover, "predates the schema cutover")
+        } else if max_lifetime.is_some_and(|max| metrics.age() >= max) {
+            (&counters.lifetime, "reached its maximum lifetime")
+        } else if max_uses.is_some_and(|max| metrics.recycle_count >= max) {
+            (&counters.uses, "reached its maximum uses")
+        } else {
+            return Ok(());
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        log::debug!(
+            "Retiring database connection after {:?} and {} uses: {reason}",
+            metrics.age(),
+            metrics.recycle_count
+        );
+        Err(HookError::Continue(Some(HookErrorCause::StaticMessage(
+            reason,
+        ))))
+    });
+
+    let search_path = Hook::async_fn(move |client, _| {
+        let sql = schemas.search_path_sql();
+        Box::pin(async move {
+            if let Some(sql) = sql {
+                client
+                    .batch_execute(&sql)
+                    .await
+                    .map_err(|e| HookError::Abort(HookErrorCause::Backend(e)))?;
+            }
+            Ok(())
+        })
+    });
 
     config
-        .create_pool(Some(Runtime::Tokio1), NoTls)
+        .builder(NoTls)
+        .expect("Invalid database configuration")
+        .runtime(Runtime::Tokio1)
+        .post_create(search_path)
+        .pre_recycle(retire)
+        .build()
         .expect("Failed to create pool")
 }
 
-pub async fn get_tables(client: &Client) -> Vec<String> {
-    let rows = client
-        .query(
-            "SELECT table_name FROM information_schema.tables WHERE table_schema = 'public'",
-            &[],
-        )
-        .await
-        .expect("Failed to fetch tables");
+pub async fn create_pool(read_only: bool, recycling: &Recycling) -> Pool {
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+
+    build_pool(&pool_config(&database_url, read_only), recycling)
+}
 
-    rows.into_iter()
-        .map(|row| row.get::<_, String>("table_name"))
-        .collect()
+/// Starts a transaction that may write even when sessions default to read-only.
+/// Only the few statements that are meant to mutate data should use this.
+pub async fn read_write(
+    client: &mut deadpool_postgres::Client,
+) -> Result<deadpool_postgres::Transaction<'_>, tokio_postgres::Error> {
+    client.build_transaction().read_only(false).start().await
 }
 
-pub async fn initialize_db() -> (Pool, Arc<Vec<String>>) {
-    let pool = create_pool().await;
-    let client = pool.get().await.expect("Failed to get client from pool");
-    let tables = Arc::new(get_tables(&client).await);
-    (pool, tables)
+/// The [`Catalog`] of the active schema; handlers read it with `borrow()`.
+/// Read at startup, re-read by the `table_refresh` maintenance task, and
+/// replaced by a schema cutover.
+pub type Tables = watch::Sender<Arc<Catalog>>;
+
+/// Re-reads the catalog from the primary, publishing it only if it changed and
+/// no cutover happened meanwhile
+pub async fn refresh_catalog(pool: &Pool, catalog: &Tables, schemas: &Schemas) {
+    let schema = schemas.schema();
+    let client = match pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Table refresh failed to get database connection: {e}");
+            return;
+        }
+    };
+    match Catalog::load(&client, &schema).await {
+        Ok(_) if schemas.schema() != schema => {}
+        Ok(fresh) => {
+            catalog.send_if_modified(|current| {
+                if **current == fresh {
+                    return false;
+                }
+                log::info!("Available tables changed: {:?}", fresh.tables);
+                *current = Arc::new(fresh);
+                true
+            });
+        }
+        Err(e) => log::error!("Failed to refresh tables: {e}"),
+    }
+}
+
+pub async fn initialize_db(
+    read_only: bool,
+    recycling: &Recycling,
+) -> Result<(Pool, Catalog), String> {
+    let pool = create_pool(read_only, recycling).await;
+    let client = pool
+        .get()
+        .await
+        .map_err(|e| format!("Failed to get client from pool: {e}"))?;
+    let catalog = Catalog::load(&client, &recycling.schemas.schema())
+        .await
+        .map_err(|e| format!("Failed to fetch tables: {e}"))?;
+    Ok((pool, catalog))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_postgres::error::SqlState;
+
+    fn read_only_pool() -> Option<Pool> {
+        let Ok(url) = env::var("DATABASE_URL") else {
+            eprintln!("DATABASE_URL not set, skipping database test");
+            return None;
+        };
+        let mut config = pool_config(&url, true);
+        // One connection, so every checkout reuses (and recycles) the same session
+        config.pool = Some(deadpool_postgres::PoolConfig::new(1));
+        let recycling = Recycling {
+            max_lifetime: None,
+            max_uses: None,
+            counters: Arc::default(),
+            schemas: Arc::default(),
+        };
+        Some(build_pool(&config, &recycling))
+    }
+
+    fn assert_read_only(result: Result<u64, tokio_postgres::Error>) {
+        let error = result.expect_err("write should have been rejected");
+        assert_eq!(error.code(), Some(&SqlState::READ_ONLY_SQL_TRANSACTION));
+    }
+
+    #[tokio::test]
+    async fn read_only_sessions_reject_writes() {
+        let Some(pool) = read_only_pool() else {
+            return;
+        };
+        let client = pool.get().await.unwrap();
+        assert_read_only(
+            client
+                .execute("CREATE TABLE read_only_probe (id int)", &[])
+                .await,
+        );
+        assert_read_only(
+            client
+                .execute("CREATE TEMP TABLE read_only_probe (id int)", &[])
+                .await,
+        );
+    }
+
+    #[tokio::test]
+    async fn checkout_restores_read_only() {
+        let Some(pool) = read_only_pool() else {
+            return;
+        };
+        {
+            let client = pool.get().await.unwrap();
+            client
+                .batch_execute("SET default_transaction_read_only = off")
+                .await
+                .unwrap();
+        }
+        let client = pool.get().await.unwrap();
+        assert_read_only(
+            client
+                .execute("CREATE TABLE read_only_probe (id int)", &[])
+                .await,
+        );
+    }
+
+    #[tokio::test]
+    async fn read_write_transactions_may_write() {
+        let Some(pool) = read_only_pool() else {
+            return;
+        };
+        let mut client = pool.get().await.unwrap();
+        let transaction = read_write(&mut client).await.unwrap();
+        transaction
+            .execute("CREATE TEMP TABLE read_write_probe (id int)", &[])
+            .await
+            .unwrap();
+        transaction
+            .execute("INSERT INTO read_write_probe VALUES (1)", &[])
+            .await
+            .unwrap();
+        transaction.rollback().await.unwrap();
+    }
 }
@@ -0,0 +1,199 @@
+//! Where the API may send requests. Outbound requests may only reach hosts on
+//! `OUTBOUND_HOST_ALLOWLIST` (any host when it is unset) and only public
+//! addresses, so webhooks can't turn the API into a proxy for the network it
+//! runs in. Hosts are checked when a URL is registered and again on every
+//! request, where DNS answers are filtered as they are resolved, so a name
+//! can't be pointed at a private address after it passed.
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use url::{Host, Url};
+
+use crate::config::Config;
+
+pub struct Egress {
+    /// Exact hosts, or `*.domain` for any subdomain of `domain`; empty allows
+    /// every host
+    hosts: Vec<String>,
+    /// Allow loopback, private, and other non-public addresses, for local
+    /// development
+    allow_private: bool,
+}
+
+impl Egress {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            hosts: config
+                .outbound_host_allowlist
+                .iter()
+                .map(|host| host.to_ascii_lowercase())
+                .collect(),
+            allow_private: config.outbound_allow_private,
+        }
+    }
+
+    fn allows_host(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        self.hosts.is_empty()
+            || self
+                .hosts
+                .iter()
+                .any(|allowed| match allowed.strip_prefix("*.") {
+                    Some(domain) => host
+                        .strip_suffix(domain)
+                        .is_some_and(|sub| sub.ends_with('.')),
+                    None => *allowed == host,
+                })
+    }
+
+    fn allows_ip(&self, ip: IpAddr) -> bool {
+        self.allow_private || is_public(ip)
+    }
+
+    /// Whether `url` may be requested, judged without resolving its host:
+    /// addresses are checked as written, names against the allowlist
+    pub fn check(&self, url: &Url) -> Result<(), String> {
+        let allowed = match url.host() {
+            Some(Host::Domain(name)) => self.allows_host(name),
+            Some(Host::Ipv4(ip)) => {
+                self.allows_host(&ip.to_string()) && self.allows_ip(IpAddr::V4(ip))
+            }
+            Some(Host::Ipv6(ip)) => {
+                self.allows_host(&ip.to_string()) && self.allows_ip(IpAddr::V6(ip))
+            }
+            None => false,
+        };
+        if allowed {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} is not an allowed outbound host",
+                url.host_str().unwrap_or("(none)")
+            ))
+        }
+    }
+
+    /// [`Egress::check`], then that `url`'s host resolves to an allowed
+    /// address, for URLs about to be registered
+    pub async fn check_resolved(&self, url: &Url) -> Result<(), String> {
+        self.check(url)?;
+        let Some(Host::Domain(name)) = url.host() else {
+            return Ok(());
+        };
+        self.resolve(name)
+            .await
+            .map(drop)
+            .map_err(|e| format!("{name}: {e}"))
+    }
+
+    /// The allowed addresses `name` resolves to; an error when there are none
+    async fn resolve(&self, name: &str) -> Result<Vec<SocketAddr>, String> {
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name, 0))
+            .await
+            .map_err(|e| format!("failed to resolve: {e}"))?
+            .collect();
+        let allowed: Vec<SocketAddr> = addrs
+            .iter()
+            .copied()
+            .filter(|addr| self.allows_ip(addr.ip()))
+            .collect();
+        match (addrs.is_empty(), allowed.is_empty()) {
+            (true, _) => Err("resolves to no addresses".to_string()),
+            (false, true) => Err("resolves only to non-public addresses".to_string()),
+            (false, false) => Ok(allowed),
+        }
+    }
+}
+
+/// Resolves names for the outbound HTTP client, dropping addresses that
+/// [`Egress`] doesn't allow
+pub struct EgressResolver(pub Arc<Egress>);
+
+impl Resolve for EgressResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let egress = Arc::clone(&self.0);
+        Box::pin(async move {
+            let addrs = egress.resolve(name.as_str()).await?;
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Whether `ip` is reachable on the public internet: not loopback, private,
+/// link-local, shared, documentation, multicast, or unspecified
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // 0.0.0.0/8, and 100.64.0.0/10 shared by carrier-grade NATs
+        || a == 0
+        || (a == 100 && (64..128).contains(&b)))
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || ip.is_unique_local()
+        || ip.is_unicast_link_local()
+        // 2001:db8::/32, for documentation
+        || (ip.segments()[0] == 0x2001 && ip.segments()[1] == 0x0db8))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_listed_public_hosts_only() {
+        let egress = Egress {
+            hosts: vec!["hooks.example.com".to_string(), "*.example.org".to_string()],
+            allow_private: false,
+        };
+        let check = |url: &str| egress.check(&Url::parse(url).unwrap()).is_ok();
+
+        assert!(check("https://hooks.example.com/chai"));
+        assert!(check("https://HOOKS.example.com./chai"));
+        assert!(check("https://a.b.example.org/"));
+        assert!(!check("https://example.org/"));
+        assert!(!check("https://evilexample.org/"));
+        assert!(!check("https://example.com/"));
+        assert!(!check("https://hooks.example.com.evil.net/"));
+
+        let open = Egress {
+            hosts: Vec::new(),
+            allow_private: false,
+        };
+        let check = |url: &str| open.check(&Url::parse(url).unwrap()).is_ok();
+        assert!(check("https://anything.example.net/"));
+        assert!(check("https://93.184.215.14/"));
+        for url in [
+            "https://127.0.0.1/",
+            "https://10.1.2.3/",
+            "https://169.254.169.254/latest/meta-data",
+            "https://100.100.1.1/",
+            "https://0.0.0.0/",
+            "https://[::1]/",
+            "https://[fd00::1]/",
+            "https://[::ffff:192.168.0.1]/",
+        ] {
+            assert!(!check(url), "{url}");
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use actix_web::http::header::{self, ETag, EntityTag, IfNoneMatch};
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpMessage, HttpRequest, HttpResponse, Responder};
+use chai_core::cache::{CacheCounters, Lookup};
+use chai_core::leaderboard::{leaderboard_rows, run_exists};
+use chai_core::models::{to_json, FromRow};
+use chai_types::format::RowFormat;
+use chai_types::models::ExportEntry;
+use chai_types::requests::{ExportParams, Format};
+use sha2::{Digest, Sha256};
+use std::io;
+use std::path::PathBuf;
+use tokio::fs;
+use tokio_postgres::{Client, Row};
+
+use crate::app_state::AppState;
+use crate::auth::caller;
+use crate::problem::{database_error, no_connection, not_found, problem};
+
+/// Exports are immutable once a run exists, so clients may cache them forever
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+/// Cache namespace of leaderboard exports
+const LEADERBOARD: &str = "leaderboard";
+
+/// On-disk cache of run-scoped exports. Bodies are stored once under
+/// `objects/<sha256>`, and `refs/<endpoint>/<run>.<format>` names the object for
+/// each export, so the hash doubles as a strong ETag.
+pub struct ExportCache {
+    dir: PathBuf,
+    pub counters: CacheCounters,
+}
+
+impl ExportCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            counters: CacheCounters::default(),
+        }
+    }
+
+    /// Number of stored objects and their total size on disk
+    pub async fn disk_usage(&self) -> io::Result<(u64, u64)> {
+        let mut entries = match fs::read_dir(self.dir.join("objects")).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((0, 0)),
+            Err(e) => return Err(e),
+        };
+        let (mut objects, mut bytes) = (0, 0);
+        while let Some(entry) = entries.next_entry().await? {
+            objects += 1;
+            bytes += entry.metadata().await?.len();
+        }
+        Ok((objects, bytes))
+    }
+
+    /// Deletes every stored export, for when runs no longer mean what they did
+    pub async fn clear(&self) -> io::Result<()> {
+        match fs::remove_dir_all(&self.dir).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn ref_path(&self, endpoint: &str, run: i32, format: Format) -> PathBuf {
+        self.dir
+            .join("refs")
+            .join(endpoint)
+            .join(format!("{run}.{}", format.extension()))
+    }
+
+    fn object_path(&self, hash: &str) -> PathBuf {
+        self.dir.join("objects").join(hash)
+    }
+
+    /// The cached body and its hash, if this export was generated before
+    pub async fn get(&self, endpoint: &str, run: i32, format: Format) -> Option<(String, Vec<u8>)> {
+        let hash = fs::read_to_string(self.ref_path(endpoint, run, format))
+            .await
+            .ok()?;
+        let body = fs::read(self.object_path(hash.trim())).await.ok()?;
+        Some((hash.trim().to_string(), body))
+    }
+
+    /// Stores `body` and points the export's ref at it, returning the hash
+    pub async fn put(
+        &self,
+        endpoint: &str,
+        run: i32,
+        format: Format,
+        body: &[u8],
+    ) -> io::Result<String> {
+        let hash = hex::encode(Sha256::digest(body));
+
+        let object = self.object_path(&hash);
+        if fs::metadata(&object).await.is_err() {
+            write_atomic(&object, body).await?;
+        }
+        write_atomic(&self.ref_path(endpoint, run, format), hash.as_bytes()).await?;
+        Ok(hash)
+    }
+}
+
+/// Writes `contents` through a temporary file, so readers never see a partial one
+pub async fn write_atomic(path: &PathBuf, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    let tmp = path.with_extension(format!("tmp-{}", std::process::id()));
+    fs::write(&tmp, contents).await?;
+    fs::rename(&tmp, path).await
+}
+
+#[utoipa::path(
+    tag = "exports",
+    params(
+        ("run" = i32, Path, description = "Run number"),
+        ExportParams,
+    ),
+    responses(
+        (
+            status = 200,
+            description = "The run's leaderboard",
+            content((Vec<ExportEntry> = "application/json"), (String = "text/csv"))
+        ),
+        (status = 304, description = "Not modified since the given ETag"),
+        (status = 400, description = "Invalid format"),
+        (status = 403, description = "Exports aren't available on the caller's tier"),
+        (status = 404, description = "No such run"),
+    )
+)]
+#[get("/export/leaderboard/{run}")]
+pub async fn export_leaderboard(
+    req: HttpRequest,
+    run: web::Path<i32>,
+    params: web::Query<ExportParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let run = run.into_inner();
+    let format = params.format;
+    if let Some(response) = require_exports(&req, &data) {
+        return response;
+    }
+
+    if let Some((hash, body)) = data.export_cache.get(LEADERBOARD, run, format).await {
+        data.export_cache.counters.record(Lookup::Hit);
+        return export_response(&req, format, hash, body);
+    }
+    data.export_cache.counters.record(Lookup::Miss);
+
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+
+    // Only runs that exist are immutable; anything else may still be written
+    match run_exists(&client, run).await {
+        Ok(true) => {}
+        Ok(false) => return not_found(format!("Run {run} not found")),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            return database_error(&e);
+        }
+    }
+
+    let rows = match leaderboard_rows(&client, run).await {
+        Ok(rows) => rows,
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            return database_error(&e);
+        }
+    };
+
+    let body = match encode_leaderboard(&rows, format, data.row_format) {
+        Ok(body) => body,
+        Err(e) => {
+            log::error!("Failed to encode leaderboard export: {e}");
+            return problem(StatusCode::INTERNAL_SERVER_ERROR, "Failed to encode export");
+        }
+    };
+
+    match data.export_cache.put(LEADERBOARD, run, format, &body).await {
+        Ok(hash) => export_response(&req, format, hash, body),
+        Err(e) => {
+            // Still serve the export; it will just be regenerated next time
+            log::warn!("Failed to cache leaderboard export for run {run}: {e}");
+            let hash = hex::encode(Sha256::digest(&body));
+            export_response(&req, format, hash, body)
+        }
+    }
+}
+
+/// A `403` when the caller's tier may not export
+pub fn require_exports(req: &HttpRequest, data: &AppState) -> Option<HttpResponse> {
+    let caller = caller(req);
+    if data.api_keys.limits(&caller).exports {
+        return None;
+    }
+    let tier = data.api_keys.tier(&caller);
+    Some(problem(
+        StatusCode::FORBIDDEN,
+        format!("Exports aren't available on the {} tier", tier.as_str()),
+    ))
+}
+
+/// Generates every format of `run`'s leaderboard export that isn't cached yet,
+/// so the first requests for it are hits. Returns the formats it generated.
+pub async fn prefill_leaderboard(
+    data: &AppState,
+    client: &Client,
+    run: i32,
+) -> Result<Vec<Format>, String> {
+    let mut missing = Vec::new();
+    for format in Format::ALL {
+        if data
+            .export_cache
+            .get(LEADERBOARD, run, format)
+            .await
+            .is_none()
+        {
+            missing.push(format);
+        }
+    }
+    if missing.is_empty() {
+        return Ok(missing);
+    }
+    let rows = leaderboard_rows(client, run)
+        .await
+        .map_err(|e| format!("Failed to query the leaderboard of run {run}: {e}"))?;
+    for &format in &missing {
+        let body = encode_leaderboard(&rows, format, data.row_format)
+            .map_err(|e| format!("Failed to encode leaderboard export: {e}"))?;
+        data.export_cache
+            .put(LEADERBOARD, run, format, &body)
+            .await
+            .map_err(|e| format!("Failed to cache leaderboard export: {e}"))?;
+    }
+    Ok(missing)
+}
+
+fn encode_leaderboard(rows: &[Row], format: Format, row_format: RowFormat) -> io::Result<Vec<u8>> {
+    match format {
+        Format::Json => {
+            serde_json::to_vec(&to_json::<ExportEntry>(rows, row_format)).map_err(io::Error::from)
+        }
+        Format::Csv => leaderboard_csv(rows),
+    }
+}
+
+fn leaderboard_csv(rows: &[Row]) -> io::Result<Vec<u8>> {
+    // Serializing writes the header with the first row, so an empty run would
+    // get none; write it up front instead
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    writer.write_record(ExportEntry::CSV_HEADER)?;
+    for row in rows {
+        writer.serialize(ExportEntry::from_row(row, RowFormat::default()))?;
+    }
+    writer.into_inner().map_err(|e| e.into_error())
+}
+
+fn export_response(req: &HttpRequest, format: Format, hash: String, body: Vec<u8>) -> HttpResponse {
+    let etag = EntityTag::new_strong(hash);
+    let not_modified = match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        None => false,
+    };
+
+    let mut response = if not_modified {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    response
+        .insert_header(ETag(etag))
+        .insert_header((header::CACHE_CONTROL, IMMUTABLE));
+    if not_modified {
+        return response.finish();
+    }
+    response.content_type(format.content_type()).body(body)
+}
@@ -0,0 +1,217 @@
+use actix_web::http::StatusCode;
+use actix_web::{dev::Payload, error::InternalError, web, FromRequest, HttpRequest, ResponseError};
+use serde_json::json;
+use std::fmt::{Debug, Display};
+use std::future::{ready, Ready};
+use uuid::Uuid;
+
+use crate::problem::{problem, problem_with};
+use crate::regions::CONSISTENCY_HEADER;
+
+/// The `{id}` path segment parsed as a UUID.
+///
+/// Accepts the hyphenated, dashless (simple), braced, and URN forms, and
+/// answers malformed values with a JSON 400 instead of actix's bare one.
+pub struct PathUuid(pub Uuid);
+
+impl FromRequest for PathUuid {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let raw = req.match_info().get("id").unwrap_or_default();
+        ready(parse_uuid(raw).map(PathUuid))
+    }
+}
+
+pub fn parse_uuid(raw: &str) -> Result<Uuid, actix_web::Error> {
+    Uuid::try_parse(raw.trim()).map_err(|_| {
+        let response = problem_with(
+            StatusCode::BAD_REQUEST,
+            "invalid uuid",
+            json!({ "value": raw }),
+        );
+        InternalError::from_response("invalid uuid", response).into()
+    })
+}
+
+/// Answers bodies, query strings and path segments that `web::Json`,
+/// `web::Query` and `web::Path` can't deserialize with problem documents
+/// rather than actix's plain text
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(web::JsonConfig::default().error_handler(|e, _| rejected(e)))
+        .app_data(web::QueryConfig::default().error_handler(|e, _| rejected(e)))
+        .app_data(web::PathConfig::default().error_handler(|e, _| rejected(e)));
+}
+
+/// Keeps actix's status for `e`, such as `413` for an oversized body
+fn rejected<E: ResponseError + Debug + Display + 'static>(e: E) -> actix_web::Error {
+    let response = problem(e.status_code(), e.to_string());
+    InternalError::from_response(e, response).into()
+}
+
+/// The optional `X-Consistency-Token` header, a WAL position such as
+/// `16/B374D848` handed out by an earlier write.
+pub struct ConsistencyToken(pub Option<String>);
+
+impl FromRequest for ConsistencyToken {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let Some(raw) = req.headers().get(CONSISTENCY_HEADER) else {
+            return ready(Ok(ConsistencyToken(None)));
+        };
+        let token = raw.to_str().ok().map(str::trim).filter(|token| {
+            token.split_once('/').is_some_and(|(high, low)| {
+                [high, low].iter().all(|half| {
+                    (1..=8).contains(&half.len()) && half.chars().all(|c| c.is_ascii_hexdigit())
+                })
+            })
+        });
+        ready(match token {
+            Some(token) => Ok(ConsistencyToken(Some(token.to_string()))),
+            None => {
+                let response = problem_with(
+                    StatusCode::BAD_REQUEST,
+                    "invalid consistency token",
+                    json!({ "value": String::from_utf8_lossy(raw.as_bytes()) }),
+                );
+                Err(InternalError::from_response("invalid consistency token", response).into())
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, read_body, read_body_json, TestRequest};
+    use actix_web::{web, App};
+    use serde::Deserialize;
+    use serde_json::Value;
+
+    #[derive(Deserialize)]
+    struct Limit {
+        limit: u32,
+    }
+
+    async fn limit(body: web::Json<Limit>, query: web::Query<Limit>) -> String {
+        (body.limit + query.limit).to_string()
+    }
+
+    async fn id(id: PathUuid) -> String {
+        id.0.to_string()
+    }
+
+    async fn token(token: ConsistencyToken) -> String {
+        token.0.unwrap_or_default()
+    }
+
+    #[actix_web::test]
+    async fn ids_parse_in_any_form_and_malformed_ones_get_a_json_400() {
+        let app = init_service(App::new().route("/project/{id}", web::get().to(id))).await;
+        let expected = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        for raw in [
+            expected,
+            "67E55044-10B1-426F-9247-BB680E5FE0C8",
+            "67e5504410b1426f9247bb680e5fe0c8",
+            "%7B67e55044-10b1-426f-9247-bb680e5fe0c8%7D",
+            "urn:uuid:67e55044-10b1-426f-9247-bb680e5fe0c8",
+        ] {
+            let request = TestRequest::get().uri(&format!("/project/{raw}"));
+            let response = call_service(&app, request.to_request()).await;
+            assert_eq!(response.status(), StatusCode::OK, "{raw}");
+            assert_eq!(read_body(response).await, expected);
+        }
+
+        for raw in [
+            "not-a-uuid",
+            "67e55044-10b1-426f-9247",
+            "67e55044-10b1-426f-9247-bb680e5fe0cz",
+        ] {
+            let request = TestRequest::get().uri(&format!("/project/{raw}"));
+            let response = call_service(&app, request.to_request()).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{raw}");
+            let body: Value = read_body_json(response).await;
+            assert_eq!(body["error"], "invalid uuid");
+            assert_eq!(body["value"], raw);
+        }
+    }
+
+    #[actix_web::test]
+    async fn consistency_tokens_are_wal_positions() {
+        let app = init_service(App::new().route("/", web::get().to(token))).await;
+        let request = |value: Option<&str>| {
+            let request = TestRequest::get().uri("/");
+            match value {
+                Some(value) => request.insert_header((CONSISTENCY_HEADER, value)),
+                None => request,
+            }
+            .to_request()
+        };
+
+        for (value, token) in [
+            (None, ""),
+            (Some("16/B374D848"), "16/B374D848"),
+            (Some(" 0/1 "), "0/1"),
+        ] {
+            let response = call_service(&app, request(value)).await;
+            assert_eq!(response.status(), StatusCode::OK, "{value:?}");
+            assert_eq!(read_body(response).await, token);
+        }
+
+        for value in [
+            "16B374D848",
+            "16/",
+            "16/B374D8480",
+            "16/B374D84G",
+            "/B374D848",
+        ] {
+            let response = call_service(&app, request(Some(value))).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{value}");
+            let body: Value = read_body_json(response).await;
+            assert_eq!(body["error"], "invalid consistency token");
+            assert_eq!(body["value"], value);
+        }
+    }
+
+    #[actix_web::test]
+    async fn bodies_and_queries_that_dont_parse_get_a_problem() {
+        let app = init_service(
+            App::new()
+                .configure(configure)
+                .route("/", web::post().to(limit)),
+        )
+        .await;
+        let request = |uri: &str, body: &'static str| {
+            TestRequest::post()
+                .uri(uri)
+                .insert_header(("Content-Type", "application/json"))
+                .set_payload(body)
+                .to_request()
+        };
+
+        let response = call_service(&app, request("/?limit=2", r#"{"limit": 1}"#)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(read_body(response).await, "3");
+
+        for (uri, body) in [
+            ("/?limit=2", r#"{"limit": "#),
+            ("/?limit=2", r#"{"limit": -1}"#),
+            ("/?limit=two", r#"{"limit": 1}"#),
+            ("/", r#"{"limit": 1}"#),
+        ] {
+            let response = call_service(&app, request(uri, body)).await;
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{uri} {body}");
+            assert_eq!(
+                response.headers().get("Content-Type").unwrap(),
+                crate::problem::PROBLEM_JSON
+            );
+            let body: Value = read_body_json(response).await;
+            assert_eq!(body["status"], 400);
+            assert_eq!(body["error"], body["detail"]);
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Experimental subsystems, switched on by name with `FEATURES=a,b`. A feature's
+//! routes are only registered while it is on, so a dark feature answers 404
+//! like any unknown path.
+
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+use crate::config::{ignore_setting, Config};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Feature {
+    /// `GET /project/{id}/funding`
+    Funding,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 1] = [Feature::Funding];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Feature::Funding => "funding",
+        }
+    }
+}
+
+impl FromStr for Feature {
+    type Err = ();
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        Feature::ALL
+            .into_iter()
+            .find(|feature| feature.as_str() == raw)
+            .ok_or(())
+    }
+}
+
+/// The features this instance runs with
+#[derive(Clone, Debug, Default)]
+pub struct Features(BTreeSet<Feature>);
+
+impl Features {
+    pub fn from_config(config: &Config) -> Self {
+        let mut enabled: BTreeSet<Feature> = config
+            .features
+            .iter()
+            .filter_map(|raw| match raw.parse() {
+                Ok(feature) => Some(feature),
+                Err(()) => {
+                    ignore_setting(format_args!("Ignoring unknown feature {raw:?} in FEATURES"));
+                    None
+                }
+            })
+            .collect();
+        if config.enable_funding {
+            log::warn!("ENABLE_FUNDING_ENDPOINT is deprecated; use FEATURES=funding");
+            enabled.insert(Feature::Funding);
+        }
+        Self(enabled)
+    }
+
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        self.0.contains(&feature)
+    }
+
+    /// Names of the enabled features, as `/version` reports them
+    pub fn names(&self) -> Vec<&'static str> {
+        self.0.iter().map(|feature| feature.as_str()).collect()
+    }
+}
@@ -1,352 +1,898 @@
-use actix_web::{get, post, web, HttpResponse, Responder};
-use serde::{Deserialize, Serialize};
+use actix_web::http::{
+    header::{HeaderName, LINK, LOCATION, VARY},
+    StatusCode,
+};
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use chai_core::cache::{cache_projects, get_cached_projects, Lookup, ProjectCacheEntry};
+use chai_core::db::{fetch_one, timed, FetchError};
+use chai_core::dependencies::{
+    count_dependents, dependency_tree, dependents, DEFAULT_TREE_DEPTH, MAX_TREE_DEPTH,
+};
+use chai_core::leaderboard::{
+    best_ranked, leaderboard_projects, new_entrants, previous_run, rank_of, top_projects,
+    LeaderboardFilter, LEADERBOARD_ENTRY_FIELDS, LEADERBOARD_SORT_FIELDS, NEW_ENTRANT_SORT_FIELDS,
+};
+use chai_core::projects::{
+    canon_by_slug, canon_exists, embed_redirects, existing_canons, fetch_project, fetch_projects,
+    maintainer_concentration, maintainer_projects, modified_since, project_funding,
+    resolve_redirects, search_projects, PROJECT_FIELDS,
+};
+use chai_core::rows::{get_column_names, row_to_json, rows_to_json, with_precision};
+use chai_core::suspects::similar_names;
+use chai_types::fields::{Fields, FieldsParams};
+use chai_types::filter::FilterParams;
+use chai_types::models::{
+    DependencyTree, LeaderboardEntry, Limits, MaintainerConcentration, MaintainerProjects,
+    NewEntrants, ProjectBody, ProjectDependents, ProjectFunding, SearchResults, SimilarNames,
+    TableList, TablePage, Version,
+};
+use chai_types::requests::{
+    AsOfParams, CheckNameParams, DependencyTreeParams, Format, FormatParams, LeaderboardRequest,
+    NewEntrantsParams, PaginationParams, PrecisionParams, ProjectBatchRequest,
+    ProjectExistsRequest, ProjectParams, ProjectView, SoftDeleteParams,
+};
+use chai_types::sort::{Sort, SortParams};
+use chai_types::tier::Tier;
+use chrono::NaiveDate;
+use serde::Deserialize;
 use serde_json::{json, Value};
+use std::collections::BTreeMap;
 use std::sync::Arc;
-use tokio_postgres::error::SqlState;
+use tokio_postgres::{error::SqlState, types::ToSql, Client};
 use uuid::Uuid;
 
 use crate::app_state::AppState;
-use crate::utils::{get_cached_projects, get_column_names, rows_to_json, Pagination};
+use crate::auth::{caller, require_admin};
+use crate::csv_stream::{response_format, rows_body, values_body};
+use crate::export::require_exports;
+use crate::extractors::PathUuid;
+use crate::problem::{database_error, no_connection, not_found, problem, problem_with};
+use crate::runs::{resolve_as_of, select_run};
+use crate::usage::TableRead;
+use crate::utils::{bind_as, Keyset, Pagination};
 
-const RESPONSE_LIMIT: i64 = 1000;
+/// Most matches one similar-names request returns
+const MAX_SIMILAR_NAMES: i64 = 100;
+/// Most ids one existence check takes
+const MAX_EXISTS_IDS: usize = 10_000;
 
-#[derive(Deserialize)]
-pub struct PaginationParams {
-    pub page: Option<i64>,
-    pub limit: Option<i64>,
+/// A generic table superseded by a business endpoint
+struct TableReplacement {
+    table: &'static str,
+    /// Replaces `/tables/{table}`
+    endpoint: &'static str,
+    /// Replaces `/tables/{table}/{id}`; `{id}` is filled in
+    row_endpoint: &'static str,
+    reason: &'static str,
 }
 
-#[derive(Serialize)]
-struct PaginatedResponse {
-    table: String,
-    total_count: i64,
-    page: i64,
-    limit: i64,
-    total_pages: i64,
-    columns: Vec<String>,
-    data: Vec<Value>,
+const TABLE_REPLACEMENTS: [TableReplacement; 2] = [
+    TableReplacement {
+        table: "canons",
+        endpoint: "/project/batch",
+        row_endpoint: "/project/{id}",
+        reason: "Project endpoints return canons with their rank, URLs, package managers and \
+            dependency counts.",
+    },
+    TableReplacement {
+        table: "tea_ranks",
+        endpoint: "/leaderboard",
+        row_endpoint: "/leaderboard",
+        reason: "The leaderboard returns ranks for the latest run joined with their projects.",
+    },
+];
+
+/// The replacement for `table`, when deprecation notices are enabled
+fn table_replacement(data: &AppState, table: &str) -> Option<&'static TableReplacement> {
+    if !data.table_deprecation_notices {
+        return None;
+    }
+    TABLE_REPLACEMENTS.iter().find(|r| r.table == table)
 }
 
-#[derive(Deserialize)]
-pub struct LeaderboardRequest {
-    #[serde(rename = "projectIds")]
-    pub project_ids: Option<Vec<Uuid>>,
-    pub limit: i64,
+impl TableReplacement {
+    /// `Link` to the replacement of a page read, or of the row `id`
+    fn link(&self, id: Option<Uuid>) -> (HeaderName, String) {
+        let target = match id {
+            Some(id) => self.row_endpoint.replace("{id}", &id.to_string()),
+            None => self.endpoint.to_string(),
+        };
+        (LINK, format!("<{target}>; rel=\"successor-version\""))
+    }
+
+    fn notice(&self) -> Value {
+        json!({
+            "message": format!(
+                "Generic access to '{}' is deprecated; use {} instead.",
+                self.table, self.endpoint
+            ),
+            "replacement": self.endpoint,
+            "rowReplacement": self.row_endpoint,
+            "reason": self.reason,
+        })
+    }
 }
 
 #[derive(Deserialize)]
-pub struct ProjectBatchRequest {
-    #[serde(rename = "projectIds")]
-    pub project_ids: Vec<Uuid>,
+pub struct TablePath {
+    pub table: String,
 }
 
 pub fn check_table_exists(table: &str, tables: &[String]) -> Option<HttpResponse> {
     if !tables.contains(&table.to_string()) {
-        Some(HttpResponse::NotFound().json(json!({
-            "error": format!("Table '{}' not found", table),
-            "valid_tables": tables,
-            "help": "Refer to the API documentation for valid table names."
-        })))
+        Some(problem_with(
+            StatusCode::NOT_FOUND,
+            format!("Table '{}' not found", table),
+            json!({
+                "valid_tables": tables,
+                "help": "Refer to the API documentation for valid table names."
+            }),
+        ))
     } else {
         None
     }
 }
 
+#[utoipa::path(
+    tag = "tables",
+    params(PaginationParams),
+    responses(
+        (status = 200, description = "OK", body = TableList),
+    )
+)]
 #[get("/tables")]
 pub async fn list_tables(
+    req: HttpRequest,
     query: web::Query<PaginationParams>,
     data: web::Data<AppState>,
 ) -> impl Responder {
-    let total_count = data.tables.len() as i64;
-    let pagination = Pagination::new(query, total_count);
+    let catalog = data.tables.borrow().clone();
+    let total_count = catalog.tables.len() as i64;
+    let pagination = Pagination::new(
+        query,
+        total_count,
+        data.api_keys.limits(&caller(&req)).response_limit,
+    );
 
     let start = pagination.offset as usize;
-    let end = (start + pagination.limit as usize).min(data.tables.len());
+    let end = (start + pagination.limit as usize).min(catalog.tables.len());
 
-    let paginated_tables = &data.tables[start..end];
+    let paginated_tables = &catalog.tables[start..end];
 
-    HttpResponse::Ok().json(json!({
-        "total_count": total_count,
-        "page": pagination.page,
-        "limit": pagination.limit,
-        "total_pages": pagination.total_pages,
-        "data": paginated_tables,
-    }))
+    HttpResponse::Ok()
+        .insert_header(pagination.link(&req))
+        .json(TableList {
+            total_count,
+            page: pagination.page,
+            limit: pagination.limit,
+            total_pages: pagination.total_pages,
+            data: paginated_tables.to_vec(),
+        })
 }
 
+#[utoipa::path(
+    tag = "health",
+    responses(
+        (status = 200, description = "The database is reachable", body = String),
+        (status = 500, description = "The database didn't answer a query"),
+        (status = 503, description = "No database connection could be had"),
+    )
+)]
 #[get("/heartbeat")]
 pub async fn heartbeat(data: web::Data<AppState>) -> impl Responder {
     match data.pool.get().await {
-        Ok(client) => match client.query_one("SELECT 1", &[]).await {
+        Ok(client) => match timed("heartbeat", client.query_one("SELECT 1", &[])).await {
             Ok(_) => HttpResponse::Ok().body("OK - Database connection is healthy"),
             Err(e) => {
                 log::error!("Database query failed: {e}");
-                HttpResponse::InternalServerError().body("Database query failed")
+                problem(StatusCode::INTERNAL_SERVER_ERROR, "Database query failed")
             }
         },
         Err(e) => {
             log::error!("Failed to get database connection: {e}");
-            HttpResponse::InternalServerError().body("Failed to get database connection")
+            no_connection()
         }
     }
 }
 
+/// The running build and the experimental features it has turned on
+#[utoipa::path(
+    tag = "health",
+    responses(
+        (status = 200, description = "OK", body = Version),
+    )
+)]
+#[get("/version")]
+pub async fn get_version(data: web::Data<AppState>) -> impl Responder {
+    HttpResponse::Ok().json(Version {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        features: data
+            .features
+            .names()
+            .into_iter()
+            .map(String::from)
+            .collect(),
+    })
+}
+
+pub fn check_include_deleted(req: &HttpRequest, params: &SoftDeleteParams) -> Option<HttpResponse> {
+    if params.include_deleted.unwrap_or(false) {
+        require_admin(req)
+    } else {
+        None
+    }
+}
+
+// Soft-deleted rows are hidden unless `?include_deleted=true` was requested (admin only)
+pub fn live_rows(params: &SoftDeleteParams, data: &AppState, table: &str) -> String {
+    if params.include_deleted.unwrap_or(false) {
+        "TRUE".to_string()
+    } else {
+        data.not_deleted(table, "t")
+    }
+}
+
+#[utoipa::path(
+    tag = "health",
+    responses(
+        (status = 200, description = "OK", body = Limits),
+    )
+)]
+#[get("/limits")]
+pub async fn get_limits(req: HttpRequest, data: web::Data<AppState>) -> impl Responder {
+    let caller = caller(&req);
+    let quota = data.rate_limiter.peek(&caller.id());
+
+    HttpResponse::Ok().json(Limits {
+        caller: caller.id(),
+        tier: data.api_keys.tier(&caller),
+        rate_limit: quota,
+        tier_limits: data.api_keys.limits(&caller),
+    })
+}
+
+#[utoipa::path(
+    tag = "tables",
+    params(
+        ("table" = String, Path, description = "Table name"),
+        PaginationParams,
+        SoftDeleteParams,
+        SortParams,
+        (
+            "filter[column]" = Option<String>,
+            Query,
+            description = "Only rows whose `column` equals the value; repeatable"
+        ),
+        FormatParams,
+    ),
+    responses(
+        (
+            status = 200,
+            description = "OK",
+            content((TablePage = "application/json"), (String = "text/csv"))
+        ),
+        (status = 400, description = "Invalid parameters"),
+        (status = 403, description = "CSV isn't available on the caller's tier"),
+        (status = 404, description = "No such table"),
+    )
+)]
 #[get("/tables/{table}")]
 pub async fn get_table(
+    req: HttpRequest,
     path: web::Path<String>,
     query: web::Query<PaginationParams>,
+    deleted: web::Query<SoftDeleteParams>,
+    sort: web::Query<SortParams>,
+    filters: web::Query<FilterParams>,
     data: web::Data<AppState>,
 ) -> impl Responder {
     let table = path.into_inner();
-    if let Some(response) = check_table_exists(&table, &data.tables) {
+    let catalog = data.tables.borrow().clone();
+    if let Some(response) = check_table_exists(&table, &catalog.tables) {
+        return response;
+    }
+    if let Some(response) = check_include_deleted(&req, &deleted) {
         return response;
     }
+    let format = match response_format(&req) {
+        Ok(format) => format,
+        Err(error) => return problem(StatusCode::BAD_REQUEST, error),
+    };
+    if format == Format::Csv {
+        if let Some(response) = require_exports(&req, &data) {
+            return response;
+        }
+    }
+    data.usage
+        .record_table_read(&table, &caller(&req).id(), TableRead::Page);
+    let live = live_rows(&deleted, &data, &table);
 
-    let count_query = format!("SELECT COUNT(*) FROM {table}");
-    match data.pool.get().await {
-        Ok(client) => match client.query_one(&count_query, &[]).await {
-            Ok(count_row) => {
-                let total_count: i64 = count_row.get(0);
-                let pagination = Pagination::new(query, total_count);
-
-                let data_query = format!("SELECT * FROM {table} LIMIT $1 OFFSET $2");
-                match client
-                    .query(&data_query, &[&pagination.limit, &pagination.offset])
-                    .await
-                {
-                    Ok(rows) => {
-                        let columns = get_column_names(&rows);
-                        let data = rows_to_json(&rows);
-                        let response = PaginatedResponse {
-                            table,
-                            total_count,
-                            page: pagination.page,
-                            limit: pagination.limit,
-                            total_pages: pagination.total_pages,
-                            columns,
-                            data,
-                        };
-                        HttpResponse::Ok().json(response)
-                    }
-                    Err(e) => {
-                        log::error!("Database query error: {e}");
-                        HttpResponse::InternalServerError().json(json!({
-                            "error": "An error occurred while querying the database"
-                        }))
-                    }
-                }
+    let columns: Vec<&str> = catalog
+        .columns
+        .get(&table)
+        .map(|columns| columns.iter().map(String::as_str).collect())
+        .unwrap_or_default();
+    let keyset = match Keyset::from_query(&query) {
+        Ok(keyset) => keyset,
+        Err(error) => return problem(StatusCode::BAD_REQUEST, error),
+    };
+    // Only known column names get through, so quoting them is enough
+    let order = match (sort.parse(&columns), &keyset) {
+        (Err(error), _) => return problem(StatusCode::BAD_REQUEST, error),
+        (Ok(Some(_)), Some(_)) => {
+            return problem(
+                StatusCode::BAD_REQUEST,
+                "sort can't be combined with cursor, which pages by id",
+            )
+        }
+        (Ok(Some(sort)), None) => format!(
+            "ORDER BY {}",
+            sort.order_by(|column| format!("t.\"{column}\""))
+        ),
+        (Ok(None), Some(_)) if !columns.contains(&"id") => {
+            return problem(
+                StatusCode::BAD_REQUEST,
+                format!("Table '{table}' has no id to page by cursor"),
+            )
+        }
+        (Ok(None), Some(_)) => "ORDER BY t.id".to_string(),
+        (Ok(None), None) => String::new(),
+    };
+    let filters = match filters.parse(&columns) {
+        Ok(filters) => filters,
+        Err(error) => return problem(StatusCode::BAD_REQUEST, error),
+    };
+    // Filters take the first parameters, then the page's
+    let n = filters.len();
+    let conditions = filters.conditions(|column| format!("t.\"{column}\""));
+    let after = match keyset {
+        Some(Keyset::After(_)) => format!("AND t.id > ${}", n + 3),
+        _ => String::new(),
+    };
+
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+
+    // Filter values bind as whatever types their columns are, read off the
+    // prepared count
+    let count_query = format!("SELECT COUNT(*) FROM {table} t WHERE {live}{conditions}");
+    let count_statement = match timed("table_prepare", client.prepare_cached(&count_query)).await {
+        Ok(statement) => statement,
+        Err(e) => {
+            log::error!("Database prepare error: {e}");
+            return database_error(&e);
+        }
+    };
+    let mut values = Vec::with_capacity(filters.len());
+    for (filter, ty) in filters.0.iter().zip(count_statement.params()) {
+        match bind_as(&filter.value, ty) {
+            Some(value) => values.push(value),
+            None => {
+                return problem(
+                    StatusCode::BAD_REQUEST,
+                    format!(
+                        "Invalid value '{}' for filter on '{}' ({})",
+                        filter.value,
+                        filter.column,
+                        ty.name()
+                    ),
+                )
             }
+        }
+    }
+    let mut params: Vec<&(dyn ToSql + Sync)> = values
+        .iter()
+        .map(|value| value.as_ref() as &(dyn ToSql + Sync))
+        .collect();
+
+    let total_count: i64 =
+        match timed("table_count", client.query_one(&count_statement, &params)).await {
+            Ok(count_row) => count_row.get(0),
             Err(e) => {
                 log::error!("Database count query error: {e}");
-                HttpResponse::InternalServerError().json(json!({
-                    "error": "An error occurred while counting rows in the database"
-                }))
+                return database_error(&e);
+            }
+        };
+    let max_limit = data.api_keys.limits(&caller(&req)).response_limit;
+    let pagination = match keyset {
+        Some(_) => Pagination::keyset(query, total_count, max_limit),
+        None => Pagination::new(query, total_count, max_limit),
+    };
+
+    let data_query = format!(
+        "SELECT * FROM {table} t WHERE {live}{conditions} {after} {order} \
+        LIMIT ${} OFFSET ${}",
+        n + 1,
+        n + 2
+    );
+    // Column names come from the statement, so empty pages still report them
+    let statement = match timed("table_prepare", client.prepare_cached(&data_query)).await {
+        Ok(statement) => statement,
+        Err(e) => {
+            log::error!("Database prepare error: {e}");
+            return database_error(&e);
+        }
+    };
+    // The cursor binds as whatever type the table's id is
+    let start = match (&keyset, statement.params().get(n + 2)) {
+        (Some(Keyset::After(id)), Some(ty)) => match Keyset::bind(id, ty) {
+            Some(start) => Some(start),
+            None => {
+                return problem(
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid cursor for table '{table}'"),
+                )
             }
         },
+        _ => None,
+    };
+    params.push(&pagination.limit);
+    params.push(&pagination.offset);
+    if let Some(start) = &start {
+        params.push(start.as_ref());
+    }
+    match timed("table_page", client.query(&statement, &params)).await {
+        Ok(rows) => {
+            let columns = get_column_names(statement.columns());
+            let next_cursor = match rows.last() {
+                Some(last) if keyset.is_some() && rows.len() as i64 == pagination.limit => {
+                    match &row_to_json(last, data.row_format)["id"] {
+                        Value::String(id) => Some(Keyset::cursor(id)),
+                        id => Some(Keyset::cursor(&id.to_string())),
+                    }
+                }
+                _ => None,
+            };
+            let replacement = table_replacement(&data, &table);
+            let mut builder = HttpResponse::Ok();
+            builder.insert_header((VARY, "accept"));
+            match (&keyset, &next_cursor) {
+                (None, _) => {
+                    builder.insert_header(pagination.link(&req));
+                }
+                (Some(_), Some(cursor)) => {
+                    builder.insert_header(pagination.next_link(&req, cursor));
+                }
+                (Some(_), None) => {}
+            }
+            if let Some(replacement) = replacement {
+                builder
+                    .insert_header(("Deprecation", "true"))
+                    .append_header(replacement.link(None));
+            }
+            // A CSV page is only the rows; its links still page through them
+            if format == Format::Csv {
+                return builder.content_type(format.content_type()).body(rows_body(
+                    columns,
+                    rows,
+                    data.row_format,
+                ));
+            }
+            builder.json(TablePage {
+                total_count,
+                page: pagination.page,
+                limit: pagination.limit,
+                total_pages: pagination.total_pages,
+                columns,
+                data: rows_to_json(&rows, data.row_format),
+                next_cursor,
+                deprecation: replacement.map(TableReplacement::notice),
+                table,
+            })
+        }
         Err(e) => {
-            log::error!("Failed to get database connection: {e}");
-            HttpResponse::InternalServerError().body("Failed to get database connection")
+            log::error!("Database query error: {e}");
+            database_error(&e)
         }
     }
 }
 
+#[utoipa::path(
+    tag = "tables",
+    params(
+        ("table" = String, Path, description = "Table name"),
+        ("id" = Uuid, Path, description = "Row id"),
+        SoftDeleteParams,
+    ),
+    responses(
+        (status = 200, description = "The row, keyed by column", body = Value),
+        (status = 404, description = "No such table or row"),
+    )
+)]
 #[get("/tables/{table}/{id}")]
 pub async fn get_table_row(
-    path: web::Path<(String, Uuid)>,
+    req: HttpRequest,
+    path: web::Path<TablePath>,
+    id: PathUuid,
+    deleted: web::Query<SoftDeleteParams>,
     data: web::Data<AppState>,
 ) -> impl Responder {
-    let (table_name, id) = path.into_inner();
+    let table_name = path.into_inner().table;
+    let PathUuid(id) = id;
 
-    if let Some(response) = check_table_exists(&table_name, &data.tables) {
+    if let Some(response) = check_table_exists(&table_name, &data.tables.borrow().tables) {
+        return response;
+    }
+    if let Some(response) = check_include_deleted(&req, &deleted) {
         return response;
     }
+    data.usage
+        .record_table_read(&table_name, &caller(&req).id(), TableRead::Row);
+    let live = live_rows(&deleted, &data, &table_name);
 
-    let query = format!("SELECT * FROM {table_name} WHERE id = $1");
+    let query = format!("SELECT * FROM {table_name} t WHERE id = $1 AND {live}");
 
     match data.pool.get().await {
-        Ok(client) => match client.query_one(&query, &[&id]).await {
-            Ok(row) => {
-                let json = rows_to_json(&[row]);
-                let value = json.first().unwrap();
-                HttpResponse::Ok().json(value)
-            }
-            Err(e) => {
-                if e.as_db_error()
-                    .is_some_and(|db_err| db_err.code() == &SqlState::UNDEFINED_TABLE)
-                {
-                    HttpResponse::NotFound().json(json!({
-                        "error": format!("Table '{}' not found", table_name)
-                    }))
-                } else if e
-                    .as_db_error()
-                    .is_some_and(|e| e.code() == &SqlState::NO_DATA_FOUND)
+        Ok(client) => {
+            let context = format!("{table_name} id '{id}'");
+            match fetch_one(&client, &query, &[&id], &context).await {
+                Ok(row) => {
+                    let mut builder = HttpResponse::Ok();
+                    if let Some(replacement) = table_replacement(&data, &table_name) {
+                        builder
+                            .insert_header(("Deprecation", "true"))
+                            .insert_header(replacement.link(Some(id)));
+                    }
+                    builder.json(row_to_json(&row, data.row_format))
+                }
+                Err(FetchError::NotFound) => not_found(format!(
+                    "No row found with id '{}' in table '{}'",
+                    id, table_name
+                )),
+                Err(FetchError::Db(e))
+                    if e.as_db_error()
+                        .is_some_and(|db_err| db_err.code() == &SqlState::UNDEFINED_TABLE) =>
                 {
-                    HttpResponse::NotFound().json(json!({
-                        "error": format!("No row found with id '{}' in table '{}'", id, table_name)
-                    }))
-                } else {
-                    HttpResponse::InternalServerError().json(json!({
-                        "error": format!("Database error: {}", e)
-                    }))
+                    not_found(format!("Table '{}' not found", table_name))
+                }
+                Err(FetchError::Db(e)) => {
+                    log::error!("Database query error: {e}");
+                    database_error(&e)
+                }
+                Err(e) => {
+                    log::error!("Database query error: {e}");
+                    problem(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
                 }
             }
-        },
+        }
         Err(e) => {
             log::error!("Failed to get database connection: {e}");
-            HttpResponse::InternalServerError().body("Failed to get database connection")
+            no_connection()
         }
     }
 }
 
+#[utoipa::path(
+    tag = "projects",
+    params(
+        ("id" = Uuid, Path, description = "Project id"),
+        ProjectParams,
+        FieldsParams,
+    ),
+    responses(
+        (status = 200, description = "OK", body = ProjectBody),
+        (
+            status = 308,
+            description = "The project was merged into another",
+            headers(("Location" = String, description = "The surviving project"))
+        ),
+        (status = 400, description = "Invalid parameters"),
+        (status = 404, description = "No such project"),
+    )
+)]
 #[get("/project/{id}")]
-pub async fn get_project(path: web::Path<Uuid>, data: web::Data<AppState>) -> impl Responder {
-    // Check if the table exists
-    let id = path.into_inner();
-
-    // Construct the query
-    let query = r#"
-        WITH base AS MATERIALIZED (
-            SELECT
-                c.id,
-                u_homepage.url AS homepage,
-                c.name,
-                COALESCE(tr_latest.rank, '0') AS "teaRank",
-                tr_latest.created_at AS "teaRankCalculatedAt",
-                (
-                SELECT ARRAY_AGG(DISTINCT s.type)
-                FROM canon_packages cp2
-                JOIN packages p2           ON cp2.package_id = p2.id
-                JOIN package_managers pm2  ON p2.package_manager_id = pm2.id
-                JOIN sources s             ON pm2.source_id = s.id
-                WHERE cp2.canon_id = c.id
-                ) AS "packageManagers",
-                (
-                SELECT COUNT(*)::bigint
-                FROM legacy_dependencies ld
-                JOIN canon_packages cp_out ON cp_out.package_id = ld.package_id
-                WHERE cp_out.canon_id = c.id
-                ) AS "dependenciesCount",
-                (
-                SELECT COUNT(*)::bigint
-                FROM legacy_dependencies ld
-                JOIN canon_packages cp_in ON cp_in.package_id = ld.dependency_id
-                WHERE cp_in.canon_id = c.id
-                ) AS "dependentsCount"
-            FROM canons c
-            JOIN urls u_homepage ON c.url_id = u_homepage.id
-            LEFT JOIN LATERAL (
-                SELECT tr.rank, tr.created_at
-                FROM tea_ranks tr
-                WHERE tr.canon_id = c.id
-                ORDER BY tr.created_at DESC
-                LIMIT 1
-            ) tr_latest ON TRUE
-            WHERE c.id = $1
-        )
-        SELECT DISTINCT ON (b.id)
-            b.id                AS "projectId",
-            b.homepage,
-            b.name,
-            u_source.url        AS source,
-            b."teaRank",
-            b."teaRankCalculatedAt",
-            b."packageManagers",
-            b."dependenciesCount",
-            b."dependentsCount"
-        FROM base b
-        JOIN canon_packages cp ON cp.canon_id = b.id
-        JOIN package_urls pu   ON pu.package_id = cp.package_id
-        JOIN urls u_source     ON pu.url_id = u_source.id
-        JOIN url_types ut      ON ut.id = u_source.url_type_id
-        WHERE ut.name = 'source'
-        ORDER BY b.id, b."teaRankCalculatedAt" DESC, u_source.url;"#;
+pub async fn get_project(
+    id: PathUuid,
+    params: web::Query<ProjectParams>,
+    fields: web::Query<FieldsParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let PathUuid(id) = id;
+    let precision = data.rank_precision(params.precision);
+    let fields = match project_fields(&fields, params.view, &data) {
+        Ok(fields) => fields,
+        Err(error) => return problem(StatusCode::BAD_REQUEST, error),
+    };
+
+    match data.pool.get().await {
+        Ok(client) => {
+            project_response(&client, &data, id, precision, &params, fields.as_ref()).await
+        }
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            no_connection()
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "projects",
+    params(
+        ("slug" = String, Path, description = "The project's slug"),
+        ProjectParams,
+        FieldsParams,
+    ),
+    responses(
+        (status = 200, description = "OK", body = ProjectBody),
+        (status = 400, description = "Invalid parameters"),
+        (status = 404, description = "No such project"),
+    )
+)]
+#[get("/project/slug/{slug}")]
+pub async fn get_project_by_slug(
+    path: web::Path<String>,
+    params: web::Query<ProjectParams>,
+    fields: web::Query<FieldsParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let slug = path.into_inner().to_lowercase();
+    let precision = data.rank_precision(params.precision);
+    let fields = match project_fields(&fields, params.view, &data) {
+        Ok(fields) => fields,
+        Err(error) => return problem(StatusCode::BAD_REQUEST, error),
+    };
 
     match data.pool.get().await {
-        Ok(client) => match client.query_one(query, &[&id]).await {
-            Ok(row) => {
-                let json = rows_to_json(&[row]);
-                let value = json.first().unwrap();
-                HttpResponse::Ok().json(value)
+        Ok(client) => match canon_by_slug(&client, &slug).await {
+            Ok(Some(id)) => {
+                project_response(&client, &data, id, precision, &params, fields.as_ref()).await
             }
+            Ok(None) => not_found(format!("No project found with slug '{}'", slug)),
             Err(e) => {
-                if e.as_db_error()
-                    .is_some_and(|e| e.code() == &SqlState::NO_DATA_FOUND)
-                {
-                    HttpResponse::NotFound().json(json!({
-                        "error": format!("No row found with id '{:?}' in table canons", id)
-                    }))
-                } else {
-                    HttpResponse::InternalServerError().json(json!({
-                        "error": format!("Database error: {}", e)
-                    }))
-                }
+                log::error!("Database query error: {e}");
+                database_error(&e)
             }
         },
         Err(e) => {
             log::error!("Failed to get database connection: {e}");
-            HttpResponse::InternalServerError().body("Failed to get database connection")
+            no_connection()
+        }
+    }
+}
+
+/// The fields `?fields` and `?include` select, which are those of the full view
+fn project_fields(
+    params: &FieldsParams,
+    view: ProjectView,
+    data: &AppState,
+) -> Result<Option<Fields>, String> {
+    if view == ProjectView::Compact {
+        if params.fields.is_some() || params.include.is_some() {
+            return Err(
+                "fields and include select from the full view and can't be used with \
+                view=compact"
+                    .to_string(),
+            );
+        }
+        return Ok(None);
+    }
+    params.parse(&PROJECT_FIELDS, data.opt_in_fields())
+}
+
+// Fetches a single canon in the requested view and fields, as of the requested day
+async fn project_response(
+    client: &Client,
+    data: &AppState,
+    id: Uuid,
+    precision: Option<u32>,
+    params: &ProjectParams,
+    fields: Option<&Fields>,
+) -> HttpResponse {
+    let run = match resolve_as_of(client, params.as_of).await {
+        Ok(run) => run,
+        Err(response) => return response,
+    };
+    let live = data.not_deleted("canons", "c");
+    match fetch_project(client, id, params.view, fields, run, &live, data.row_format).await {
+        Ok(Some(mut value)) => {
+            let hydrated = data
+                .package_managers
+                .hydrate(client, std::slice::from_mut(&mut value));
+            if let Err(e) = hydrated.await {
+                log::error!("Database query error: {e}");
+                return database_error(&e);
+            }
+            if let Some(fields) = fields {
+                fields.select(&mut value);
+            }
+            HttpResponse::Ok().json(with_precision(value, precision))
+        }
+        Ok(None) => match resolve_redirects(client, &[id]).await {
+            Ok(redirects) if redirects.contains_key(&id) => {
+                let target = redirects[&id];
+                HttpResponse::PermanentRedirect()
+                    .insert_header((LOCATION, format!("/project/{target}")))
+                    .json(json!({
+                        "redirectTo": target,
+                        "redirectedFrom": id,
+                    }))
+            }
+            Ok(_) => not_found(format!("No row found with id '{}' in table canons", id)),
+            Err(e) => {
+                log::error!("Database query error: {e}");
+                database_error(&e)
+            }
+        },
+        Err(FetchError::Db(e)) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            problem(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error")
         }
     }
 }
 
+#[utoipa::path(
+    tag = "projects",
+    params(
+        ProjectParams,
+        FieldsParams,
+    ),
+    responses(
+        (status = 200, description = "OK", body = Vec<ProjectBody>),
+        (status = 400, description = "Invalid request"),
+    )
+)]
 #[post("/project/batch")]
 pub async fn list_projects_by_id(
+    http: HttpRequest,
     req: web::Json<ProjectBatchRequest>,
+    params: web::Query<ProjectParams>,
+    fields: web::Query<FieldsParams>,
     data: web::Data<AppState>,
 ) -> impl Responder {
     if req.project_ids.is_empty() {
-        return HttpResponse::BadRequest().json(json!({
-            "error": "No project IDs provided"
-        }));
+        return problem(StatusCode::BAD_REQUEST, "No project IDs provided");
+    }
+    let batch_size = data.api_keys.limits(&caller(&http)).batch_size;
+    if req.project_ids.len() > batch_size {
+        return problem(
+            StatusCode::BAD_REQUEST,
+            format!("Too many project IDs (maximum {batch_size} allowed)"),
+        );
     }
+    // Only the full view says when a rank was calculated
+    if req.if_modified_since.is_some() && params.view == ProjectView::Compact {
+        return problem(
+            StatusCode::BAD_REQUEST,
+            "ifModifiedSince needs the full view",
+        );
+    }
+    let fields = match project_fields(&fields, params.view, &data) {
+        Ok(fields) => fields,
+        Err(error) => return problem(StatusCode::BAD_REQUEST, error),
+    };
 
-    // Construct the query
-    let query = r#"
-        SELECT DISTINCT ON (c.id)
-            c.id AS "projectId",
-            u_homepage.url AS homepage,
-            c.name,
-            u_source.url AS source,
-            COALESCE(tr.rank,'0') AS "teaRank",
-            tr.created_at AS "teaRankCalculatedAt",
-            (
-                SELECT ARRAY_AGG(DISTINCT s.type)
-                FROM canon_packages cp2
-                JOIN packages p2 ON cp2.package_id = p2.id
-                JOIN package_managers pm2 ON p2.package_manager_id = pm2.id
-                JOIN sources s ON pm2.source_id = s.id
-                WHERE cp2.canon_id = c.id
-            ) AS "packageManagers"
-        FROM canons c
-        JOIN urls u_homepage ON u_homepage.id = c.url_id
-        JOIN canon_packages cp ON cp.canon_id = c.id
-        JOIN package_urls pu ON pu.package_id = cp.package_id
-        JOIN urls u_source ON pu.url_id = u_source.id
-        JOIN url_types ut ON ut.id = u_source.url_type_id
-        LEFT JOIN tea_ranks tr ON tr.canon_id = c.id
-        WHERE c.id = ANY($1::uuid[]) AND ut.name = 'source'
-        ORDER BY c.id, tr.created_at DESC, u_source.url;"#;
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
 
-    match data.pool.get().await {
-        Ok(client) => match client.query(query, &[&req.project_ids]).await {
-            Ok(rows) => {
-                let json = rows_to_json(&rows);
-                HttpResponse::Ok().json(json)
-            }
-            Err(e) => {
+    // Merged canons are answered with their survivor, marked with `redirectedFrom`
+    let redirects = match resolve_redirects(&client, &req.project_ids).await {
+        Ok(redirects) => redirects,
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            return database_error(&e);
+        }
+    };
+    let ids: Vec<Uuid> = req
+        .project_ids
+        .iter()
+        .map(|id| *redirects.get(id).unwrap_or(id))
+        .collect();
+
+    let run = match resolve_as_of(&client, params.as_of).await {
+        Ok(run) => run,
+        Err(response) => return response,
+    };
+    let live = data.not_deleted("canons", "c");
+    let view = params.view;
+    match fetch_projects(
+        &client,
+        &ids,
+        view,
+        fields.as_ref(),
+        run,
+        &live,
+        data.row_format,
+    )
+    .await
+    {
+        Ok(mut projects) => {
+            if let Err(e) = data.package_managers.hydrate(&client, &mut projects).await {
                 log::error!("Database query error: {e}");
-                HttpResponse::InternalServerError().json(json!({
-                    "error": format!("Database error: {}", e)
-                }))
+                return database_error(&e);
             }
-        },
+            let mut json = embed_redirects(projects, &req.project_ids, &redirects);
+            if let Some(since) = &req.if_modified_since {
+                json = modified_since(json, since);
+            }
+            let mut json = Value::Array(json);
+            if let Some(fields) = &fields {
+                fields.select(&mut json);
+            }
+            let precision = data.rank_precision(params.precision);
+            HttpResponse::Ok().json(with_precision(json, precision))
+        }
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+/// Whether each requested id is a live canon, without the joins of a batch
+/// lookup: for importers validating references. Merged ids are not followed.
+#[utoipa::path(
+    tag = "projects",
+    responses(
+        (
+            status = 200,
+            description = "Whether each id is a live project",
+            body = BTreeMap<Uuid, bool>
+        ),
+        (status = 400, description = "Invalid request"),
+    )
+)]
+#[post("/project/exists")]
+pub async fn projects_exist(
+    req: web::Json<ProjectExistsRequest>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    if req.project_ids.is_empty() {
+        return problem(StatusCode::BAD_REQUEST, "No project IDs provided");
+    }
+    if req.project_ids.len() > MAX_EXISTS_IDS {
+        return problem(
+            StatusCode::BAD_REQUEST,
+            format!("At most {MAX_EXISTS_IDS} project IDs can be checked at once"),
+        );
+    }
+
+    let client = match data.pool.get().await {
+        Ok(client) => client,
         Err(e) => {
             log::error!("Failed to get database connection: {e}");
-            HttpResponse::InternalServerError().body("Failed to get database connection")
+            return no_connection();
+        }
+    };
+
+    let live = data.not_deleted("canons", "c");
+    match existing_canons(&client, &req.project_ids, &live).await {
+        Ok(existing) => {
+            let exists: BTreeMap<Uuid, bool> = req
+                .project_ids
+                .iter()
+                .map(|id| (*id, existing.contains(id)))
+                .collect();
+            HttpResponse::Ok().json(exists)
+        }
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
         }
     }
 }
 
+#[utoipa::path(
+    tag = "projects",
+    params(("name" = String, Path, description = "Name to search for")),
+    responses(
+        (status = 200, description = "OK", body = SearchResults),
+        (status = 400, description = "Invalid name"),
+    )
+)]
 #[get("/project/search/{name}")]
 pub async fn list_projects_by_name(
     path: web::Path<String>,
@@ -355,240 +901,861 @@ pub async fn list_projects_by_name(
     let name = path.into_inner();
 
     if name.trim().is_empty() {
-        return HttpResponse::BadRequest().json(json!({
-            "error": "Search name cannot be empty"
-        }));
+        return problem(StatusCode::BAD_REQUEST, "Search name cannot be empty");
     }
 
-    let wildcard = format!("%{name}%");
-
-    // Construct the query
-    let query = r#"
-        SELECT *
-        FROM (
-            SELECT DISTINCT ON (c.id)
-                c.id AS "projectId",
-                u_homepage.url AS homepage,
-                c.name,
-                u_source.url AS source,
-                (
-                    SELECT ARRAY_AGG(DISTINCT s.type)
-                    FROM canon_packages cp2
-                    JOIN packages p2 ON cp2.package_id = p2.id
-                    JOIN package_managers pm2 ON p2.package_manager_id = pm2.id
-                    JOIN sources s ON pm2.source_id = s.id
-                    WHERE cp2.canon_id = c.id
-                ) AS "packageManagers"
-            FROM canons c
-            JOIN urls u_homepage ON c.url_id = u_homepage.id
-            JOIN canon_packages cp ON cp.canon_id = c.id
-            JOIN package_urls pu ON pu.package_id = cp.package_id
-            JOIN urls u_source ON pu.url_id = u_source.id
-            JOIN url_types ut_source ON ut_source.id = u_source.url_type_id
-            WHERE ut_source.name = 'source' AND (c.name ILIKE $1)
-            ORDER BY c.id
-        ) sub
-        ORDER BY LENGTH(name), name
-        LIMIT 10;"#;
-
+    let live = data.not_deleted("canons", "c");
     match data.pool.get().await {
-        Ok(client) => match client.query(query, &[&wildcard]).await {
-            Ok(rows) => {
-                let json = rows_to_json(&rows);
-                HttpResponse::Ok().json(json)
-            }
+        Ok(client) => match search_projects(&client, &name, &live, data.row_format).await {
+            Ok(json) => HttpResponse::Ok().json(json),
             Err(e) => {
                 log::error!("Database query error: {e}");
-                HttpResponse::InternalServerError().json(json!({
-                    "error": format!("Database error: {e}")
-                }))
+                database_error(&e)
             }
         },
         Err(e) => {
             log::error!("Failed to get database connection: {e}");
-            HttpResponse::InternalServerError().body("Failed to get database connection")
+            no_connection()
         }
     }
 }
 
+/// Projects named like project `id`, for typosquatting investigations
+#[utoipa::path(
+    tag = "projects",
+    params(
+        ("id" = Uuid, Path, description = "Project id"),
+        PaginationParams,
+    ),
+    responses(
+        (status = 200, description = "OK", body = SimilarNames),
+        (status = 404, description = "No such project"),
+    )
+)]
+#[get("/project/{id}/similar-names")]
+pub async fn list_similar_names(
+    id: PathUuid,
+    params: web::Query<PaginationParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let PathUuid(id) = id;
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+
+    let live = data.not_deleted("canons", "c");
+    let name_query = format!("SELECT c.name FROM canons c WHERE c.id = $1 AND {live}");
+    let name: String =
+        match timed("similar_names_canon", client.query_opt(&name_query, &[&id])).await {
+            Ok(Some(row)) => row.get("name"),
+            Ok(None) => return not_found(format!("No row found with id '{id}' in table canons")),
+            Err(e) => {
+                log::error!("Database query error: {e}");
+                return database_error(&e);
+            }
+        };
+    similar_names_response(&client, &data, name, Some(id), params.limit).await
+}
+
+/// How many maintainers project `id`'s packages have, and whether one of them
+/// maintains all of them: a bus factor next to the project's rank
+#[utoipa::path(
+    tag = "projects",
+    params(("id" = Uuid, Path, description = "Project id")),
+    responses(
+        (status = 200, description = "OK", body = MaintainerConcentration),
+        (status = 404, description = "No such project"),
+    )
+)]
+#[get("/project/{id}/maintainer-concentration")]
+pub async fn get_maintainer_concentration(
+    id: PathUuid,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let PathUuid(id) = id;
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+
+    let live = data.not_deleted("canons", "c");
+    match canon_exists(&client, id, &live).await {
+        Ok(true) => {}
+        Ok(false) => return not_found(format!("No row found with id '{id}' in table canons")),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            return database_error(&e);
+        }
+    }
+
+    let run = *data.latest_run.borrow();
+    match maintainer_concentration(&client, id, run, data.row_format).await {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+/// The projects project `id` depends on, nested `depth` levels deep. A
+/// dependency already on the path from the root is marked as a cycle instead
+/// of being expanded again.
+#[utoipa::path(
+    tag = "projects",
+    params(
+        ("id" = Uuid, Path, description = "Project id"),
+        DependencyTreeParams,
+    ),
+    responses(
+        (status = 200, description = "OK", body = DependencyTree),
+        (status = 400, description = "Invalid depth"),
+        (status = 404, description = "No such project"),
+    )
+)]
+#[get("/project/{id}/dependencies")]
+pub async fn get_dependency_tree(
+    id: PathUuid,
+    params: web::Query<DependencyTreeParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let PathUuid(id) = id;
+    let depth = params.depth.unwrap_or(DEFAULT_TREE_DEPTH);
+    if !(1..=MAX_TREE_DEPTH).contains(&depth) {
+        return problem(
+            StatusCode::BAD_REQUEST,
+            format!("depth must be between 1 and {MAX_TREE_DEPTH}"),
+        );
+    }
+
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+
+    let live = data.not_deleted("canons", "c");
+    match dependency_tree(&client, id, depth, &live).await {
+        Ok(Some(tree)) => HttpResponse::Ok().json(tree),
+        Ok(None) => not_found(format!("No row found with id '{id}' in table canons")),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+/// The projects depending directly on project `id`, best ranked first, a page
+/// at a time
+#[utoipa::path(
+    tag = "projects",
+    params(
+        ("id" = Uuid, Path, description = "Project id"),
+        PaginationParams,
+    ),
+    responses(
+        (status = 200, description = "OK", body = ProjectDependents),
+        (status = 404, description = "No such project"),
+    )
+)]
+#[get("/project/{id}/dependents")]
+pub async fn list_dependents(
+    req: HttpRequest,
+    id: PathUuid,
+    pagination: web::Query<PaginationParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let PathUuid(id) = id;
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+
+    let live = data.not_deleted("canons", "c");
+    match canon_exists(&client, id, &live).await {
+        Ok(true) => {}
+        Ok(false) => return not_found(format!("No row found with id '{id}' in table canons")),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            return database_error(&e);
+        }
+    }
+    let total_count = match count_dependents(&client, id, &live).await {
+        Ok(count) => count,
+        Err(e) => {
+            log::error!("Database count query error: {e}");
+            return database_error(&e);
+        }
+    };
+    let pagination = Pagination::new(
+        pagination,
+        total_count,
+        data.api_keys.limits(&caller(&req)).response_limit,
+    );
+
+    let run = *data.latest_run.borrow();
+    match dependents(
+        &client,
+        id,
+        run,
+        pagination.limit,
+        pagination.offset,
+        &live,
+        data.row_format,
+    )
+    .await
+    {
+        Ok(dependents) => HttpResponse::Ok()
+            .insert_header(pagination.link(&req))
+            .json(ProjectDependents {
+                project_id: id,
+                run,
+                total_count,
+                page: pagination.page,
+                limit: pagination.limit,
+                total_pages: pagination.total_pages,
+                dependents,
+            }),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+/// Projects named like `name`, e.g. before publishing a package under it
+#[utoipa::path(
+    tag = "projects",
+    params(CheckNameParams),
+    responses(
+        (status = 200, description = "OK", body = SimilarNames),
+        (status = 400, description = "Empty name"),
+    )
+)]
+#[get("/check-name")]
+pub async fn check_name(
+    params: web::Query<CheckNameParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let name = params.name.trim();
+    if name.is_empty() {
+        return problem(StatusCode::BAD_REQUEST, "name cannot be empty");
+    }
+
+    match data.pool.get().await {
+        Ok(client) => {
+            similar_names_response(&client, &data, name.to_string(), None, params.limit).await
+        }
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            no_connection()
+        }
+    }
+}
+
+async fn similar_names_response(
+    client: &Client,
+    data: &AppState,
+    name: String,
+    exclude: Option<Uuid>,
+    limit: Option<i64>,
+) -> HttpResponse {
+    let limit = limit.unwrap_or(20).clamp(1, MAX_SIMILAR_NAMES) as usize;
+    let run = *data.latest_run.borrow();
+    let live = data.not_deleted("canons", "c");
+    match similar_names(client, &name, exclude, run, limit, &live, data.row_format).await {
+        Ok(matches) => HttpResponse::Ok().json(SimilarNames { name, run, matches }),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "projects",
+    params(("id" = Uuid, Path, description = "Project id")),
+    responses(
+        (status = 200, description = "OK", body = ProjectFunding),
+        (status = 404, description = "No such project"),
+    )
+)]
+#[get("/project/{id}/funding")]
+pub async fn get_project_funding(id: PathUuid, data: web::Data<AppState>) -> impl Responder {
+    let PathUuid(id) = id;
+
+    let counters = &data.cache_metrics.funding;
+    match data.funding_cache.get(&id) {
+        Some(entry) if !entry.is_expired() => {
+            counters.record(Lookup::Hit);
+            return HttpResponse::Ok().json(entry.data.as_ref());
+        }
+        Some(_) => counters.record(Lookup::Expired),
+        None => counters.record(Lookup::Miss),
+    }
+
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+
+    let live = data.not_deleted("canons", "c");
+    match project_funding(&client, id, &live).await {
+        Ok(Some(value)) => {
+            data.funding_cache
+                .insert(id, ProjectCacheEntry::new(value.clone()));
+            HttpResponse::Ok().json(value)
+        }
+        Ok(None) => not_found(format!("No row found with id '{}' in table canons", id)),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "maintainers",
+    params(
+        ("name" = String, Path, description = "Maintainer name"),
+        PrecisionParams,
+    ),
+    responses(
+        (status = 200, description = "OK", body = MaintainerProjects),
+    )
+)]
+#[get("/maintainer/{name}/projects")]
+pub async fn list_maintainer_projects(
+    path: web::Path<String>,
+    params: web::Query<PrecisionParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let maintainer = path.into_inner();
+    let live = data.not_deleted("canons", "c");
+
+    match data.pool.get().await {
+        Ok(client) => {
+            match maintainer_projects(&client, &maintainer, &live, data.row_format).await {
+                Ok(projects) => {
+                    let precision = data.rank_precision(params.precision);
+                    HttpResponse::Ok().json(MaintainerProjects {
+                        project_count: projects.len(),
+                        projects: projects
+                            .into_iter()
+                            .map(|project| with_precision(project, precision))
+                            .collect(),
+                        maintainer,
+                    })
+                }
+                Err(e) => {
+                    log::error!("Database query error: {e}");
+                    database_error(&e)
+                }
+            }
+        }
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            no_connection()
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "leaderboard",
+    params(
+        PrecisionParams,
+        AsOfParams,
+        SortParams,
+        FieldsParams,
+        FormatParams,
+    ),
+    responses(
+        (
+            status = 200,
+            description = "OK",
+            content((Vec<LeaderboardEntry> = "application/json"), (String = "text/csv"))
+        ),
+        (status = 400, description = "Invalid request"),
+        (status = 403, description = "CSV isn't available on the caller's tier"),
+    )
+)]
 #[post("/leaderboard")]
 pub async fn get_leaderboard(
+    http: HttpRequest,
     req: web::Json<LeaderboardRequest>,
+    params: web::Query<PrecisionParams>,
+    as_of: web::Query<AsOfParams>,
+    sort: web::Query<SortParams>,
+    fields: web::Query<FieldsParams>,
     data: web::Data<AppState>,
 ) -> impl Responder {
-    let limit = req.limit.clamp(1, RESPONSE_LIMIT);
+    let limits = data.api_keys.limits(&caller(&http));
+    let limit = req.limit.clamp(1, limits.response_limit);
+    let precision = data.rank_precision(params.precision);
+    let sort = match sort.parse(&LEADERBOARD_SORT_FIELDS) {
+        Ok(sort) => sort,
+        Err(error) => return problem(StatusCode::BAD_REQUEST, error),
+    };
+    let fields = match fields.parse(&LEADERBOARD_ENTRY_FIELDS, data.opt_in_fields()) {
+        Ok(fields) => fields,
+        Err(error) => return problem(StatusCode::BAD_REQUEST, error),
+    };
+    let format = match response_format(&http) {
+        Ok(format) => format,
+        Err(error) => return problem(StatusCode::BAD_REQUEST, error),
+    };
+    if format == Format::Csv {
+        if let Some(response) = require_exports(&http, &data) {
+            return response;
+        }
+    }
 
+    let filter =
+        match LeaderboardFilter::new(req.name_prefix.as_deref(), req.source_host.as_deref()) {
+            Ok(filter) => filter,
+            Err(error) => return problem(StatusCode::BAD_REQUEST, error),
+        };
     let Some(project_ids) = req.project_ids.as_deref() else {
-        return get_top_projects(data, limit).await;
+        let top = get_top_projects(data, as_of.as_of, limit, &filter, sort.as_ref()).await;
+        return match top {
+            Ok(projects) => leaderboard_page(projects, fields.as_ref(), precision, format),
+            Err(response) => response,
+        };
     };
+    if !filter.is_empty() {
+        return problem(
+            StatusCode::BAD_REQUEST,
+            "namePrefix and sourceHost filter the top of the leaderboard and can't be \
+                combined with projectIds",
+        );
+    }
 
-    if project_ids.len() > RESPONSE_LIMIT as usize {
-        return HttpResponse::BadRequest().json(json!({
-            "error": format!("Too many project IDs (maximum {} allowed)", RESPONSE_LIMIT)
-        }));
+    if project_ids.len() > limits.batch_size {
+        return problem(
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Too many project IDs (maximum {} allowed)",
+                limits.batch_size
+            ),
+        );
     }
 
-    // Get cached projects and identify missing ones
-    let (cached_projects, missing_ids) =
-        get_cached_projects(data.project_cache.clone(), project_ids);
+    // The cache holds current ranks, so past ones always come from the database
+    let (cached_projects, missing_ids) = if as_of.as_of.is_some() {
+        (Vec::new(), project_ids.to_vec())
+    } else {
+        // Get cached projects and identify missing ones
+        let (cached_projects, missing_ids, stale_ids) = get_cached_projects(
+            data.project_cache.clone(),
+            project_ids,
+            data.cache_max_staleness,
+            &data.cache_metrics.project,
+        );
+
+        // Serve stale entries now and refresh them off the request path
+        if !stale_ids.is_empty() {
+            refresh_stale_projects(data.clone(), stale_ids);
+        }
+        (cached_projects, missing_ids)
+    };
 
     // If we have all projects cached, return them sorted
     if missing_ids.is_empty() {
-        return sort_truncate_and_return(cached_projects, limit);
-    }
-
-    // Query for missing projects
-    let query = r#"
-        SELECT *
-        FROM (
-            SELECT DISTINCT ON (c.id)
-                c.id AS "projectId",
-                u_homepage.url AS homepage,
-                c.name,
-                u_source.url AS source,
-                COALESCE(tr.rank,'0') AS "teaRank",
-                tr.created_at AS "teaRankCalculatedAt",
-                (
-                    SELECT ARRAY_AGG(DISTINCT s.type)
-                    FROM canon_packages cp2
-                    JOIN packages p2 ON cp2.package_id = p2.id
-                    JOIN package_managers pm2 ON p2.package_manager_id = pm2.id
-                    JOIN sources s ON pm2.source_id = s.id
-                    WHERE cp2.canon_id = c.id
-                ) AS "packageManagers"
-            FROM canons c
-            JOIN urls u_homepage ON c.url_id = u_homepage.id
-            JOIN canon_packages cp ON cp.canon_id = c.id
-            JOIN package_urls pu ON pu.package_id = cp.package_id
-            JOIN urls u_source ON pu.url_id = u_source.id
-            JOIN url_types ut_source ON ut_source.id = u_source.url_type_id
-            LEFT JOIN tea_ranks tr ON tr.canon_id = c.id
-            WHERE
-            c.id = ANY($1::uuid[])
-            AND ut_source.name = 'source'
-            AND CAST(tr.rank AS NUMERIC) > 0
-            ORDER BY c.id, tr.created_at DESC, u_source.url
-        ) sub
-        ORDER BY CAST("teaRank" AS NUMERIC) DESC NULLS LAST
-        LIMIT $2"#;
-
-    match data.pool.get().await {
-        Ok(client) => match client.query(query, &[&missing_ids, &limit]).await {
-            Ok(rows) => {
-                let fresh_projects = rows_to_json(&rows);
-
-                // Cache the fresh projects
-                for project in &fresh_projects {
-                    if let Some(project_id) = project.get("projectId").and_then(|v| v.as_str()) {
-                        if let Ok(uuid) = Uuid::parse_str(project_id) {
-                            data.project_cache.insert(
-                                uuid,
-                                crate::app_state::ProjectCacheEntry::new(project.clone()),
-                            );
-                        } else {
-                            log::warn!("Failed to parse project ID as UUID: {}", project_id);
-                        }
-                    } else {
-                        log::warn!("No projectId found in project: {:?}", project);
-                    }
-                }
-
-                // Combine cached and fresh projects - keep Arc<Value> for cached ones
-                let mut all_projects: Vec<Arc<Value>> = cached_projects;
+        let projects = best_ranked(cached_projects, limit, sort.as_ref());
+        // Round and select only on the way out so cached values stay whole
+        return leaderboard_page(projects, fields.as_ref(), precision, format);
+    }
 
-                // Convert fresh projects to Arc<Value> to match the type
-                let fresh_arcs: Vec<Arc<Value>> =
-                    fresh_projects.into_iter().map(Arc::new).collect();
-                all_projects.extend(fresh_arcs);
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+    let run = match resolve_as_of(&client, as_of.as_of).await {
+        Ok(run) => run,
+        Err(response) => return response,
+    };
 
-                sort_truncate_and_return(all_projects, limit)
-            }
-            Err(e) => {
+    let live = data.not_deleted("canons", "c");
+    match leaderboard_projects(
+        &client,
+        &missing_ids,
+        limit,
+        run,
+        &live,
+        req.include_unranked,
+        data.row_format,
+    )
+    .await
+    {
+        Ok(mut projects) => {
+            if let Err(e) = data.package_managers.hydrate(&client, &mut projects).await {
                 log::error!("Database query error: {e}");
-                HttpResponse::InternalServerError().json(json!({
-                    "error": format!("Database error: {}", e)
-                }))
+                return database_error(&e);
             }
-        },
+            let (fresh_projects, unranked): (Vec<Value>, Vec<Value>) = projects
+                .into_iter()
+                .partition(|project| rank_of(project) > 0.0);
+
+            // Cache the fresh projects; unranked ones are only served on
+            // request
+            if run.is_none() {
+                cache_projects(&data.project_cache, &fresh_projects);
+            }
+            let fresh_projects =
+                fresh_projects
+                    .into_iter()
+                    .chain(unranked.into_iter().map(|mut project| {
+                        project["unranked"] = Value::Bool(true);
+                        project
+                    }));
+
+            // Combine cached and fresh projects - keep Arc<Value> for cached ones
+            let mut all_projects: Vec<Arc<Value>> = cached_projects;
+            all_projects.extend(fresh_projects.map(Arc::new));
+
+            let projects = best_ranked(all_projects, limit, sort.as_ref());
+            leaderboard_page(projects, fields.as_ref(), precision, format)
+        }
         Err(e) => {
-            log::error!("Failed to get database connection: {e}");
-            HttpResponse::InternalServerError().body("Failed to get database connection")
+            log::error!("Database query error: {e}");
+            database_error(&e)
         }
     }
 }
 
-// Helper function to sort, truncate, and return the final response
-fn sort_truncate_and_return(projects: Vec<Arc<Value>>, limit: i64) -> actix_web::HttpResponse {
-    let mut projects = projects;
+/// Canons ranked in a run that had no rank in the run before it, best first,
+/// with the position each one entered at.
+#[utoipa::path(
+    tag = "leaderboard",
+    params(
+        NewEntrantsParams,
+        SortParams,
+    ),
+    responses(
+        (status = 200, description = "OK", body = NewEntrants),
+        (status = 400, description = "Invalid parameters"),
+        (status = 404, description = "No such run"),
+    )
+)]
+#[get("/leaderboard/new")]
+pub async fn list_new_entrants(
+    req: HttpRequest,
+    params: web::Query<NewEntrantsParams>,
+    sort: web::Query<SortParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let response_limit = data.api_keys.limits(&caller(&req)).response_limit;
+    let limit = params.limit.unwrap_or(100).clamp(1, response_limit);
+    let precision = data.rank_precision(params.precision);
+    let sort = match sort.parse(&NEW_ENTRANT_SORT_FIELDS) {
+        Ok(sort) => sort,
+        Err(error) => return problem(StatusCode::BAD_REQUEST, error),
+    };
 
-    // Sort projects by teaRank (descending) - Arc<Value> derefs to Value
-    projects.sort_by(|a, b| {
-        let rank_a = a
-            .get("teaRank")
-            .and_then(|v| v.as_str())
-            .and_then(|s| s.parse::<i64>().ok())
-            .unwrap_or(0);
-        let rank_b = b
-            .get("teaRank")
-            .and_then(|v| v.as_str())
-            .and_then(|s| s.parse::<i64>().ok())
-            .unwrap_or(0);
-        rank_b.cmp(&rank_a)
-    });
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
 
-    // Apply limit
-    projects.truncate(limit as usize);
+    let latest = *data.latest_run.borrow();
+    let run = match select_run(&client, params.run, params.as_of, latest).await {
+        Ok(run) => run,
+        Err(response) => return response,
+    };
+    // No runs yet means nobody is new
+    let Some(run) = run else {
+        return HttpResponse::Ok().json(NewEntrants::<Value> {
+            run: None,
+            previous_run: None,
+            projects: Vec::new(),
+        });
+    };
 
-    // Convert to Vec<Value> only for the final response - Arc<Value> doesn't implement Serialize
-    let final_projects: Vec<Value> = projects
+    let previous = match previous_run(&client, run).await {
+        Ok(Some(previous)) => previous,
+        Ok(None) => return not_found(format!("Run {run} not found")),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            return database_error(&e);
+        }
+    };
+
+    let live = data.not_deleted("canons", "c");
+    match new_entrants(
+        &client,
+        run,
+        previous,
+        limit,
+        &live,
+        sort.as_ref(),
+        data.row_format,
+    )
+    .await
+    {
+        Ok(projects) => HttpResponse::Ok().json(NewEntrants {
+            run: Some(run),
+            previous_run: previous,
+            projects: projects
+                .into_iter()
+                .map(|project| with_precision(project, precision))
+                .collect(),
+        }),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+/// Re-queries stale cache entries in the background. Ids already being refreshed
+/// are skipped, and projects that no longer qualify are evicted.
+fn refresh_stale_projects(data: web::Data<AppState>, stale_ids: Vec<Uuid>) {
+    let ids: Vec<Uuid> = stale_ids
         .into_iter()
-        .map(|arc_val| (*arc_val).clone())
+        .filter(|id| data.refreshing.insert(*id))
         .collect();
-    actix_web::HttpResponse::Ok().json(final_projects)
+    if ids.is_empty() {
+        return;
+    }
+
+    actix_web::rt::spawn(async move {
+        let live = data.not_deleted("canons", "c");
+        let limit = ids.len() as i64;
+        let result = match data.pool.get().await {
+            Ok(client) => {
+                let fresh = async {
+                    let mut projects = leaderboard_projects(
+                        &client,
+                        &ids,
+                        limit,
+                        None,
+                        &live,
+                        false,
+                        data.row_format,
+                    )
+                    .await?;
+                    data.package_managers
+                        .hydrate(&client, &mut projects)
+                        .await?;
+                    Ok::<_, tokio_postgres::Error>(projects)
+                };
+                fresh.await.map_err(|e| e.to_string())
+            }
+            Err(e) => Err(e.to_string()),
+        };
+
+        match result {
+            Ok(fresh_projects) => {
+                cache_projects(&data.project_cache, &fresh_projects);
+                for id in &ids {
+                    data.project_cache
+                        .remove_if(id, |_, entry| entry.is_expired());
+                }
+            }
+            Err(e) => log::error!("Failed to refresh {} stale projects: {e}", ids.len()),
+        }
+
+        for id in &ids {
+            data.refreshing.remove(id);
+        }
+    });
 }
 
-async fn get_top_projects(data: web::Data<AppState>, limit: i64) -> HttpResponse {
+/// A page of leaderboard entries with only `fields`, ranks rounded to
+/// `precision`, as `format`
+fn leaderboard_page(
+    projects: Vec<Value>,
+    fields: Option<&Fields>,
+    precision: Option<u32>,
+    format: Format,
+) -> HttpResponse {
+    let mut json = Value::Array(projects);
+    if let Some(fields) = fields {
+        fields.select(&mut json);
+    }
+    let json = with_precision(json, precision);
+    match format {
+        Format::Json => HttpResponse::Ok().json(json),
+        // Markers like `unranked` aren't fields, so CSV leaves them out
+        Format::Csv => {
+            let columns = LEADERBOARD_ENTRY_FIELDS
+                .iter()
+                .filter(|field| fields.is_none_or(|fields| fields.contains(field)))
+                .map(|field| field.to_string())
+                .collect();
+            HttpResponse::Ok()
+                .content_type(format.content_type())
+                .body(values_body(columns, json))
+        }
+    }
+}
+
+/// The top of the leaderboard, or the response to fail with
+async fn get_top_projects(
+    data: web::Data<AppState>,
+    as_of: Option<NaiveDate>,
+    limit: i64,
+    filter: &LeaderboardFilter,
+    sort: Option<&Sort>,
+) -> Result<Vec<Value>, HttpResponse> {
     // get client
     let Ok(client) = data.pool.get().await else {
-        return HttpResponse::InternalServerError().body("Failed to get database connection");
-    };
-
-    // get latest run id
-    let run_query = r#"SELECT MAX(run) from tea_rank_runs"#;
-    let Ok(run_row) = client.query_one(run_query, &[]).await else {
-        return HttpResponse::InternalServerError().body("Failed to get latest run");
-    };
-    let run: i32 = run_row.get(0);
-
-    // get top projects (1-RESPONSE_LIMIT)
-    let top_ranks_query = r#"SELECT
-            canon_id as "projectId",
-            name,
-            rank as "teaRank",
-            (
-                SELECT ARRAY_AGG(DISTINCT s.type)
-                FROM canon_packages cp2
-                JOIN packages p2 ON cp2.package_id = p2.id
-                JOIN package_managers pm2 ON p2.package_manager_id = pm2.id
-                JOIN sources s ON pm2.source_id = s.id
-                WHERE cp2.canon_id = canon_id
-            ) AS "packageManagers"
-        FROM
-            tea_ranks
-            JOIN canons ON canon_id = canons.id
-        WHERE
-            tea_rank_run = $1
-        ORDER BY
-            rank DESC
-        LIMIT $2"#;
-    let Ok(top_ranks) = client
-        .query(top_ranks_query, &[&run, &limit.clamp(1, RESPONSE_LIMIT)])
+        return Err(no_connection());
+    };
+    let latest = *data.latest_run.borrow();
+    let run = resolve_as_of(&client, as_of).await?.or(latest);
+    // No runs yet is an empty leaderboard, not an error
+    let Some(run) = run else {
+        return Ok(Vec::new());
+    };
+
+    let live = data.not_deleted("canons", "c");
+    let mut projects = top_projects(&client, run, limit, filter, &live, sort, data.row_format)
         .await
-    else {
-        return HttpResponse::InternalServerError().json(json!({
-            "error": "Failed to fetch top ranks"
-        }));
+        .map_err(|e| {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        })?;
+    data.package_managers
+        .hydrate(&client, &mut projects)
+        .await
+        .map_err(|e| {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        })?;
+    Ok(projects)
+}
+
+/// Caches the latest run's top projects ahead of requests for them, as many as
+/// the `free` tier may ask for, returning how many were cached. One pass of
+/// the `leaderboard_warmup` maintenance task.
+pub async fn warm_leaderboard(data: &AppState) -> usize {
+    let Some(run) = *data.latest_run.borrow() else {
+        return 0;
+    };
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Leaderboard warm-up failed to get database connection: {e}");
+            return 0;
+        }
     };
-    let json = rows_to_json(&top_ranks);
-    HttpResponse::Ok().json(json)
+    let live = data.not_deleted("canons", "c");
+    let unfiltered = LeaderboardFilter::default();
+    let limit = data.api_keys.tier_limits(Tier::Free).response_limit;
+    let top = async {
+        let projects = top_projects(
+            &client,
+            run,
+            limit,
+            &unfiltered,
+            &live,
+            None,
+            data.row_format,
+        )
+        .await?;
+        // As on the request path, only ranked projects are cached
+        let mut projects: Vec<Value> = projects
+            .into_iter()
+            .filter(|project| rank_of(project) > 0.0)
+            .collect();
+        data.package_managers
+            .hydrate(&client, &mut projects)
+            .await?;
+        Ok::<_, tokio_postgres::Error>(projects)
+    };
+    match top.await {
+        Ok(projects) => {
+            cache_projects(&data.project_cache, &projects);
+            log::debug!("Warmed {} leaderboard projects", projects.len());
+            projects.len()
+        }
+        Err(e) => {
+            log::error!("Failed to warm the leaderboard: {e}");
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::App;
+    use chai_core::db::Catalog;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use crate::config::Config;
+    use crate::test_state::{test_state, UNREACHABLE_DATABASE_URL};
+
+    #[actix_web::test]
+    async fn csv_needs_exports() {
+        let config = Config {
+            tier_exports: vec![("free".to_string(), false)],
+            ..Config::from_env()
+        };
+        let state = test_state(&config, UNREACHABLE_DATABASE_URL);
+        state.tables.send_replace(Arc::new(Catalog {
+            tables: vec!["canons".to_string()],
+            columns: HashMap::new(),
+        }));
+        let app = init_service(
+            App::new()
+                .app_data(state)
+                .service(get_table)
+                .service(get_leaderboard),
+        )
+        .await;
+
+        let requests = [
+            TestRequest::get().uri("/tables/canons?format=csv"),
+            TestRequest::get()
+                .uri("/tables/canons")
+                .insert_header(("Accept", "text/csv")),
+            TestRequest::post()
+                .uri("/leaderboard")
+                .insert_header(("Accept", "text/csv"))
+                .set_json(json!({ "limit": 10 })),
+        ];
+        for request in requests {
+            let response = call_service(&app, request.to_request()).await;
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        }
+    }
+
+    #[actix_web::test]
+    async fn no_connection_is_a_503_to_retry() {
+        let state = test_state(&Config::from_env(), UNREACHABLE_DATABASE_URL);
+        let app = init_service(App::new().app_data(state).service(heartbeat)).await;
+        let request = TestRequest::get().uri("/heartbeat").to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers().get("Retry-After").unwrap(), "1");
+        assert_eq!(
+            response.headers().get("Content-Type").unwrap(),
+            crate::problem::PROBLEM_JSON
+        );
+    }
 }
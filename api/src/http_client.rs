@@ -0,0 +1,280 @@
+//! The one HTTP client every outbound call goes through: run alerts,
+//! collection webhooks, and maintainer verification. It pools connections,
+//! only reaches hosts [`Egress`] allows, bounds how many requests are in
+//! flight at once and how many go to one host per window, retries transient
+//! failures, and counts outcomes per host for `/admin/http/stats`.
+
+use dashmap::DashMap;
+use reqwest::redirect::Policy;
+use reqwest::{IntoUrl, Method, RequestBuilder, Response, StatusCode};
+use serde_json::{json, Map, Value};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+use crate::config::Config;
+use crate::egress::{Egress, EgressResolver};
+use crate::rate_limit::RateLimiter;
+
+/// How long establishing a connection may take, within the request timeout
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Idle connections kept open per host
+const MAX_IDLE_PER_HOST: usize = 4;
+/// How long an idle connection is kept open
+const IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+/// Wait before the first retry, doubled for each one after
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+/// Redirects followed before a request fails
+const MAX_REDIRECTS: usize = 5;
+
+/// Why an outbound request got no response
+#[derive(Debug)]
+pub enum OutboundError {
+    /// The URL's host isn't one [`Egress`] allows
+    Blocked(String),
+    /// `host` used up its requests for the current window
+    RateLimited(String),
+    /// No slot freed up among the requests in flight within the timeout
+    Busy,
+    Request(reqwest::Error),
+}
+
+impl fmt::Display for OutboundError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Blocked(reason) => write!(f, "{reason}"),
+            Self::RateLimited(host) => write!(f, "too many requests to {host}"),
+            Self::Busy => write!(f, "too many outbound requests in flight"),
+            Self::Request(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for OutboundError {}
+
+#[derive(Default)]
+struct HostStats {
+    requests: u64,
+    failures: u64,
+    retries: u64,
+    rate_limited: u64,
+}
+
+pub struct HttpClient {
+    client: reqwest::Client,
+    egress: Arc<Egress>,
+    timeout: Duration,
+    retries: u32,
+    max_in_flight: usize,
+    in_flight: Semaphore,
+    host_rate_limit: u64,
+    hosts: RateLimiter,
+    stats: DashMap<String, HostStats>,
+}
+
+impl HttpClient {
+    pub fn from_config(config: &Config) -> Result<Self, reqwest::Error> {
+        let egress = Arc::new(Egress::from_config(config));
+        // Redirects are requests too, and may name addresses the resolver
+        // never sees
+        let redirects = Arc::clone(&egress);
+        let redirect = Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if let Err(reason) = redirects.check(attempt.url()) {
+                attempt.error(reason)
+            } else {
+                attempt.follow()
+            }
+        });
+        let client = reqwest::Client::builder()
+            .dns_resolver(Arc::new(EgressResolver(Arc::clone(&egress))))
+            .redirect(redirect)
+            .timeout(config.http_timeout)
+            .connect_timeout(CONNECT_TIMEOUT.min(config.http_timeout))
+            .pool_max_idle_per_host(MAX_IDLE_PER_HOST)
+            .pool_idle_timeout(IDLE_TIMEOUT)
+            .user_agent(concat!("chai-api/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+        Ok(Self {
+            client,
+            egress,
+            timeout: config.http_timeout,
+            retries: config.http_retries,
+            max_in_flight: config.http_max_in_flight,
+            in_flight: Semaphore::new(config.http_max_in_flight),
+            host_rate_limit: config.http_host_rate_limit,
+            hosts: RateLimiter::new(config.http_host_rate_limit, config.http_host_rate_window),
+            stats: DashMap::new(),
+        })
+    }
+
+    pub fn get(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.client.request(Method::GET, url)
+    }
+
+    pub fn post(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.client.request(Method::POST, url)
+    }
+
+    /// Where requests may go
+    pub fn egress(&self) -> &Egress {
+        &self.egress
+    }
+
+    /// Sends `request` once its host is allowed, has requests left in the
+    /// window, and a slot is free, retrying connection failures, timeouts, `429`s, and `5xx`s
+    /// with backoff. Other error statuses are returned as responses.
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, OutboundError> {
+        let mut request = request.build().map_err(OutboundError::Request)?;
+        let host = request.url().host_str().unwrap_or_default().to_string();
+        self.count(&host, |stats| stats.requests += 1);
+        if let Err(reason) = self.egress.check(request.url()) {
+            self.count(&host, |stats| stats.failures += 1);
+            return Err(OutboundError::Blocked(reason));
+        }
+        if !self.hosts.check(&host).0 {
+            self.count(&host, |stats| stats.rate_limited += 1);
+            return Err(OutboundError::RateLimited(host));
+        }
+
+        let mut attempt = 0;
+        loop {
+            // Requests with streamed bodies can't be sent twice
+            let retry = (attempt < self.retries)
+                .then(|| request.try_clone())
+                .flatten();
+            let result = match tokio::time::timeout(self.timeout, self.in_flight.acquire()).await {
+                Ok(Ok(_permit)) => self.client.execute(request).await,
+                _ => {
+                    self.count(&host, |stats| stats.failures += 1);
+                    return Err(OutboundError::Busy);
+                }
+            };
+            match (result, retry) {
+                (result, Some(next)) if transient(&result) => {
+                    self.count(&host, |stats| stats.retries += 1);
+                    tokio::time::sleep(RETRY_BACKOFF * 2u32.pow(attempt)).await;
+                    request = next;
+                    attempt += 1;
+                }
+                (Ok(response), _) => {
+                    if !response.status().is_success() {
+                        self.count(&host, |stats| stats.failures += 1);
+                    }
+                    return Ok(response);
+                }
+                (Err(e), _) => {
+                    self.count(&host, |stats| stats.failures += 1);
+                    return Err(OutboundError::Request(e));
+                }
+            }
+        }
+    }
+
+    fn count(&self, host: &str, update: impl FnOnce(&mut HostStats)) {
+        update(&mut self.stats.entry(host.to_string()).or_default());
+    }
+
+    /// Drops per-host windows that have already reset
+    pub fn sweep(&self) {
+        self.hosts.sweep();
+    }
+
+    pub fn to_json(&self) -> Value {
+        let hosts: Map<String, Value> = self
+            .stats
+            .iter()
+            .map(|entry| {
+                let stats = entry.value();
+                (
+                    entry.key().clone(),
+                    json!({
+                        "requests": stats.requests,
+                        "failures": stats.failures,
+                        "retries": stats.retries,
+                        "rateLimited": stats.rate_limited,
+                    }),
+                )
+            })
+            .collect();
+        json!({
+            "inFlight": self.max_in_flight - self.in_flight.available_permits(),
+            "maxInFlight": self.max_in_flight,
+            "timeoutSecs": self.timeout.as_secs(),
+            "retries": self.retries,
+            "hostRateLimit": {
+                "requests": self.host_rate_limit,
+                "windowSecs": self.hosts.window().as_secs(),
+            },
+            "hosts": hosts,
+        })
+    }
+}
+
+/// Whether a failed attempt may succeed if tried again
+fn transient(result: &Result<Response, reqwest::Error>) -> bool {
+    match result {
+        Ok(response) => {
+            let status = response.status();
+            status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+        }
+        Err(e) => e.is_connect() || e.is_timeout(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn retries_transient_failures_within_host_limits() {
+        let http = HttpClient::from_config(&Config {
+            http_retries: 1,
+            http_host_rate_limit: 2,
+            outbound_allow_private: true,
+            ..Config::from_env()
+        })
+        .unwrap();
+
+        // Answers every request with a 503
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counted = Arc::clone(&attempts);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                counted.fetch_add(1, Ordering::Relaxed);
+                let mut request = [0; 1024];
+                let _ = socket.read(&mut request).await;
+                let _ = socket
+                    .write_all(
+                        b"HTTP/1.1 503 Service Unavailable\r\n\
+                        Content-Length: 0\r\nConnection: close\r\n\r\n",
+                    )
+                    .await;
+            }
+        });
+
+        for _ in 0..2 {
+            let response = http.send(http.post(&url).body("{}")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        }
+        assert_eq!(attempts.load(Ordering::Relaxed), 4);
+        assert!(matches!(
+            http.send(http.post(&url)).await,
+            Err(OutboundError::RateLimited(_))
+        ));
+
+        let stats = &http.to_json()["hosts"]["127.0.0.1"];
+        assert_eq!(stats["requests"], 3);
+        assert_eq!(stats["retries"], 2);
+        assert_eq!(stats["failures"], 2);
+        assert_eq!(stats["rateLimited"], 1);
+    }
+}
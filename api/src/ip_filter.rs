@@ -0,0 +1,139 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::StatusCode,
+    middleware::Next,
+    web, Error, HttpRequest,
+};
+use ipnet::IpNet;
+use std::net::IpAddr;
+
+use crate::app_state::AppState;
+use crate::config::Config;
+use crate::problem::problem;
+
+/// CIDR-based access control evaluated before routing.
+pub struct IpFilter {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+    admin_allow: Vec<IpNet>,
+    trust_proxy_headers: bool,
+}
+
+impl IpFilter {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            allow: config.ip_allowlist.clone(),
+            deny: config.ip_denylist.clone(),
+            admin_allow: config.admin_ip_allowlist.clone(),
+            trust_proxy_headers: config.trust_proxy_headers,
+        }
+    }
+
+    /// Denylisted addresses are always rejected; when an allowlist is set only
+    /// matching addresses get through. `/admin` paths additionally require a
+    /// match in the admin allowlist, if one is configured. `path` must be the
+    /// decoded path the router matches, not the raw request path.
+    pub fn permits(&self, ip: IpAddr, path: &str) -> bool {
+        let matches = |nets: &[IpNet]| nets.iter().any(|net| net.contains(&ip));
+
+        if matches(&self.deny) {
+            return false;
+        }
+        if !self.allow.is_empty() && !matches(&self.allow) {
+            return false;
+        }
+        if is_admin_path(path) && !self.admin_allow.is_empty() {
+            return matches(&self.admin_allow);
+        }
+        true
+    }
+}
+
+/// The client's address: the one proxy headers name when `trust_proxy_headers`,
+/// else the connection's peer. Untrusted, the headers are anyone's to forge.
+pub fn client_ip(req: &HttpRequest, trust_proxy_headers: bool) -> Option<IpAddr> {
+    if trust_proxy_headers {
+        let info = req.connection_info();
+        if let Some(ip) = info.realip_remote_addr().and_then(|addr| addr.parse().ok()) {
+            return Some(ip);
+        }
+    }
+    req.peer_addr().map(|addr| addr.ip())
+}
+
+/// Whether `path` falls under `/admin`, in any letter case
+fn is_admin_path(path: &str) -> bool {
+    path.get(..6)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("/admin"))
+}
+
+pub async fn ip_filter<B: MessageBody + 'static>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let Some(data) = req.app_data::<web::Data<AppState>>().cloned() else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+
+    let ip = client_ip(req.request(), data.ip_filter.trust_proxy_headers);
+    // The router matches the percent-decoded path, so `/%61dmin` reaches `/admin`
+    let path = req.match_info().as_str();
+    let permitted = ip.is_some_and(|ip| data.ip_filter.permits(ip, path));
+    if permitted {
+        return Ok(next.call(req).await?.map_into_left_body());
+    }
+
+    log::warn!(
+        target: "chai_api::audit",
+        "Denied {} {} from {}",
+        req.method(),
+        req.path(),
+        ip.map_or_else(|| "unknown".to_string(), |ip| ip.to_string()),
+    );
+    let response = problem(StatusCode::FORBIDDEN, "Access denied");
+    Ok(req.into_response(response).map_into_right_body())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    fn filter(allow: &[&str], deny: &[&str], admin_allow: &[&str]) -> IpFilter {
+        let nets = |cidrs: &[&str]| cidrs.iter().map(|cidr| cidr.parse().unwrap()).collect();
+        IpFilter {
+            allow: nets(allow),
+            deny: nets(deny),
+            admin_allow: nets(admin_allow),
+            trust_proxy_headers: false,
+        }
+    }
+
+    #[test]
+    fn lists_and_admin_allowlist_apply_in_order() {
+        let ip = |raw: &str| raw.parse::<IpAddr>().unwrap();
+        let open = filter(&[], &[], &[]);
+        assert!(open.permits(ip("203.0.113.7"), "/admin/usage"));
+
+        let filter = filter(&["10.0.0.0/8"], &["10.0.0.13/32"], &["10.1.0.0/16"]);
+        assert!(filter.permits(ip("10.2.0.1"), "/leaderboard"));
+        assert!(!filter.permits(ip("203.0.113.7"), "/leaderboard"));
+        assert!(!filter.permits(ip("10.0.0.13"), "/leaderboard"));
+        assert!(filter.permits(ip("10.1.0.1"), "/admin/usage"));
+        assert!(!filter.permits(ip("10.2.0.1"), "/admin/usage"));
+        assert!(!filter.permits(ip("10.2.0.1"), "/ADMIN/usage"));
+        assert!(!filter.permits(ip("10.2.0.1"), "/admin"));
+    }
+
+    #[test]
+    fn encoded_admin_paths_are_checked_as_routed() {
+        let filter = filter(&[], &[], &["10.1.0.0/16"]);
+        let ip = "10.2.0.1".parse().unwrap();
+        for uri in ["/%61dmin/usage", "/%61%64%6d%69%6e/usage"] {
+            let req = TestRequest::with_uri(uri).to_srv_request();
+            assert_eq!(req.match_info().as_str(), "/admin/usage");
+            assert!(!filter.permits(ip, req.match_info().as_str()));
+        }
+    }
+}
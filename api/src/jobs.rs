@@ -0,0 +1,814 @@
+//! Analyses too slow for a request. `POST /jobs` queues one and returns its id
+//! right away; `GET /jobs/{id}` reports its status and, once done, its result.
+//! Jobs run on a fixed number of workers, highest priority first, and every
+//! state change is written to `JOB_DIR` so results outlive the process until
+//! their retention runs out.
+
+use actix_web::http::header::{HeaderValue, LOCATION, RETRY_AFTER};
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Responder};
+use chai_core::db::timed;
+use chai_core::dependencies::{closure_query, package_match_query};
+use chai_core::leaderboard::{leaderboard_rows, run_exists};
+use chai_core::models::to_json;
+use chai_core::projects::canon_exists;
+use chai_types::jobs::{
+    ClosureJob, ExportJob, Job, JobRequest, JobSpec, JobStatus, Priority, SbomJob, SbomPackage,
+    DEFAULT_CLOSURE_DEPTH, JOB_KINDS,
+};
+use chai_types::models::{ClosureEntry, ExportEntry, SbomMatch};
+use chai_types::tier::Tier;
+use chrono::Utc;
+use dashmap::DashMap;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::fs;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio_postgres::Client;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::auth::caller;
+use crate::config::{ignore_setting, Config};
+use crate::export::{require_exports, write_atomic};
+use crate::extractors::PathUuid;
+use crate::problem::{database_problem, not_found, problem, problem_with};
+
+/// Suggested wait before resubmitting when the queue is full
+const QUEUE_FULL_RETRY_SECS: u64 = 30;
+/// How often finished jobs past their retention are removed
+pub const CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+/// Highest priority `tier` may submit at
+fn max_priority(tier: Tier) -> Priority {
+    match tier {
+        Tier::Free => Priority::Normal,
+        Tier::Partner | Tier::Internal => Priority::High,
+    }
+}
+
+pub enum SubmitError {
+    QueueFull(usize),
+    Io(io::Error),
+}
+
+/// Counts a submitted job as pending until dropped, which also happens when its
+/// task is aborted before it ever ran
+struct PendingSlot(Arc<AtomicUsize>);
+
+impl Drop for PendingSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Hands free workers to queued jobs: the highest [`Priority`] first, then the
+/// highest tier, then the oldest, skipping tiers already at their cap.
+struct Scheduler {
+    state: Mutex<SchedulerState>,
+    workers: usize,
+    /// Most running jobs per tier; tiers not listed are only bound by `workers`
+    tier_caps: HashMap<Tier, usize>,
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    waiting: Vec<Waiting>,
+    running: usize,
+    running_by_tier: HashMap<Tier, usize>,
+    /// Submission order, for ties
+    next_seq: u64,
+}
+
+struct Waiting {
+    priority: Priority,
+    tier: Tier,
+    seq: u64,
+    grant: oneshot::Sender<Turn>,
+}
+
+/// A worker held by one job, handed back when dropped. A turn granted to a job
+/// that was cancelled meanwhile is dropped with the channel and freed the same way.
+struct Turn {
+    scheduler: Arc<Scheduler>,
+    tier: Tier,
+    /// Cleared when the turn never reached its job and was already given back
+    held: bool,
+}
+
+impl Drop for Turn {
+    fn drop(&mut self) {
+        if self.held {
+            let mut state = self
+                .scheduler
+                .state
+                .lock()
+                .expect("scheduler lock poisoned");
+            state.release(self.tier);
+            self.scheduler.dispatch(&mut state);
+        }
+    }
+}
+
+impl SchedulerState {
+    fn release(&mut self, tier: Tier) {
+        self.running -= 1;
+        *self.running_by_tier.entry(tier).or_default() -= 1;
+    }
+}
+
+impl Scheduler {
+    /// Waits until the scheduler gives this job a worker
+    async fn turn(self: &Arc<Self>, priority: Priority, tier: Tier) -> Turn {
+        let (grant, granted) = oneshot::channel();
+        {
+            let mut state = self.state.lock().expect("scheduler lock poisoned");
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.waiting.push(Waiting {
+                priority,
+                tier,
+                seq,
+                grant,
+            });
+            self.dispatch(&mut state);
+        }
+        granted
+            .await
+            .expect("waiting jobs are only dropped once granted")
+    }
+
+    /// Grants free workers to the best waiting jobs whose tier has room
+    fn dispatch(self: &Arc<Self>, state: &mut SchedulerState) {
+        // Jobs cancelled while waiting can't take a worker
+        state.waiting.retain(|waiting| !waiting.grant.is_closed());
+        while state.running < self.workers {
+            let next = state
+                .waiting
+                .iter()
+                .enumerate()
+                .filter(|(_, waiting)| {
+                    self.tier_caps.get(&waiting.tier).is_none_or(|cap| {
+                        state
+                            .running_by_tier
+                            .get(&waiting.tier)
+                            .copied()
+                            .unwrap_or(0)
+                            < *cap
+                    })
+                })
+                .max_by_key(|(_, waiting)| (waiting.priority, waiting.tier, Reverse(waiting.seq)))
+                .map(|(index, _)| index);
+            let Some(index) = next else {
+                break;
+            };
+            let waiting = state.waiting.swap_remove(index);
+            state.running += 1;
+            *state.running_by_tier.entry(waiting.tier).or_default() += 1;
+            let turn = Turn {
+                scheduler: Arc::clone(self),
+                tier: waiting.tier,
+                held: true,
+            };
+            if let Err(mut turn) = waiting.grant.send(turn) {
+                turn.held = false;
+                state.release(waiting.tier);
+            }
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let state = self.state.lock().expect("scheduler lock poisoned");
+        let tiers: Vec<Value> = Tier::ALL
+            .into_iter()
+            .map(|tier| {
+                json!({
+                    "tier": tier,
+                    "waiting": state.waiting.iter().filter(|w| w.tier == tier).count(),
+                    "running": state.running_by_tier.get(&tier).copied().unwrap_or(0),
+                    "cap": self.tier_caps.get(&tier),
+                })
+            })
+            .collect();
+        json!({
+            "workers": self.workers,
+            "busyWorkers": state.running,
+            "tiers": tiers,
+        })
+    }
+}
+
+/// Outcomes and timings of one kind of job since startup
+#[derive(Default)]
+struct KindCounters {
+    succeeded: AtomicU64,
+    failed: AtomicU64,
+    cancelled: AtomicU64,
+    wait_micros: AtomicU64,
+    run_micros: AtomicU64,
+    max_run_micros: AtomicU64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KindStats {
+    pub kind: &'static str,
+    pub queued: usize,
+    pub running: usize,
+    /// Finished jobs still kept
+    pub stored: usize,
+    pub succeeded: u64,
+    pub failed: u64,
+    pub cancelled: u64,
+    /// Mean time from submission to start, over finished jobs
+    pub avg_wait_ms: f64,
+    pub avg_run_ms: f64,
+    pub max_run_ms: f64,
+    pub retention_secs: u64,
+}
+
+/// Submitted jobs, kept in memory and mirrored to `dir/<id>.json`
+pub struct Jobs {
+    dir: PathBuf,
+    jobs: DashMap<Uuid, Job>,
+    /// Tasks of jobs that may still be queued or running
+    tasks: DashMap<Uuid, JoinHandle<()>>,
+    scheduler: Arc<Scheduler>,
+    /// Jobs submitted to this process that have not finished
+    pending: Arc<AtomicUsize>,
+    queue_limit: usize,
+    timeout: Duration,
+    /// How long finished jobs are kept, by kind
+    retention: HashMap<&'static str, Duration>,
+    counters: HashMap<&'static str, KindCounters>,
+}
+
+impl Jobs {
+    pub fn from_config(config: &Config) -> Self {
+        for (kind, _) in &config.job_retention_by_kind {
+            if !JOB_KINDS.contains(&kind.as_str()) {
+                ignore_setting(format_args!(
+                    "Ignoring JOB_RETENTION for unknown job kind {kind:?}"
+                ));
+            }
+        }
+        let retention = JOB_KINDS
+            .into_iter()
+            .map(|kind| {
+                let ttl = config
+                    .job_retention_by_kind
+                    .iter()
+                    .find(|(k, _)| k == kind)
+                    .map_or(config.job_retention, |(_, ttl)| *ttl);
+                (kind, ttl)
+            })
+            .collect();
+        let tier_caps = config
+            .job_tier_concurrency
+            .iter()
+            .filter_map(|(raw, cap)| match raw.parse() {
+                Ok(tier) => Some((tier, *cap)),
+                Err(()) => {
+                    ignore_setting(format_args!(
+                        "Ignoring JOB_TIER_CONCURRENCY for unknown tier {raw:?}"
+                    ));
+                    None
+                }
+            })
+            .collect();
+        Self {
+            dir: config.job_dir.clone(),
+            jobs: DashMap::new(),
+            tasks: DashMap::new(),
+            scheduler: Arc::new(Scheduler {
+                state: Mutex::new(SchedulerState::default()),
+                workers: config.job_workers,
+                tier_caps,
+            }),
+            pending: Arc::new(AtomicUsize::new(0)),
+            queue_limit: config.job_queue_limit,
+            timeout: config.job_timeout,
+            retention,
+            counters: JOB_KINDS
+                .into_iter()
+                .map(|kind| (kind, KindCounters::default()))
+                .collect(),
+        }
+    }
+
+    fn counters(&self, kind: &str) -> &KindCounters {
+        &self.counters[kind]
+    }
+
+    fn path(&self, id: Uuid) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+
+    /// Loads jobs saved by earlier runs. Jobs that were still queued or running
+    /// are marked failed, since nothing will pick them up again.
+    pub async fn restore(&self) -> io::Result<usize> {
+        let mut entries = match fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        let mut restored = 0;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let mut job: Job = match serde_json::from_slice(&fs::read(&path).await?) {
+                Ok(job) => job,
+                Err(e) => {
+                    log::warn!("Skipping unreadable job {}: {e}", path.display());
+                    continue;
+                }
+            };
+            self.jobs.insert(job.id, job.clone());
+            if !job.status.is_finished() {
+                job.status = JobStatus::Failed;
+                job.finished_at = Some(Utc::now());
+                job.error = Some("Interrupted by a server restart".to_string());
+                self.save(&job).await;
+            }
+            restored += 1;
+        }
+        Ok(restored)
+    }
+
+    pub fn get(&self, id: Uuid) -> Option<Job> {
+        self.jobs.get(&id).map(|job| job.clone())
+    }
+
+    /// Records `job` in memory and on disk, unless it was deleted meanwhile.
+    /// Write failures are only logged: the job keeps running and stays readable
+    /// from this process.
+    async fn save(&self, job: &Job) {
+        match self.jobs.get_mut(&job.id) {
+            Some(mut stored) => *stored = job.clone(),
+            None => return,
+        }
+        if let Err(e) = self.write(job).await {
+            log::warn!("Failed to persist job {}: {e}", job.id);
+        }
+    }
+
+    async fn write(&self, job: &Job) -> io::Result<()> {
+        write_atomic(&self.path(job.id), &serde_json::to_vec(job)?).await
+    }
+
+    /// Queues `spec` for `caller`. The job is on disk before this returns, so
+    /// its id is never handed out for a job that would be lost.
+    async fn submit(
+        &self,
+        spec: JobSpec,
+        caller: String,
+        tier: Tier,
+        priority: Priority,
+    ) -> Result<(Job, PendingSlot), SubmitError> {
+        let slot = PendingSlot(Arc::clone(&self.pending));
+        let pending = self.pending.fetch_add(1, Ordering::Relaxed);
+        if pending >= self.queue_limit {
+            return Err(SubmitError::QueueFull(pending));
+        }
+        let job = Job {
+            id: Uuid::new_v4(),
+            spec,
+            caller,
+            tier,
+            priority,
+            status: JobStatus::Queued,
+            created_at: Utc::now(),
+            started_at: None,
+            finished_at: None,
+            result: None,
+            error: None,
+        };
+        self.write(&job).await.map_err(SubmitError::Io)?;
+        self.jobs.insert(job.id, job.clone());
+        Ok((job, slot))
+    }
+
+    /// Removes the job `id`, cancelling it first if it is still queued or
+    /// running. Returns the removed job.
+    async fn delete(&self, id: Uuid) -> Option<Job> {
+        if let Some((_, task)) = self.tasks.remove(&id) {
+            if !task.is_finished() {
+                task.abort();
+                // Wait for the task to drop, so it can't save the job again
+                let _ = task.await;
+            }
+        }
+        let (_, job) = self.jobs.remove(&id)?;
+        if !job.status.is_finished() {
+            self.counters(job.spec.kind())
+                .cancelled
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        match fs::remove_file(self.path(id)).await {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("Failed to delete job file for {id}: {e}"),
+        }
+        Some(job)
+    }
+
+    /// One pass of the `job_cleanup` maintenance task
+    pub async fn clean_up(&self) {
+        let removed = self.remove_expired().await;
+        if removed > 0 {
+            log::info!("Removed {removed} expired jobs");
+        }
+    }
+
+    /// Deletes finished jobs older than their kind's retention
+    async fn remove_expired(&self) -> usize {
+        self.tasks.retain(|_, task| !task.is_finished());
+        let now = Utc::now();
+        let expired: Vec<Uuid> = self
+            .jobs
+            .iter()
+            .filter(|job| {
+                let retention = self.retention[job.spec.kind()];
+                job.finished_at.is_some_and(|finished| {
+                    (now - finished).to_std().unwrap_or_default() > retention
+                })
+            })
+            .map(|job| job.id)
+            .collect();
+        for id in &expired {
+            self.delete(*id).await;
+        }
+        expired.len()
+    }
+
+    /// Queue depth, outcomes and timings per kind
+    pub fn stats(&self) -> Value {
+        let kinds: Vec<KindStats> = JOB_KINDS
+            .into_iter()
+            .map(|kind| {
+                let (mut queued, mut running, mut stored) = (0, 0, 0);
+                for job in self.jobs.iter().filter(|job| job.spec.kind() == kind) {
+                    match job.status {
+                        JobStatus::Queued => queued += 1,
+                        JobStatus::Running => running += 1,
+                        _ => stored += 1,
+                    }
+                }
+                let counters = self.counters(kind);
+                let succeeded = counters.succeeded.load(Ordering::Relaxed);
+                let failed = counters.failed.load(Ordering::Relaxed);
+                let finished = (succeeded + failed).max(1) as f64;
+                let millis = |micros: &AtomicU64| micros.load(Ordering::Relaxed) as f64 / 1000.0;
+                KindStats {
+                    kind,
+                    queued,
+                    running,
+                    stored,
+                    succeeded,
+                    failed,
+                    cancelled: counters.cancelled.load(Ordering::Relaxed),
+                    avg_wait_ms: millis(&counters.wait_micros) / finished,
+                    avg_run_ms: millis(&counters.run_micros) / finished,
+                    max_run_ms: millis(&counters.max_run_micros),
+                    retention_secs: self.retention[kind].as_secs(),
+                }
+            })
+            .collect();
+        let mut stats = self.scheduler.to_json();
+        stats["pending"] = json!(self.pending.load(Ordering::Relaxed));
+        stats["queueLimit"] = json!(self.queue_limit);
+        stats["kinds"] = json!(kinds);
+        stats
+    }
+}
+
+/// Waits for the scheduler to give `job` a worker, then runs it and records how
+/// it ended. The job stays pending until `_slot` drops.
+async fn execute(data: web::Data<AppState>, mut job: Job, _slot: PendingSlot) {
+    let jobs = &data.jobs;
+    let _turn = jobs.scheduler.turn(job.priority, job.tier).await;
+
+    job.status = JobStatus::Running;
+    job.started_at = Some(Utc::now());
+    jobs.save(&job).await;
+
+    let started = Instant::now();
+    match tokio::time::timeout(jobs.timeout, run(&data, &job.spec)).await {
+        Ok(Ok(result)) => {
+            job.status = JobStatus::Succeeded;
+            job.result = Some(result);
+        }
+        Ok(Err(error)) => {
+            job.status = JobStatus::Failed;
+            job.error = Some(error);
+        }
+        Err(_) => {
+            job.status = JobStatus::Failed;
+            job.error = Some(format!(
+                "Timed out after {} seconds",
+                jobs.timeout.as_secs()
+            ));
+        }
+    }
+    job.finished_at = Some(Utc::now());
+    jobs.save(&job).await;
+
+    let run_time = started.elapsed();
+    let wait_time = (job.started_at.unwrap_or(job.created_at) - job.created_at)
+        .to_std()
+        .unwrap_or_default();
+    let counters = jobs.counters(job.spec.kind());
+    let outcome = match job.status {
+        JobStatus::Succeeded => &counters.succeeded,
+        _ => &counters.failed,
+    };
+    outcome.fetch_add(1, Ordering::Relaxed);
+    counters
+        .wait_micros
+        .fetch_add(wait_time.as_micros() as u64, Ordering::Relaxed);
+    counters
+        .run_micros
+        .fetch_add(run_time.as_micros() as u64, Ordering::Relaxed);
+    counters
+        .max_run_micros
+        .fetch_max(run_time.as_micros() as u64, Ordering::Relaxed);
+    log::info!(
+        "Job {} ({}) {:?} after {:?}",
+        job.id,
+        job.spec.kind(),
+        job.status,
+        run_time
+    );
+}
+
+async fn run(data: &AppState, spec: &JobSpec) -> Result<Value, String> {
+    let client = data
+        .pool
+        .get()
+        .await
+        .map_err(|e| format!("Failed to get database connection: {e}"))?;
+    let live = data.not_deleted("canons", "c");
+    let result = match spec {
+        JobSpec::Closure(job) => closure(&client, &live, job).await,
+        JobSpec::Sbom(job) => {
+            let latest = *data.latest_run.borrow();
+            let run = job.run.or(latest);
+            sbom(&client, &live, job, run, run == latest).await
+        }
+        JobSpec::Export(job) => export(&client, job, data).await,
+    };
+    result.map_err(|e| {
+        log::error!("Database query error: {e}");
+        database_problem(&e).1
+    })?
+}
+
+type JobResult = Result<Result<Value, String>, tokio_postgres::Error>;
+
+async fn closure(client: &Client, live: &str, job: &ClosureJob) -> JobResult {
+    let id = job.project_id;
+    if !canon_exists(client, id, live).await? {
+        return Ok(Err(format!("Project {id} not found")));
+    }
+
+    let max_depth = job.max_depth.unwrap_or(DEFAULT_CLOSURE_DEPTH);
+    let rows = timed(
+        "dependency_closure",
+        client.query(&closure_query(live), &[&id, &max_depth]),
+    )
+    .await?;
+    let dependencies = to_json::<ClosureEntry>(&rows, Default::default());
+    Ok(Ok(json!({
+        "projectId": id,
+        "maxDepth": max_depth,
+        "count": dependencies.len(),
+        "dependencies": dependencies,
+    })))
+}
+
+/// `current` when `run` is the latest
+async fn sbom(
+    client: &Client,
+    live: &str,
+    job: &SbomJob,
+    run: Option<i32>,
+    current: bool,
+) -> JobResult {
+    let (managers, names): (Vec<&str>, Vec<&str>) = job
+        .packages
+        .iter()
+        .map(|package| (package.package_manager.as_str(), package.name.as_str()))
+        .unzip();
+    let rows = timed(
+        "sbom_packages",
+        client.query(
+            &package_match_query(live, current),
+            &[&managers, &names, &run],
+        ),
+    )
+    .await?;
+
+    let matched: HashSet<i64> = rows.iter().map(|row| row.get("position")).collect();
+    let unmatched: Vec<&SbomPackage> = job
+        .packages
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !matched.contains(&(*i as i64 + 1)))
+        .map(|(_, package)| package)
+        .collect();
+    Ok(Ok(json!({
+        "run": run,
+        "matched": to_json::<SbomMatch>(&rows, Default::default()),
+        "unmatched": unmatched,
+    })))
+}
+
+async fn export(client: &Client, job: &ExportJob, data: &AppState) -> JobResult {
+    let run = job.run;
+    if !run_exists(client, run).await? {
+        return Ok(Err(format!("Run {run} not found")));
+    }
+    let rows = leaderboard_rows(client, run).await?;
+    Ok(Ok(json!({
+        "run": run,
+        "count": rows.len(),
+        "entries": to_json::<ExportEntry>(&rows, data.row_format),
+    })))
+}
+
+#[utoipa::path(
+    tag = "jobs",
+    responses(
+        (status = 202, description = "Job queued", body = Job),
+        (status = 400, description = "Invalid job"),
+        (status = 403, description = "Exports aren't available on the caller's tier"),
+        (status = 503, description = "The queue is full"),
+    )
+)]
+#[post("/jobs")]
+pub async fn submit_job(
+    req: HttpRequest,
+    body: web::Json<JobRequest>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let JobRequest { spec, priority } = body.into_inner();
+    if let Err(error) = spec.validate() {
+        return problem(StatusCode::BAD_REQUEST, error);
+    }
+    if matches!(spec, JobSpec::Export(_)) {
+        if let Some(response) = require_exports(&req, &data) {
+            return response;
+        }
+    }
+    let caller = caller(&req);
+    let tier = data.api_keys.tier(&caller);
+    let priority = priority.unwrap_or_else(|| spec.default_priority());
+    if priority > max_priority(tier) {
+        return problem_with(
+            StatusCode::FORBIDDEN,
+            format!(
+                "The {} tier may not submit jobs at this priority",
+                tier.as_str()
+            ),
+            json!({ "maxPriority": max_priority(tier) }),
+        );
+    }
+
+    match data.jobs.submit(spec, caller.id(), tier, priority).await {
+        Ok((job, slot)) => {
+            let location = format!("/jobs/{}", job.id);
+            let task = actix_web::rt::spawn(execute(data.clone(), job.clone(), slot));
+            data.jobs.tasks.insert(job.id, task);
+            HttpResponse::Accepted()
+                .insert_header((LOCATION, location))
+                .json(job)
+        }
+        Err(SubmitError::QueueFull(pending)) => {
+            let mut response = problem_with(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "The job queue is full",
+                json!({ "pending": pending }),
+            );
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(QUEUE_FULL_RETRY_SECS));
+            response
+        }
+        Err(SubmitError::Io(e)) => {
+            log::error!("Failed to persist job: {e}");
+            problem(StatusCode::INTERNAL_SERVER_ERROR, "Failed to store the job")
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "jobs",
+    params(("id" = Uuid, Path, description = "Job id")),
+    responses(
+        (status = 200, description = "OK", body = Job),
+        (status = 404, description = "No such job"),
+    )
+)]
+#[get("/jobs/{id}")]
+pub async fn get_job(req: HttpRequest, id: PathUuid, data: web::Data<AppState>) -> impl Responder {
+    let PathUuid(id) = id;
+    let caller = caller(&req);
+    // Other callers' jobs are indistinguishable from missing ones
+    match data.jobs.get(id) {
+        Some(job) if caller.is_admin() || job.caller == caller.id() => HttpResponse::Ok().json(job),
+        _ => not_found(format!("Job {id} not found")),
+    }
+}
+
+#[utoipa::path(
+    tag = "jobs",
+    params(("id" = Uuid, Path, description = "Job id")),
+    responses(
+        (status = 204, description = "Job cancelled"),
+        (status = 404, description = "No such job"),
+    )
+)]
+#[delete("/jobs/{id}")]
+pub async fn delete_job(
+    req: HttpRequest,
+    id: PathUuid,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let PathUuid(id) = id;
+    let caller = caller(&req);
+    match data.jobs.get(id) {
+        Some(job) if caller.is_admin() || job.caller == caller.id() => {
+            if let Some(job) = data.jobs.delete(id).await {
+                if !job.status.is_finished() {
+                    log::info!("Job {id} ({}) cancelled", job.spec.kind());
+                }
+            }
+            HttpResponse::NoContent().finish()
+        }
+        _ => not_found(format!("Job {id} not found")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn scheduler_orders_by_priority_and_tier_within_caps() {
+        let scheduler = Arc::new(Scheduler {
+            state: Mutex::default(),
+            workers: 2,
+            tier_caps: HashMap::from([(Tier::Free, 1)]),
+        });
+        let free = scheduler.turn(Priority::Normal, Tier::Free).await;
+
+        // The second worker skips the capped free tier
+        let capped = tokio::spawn({
+            let scheduler = Arc::clone(&scheduler);
+            async move { scheduler.turn(Priority::High, Tier::Free).await }
+        });
+        tokio::task::yield_now().await;
+        let partner = tokio::time::timeout(
+            Duration::from_secs(1),
+            scheduler.turn(Priority::Low, Tier::Partner),
+        )
+        .await
+        .expect("a capped tier doesn't hold on to free workers");
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut waiting = Vec::new();
+        for (name, priority, tier) in [
+            ("bulk", Priority::Low, Tier::Internal),
+            ("interactive", Priority::High, Tier::Partner),
+            ("internal", Priority::Normal, Tier::Internal),
+            ("partner", Priority::Normal, Tier::Partner),
+        ] {
+            let (scheduler, order) = (Arc::clone(&scheduler), Arc::clone(&order));
+            waiting.push(tokio::spawn(async move {
+                let _turn = scheduler.turn(priority, tier).await;
+                order.lock().unwrap().push(name);
+            }));
+        }
+        tokio::task::yield_now().await;
+
+        drop(partner);
+        drop(free);
+        drop(capped.await.unwrap());
+        for task in waiting {
+            task.await.unwrap();
+        }
+        assert_eq!(
+            *order.lock().unwrap(),
+            ["interactive", "internal", "partner", "bulk"]
+        );
+    }
+}
@@ -0,0 +1,381 @@
+//! Error messages in the caller's language. Handlers keep writing English;
+//! [`localize_errors`] rewrites error bodies for callers whose
+//! `Accept-Language` prefers another language in the catalog, so front ends
+//! can show `detail` as is. Messages without a translation stay English.
+
+use actix_web::{
+    body::{to_bytes, EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    error::ErrorInternalServerError,
+    http::header::{HeaderValue, ACCEPT_LANGUAGE, CONTENT_LANGUAGE, CONTENT_TYPE, VARY},
+    middleware::Next,
+    web::Bytes,
+    Error,
+};
+use serde_json::Value;
+use std::str::FromStr;
+
+/// A language error messages are available in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Locale {
+    #[default]
+    En,
+    Es,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 2] = [Locale::En, Locale::Es];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Es => "es",
+        }
+    }
+
+    /// English messages and their translations; `{}` stands for a value
+    /// copied over from the English message, in order
+    fn catalog(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Locale::En => &[],
+            Locale::Es => SPANISH,
+        }
+    }
+
+    /// `message` in this language, or `None` when the catalog lacks it
+    pub fn translate(self, message: &str) -> Option<String> {
+        self.catalog()
+            .iter()
+            .find_map(|(english, translated)| fill(english, translated, message))
+    }
+}
+
+impl FromStr for Locale {
+    type Err = ();
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        Locale::ALL
+            .into_iter()
+            .find(|locale| locale.as_str() == raw)
+            .ok_or(())
+    }
+}
+
+/// The catalog language an `Accept-Language` header prefers most, matching on
+/// the primary subtag (`es-MX` is `es`); English when none is available
+pub fn negotiate(accept_language: &str) -> Locale {
+    let mut best = (0.0, Locale::En);
+    for range in accept_language.split(',') {
+        let mut parts = range.split(';');
+        let tag = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+        let quality = parts
+            .filter_map(|param| param.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        let primary = tag.split('-').next().unwrap_or_default();
+        if let Ok(locale) = primary.parse::<Locale>() {
+            if quality > best.0 {
+                best = (quality, locale);
+            }
+        }
+    }
+    best.1
+}
+
+/// `translated` with the values `message` holds in place of the `{}`s of
+/// `english`, when `message` matches that template
+fn fill(english: &str, translated: &str, message: &str) -> Option<String> {
+    let mut literals = english.split("{}");
+    let mut rest = message.strip_prefix(literals.next().unwrap_or_default())?;
+    let mut values = Vec::new();
+    let literals: Vec<&str> = literals.collect();
+    for (i, literal) in literals.iter().enumerate() {
+        let end = if i + 1 == literals.len() {
+            rest.strip_suffix(literal).map(str::len)?
+        } else {
+            rest.find(literal)?
+        };
+        values.push(&rest[..end]);
+        rest = &rest[end + literal.len()..];
+    }
+    if !rest.is_empty() {
+        return None;
+    }
+
+    let mut out = String::new();
+    let mut values = values.into_iter();
+    let mut pieces = translated.split("{}");
+    out.push_str(pieces.next().unwrap_or_default());
+    for piece in pieces {
+        out.push_str(values.next().unwrap_or_default());
+        out.push_str(piece);
+    }
+    Some(out)
+}
+
+/// Translates the messages of error responses: `title`, `detail` and `error`
+/// of JSON bodies, or the whole of a plain text body. Every error response
+/// gets `Vary: Accept-Language`, and `Content-Language` once its message is in
+/// that language.
+pub async fn localize_errors<B: MessageBody + 'static>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B, Bytes>>, Error> {
+    let locale = req
+        .headers()
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .map(negotiate)
+        .unwrap_or_default();
+
+    let mut res = next.call(req).await?;
+    let status = res.status();
+    if !status.is_client_error() && !status.is_server_error() {
+        return Ok(res.map_into_left_body());
+    }
+    res.headers_mut()
+        .append(VARY, HeaderValue::from_static("accept-language"));
+    if locale == Locale::En {
+        res.headers_mut()
+            .insert(CONTENT_LANGUAGE, HeaderValue::from_static("en"));
+        return Ok(res.map_into_left_body());
+    }
+
+    let content_type = res
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let (req, res) = res.into_parts();
+    let (mut res, body) = res.into_parts();
+    let body = to_bytes(body)
+        .await
+        .map_err(|e| ErrorInternalServerError(e.into()))?;
+
+    let (body, translated) = if content_type.contains("json") {
+        localize_json(locale, &body)
+    } else if content_type.starts_with("text/plain") {
+        match std::str::from_utf8(&body)
+            .ok()
+            .and_then(|text| locale.translate(text))
+        {
+            Some(text) => (Bytes::from(text), true),
+            None => (body, false),
+        }
+    } else {
+        (body, false)
+    };
+
+    let language = if translated { locale.as_str() } else { "en" };
+    res.headers_mut()
+        .insert(CONTENT_LANGUAGE, HeaderValue::from_static(language));
+    Ok(ServiceResponse::new(req, res.set_body(body)).map_into_right_body())
+}
+
+/// The body with its messages translated, and whether the message itself
+/// (`detail` or `error`) was
+fn localize_json(locale: Locale, body: &Bytes) -> (Bytes, bool) {
+    let Ok(Value::Object(mut fields)) = serde_json::from_slice::<Value>(body) else {
+        return (body.clone(), false);
+    };
+    let mut translated = false;
+    for key in ["title", "detail", "error"] {
+        let Some(Value::String(message)) = fields.get_mut(key) else {
+            continue;
+        };
+        if let Some(text) = locale.translate(message) {
+            *message = text;
+            translated |= key != "title";
+        }
+    }
+    (Bytes::from(Value::Object(fields).to_string()), translated)
+}
+
+const SPANISH: &[(&str, &str)] = &[
+    // Titles of problem responses
+    ("Bad Request", "Solicitud incorrecta"),
+    ("Unauthorized", "No autorizado"),
+    ("Forbidden", "Prohibido"),
+    ("Not Found", "No encontrado"),
+    ("Conflict", "Conflicto"),
+    ("Payload Too Large", "Carga demasiado grande"),
+    ("Unsupported Media Type", "Tipo de contenido no admitido"),
+    ("Too Many Requests", "Demasiadas solicitudes"),
+    ("Internal Server Error", "Error interno del servidor"),
+    ("Service Unavailable", "Servicio no disponible"),
+    ("Gateway Timeout", "Tiempo de espera agotado"),
+    // Callers and limits
+    ("Invalid API key", "Clave de API no válida"),
+    (
+        "This endpoint requires an admin API key",
+        "Este endpoint requiere una clave de API de administrador",
+    ),
+    (
+        "Requesting a verification token requires an API key",
+        "Solicitar un token de verificación requiere una clave de API",
+    ),
+    ("Access denied", "Acceso denegado"),
+    ("Rate limit exceeded", "Límite de solicitudes excedido"),
+    (
+        "Request body must be JSON (Content-Type: application/json)",
+        "El cuerpo de la solicitud debe ser JSON (Content-Type: application/json)",
+    ),
+    // Lookups
+    ("Table '{}' not found", "No se encontró la tabla '{}'"),
+    (
+        "No row found with id '{}' in table '{}'",
+        "No se encontró ninguna fila con id '{}' en la tabla '{}'",
+    ),
+    (
+        "No row found with id '{}' in table canons",
+        "No se encontró ninguna fila con id '{}' en la tabla canons",
+    ),
+    (
+        "No project found with slug '{}'",
+        "No se encontró ningún proyecto con slug '{}'",
+    ),
+    ("Run {} not found", "No se encontró la ejecución {}"),
+    ("Job {} not found", "No se encontró el trabajo {}"),
+    ("invalid uuid", "uuid no válido"),
+    (
+        "invalid consistency token",
+        "token de consistencia no válido",
+    ),
+    // Parameters
+    (
+        "No project IDs provided",
+        "No se proporcionaron IDs de proyecto",
+    ),
+    (
+        "Too many project IDs (maximum {} allowed)",
+        "Demasiados IDs de proyecto (se permiten como máximo {})",
+    ),
+    (
+        "Search name cannot be empty",
+        "El nombre de búsqueda no puede estar vacío",
+    ),
+    (
+        "Invalid run '{}': expected 'latest' or a run number",
+        "Ejecución '{}' no válida: se esperaba 'latest' o un número de ejecución",
+    ),
+    (
+        "Invalid percentile '{}': expected a number",
+        "Percentil '{}' no válido: se esperaba un número",
+    ),
+    (
+        "Invalid percentile {}: must be between 0 and 100",
+        "Percentil {} no válido: debe estar entre 0 y 100",
+    ),
+    (
+        "Invalid percentile {}: at most two decimal places are supported",
+        "Percentil {} no válido: se admiten como máximo dos decimales",
+    ),
+    (
+        "Too many percentiles (maximum {} allowed)",
+        "Demasiados percentiles (se permiten como máximo {})",
+    ),
+    (
+        "Cannot sort by '{}' (expected one of: {})",
+        "No se puede ordenar por '{}' (se esperaba uno de: {})",
+    ),
+    (
+        "Invalid direction '{}' for '{}' (expected 'asc' or 'desc')",
+        "Dirección '{}' no válida para '{}' (se esperaba 'asc' o 'desc')",
+    ),
+    (
+        "Invalid nulls '{}' (expected 'first' or 'last')",
+        "Valor de nulls '{}' no válido (se esperaba 'first' o 'last')",
+    ),
+    ("nulls requires a sort", "nulls requiere un sort"),
+    (
+        "'{}' is sorted on more than once",
+        "'{}' aparece más de una vez en sort",
+    ),
+    (
+        "At most {} sort keys are allowed",
+        "Se permiten como máximo {} claves de ordenación",
+    ),
+    // Jobs
+    (
+        "The {} tier may not submit jobs at this priority",
+        "El nivel {} no puede enviar trabajos con esta prioridad",
+    ),
+    ("The job queue is full", "La cola de trabajos está llena"),
+    ("Failed to store the job", "No se pudo guardar el trabajo"),
+    (
+        "maxDepth must be between 1 and {}",
+        "maxDepth debe estar entre 1 y {}",
+    ),
+    (
+        "packages must not be empty",
+        "packages no puede estar vacío",
+    ),
+    (
+        "At most {} packages are allowed per job",
+        "Se permiten como máximo {} paquetes por trabajo",
+    ),
+    // The database
+    (
+        "Failed to get database connection",
+        "No se pudo obtener una conexión a la base de datos",
+    ),
+    (
+        "Database query failed",
+        "La consulta a la base de datos falló",
+    ),
+    (
+        "The database connection was lost. Retry shortly.",
+        "Se perdió la conexión con la base de datos. Reintente en breve.",
+    ),
+    (
+        "The query was cancelled for running too long. Narrow the request, for example with a \
+        smaller limit or page, and retry.",
+        "La consulta se canceló por tardar demasiado. Acote la solicitud, por ejemplo con un \
+        limit o una página menores, y reintente.",
+    ),
+    (
+        "The database is only accepting reads right now. Retry the write later.",
+        "La base de datos solo acepta lecturas en este momento. Reintente la escritura más tarde.",
+    ),
+    (
+        "The database is temporarily unavailable. Retry shortly.",
+        "La base de datos no está disponible temporalmente. Reintente en breve.",
+    ),
+    (
+        "The request conflicted with a concurrent change. Retry it.",
+        "La solicitud entró en conflicto con un cambio simultáneo. Reinténtela.",
+    ),
+    (
+        "A parameter couldn't be interpreted by the database. Check its type and range.",
+        "La base de datos no pudo interpretar un parámetro. Revise su tipo y rango.",
+    ),
+    ("Internal server error", "Error interno del servidor"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_follow_accept_language() {
+        assert_eq!(negotiate("es-MX,es;q=0.9,en;q=0.8"), Locale::Es);
+        assert_eq!(negotiate("fr-CH, fr;q=0.9, en;q=0.8, es;q=0.7"), Locale::En);
+        assert_eq!(negotiate("de, es;q=0.5"), Locale::Es);
+        assert_eq!(negotiate("es;q=0, *"), Locale::En);
+        assert_eq!(negotiate(""), Locale::En);
+
+        assert_eq!(
+            Locale::Es.translate("Run 42 not found").as_deref(),
+            Some("No se encontró la ejecución 42")
+        );
+        assert_eq!(
+            Locale::Es
+                .translate("Invalid direction 'up' for 'rank' (expected 'asc' or 'desc')")
+                .as_deref(),
+            Some("Dirección 'up' no válida para 'rank' (se esperaba 'asc' o 'desc')")
+        );
+        assert_eq!(Locale::Es.translate("Run 42 not found, sorry"), None);
+        assert_eq!(Locale::En.translate("Run 42 not found"), None);
+    }
+}
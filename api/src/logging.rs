@@ -1,4 +1,14 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+    Error, HttpMessage, HttpRequest,
+};
 use env_logger::Env;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
 
 pub fn setup_logger() {
     env_logger::init_from_env(Env::default().default_filter_or("info"));
@@ -8,6 +18,41 @@ pub struct Logger;
 
 impl Logger {
     pub fn default() -> actix_web::middleware::Logger {
-        actix_web::middleware::Logger::new("%a '%r' %s %b '%{Referer}i' '%{User-Agent}i' %T")
+        actix_web::middleware::Logger::new(
+            "%a '%r' %s %b '%{Referer}i' '%{User-Agent}i' %T %{x-request-id}o",
+        )
+    }
+}
+
+/// Correlation id for one request, stored in the request extensions
+#[derive(Clone)]
+pub struct RequestId(pub String);
+
+/// The id assigned by [`request_id`], if the middleware ran
+pub fn request_id_of(req: &HttpRequest) -> Option<String> {
+    req.extensions().get::<RequestId>().map(|id| id.0.clone())
+}
+
+/// Tags each request with the caller's `X-Request-Id` (when it looks sane) or a
+/// fresh UUID, and echoes it on the response.
+pub async fn request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let id = req
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| {
+            !value.is_empty() && value.len() <= 128 && value.bytes().all(|b| b.is_ascii_graphic())
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    req.extensions_mut().insert(RequestId(id.clone()));
+
+    let mut res = next.call(req).await?;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        res.headers_mut().insert(REQUEST_ID_HEADER, value);
     }
+    Ok(res)
 }
@@ -1,59 +1,596 @@
+mod admin;
+mod analyze;
 mod app_state;
+mod auth;
+mod cache;
+mod check;
+mod cli;
+mod collections;
+mod config;
+mod csv_stream;
 mod db;
+mod egress;
+mod export;
+mod extractors;
+mod features;
 mod handlers;
+mod http_client;
+mod ip_filter;
+mod jobs;
+mod locale;
 mod logging;
+mod metrics;
+mod openapi;
+mod orgs;
+mod overrides;
+mod package_managers;
+mod problem;
+mod ranks;
+mod rate_limit;
+mod recover;
+mod regions;
+mod reports;
+mod routing;
+mod run_alerts;
+mod runs;
+mod scheduler;
+mod schemas;
+mod security;
+mod stats;
+mod status;
+mod table_export;
+mod table_stream;
+#[cfg(test)]
+mod test_state;
+mod usage;
 mod utils;
+mod verification;
 
-use actix_web::{web, App, HttpServer};
-use dashmap::DashMap;
+use actix_web::{
+    middleware::{from_fn, Condition},
+    web, App, HttpServer,
+};
+use chai_core::cache::{save_snapshot, CacheMetrics};
+use chai_core::db::Catalog;
+use chai_types::format::RowFormat;
+use clap::Parser;
+use dashmap::{DashMap, DashSet};
 use dotenv::dotenv;
 use std::env;
 use std::sync::Arc;
+use tokio::sync::{watch, Semaphore};
 
+use crate::admin::{
+    clear_quarantine, cutover_schema, explain, get_cache_stats, get_http_stats, get_job_stats,
+    get_pool_stats, get_run_checks, get_schedule, get_schema, get_table_usage, get_usage,
+    invalidate_cache, list_suspects, quarantine_run,
+};
+use crate::analyze::analyze_dependency_confusion;
 use crate::app_state::AppState;
+use crate::auth::{authenticate, ApiKeys};
+use crate::cli::{Cli, Command};
+use crate::collections::{
+    create_collection, delete_collection, delete_collection_webhook, get_collection,
+    get_collection_changes, get_collection_leaderboard, list_collections, put_collection,
+    put_collection_webhook, rotate_collection_webhook_secret,
+};
+use crate::config::Config;
+use crate::export::{export_leaderboard, ExportCache};
+use crate::features::{Feature, Features};
 use crate::handlers::{
-    get_leaderboard, get_project, get_table, get_table_row, heartbeat, list_projects_by_id,
-    list_projects_by_name, list_tables,
+    check_name, get_dependency_tree, get_leaderboard, get_limits, get_maintainer_concentration,
+    get_project, get_project_by_slug, get_project_funding, get_table, get_table_row, get_version,
+    heartbeat, list_dependents, list_maintainer_projects, list_new_entrants, list_projects_by_id,
+    list_projects_by_name, list_similar_names, list_tables, projects_exist, warm_leaderboard,
+};
+use crate::http_client::HttpClient;
+use crate::ip_filter::{ip_filter, IpFilter};
+use crate::jobs::{delete_job, get_job, submit_job, Jobs, CLEANUP_INTERVAL};
+use crate::locale::localize_errors;
+use crate::logging::{request_id, setup_logger};
+use crate::metrics::{get_metrics, record_metrics, Metrics};
+use crate::openapi::{get_docs, get_openapi};
+use crate::orgs::{get_org, get_org_leaderboard, OrgRankCache};
+use crate::overrides::{delete_override, list_overrides, put_override};
+use crate::package_managers::{refresh_package_managers, PackageManagers};
+use crate::ranks::{
+    get_project_metrics, get_project_trend, get_rank_contributors, get_rank_percentiles,
+    get_run_comparison, PercentileCache,
 };
-use crate::logging::setup_logger;
+use crate::rate_limit::{rate_limit, RateLimiter};
+use crate::recover::{catch_panic, install_panic_hook};
+use crate::regions::{Pools, RegionPool};
+use crate::reports::get_stale_report;
+use crate::routing::normalize_path;
+use crate::run_alerts::{watch_runs, RunAlerts};
+use crate::runs::{detect_latest_run, watch_latest_ranks, watch_latest_run, LatestRun};
+use crate::scheduler::{
+    Scheduler, CACHE_SNAPSHOT_INTERVAL, CACHE_SWEEP_INTERVAL, GRAPH_STATS_INTERVAL,
+    INGEST_CHECK_INTERVAL, LEADERBOARD_WARMUP_INTERVAL, PACKAGE_MANAGERS_INTERVAL,
+    TABLE_REFRESH_INTERVAL,
+};
+use crate::schemas::Schemas;
+use crate::security::{require_json, security_headers};
+use crate::stats::{
+    get_cross_ecosystem, get_graph_stats, get_source_hosts, get_top_dependencies,
+    get_top_dependents, refresh_graph_stats, DegreeCache, GraphCache,
+};
+use crate::status::{get_status, refresh_ingests, IngestCache, StatusLimits};
+use crate::table_stream::stream_table;
+use crate::usage::{track_usage, UsageTracker};
+use crate::verification::{get_verification, request_verification, verify_pending};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    let cli = Cli::parse();
     dotenv().ok();
     setup_logger();
+    install_panic_hook();
+
+    let config = Config::from_env();
+    match cli.command() {
+        Command::Serve => serve(config).await,
+        Command::Check => {
+            let passed = check::run(&config).await;
+            std::process::exit(if passed { 0 } else { 1 });
+        }
+        Command::WarmCache => exit_on_error(cli::warm_cache(&config).await),
+        Command::Export(args) => exit_on_error(cli::export(&config, &args).await),
+    }
+}
 
+/// Ends a one-off command, exiting with status 1 if it failed
+fn exit_on_error(result: Result<(), String>) -> std::io::Result<()> {
+    if let Err(e) = result {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+async fn serve(config: Config) -> std::io::Result<()> {
     let host = env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
     let port = env::var("PORT").unwrap_or_else(|_| "8080".to_string());
     let bind_address = format!("{host}:{port}");
 
-    let (pool, tables) = db::initialize_db().await;
-    // Cache for project data to reduce database load on leaderboard routes
-    let project_cache = Arc::new(DashMap::new());
+    let state = build_state(&config).await.map_err(std::io::Error::other)?;
+    if let Some(path) = &config.cache_snapshot_path {
+        state.restore_caches(path);
+    }
+    match state.jobs.restore().await {
+        Ok(0) => {}
+        Ok(count) => log::info!("Restored {count} jobs from {}", config.job_dir.display()),
+        Err(e) => log::warn!(
+            "Failed to restore jobs from {}: {e}",
+            config.job_dir.display()
+        ),
+    }
+    // Statement timings leak query structure, so they never leave release builds
+    let debug_sql = config.debug_sql && cfg!(debug_assertions);
+    if config.debug_sql && !debug_sql {
+        log::warn!("Ignoring DEBUG_SQL in a release build");
+    }
 
-    log::info!("Available tables: {tables:?}");
+    // MAINTENANCE
+    let scheduler = &state.scheduler;
+    let pool = &state.pool;
+    scheduler.every(
+        "rate_limit_sweep",
+        state.rate_limiter.window(),
+        (Arc::clone(&state.rate_limiter), Arc::clone(&state.http)),
+        |(limiter, http)| async move {
+            limiter.sweep();
+            http.sweep();
+        },
+    );
+    scheduler.every(
+        "usage_flush",
+        config.usage_flush_interval,
+        Arc::clone(&state.usage),
+        |usage| async move { usage.flush() },
+    );
+    // With replicas, reads follow whichever region is currently fastest
+    if !config.database_replicas.is_empty() {
+        scheduler.every(
+            "region_probe",
+            config.db_probe_interval,
+            Arc::clone(pool),
+            |pool| async move { pool.probe().await },
+        );
+    }
+    scheduler.every(
+        "run_detection",
+        config.run_poll_interval,
+        (Arc::clone(pool), state.latest_run.clone()),
+        |(pool, latest)| async move { detect_latest_run(&pool, &latest).await },
+    );
+    // Rebuilds the current ranks business queries read for each run it picks up
+    actix_web::rt::spawn(watch_latest_ranks(
+        Arc::clone(pool),
+        state.latest_run.clone(),
+    ));
+    // Compares each run the detection above picks up with the one before it
+    actix_web::rt::spawn(watch_runs(
+        Arc::clone(pool),
+        state.latest_run.clone(),
+        Arc::clone(&state.run_alerts),
+        Arc::clone(&state.http),
+    ));
+    scheduler.every(
+        "verification",
+        config.verification_interval,
+        (pool.primary().clone(), Arc::clone(&state.http)),
+        |(pool, http)| async move { verify_pending(&pool, &http).await },
+    );
+    scheduler.every(
+        "job_cleanup",
+        CLEANUP_INTERVAL,
+        Arc::clone(&state.jobs),
+        |jobs| async move { jobs.clean_up().await },
+    );
+    scheduler.every(
+        "cache_sweep",
+        CACHE_SWEEP_INTERVAL,
+        state.clone(),
+        |data| async move { data.sweep_caches() },
+    );
+    scheduler.every(
+        "table_refresh",
+        TABLE_REFRESH_INTERVAL,
+        state.clone(),
+        |data| async move {
+            db::refresh_catalog(data.pool.primary(), &data.tables, &data.schemas).await
+        },
+    );
+    scheduler.every(
+        "leaderboard_warmup",
+        LEADERBOARD_WARMUP_INTERVAL,
+        state.clone(),
+        |data| async move {
+            warm_leaderboard(&data).await;
+        },
+    );
+    scheduler.every(
+        "graph_stats",
+        GRAPH_STATS_INTERVAL,
+        state.clone(),
+        |data| async move { refresh_graph_stats(&data).await },
+    );
+    scheduler.every(
+        "ingest_check",
+        INGEST_CHECK_INTERVAL,
+        state.clone(),
+        |data| async move { refresh_ingests(&data).await },
+    );
+    scheduler.every(
+        "package_managers",
+        PACKAGE_MANAGERS_INTERVAL,
+        state.clone(),
+        |data| async move { refresh_package_managers(&data).await },
+    );
+    if let Some(path) = config.cache_snapshot_path.clone() {
+        scheduler.every(
+            "cache_snapshot",
+            CACHE_SNAPSHOT_INTERVAL,
+            (state.clone(), Arc::new(path)),
+            |(data, path)| async move {
+                let saved = tokio::task::spawn_blocking(move || {
+                    save_snapshot(&path, &data.caches())
+                        .map_err(|e| log::error!("Failed to save cache snapshot: {e}"))
+                });
+                if let Ok(Ok(count)) = saved.await {
+                    log::debug!("Saved {count} cache entries");
+                }
+            },
+        );
+    }
+
+    log::info!("Available tables: {:?}", state.tables.borrow().tables);
     log::info!("Starting server at http://{bind_address}");
 
+    let app_state = state.clone();
     HttpServer::new(move || {
+        let features = app_state.features.clone();
         App::new()
+            .wrap(Condition::new(debug_sql, from_fn(db::debug_timings)))
+            .wrap(from_fn(require_json))
+            .wrap(from_fn(rate_limit))
+            .wrap(from_fn(track_usage))
+            .wrap(from_fn(authenticate))
+            .wrap(from_fn(ip_filter))
+            .wrap(from_fn(localize_errors))
+            .wrap(from_fn(security_headers))
+            .wrap(from_fn(catch_panic))
+            .wrap(from_fn(record_metrics))
+            .wrap(from_fn(normalize_path))
+            .wrap(from_fn(request_id))
             .wrap(logging::Logger::default())
-            .app_data(web::Data::new(AppState {
-                pool: pool.clone(),
-                tables: Arc::clone(&tables),
-                project_cache: Arc::clone(&project_cache),
-            }))
-            // HEALTH
-            .service(heartbeat)
-            // SIMPLE CRUD OPERATIONS
-            .service(list_tables)
-            .service(get_table)
-            .service(get_table_row)
-            // BUSINESS LOGIC
-            .service(get_leaderboard)
-            .service(get_project)
-            .service(list_projects_by_id)
-            .service(list_projects_by_name)
+            .app_data(app_state.clone())
+            .configure(|cfg| routes(cfg, &features))
     })
     .bind(&bind_address)?
     .run()
-    .await
+    .await?;
+
+    // Save caches so the next start doesn't begin cold
+    if let Some(path) = &config.cache_snapshot_path {
+        match save_snapshot(path, &state.caches()) {
+            Ok(count) => log::info!("Saved {count} cache entries to {}", path.display()),
+            Err(e) => log::error!("Failed to save cache snapshot {}: {e}", path.display()),
+        }
+    }
+
+    Ok(())
+}
+
+/// What the state is built from that comes from settings alone
+struct Settings {
+    features: Features,
+    scheduler: Arc<Scheduler>,
+    jobs: Arc<Jobs>,
+    api_keys: Arc<ApiKeys>,
+    ip_filter: Arc<IpFilter>,
+    schemas: Arc<Schemas>,
+    http: Arc<HttpClient>,
+}
+
+impl Settings {
+    fn from_config(config: &Config) -> Result<Self, String> {
+        Ok(Self {
+            features: Features::from_config(config),
+            scheduler: Arc::new(Scheduler::from_config(config)),
+            jobs: Arc::new(Jobs::from_config(config)),
+            api_keys: Arc::new(ApiKeys::from_config(config)),
+            ip_filter: Arc::new(IpFilter::from_config(config)),
+            schemas: Arc::new(Schemas::from_config(config)),
+            http: Arc::new(
+                HttpClient::from_config(config)
+                    .map_err(|e| format!("Failed to build HTTP client: {e}"))?,
+            ),
+        })
+    }
+
+    /// How pooled connections are retired under these settings
+    fn recycling(&self, config: &Config) -> db::Recycling {
+        db::Recycling {
+            max_lifetime: config.db_max_connection_lifetime,
+            max_uses: config.db_max_connection_uses,
+            counters: Arc::new(db::RecycleCounters::default()),
+            schemas: Arc::clone(&self.schemas),
+        }
+    }
+}
+
+/// Connects to the database and assembles the state shared by every worker,
+/// the maintenance tasks and `--check`
+async fn build_state(config: &Config) -> Result<web::Data<AppState>, String> {
+    // Everything read from settings comes first, so a bad setting is reported
+    // even when the database is unreachable
+    let settings = Settings::from_config(config)?;
+    let recycling = settings.recycling(config);
+    let (pool, catalog) = db::initialize_db(config.db_read_only, &recycling).await?;
+    let replicas: Vec<RegionPool> = config
+        .database_replicas
+        .iter()
+        .map(|(region, url)| {
+            let replica = db::build_pool(&db::pool_config(url, config.db_read_only), &recycling);
+            RegionPool::new(region.clone(), replica)
+        })
+        .collect();
+    let pool = Arc::new(Pools::new(
+        RegionPool::new(config.database_region.clone(), pool),
+        replicas,
+    ));
+
+    // Track the latest ranking run so handlers don't each look it up
+    let latest_run = watch_latest_run(&pool).await;
+
+    Ok(assemble_state(
+        config, settings, pool, catalog, latest_run, recycling,
+    ))
+}
+
+/// The state around a connected `pool` whose active schema holds `catalog`
+fn assemble_state(
+    config: &Config,
+    settings: Settings,
+    pool: Arc<Pools>,
+    catalog: Catalog,
+    latest_run: LatestRun,
+    recycling: db::Recycling,
+) -> web::Data<AppState> {
+    let Settings {
+        features,
+        scheduler,
+        jobs,
+        api_keys,
+        ip_filter,
+        schemas,
+        http,
+    } = settings;
+    web::Data::new(AppState {
+        pool,
+        tables: watch::Sender::new(Arc::new(catalog)),
+        // Cache for project data to reduce database load on leaderboard routes
+        project_cache: Arc::new(DashMap::new()),
+        funding_cache: Arc::new(DashMap::new()),
+        refreshing: Arc::new(DashSet::new()),
+        cache_max_staleness: config.cache_max_staleness,
+        cache_metrics: Arc::new(CacheMetrics::default()),
+        rank_precision: config.rank_precision,
+        row_format: RowFormat {
+            bigint_as_string: config.bigint_as_string,
+            legacy_timestamps: config.legacy_timestamps,
+        },
+        latest_run,
+        run_alerts: Arc::new(RunAlerts::from_config(config, Arc::clone(&http))),
+        http,
+        schemas,
+        export_cache: Arc::new(ExportCache::new(config.export_cache_dir.clone())),
+        jobs,
+        recycles: recycling.counters,
+        percentile_cache: Arc::new(PercentileCache::new()),
+        degree_cache: Arc::new(DegreeCache::new()),
+        org_ranks: Arc::new(OrgRankCache::new()),
+        graph_stats: Arc::new(GraphCache::default()),
+        ingests: Arc::new(IngestCache::default()),
+        package_managers: Arc::new(PackageManagers::default()),
+        status_limits: StatusLimits {
+            run_max_age: config.status_run_max_age,
+            ingest_max_age: config.status_ingest_max_age,
+        },
+        rate_limiter: Arc::new(RateLimiter::new(
+            config.rate_limit_requests,
+            config.rate_limit_window,
+        )),
+        api_keys,
+        usage: Arc::new(UsageTracker::new(config.response_size_warn_bytes)),
+        metrics: Arc::new(Metrics::default()),
+        ip_filter,
+        default_cache_control: Arc::from(config.default_cache_control.as_str()),
+        features,
+        scheduler,
+        table_deprecation_notices: config.table_deprecation_notices,
+        package_managers_opt_in: config.package_managers_opt_in,
+        table_streams: Arc::new(Semaphore::new(config.table_streams_max)),
+    })
+}
+
+/// Every route the API serves, given the features switched on
+fn routes(cfg: &mut web::ServiceConfig, features: &Features) {
+    extractors::configure(cfg);
+    cfg
+        // HEALTH
+        .service(heartbeat)
+        .service(get_status)
+        .service(get_version)
+        .service(get_limits)
+        // DOCUMENTATION
+        .service(get_openapi)
+        .service(get_docs)
+        // SIMPLE CRUD OPERATIONS
+        .service(list_tables)
+        .service(get_table)
+        // Ahead of `/tables/{table}/{id}`, which would take `stream` for an id
+        .service(stream_table)
+        .service(get_table_row)
+        // BUSINESS LOGIC
+        .service(get_leaderboard)
+        .service(list_new_entrants)
+        .service(get_org_leaderboard)
+        .service(get_rank_percentiles)
+        .service(get_cross_ecosystem)
+        .service(get_source_hosts)
+        .service(get_graph_stats)
+        .service(get_top_dependents)
+        .service(get_top_dependencies)
+        .service(get_stale_report)
+        .service(get_org)
+        .service(get_run_comparison)
+        .service(get_project)
+        .service(get_project_by_slug)
+        // Ahead of `/project/{id}/…`, which would take `search` for an id
+        .service(list_projects_by_name)
+        .service(get_project_trend)
+        .service(get_project_metrics)
+        .service(get_rank_contributors)
+        .service(list_projects_by_id)
+        .service(projects_exist)
+        .service(list_similar_names)
+        .service(get_maintainer_concentration)
+        .service(get_dependency_tree)
+        .service(list_dependents)
+        .service(check_name)
+        .service(analyze_dependency_confusion)
+        .service(create_collection)
+        .service(list_collections)
+        .service(get_collection)
+        .service(put_collection)
+        .service(put_collection_webhook)
+        .service(delete_collection_webhook)
+        .service(rotate_collection_webhook_secret)
+        .service(delete_collection)
+        .service(get_collection_leaderboard)
+        .service(get_collection_changes)
+        .service(export_leaderboard)
+        // JOBS
+        .service(submit_job)
+        .service(get_job)
+        .service(delete_job)
+        // MAINTAINERS
+        .service(request_verification)
+        .service(get_verification)
+        .service(list_maintainer_projects)
+        // ADMIN
+        .service(get_usage)
+        .service(get_table_usage)
+        .service(invalidate_cache)
+        .service(get_cache_stats)
+        .service(explain)
+        .service(get_pool_stats)
+        .service(get_job_stats)
+        .service(get_schedule)
+        .service(get_run_checks)
+        .service(get_http_stats)
+        .service(get_metrics)
+        .service(quarantine_run)
+        .service(clear_quarantine)
+        .service(list_overrides)
+        .service(put_override)
+        .service(delete_override)
+        .service(list_suspects)
+        .service(get_schema)
+        .service(cutover_schema);
+    // EXPERIMENTAL
+    if features.is_enabled(Feature::Funding) {
+        cfg.service(get_project_funding);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+    use chai_core::test_support::{create_fixture_tables, insert_project};
+    use serde_json::Value;
+
+    use crate::config::Config;
+    use crate::test_state::test_state;
+
+    /// Names that are also the last segment of a `/project/{id}/…` route are
+    /// searched for, rather than parsed as an id
+    #[actix_web::test]
+    async fn search_wins_over_project_routes() {
+        let Ok(url) = env::var("DATABASE_URL") else {
+            eprintln!("DATABASE_URL not set, skipping database test");
+            return;
+        };
+        let state = test_state(&Config::from_env(), &url);
+        let client = state.pool.primary().get().await.unwrap();
+        create_fixture_tables(&client).await;
+        let names = ["trend", "metrics", "rank-contributors", "funding"];
+        for name in names {
+            insert_project(&client, name, "npm", &[(1, "1.0")]).await;
+        }
+        drop(client);
+
+        let features = state.features.clone();
+        let app = init_service(
+            App::new()
+                .app_data(state)
+                .configure(|cfg| routes(cfg, &features)),
+        )
+        .await;
+        for name in names {
+            let request = TestRequest::get()
+                .uri(&format!("/project/search/{name}"))
+                .to_request();
+            let response = call_service(&app, request).await;
+            assert_eq!(response.status(), StatusCode::OK, "{name}");
+            let body: Value = read_body_json(response).await;
+            assert_eq!(body["name"], name);
+            assert!(body["totalMatches"].as_i64().unwrap() >= 1, "{name}");
+        }
+    }
 }
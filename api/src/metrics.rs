@@ -0,0 +1,303 @@
+//! Prometheus metrics at `/metrics`: requests and their latencies per route,
+//! connection pool usage per region, and lookups per in-memory cache. Requests
+//! are counted as they finish; pools and caches are read at scrape time.
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    get,
+    middleware::Next,
+    web, Error, HttpResponse, Responder,
+};
+use dashmap::DashMap;
+use deadpool_postgres::Status;
+use std::fmt::{Display, Write};
+use std::time::{Duration, Instant};
+
+use crate::app_state::AppState;
+use crate::auth::Admin;
+
+/// Media type of the Prometheus text exposition format
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Upper bounds in seconds of the latency histogram's buckets
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Default)]
+struct Histogram {
+    /// Observations at or under each of [`LATENCY_BUCKETS`], not cumulative
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+/// Requests since startup, by route pattern (e.g. `/project/{id}`) rather than
+/// path, so labels stay few
+#[derive(Default)]
+pub struct Metrics {
+    /// Keyed by method, route and status
+    requests: DashMap<(String, String, u16), u64>,
+    /// Keyed by method and route
+    latencies: DashMap<(String, String), Histogram>,
+}
+
+impl Metrics {
+    pub fn record(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        *self
+            .requests
+            .entry((method.to_string(), route.to_string(), status))
+            .or_default() += 1;
+
+        let seconds = elapsed.as_secs_f64();
+        let mut histogram = self
+            .latencies
+            .entry((method.to_string(), route.to_string()))
+            .or_default();
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            histogram.buckets[i] += 1;
+        }
+        histogram.count += 1;
+        histogram.sum += seconds;
+    }
+
+    fn render(&self, out: &mut Exposition) {
+        let name = "chai_http_requests_total";
+        out.family(name, "counter", "Requests served");
+        let mut requests: Vec<_> = self
+            .requests
+            .iter()
+            .map(|entry| (entry.key().clone(), *entry.value()))
+            .collect();
+        requests.sort();
+        for ((method, route, status), count) in requests {
+            let status = status.to_string();
+            let labels = [("method", &*method), ("route", &route), ("status", &status)];
+            out.sample(name, &labels, count);
+        }
+
+        let name = "chai_http_request_duration_seconds";
+        out.family(
+            name,
+            "histogram",
+            "Time from receiving a request to responding",
+        );
+        let mut latencies: Vec<_> = self
+            .latencies
+            .iter()
+            .map(|entry| {
+                let histogram = entry.value();
+                (
+                    entry.key().clone(),
+                    histogram.buckets,
+                    histogram.count,
+                    histogram.sum,
+                )
+            })
+            .collect();
+        latencies.sort_by(|a, b| a.0.cmp(&b.0));
+        for ((method, route), buckets, count, sum) in latencies {
+            let labels = [("method", &*method), ("route", &route)];
+            let mut cumulative = 0;
+            for (bound, observed) in LATENCY_BUCKETS.iter().zip(buckets) {
+                cumulative += observed;
+                let le = bound.to_string();
+                let bucket = [labels[0], labels[1], ("le", &le)];
+                out.sample(&format!("{name}_bucket"), &bucket, cumulative);
+            }
+            let bucket = [labels[0], labels[1], ("le", "+Inf")];
+            out.sample(&format!("{name}_bucket"), &bucket, count);
+            out.sample(&format!("{name}_sum"), &labels, sum);
+            out.sample(&format!("{name}_count"), &labels, count);
+        }
+    }
+}
+
+/// A Prometheus text exposition being written
+#[derive(Default)]
+struct Exposition(String);
+
+impl Exposition {
+    /// Starts the metric family `name`
+    fn family(&mut self, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(self.0, "# HELP {name} {help}");
+        let _ = writeln!(self.0, "# TYPE {name} {kind}");
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: impl Display) {
+        let labels = labels
+            .iter()
+            .map(|(label, value)| format!("{label}=\"{}\"", escape(value)))
+            .collect::<Vec<_>>()
+            .join(",");
+        let _ = writeln!(self.0, "{name}{{{labels}}} {value}");
+    }
+}
+
+/// A label value with `\`, `"` and newlines escaped
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+/// Counts every request with its route, status and latency
+pub async fn record_metrics(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let started = Instant::now();
+    let data = req.app_data::<web::Data<AppState>>().cloned();
+    let res = next.call(req).await?;
+
+    if let Some(data) = data {
+        let route = res
+            .request()
+            .match_pattern()
+            .unwrap_or_else(|| "unmatched".to_string());
+        data.metrics.record(
+            res.request().method().as_str(),
+            &route,
+            res.status().as_u16(),
+            started.elapsed(),
+        );
+    }
+    Ok(res)
+}
+
+/// Reads one gauge off a pool's status
+type PoolGauge = fn(&Status) -> isize;
+
+/// Connections of each region's pool
+fn render_pools(out: &mut Exposition, data: &AppState) {
+    let statuses: Vec<_> = data.pool.statuses().collect();
+    let gauges: [(&str, &str, PoolGauge); 4] = [
+        ("chai_db_pool_connections", "Open connections", |s| {
+            s.size as isize
+        }),
+        (
+            "chai_db_pool_max_connections",
+            "Most connections the pool opens",
+            |s| s.max_size as isize,
+        ),
+        (
+            "chai_db_pool_idle_connections",
+            "Open connections not checked out",
+            |s| s.available.max(0),
+        ),
+        // deadpool counts waiters as negative availability
+        (
+            "chai_db_pool_waiting",
+            "Requests waiting for a connection",
+            |s| (-s.available).max(0),
+        ),
+    ];
+    for (name, help, value) in gauges {
+        out.family(name, "gauge", help);
+        for (region, status) in &statuses {
+            out.sample(name, &[("region", region)], value(status));
+        }
+    }
+}
+
+/// Entries and lookups of each in-memory cache
+fn render_caches(out: &mut Exposition, data: &AppState) {
+    let name = "chai_cache_entries";
+    out.family(name, "gauge", "Entries held per cache");
+    for (cache, entries) in data.caches() {
+        out.sample(name, &[("cache", cache)], entries.len());
+    }
+
+    let lookups: Vec<_> = data
+        .cache_metrics
+        .namespaces()
+        .into_iter()
+        .map(|(cache, counters)| (cache, counters.lookups()))
+        .collect();
+    let name = "chai_cache_lookups_total";
+    out.family(name, "counter", "Cache lookups by outcome");
+    for (cache, counts) in &lookups {
+        for (result, count) in counts {
+            out.sample(name, &[("cache", cache), ("result", result)], count);
+        }
+    }
+    // Stale entries are served too, as `/admin/cache/stats` counts them
+    let name = "chai_cache_hit_ratio";
+    out.family(
+        name,
+        "gauge",
+        "Share of cache lookups answered from the cache",
+    );
+    for (cache, counts) in &lookups {
+        let served: u64 = counts
+            .iter()
+            .filter(|(result, _)| matches!(*result, "hit" | "stale"))
+            .map(|(_, count)| count)
+            .sum();
+        let total: u64 = counts.iter().map(|(_, count)| count).sum();
+        out.sample(
+            name,
+            &[("cache", cache)],
+            served as f64 / total.max(1) as f64,
+        );
+    }
+}
+
+/// Requests, database pools, and caches in the Prometheus text format
+#[utoipa::path(
+    tag = "admin",
+    security(("apiKey" = [])),
+    responses(
+        (
+            status = 200,
+            description = "Metrics since startup",
+            content_type = "text/plain",
+            body = String
+        ),
+        (status = 403, description = "Not an admin API key"),
+    )
+)]
+#[get("/metrics")]
+pub async fn get_metrics(_: Admin, data: web::Data<AppState>) -> impl Responder {
+    let mut out = Exposition::default();
+    data.metrics.render(&mut out);
+    render_pools(&mut out, &data);
+    render_caches(&mut out, &data);
+    HttpResponse::Ok().content_type(CONTENT_TYPE).body(out.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latencies_render_as_cumulative_histograms() {
+        let metrics = Metrics::default();
+        for millis in [3, 40, 40, 20_000] {
+            metrics.record("GET", "/project/{id}", 200, Duration::from_millis(millis));
+        }
+        metrics.record("GET", "/project/{id}", 404, Duration::from_millis(1));
+
+        let mut out = Exposition::default();
+        metrics.render(&mut out);
+        let labels = r#"method="GET",route="/project/{id}""#;
+        for line in [
+            format!(r#"chai_http_requests_total{{{labels},status="200"}} 4"#),
+            format!(r#"chai_http_requests_total{{{labels},status="404"}} 1"#),
+            format!(r#"chai_http_request_duration_seconds_bucket{{{labels},le="0.005"}} 2"#),
+            format!(r#"chai_http_request_duration_seconds_bucket{{{labels},le="0.05"}} 4"#),
+            format!(r#"chai_http_request_duration_seconds_bucket{{{labels},le="10"}} 4"#),
+            format!(r#"chai_http_request_duration_seconds_bucket{{{labels},le="+Inf"}} 5"#),
+            format!(r#"chai_http_request_duration_seconds_count{{{labels}}} 5"#),
+        ] {
+            assert!(
+                out.0.lines().any(|l| l == line),
+                "{line} missing from\n{}",
+                out.0
+            );
+        }
+        assert_eq!(escape(r#"a"b\c"#), r#"a\"b\\c"#);
+    }
+}
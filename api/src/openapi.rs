@@ -0,0 +1,654 @@
+//! The OpenAPI document for every route, generated from the handlers'
+//! `#[utoipa::path]` annotations, served at `/openapi.json` with Swagger UI at
+//! `/docs`.
+
+use actix_web::{get, web, HttpResponse, Responder};
+use utoipa::openapi::schema::AdditionalProperties;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::openapi::{self, Content, ObjectBuilder, Ref, RefOr, Type};
+use utoipa::{Modify, OpenApi};
+
+use crate::app_state::AppState;
+use crate::features::Feature;
+
+/// Swagger UI, loaded from a CDN so the API doesn't have to bundle it
+const DOCS_HTML: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>CHAI API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+  </script>
+</body>
+</html>
+"##;
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        crate::handlers::heartbeat,
+        crate::status::get_status,
+        crate::handlers::get_version,
+        crate::handlers::get_limits,
+        crate::handlers::list_tables,
+        crate::handlers::get_table,
+        crate::handlers::get_table_row,
+        crate::table_stream::stream_table,
+        crate::handlers::get_leaderboard,
+        crate::handlers::list_new_entrants,
+        crate::orgs::get_org_leaderboard,
+        crate::orgs::get_org,
+        crate::ranks::get_rank_percentiles,
+        crate::ranks::get_run_comparison,
+        crate::ranks::get_project_trend,
+        crate::ranks::get_project_metrics,
+        crate::ranks::get_rank_contributors,
+        crate::stats::get_cross_ecosystem,
+        crate::stats::get_source_hosts,
+        crate::stats::get_graph_stats,
+        crate::stats::get_top_dependents,
+        crate::stats::get_top_dependencies,
+        crate::reports::get_stale_report,
+        crate::handlers::get_project,
+        crate::handlers::get_project_by_slug,
+        crate::handlers::list_projects_by_id,
+        crate::handlers::projects_exist,
+        crate::handlers::list_projects_by_name,
+        crate::handlers::list_similar_names,
+        crate::handlers::get_maintainer_concentration,
+        crate::handlers::get_dependency_tree,
+        crate::handlers::list_dependents,
+        crate::handlers::check_name,
+        crate::handlers::get_project_funding,
+        crate::analyze::analyze_dependency_confusion,
+        crate::collections::create_collection,
+        crate::collections::list_collections,
+        crate::collections::get_collection,
+        crate::collections::put_collection,
+        crate::collections::delete_collection,
+        crate::collections::get_collection_leaderboard,
+        crate::collections::get_collection_changes,
+        crate::collections::put_collection_webhook,
+        crate::collections::rotate_collection_webhook_secret,
+        crate::collections::delete_collection_webhook,
+        crate::export::export_leaderboard,
+        crate::jobs::submit_job,
+        crate::jobs::get_job,
+        crate::jobs::delete_job,
+        crate::verification::request_verification,
+        crate::verification::get_verification,
+        crate::handlers::list_maintainer_projects,
+        crate::admin::get_usage,
+        crate::admin::get_table_usage,
+        crate::admin::invalidate_cache,
+        crate::admin::get_cache_stats,
+        crate::admin::explain,
+        crate::admin::get_pool_stats,
+        crate::admin::get_job_stats,
+        crate::admin::get_schedule,
+        crate::admin::get_run_checks,
+        crate::admin::get_http_stats,
+        crate::metrics::get_metrics,
+        crate::admin::quarantine_run,
+        crate::admin::clear_quarantine,
+        crate::overrides::list_overrides,
+        crate::overrides::put_override,
+        crate::overrides::delete_override,
+        crate::admin::list_suspects,
+        crate::admin::get_schema,
+        crate::admin::cutover_schema,
+    ),
+    // Referenced through generic parameters, which utoipa doesn't collect
+    components(schemas(
+        chai_types::models::MetricsPoint,
+        chai_types::models::NewEntrant,
+        chai_types::models::OrgProject,
+        chai_types::models::OrgRank,
+        chai_types::models::SearchResult,
+        chai_types::models::TrendPoint,
+    )),
+    tags(
+        (name = "health", description = "Whether the API is up, and what it allows the caller"),
+        (name = "tables", description = "Raw rows of the database's tables"),
+        (name = "projects", description = "Projects and their packages, dependencies, and names"),
+        (name = "leaderboard", description = "Projects and organizations by rank"),
+        (name = "ranks", description = "How ranks are distributed and how they move between runs"),
+        (name = "stats", description = "Aggregates over the whole dataset"),
+        (name = "analysis", description = "Checks over a caller's own package list"),
+        (name = "collections", description = "Saved lists of projects, with change webhooks"),
+        (name = "exports", description = "A run's full leaderboard as a file"),
+        (name = "jobs", description = "Long running work, polled until done"),
+        (name = "maintainers", description = "Maintainers and verifying them"),
+        (name = "admin", description = "Operating the API; requires an admin API key"),
+    ),
+    modifiers(&Conventions),
+    security((), ("apiKey" = []))
+)]
+pub struct ApiDoc;
+
+/// What every route shares: the `X-API-Key` scheme, and the problem document
+/// body of errors
+struct Conventions;
+
+impl Modify for Conventions {
+    fn modify(&self, api: &mut openapi::OpenApi) {
+        let components = api.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "apiKey",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))),
+        );
+        components.schemas.insert(
+            "Error".to_string(),
+            ObjectBuilder::new()
+                .description(Some(
+                    "Why the request failed, as RFC 9457 problem details that repeat \
+                    `detail` in `error`.",
+                ))
+                .property("error", ObjectBuilder::new().schema_type(Type::String))
+                .required("error")
+                .property("type", ObjectBuilder::new().schema_type(Type::String))
+                .property("title", ObjectBuilder::new().schema_type(Type::String))
+                .property("status", ObjectBuilder::new().schema_type(Type::Integer))
+                .property("detail", ObjectBuilder::new().schema_type(Type::String))
+                // Problems may add members of their own, such as `value`
+                .additional_properties(Some(AdditionalProperties::FreeForm(true)))
+                .into(),
+        );
+
+        let operations = api.paths.paths.values_mut().flat_map(|item| {
+            [
+                &mut item.get,
+                &mut item.post,
+                &mut item.put,
+                &mut item.delete,
+            ]
+            .into_iter()
+            .flatten()
+        });
+        for operation in operations {
+            for (status, response) in &mut operation.responses.responses {
+                if let RefOr::T(response) = response {
+                    let failed = status.starts_with('4') || status.starts_with('5');
+                    if failed && response.content.is_empty() {
+                        response.content.insert(
+                            "application/json".to_string(),
+                            Content::new(Some(Ref::from_schema_name("Error"))),
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The OpenAPI document, without the routes of experimental features that
+/// are switched off
+#[get("/openapi.json")]
+pub async fn get_openapi(data: web::Data<AppState>) -> impl Responder {
+    let mut api = ApiDoc::openapi();
+    if !data.features.is_enabled(Feature::Funding) {
+        api.paths.paths.remove("/project/{id}/funding");
+    }
+    HttpResponse::Ok().json(api)
+}
+
+#[get("/docs")]
+pub async fn get_docs() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(DOCS_HTML)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::{header::CONTENT_TYPE, Method};
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::App;
+    use chai_core::test_support::{create_fixture_tables, insert_project};
+    use serde_json::{json, Value};
+    use uuid::Uuid;
+
+    use crate::auth::authenticate;
+    use crate::config::Config;
+    use crate::routes;
+    use crate::test_state::test_state;
+
+    const ADMIN_KEY: &str = "contract-admin";
+
+    /// A request as method, URI, path template, body, and the status it must
+    /// answer, if any particular one
+    type Case = (Method, String, String, Option<Value>, Option<u16>);
+
+    /// Where `value` strays from `schema`, resolving `$ref`s against `spec`'s
+    /// components. Objects may only carry the properties their schema lists,
+    /// so a field the handlers add but the document misses is caught too.
+    fn mismatches(spec: &Value, schema: &Value, value: &Value, at: &str) -> Vec<String> {
+        if let Some(reference) = schema["$ref"].as_str() {
+            let name = reference.trim_start_matches("#/components/schemas/");
+            let resolved = &spec["components"]["schemas"][name];
+            if !resolved.is_object() {
+                return vec![format!("{at}: {reference} isn't in the document")];
+            }
+            return mismatches(spec, resolved, value, at);
+        }
+        if let Some(all) = schema["allOf"].as_array() {
+            return all
+                .iter()
+                .flat_map(|schema| mismatches(spec, schema, value, at))
+                .collect();
+        }
+        for key in ["oneOf", "anyOf"] {
+            if let Some(any) = schema[key].as_array() {
+                let tried: Vec<Vec<String>> = any
+                    .iter()
+                    .map(|schema| mismatches(spec, schema, value, at))
+                    .collect();
+                if tried.iter().any(Vec::is_empty) {
+                    return Vec::new();
+                }
+                return vec![format!("{at}: matches none of {key}: {tried:?}")];
+            }
+        }
+        if let Some(allowed) = schema["enum"].as_array() {
+            if !allowed.contains(value) {
+                return vec![format!("{at}: {value} is not one of {allowed:?}")];
+            }
+        }
+
+        let types: Vec<&str> = match &schema["type"] {
+            Value::String(single) => vec![single.as_str()],
+            Value::Array(several) => several.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        let is = |name: &str| match name {
+            "null" => value.is_null(),
+            "boolean" => value.is_boolean(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "number" => value.is_number(),
+            "string" => value.is_string(),
+            "array" => value.is_array(),
+            "object" => value.is_object(),
+            _ => false,
+        };
+        if !types.is_empty() && !types.iter().any(|name| is(name)) {
+            return vec![format!("{at}: {value} is not {types:?}")];
+        }
+
+        let mut found = Vec::new();
+        match value {
+            Value::Array(items) if schema["items"].is_object() => {
+                for (i, item) in items.iter().enumerate() {
+                    found.extend(mismatches(
+                        spec,
+                        &schema["items"],
+                        item,
+                        &format!("{at}[{i}]"),
+                    ));
+                }
+            }
+            Value::Object(fields) => {
+                let properties = schema["properties"].as_object();
+                for required in schema["required"].as_array().into_iter().flatten() {
+                    let required = required.as_str().unwrap_or_default();
+                    if !fields.contains_key(required) {
+                        found.push(format!("{at}: missing required {required}"));
+                    }
+                }
+                for (key, field) in fields {
+                    let at = format!("{at}.{key}");
+                    match properties.and_then(|properties| properties.get(key)) {
+                        Some(property) => found.extend(mismatches(spec, property, field, &at)),
+                        None => match &schema["additionalProperties"] {
+                            additional if additional.is_object() => {
+                                found.extend(mismatches(spec, additional, field, &at))
+                            }
+                            Value::Bool(true) => {}
+                            _ if properties.is_some() => found.push(format!("{at}: undocumented")),
+                            _ => {}
+                        },
+                    }
+                }
+            }
+            _ => {}
+        }
+        found
+    }
+
+    /// Where the `status` answer of `method template` strays from what `spec`
+    /// documents for it: an undocumented status, or a JSON body that doesn't
+    /// match the schema of its media type
+    fn undocumented(
+        spec: &Value,
+        method: &Method,
+        template: &str,
+        status: u16,
+        content_type: &str,
+        body: &[u8],
+    ) -> Vec<String> {
+        let at = format!("{method} {template} {status}");
+        let response = &spec["paths"][template][method.as_str().to_lowercase()]["responses"]
+            [status.to_string()];
+        if !response.is_object() {
+            return vec![format!(
+                "{at} isn't documented: {}",
+                String::from_utf8_lossy(body)
+            )];
+        }
+        let media = content_type.split(';').next().unwrap_or_default().trim();
+        let is_json = media == "application/json" || media.ends_with("+json");
+        if body.is_empty() || !is_json {
+            return Vec::new();
+        }
+        // Problem documents are documented as the JSON `Error` schema
+        let content = &response["content"];
+        let schema = match &content[media] {
+            Value::Null => &content["application/json"]["schema"],
+            documented => &documented["schema"],
+        };
+        if schema.is_null() {
+            return vec![format!("{at} documents no {media} body")];
+        }
+        match serde_json::from_slice(body) {
+            Ok(value) => mismatches(spec, schema, &value, &at),
+            Err(e) => vec![format!("{at}: invalid JSON: {e}")],
+        }
+    }
+
+    /// Path parameters of the fixture projects, by name
+    fn path_value(name: &str, alpha: Uuid) -> String {
+        match name {
+            "id" => alpha.to_string(),
+            "name" | "slug" => "alpha".to_string(),
+            "table" => "canons".to_string(),
+            "run" => "2".to_string(),
+            "host" => "github.com".to_string(),
+            "org" => "example".to_string(),
+            other => panic!("No fixture value for path parameter {{{other}}}"),
+        }
+    }
+
+    /// Every operation of the document is called, those below with the status
+    /// they must answer and the rest with their path filled in from the
+    /// fixtures. Whatever comes back must be documented for its operation.
+    #[actix_web::test]
+    async fn responses_match_the_document() {
+        let Ok(url) = std::env::var("DATABASE_URL") else {
+            eprintln!("DATABASE_URL not set, skipping database test");
+            return;
+        };
+        let config = Config {
+            admin_api_key: Some(ADMIN_KEY.to_string()),
+            ..Config::from_env()
+        };
+        let state = test_state(&config, &url);
+        let client = state.pool.primary().get().await.unwrap();
+        create_fixture_tables(&client).await;
+        let alpha = insert_project(&client, "alpha", "npm", &[(1, "2.5"), (2, "3.5")]).await;
+        let beta = insert_project(&client, "beta", "pypi", &[(2, "1.5")]).await;
+        drop(client);
+        state.latest_run.send_replace(Some(2));
+
+        let features = state.features.clone();
+        let app = init_service(
+            App::new()
+                .app_data(state.clone())
+                .wrap(from_fn(authenticate))
+                .configure(|cfg| routes(cfg, &features)),
+        )
+        .await;
+        let spec = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let ids = json!([alpha, beta]);
+        let requests: Vec<(Method, String, &str, Option<Value>, u16)> = vec![
+            (Method::GET, "/version".into(), "/version", None, 200),
+            (Method::GET, "/limits".into(), "/limits", None, 200),
+            (
+                Method::GET,
+                format!("/project/{alpha}"),
+                "/project/{id}",
+                None,
+                200,
+            ),
+            (
+                Method::GET,
+                format!("/project/{alpha}?view=compact"),
+                "/project/{id}",
+                None,
+                200,
+            ),
+            (
+                Method::GET,
+                format!("/project/{}", uuid::Uuid::nil()),
+                "/project/{id}",
+                None,
+                404,
+            ),
+            (
+                Method::POST,
+                "/project/batch".into(),
+                "/project/batch",
+                Some(json!({ "projectIds": ids })),
+                200,
+            ),
+            (
+                Method::POST,
+                "/project/exists".into(),
+                "/project/exists",
+                Some(json!({ "projectIds": ids })),
+                200,
+            ),
+            (
+                Method::GET,
+                "/project/search/alpha".into(),
+                "/project/search/{name}",
+                None,
+                200,
+            ),
+            (
+                Method::POST,
+                "/leaderboard".into(),
+                "/leaderboard",
+                Some(json!({ "projectIds": ids, "limit": 10 })),
+                200,
+            ),
+            (
+                Method::POST,
+                "/leaderboard".into(),
+                "/leaderboard",
+                Some(json!({ "limit": 10 })),
+                200,
+            ),
+            (
+                Method::GET,
+                "/leaderboard/new".into(),
+                "/leaderboard/new",
+                None,
+                200,
+            ),
+            (
+                Method::GET,
+                "/ranks/compare?from=1&to=2".into(),
+                "/ranks/compare",
+                None,
+                200,
+            ),
+            (
+                Method::GET,
+                "/ranks/percentiles".into(),
+                "/ranks/percentiles",
+                None,
+                200,
+            ),
+            (
+                Method::GET,
+                format!("/project/{alpha}/trend"),
+                "/project/{id}/trend",
+                None,
+                200,
+            ),
+            (
+                Method::GET,
+                format!("/project/{alpha}/metrics"),
+                "/project/{id}/metrics",
+                None,
+                200,
+            ),
+            (
+                Method::GET,
+                format!("/project/{alpha}/rank-contributors"),
+                "/project/{id}/rank-contributors",
+                None,
+                200,
+            ),
+            (
+                Method::GET,
+                format!("/project/{alpha}/dependents?limit=5"),
+                "/project/{id}/dependents",
+                None,
+                200,
+            ),
+            (
+                Method::GET,
+                format!("/project/{alpha}/dependencies?depth=2"),
+                "/project/{id}/dependencies",
+                None,
+                200,
+            ),
+            (
+                Method::GET,
+                "/stats/top-dependents?limit=10".into(),
+                "/stats/top-dependents",
+                None,
+                200,
+            ),
+        ];
+
+        // Operations without a case above get one with only their path filled in
+        let covered: Vec<(Method, &str)> = requests
+            .iter()
+            .map(|(method, _, template, _, _)| (method.clone(), *template))
+            .collect();
+        let mut requests: Vec<Case> = requests
+            .into_iter()
+            .map(|(method, uri, template, body, status)| {
+                (method, uri, template.to_string(), body, Some(status))
+            })
+            .collect();
+        let paths = spec["paths"].as_object().expect("the document has paths");
+        for (template, item) in paths {
+            for method in [Method::GET, Method::POST, Method::PUT, Method::DELETE] {
+                let documented = item[method.as_str().to_lowercase()].is_object();
+                if !documented || covered.contains(&(method.clone(), template.as_str())) {
+                    continue;
+                }
+                let uri = template
+                    .split('/')
+                    .map(|segment| match segment.strip_prefix('{') {
+                        Some(name) => path_value(name.trim_end_matches('}'), alpha),
+                        None => segment.to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("/");
+                let body = matches!(method, Method::POST | Method::PUT).then(|| json!({}));
+                requests.push((method, uri, template.clone(), body, None));
+            }
+        }
+
+        let mut failures = Vec::new();
+        for (method, uri, template, body, status) in requests {
+            let mut req = TestRequest::default()
+                .method(method.clone())
+                .uri(&uri)
+                .insert_header(("X-API-Key", ADMIN_KEY));
+            if let Some(body) = body {
+                req = req.set_json(body);
+            }
+            let response = call_service(&app, req.to_request()).await;
+            let actual = response.status().as_u16();
+            let content_type = response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            let body = read_body(response).await;
+            let unexpected = match status {
+                Some(status) => actual != status,
+                // A fixture gap, not a documentation one
+                None => actual == 500,
+            };
+            if unexpected {
+                failures.push(format!(
+                    "{method} {uri}: {actual}, expected {}: {}",
+                    status.map_or("no server error".to_string(), |s| s.to_string()),
+                    String::from_utf8_lossy(&body)
+                ));
+                continue;
+            }
+            failures.extend(undocumented(
+                &spec,
+                &method,
+                &template,
+                actual,
+                &content_type,
+                &body,
+            ));
+        }
+        assert!(failures.is_empty(), "{failures:#?}");
+    }
+
+    #[test]
+    fn every_operation_is_tagged_and_documented() {
+        let api = ApiDoc::openapi();
+        let mut operations = 0;
+        for (path, item) in &api.paths.paths {
+            for operation in [&item.get, &item.post, &item.put, &item.delete]
+                .into_iter()
+                .flatten()
+            {
+                operations += 1;
+                assert_eq!(operation.tags.as_ref().map(Vec::len), Some(1), "{path}");
+                assert!(
+                    operation
+                        .responses
+                        .responses
+                        .keys()
+                        .any(|status| status.starts_with('2')),
+                    "{path}"
+                );
+                // Every `{segment}` of the path is a documented parameter
+                for segment in path.split('/').filter(|s| s.starts_with('{')) {
+                    let name = segment.trim_matches(|c| c == '{' || c == '}');
+                    assert!(
+                        operation
+                            .parameters
+                            .iter()
+                            .flatten()
+                            .any(|parameter| parameter.name == name),
+                        "{path} is missing {name}"
+                    );
+                }
+            }
+        }
+        assert_eq!(operations, 71);
+
+        let json = serde_json::to_value(&api).unwrap();
+        let leaderboard = &json["paths"]["/leaderboard"]["post"];
+        assert_eq!(
+            leaderboard["requestBody"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/LeaderboardRequest"
+        );
+        assert!(json["components"]["schemas"]["Project"].is_object());
+        assert_eq!(
+            json["paths"]["/admin/usage"]["get"]["security"][0]["apiKey"],
+            serde_json::json!([])
+        );
+    }
+}
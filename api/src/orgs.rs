@@ -0,0 +1,163 @@
+//! Projects by the organization their source lives under, for companies
+//! looking at their open source portfolio.
+
+use actix_web::http::StatusCode;
+use actix_web::web::Query;
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use chai_core::orgs::{org_projects, org_ranks, org_totals, Org};
+use chai_types::format::Int8;
+use chai_types::models::{OrgLeaderboard, OrgPortfolio};
+use chai_types::requests::{OrgLeaderboardParams, OrgScore, PaginationParams};
+use dashmap::DashMap;
+use serde_json::Value;
+use std::sync::Arc;
+
+use crate::app_state::AppState;
+use crate::auth::caller;
+use crate::problem::{database_error, no_connection, problem};
+use crate::utils::Pagination;
+
+/// Most organizations one org leaderboard request lists, and how many of each
+/// score are cached
+const MAX_ORGS: i64 = 1000;
+
+/// The best organizations by each score, `MAX_ORGS` of them with ranks from
+/// the run they are keyed by. Only the latest run's are kept.
+pub type OrgRankCache = DashMap<(Option<i32>, OrgScore), Arc<Vec<Value>>>;
+
+/// Every project with a source under `host/org`, e.g. `github.com/tokio-rs`,
+/// best ranked first, with the organization's combined rank. `org` may span
+/// several segments, for GitLab subgroups.
+#[utoipa::path(
+    tag = "leaderboard",
+    params(
+        ("host" = String, Path, description = "Forge host, e.g. `github.com`"),
+        ("org" = String, Path, description = "Organization, possibly spanning several segments"),
+    ),
+    responses(
+        (status = 200, description = "OK", body = OrgPortfolio),
+        (status = 400, description = "Unknown host"),
+    )
+)]
+#[get("/org/{host}/{org:.+}")]
+pub async fn get_org(
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    pagination: Query<PaginationParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let (host, org) = path.into_inner();
+    let org = match Org::new(&host, &org) {
+        Ok(org) => org,
+        Err(error) => return problem(StatusCode::BAD_REQUEST, error),
+    };
+
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+
+    let run = *data.latest_run.borrow();
+    let live = data.not_deleted("canons", "c");
+    let totals = match org_totals(&client, &org, run, &live).await {
+        Ok(totals) => totals,
+        Err(e) => {
+            log::error!("Database count query error: {e}");
+            return database_error(&e);
+        }
+    };
+    let pagination = Pagination::new(
+        pagination,
+        totals.projects,
+        data.api_keys.limits(&caller(&req)).response_limit,
+    );
+
+    match org_projects(
+        &client,
+        &org,
+        run,
+        pagination.limit,
+        pagination.offset,
+        &live,
+        data.row_format,
+    )
+    .await
+    {
+        Ok(projects) => HttpResponse::Ok()
+            .insert_header(pagination.link(&req))
+            .json(OrgPortfolio {
+                host: org.host,
+                org: org.org,
+                run,
+                ranked_projects: Int8(totals.ranked, data.row_format),
+                rank_mass: totals.rank_mass,
+                total_count: totals.projects,
+                page: pagination.page,
+                limit: pagination.limit,
+                total_pages: pagination.total_pages,
+                projects,
+            }),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+/// Organizations on the major forges by the ranks of their projects in the
+/// latest run, summed (the default) or their best project's
+#[utoipa::path(
+    tag = "leaderboard",
+    params(OrgLeaderboardParams),
+    responses(
+        (status = 200, description = "OK", body = OrgLeaderboard),
+    )
+)]
+#[get("/leaderboard/orgs")]
+pub async fn get_org_leaderboard(
+    params: Query<OrgLeaderboardParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let limit = params.limit.unwrap_or(100).clamp(1, MAX_ORGS);
+    let run = *data.latest_run.borrow();
+
+    let cached = data
+        .org_ranks
+        .get(&(run, params.by))
+        .map(|entry| Arc::clone(&entry));
+    let orgs = match cached {
+        Some(orgs) => orgs,
+        None => {
+            let client = match data.pool.get().await {
+                Ok(client) => client,
+                Err(e) => {
+                    log::error!("Failed to get database connection: {e}");
+                    return no_connection();
+                }
+            };
+            let live = data.not_deleted("canons", "c");
+            let orgs =
+                match org_ranks(&client, run, params.by, MAX_ORGS, &live, data.row_format).await {
+                    Ok(orgs) => Arc::new(orgs),
+                    Err(e) => {
+                        log::error!("Database query error: {e}");
+                        return database_error(&e);
+                    }
+                };
+            // A new run makes the other runs' entries stale
+            data.org_ranks
+                .retain(|(cached_run, _), _| *cached_run == run);
+            data.org_ranks.insert((run, params.by), Arc::clone(&orgs));
+            orgs
+        }
+    };
+
+    HttpResponse::Ok().json(OrgLeaderboard {
+        run,
+        by: params.by,
+        orgs: orgs.iter().take(limit as usize).collect::<Vec<_>>(),
+    })
+}
@@ -0,0 +1,210 @@
+//! Curator overrides of the leaderboard: a project can be left off (say, a
+//! confirmed spam package) or shown with a pinned rank, until the override is
+//! removed or expires. The leaderboard queries apply them; these admin
+//! endpoints manage them, and every change goes to the audit log.
+
+use actix_web::http::StatusCode;
+use actix_web::{delete, get, put, web, HttpResponse, Responder};
+use chai_core::cache::Target;
+use chai_core::db::timed;
+use chai_core::leaderboard::leaderboard_overrides;
+use chai_types::models::LeaderboardOverride;
+use chai_types::requests::{OverrideAction, OverrideRequest};
+use chrono::{NaiveDateTime, Utc};
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::auth::Admin;
+use crate::db::read_write;
+use crate::extractors::PathUuid;
+use crate::problem::{database_error, no_connection, not_found, problem};
+
+/// Checks `body`, returning the rank to pin (`None` to exclude)
+fn pinned_rank(body: &OverrideRequest) -> Result<Option<String>, String> {
+    if body.reason.trim().is_empty() {
+        return Err("An override needs a reason".to_string());
+    }
+    if body.expires_at.is_some_and(|at| at <= Utc::now()) {
+        return Err("expiresAt must be in the future".to_string());
+    }
+    match (body.action, &body.tea_rank) {
+        (OverrideAction::Exclude, None) => Ok(None),
+        (OverrideAction::Exclude, Some(_)) => {
+            Err("teaRank only applies to pin overrides".to_string())
+        }
+        (OverrideAction::Pin, None) => Err("Pin overrides need a teaRank".to_string()),
+        (OverrideAction::Pin, Some(rank)) => match rank.trim().parse::<f64>() {
+            Ok(value) if value.is_finite() && value > 0.0 => Ok(Some(rank.trim().to_string())),
+            _ => Err(format!(
+                "Invalid teaRank '{rank}': must be a positive number"
+            )),
+        },
+    }
+}
+
+#[utoipa::path(
+    tag = "admin",
+    security(("apiKey" = [])),
+    responses(
+        (status = 200, description = "OK", body = Vec<LeaderboardOverride>),
+        (status = 403, description = "Not an admin API key"),
+    )
+)]
+#[get("/admin/overrides")]
+pub async fn list_overrides(_: Admin, data: web::Data<AppState>) -> impl Responder {
+    let client = match data.pool.primary().get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+    match leaderboard_overrides(&client, None, data.row_format).await {
+        Ok(overrides) => HttpResponse::Ok().json(overrides),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+/// Sets a project's override, replacing any it had
+#[utoipa::path(
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Project id")),
+    security(("apiKey" = [])),
+    responses(
+        (status = 200, description = "OK", body = LeaderboardOverride),
+        (status = 400, description = "Invalid override"),
+        (status = 403, description = "Not an admin API key"),
+        (status = 404, description = "No such project"),
+    )
+)]
+#[put("/admin/overrides/{id}")]
+pub async fn put_override(
+    _: Admin,
+    id: PathUuid,
+    body: web::Json<OverrideRequest>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let PathUuid(id) = id;
+    let pinned_rank = match pinned_rank(&body) {
+        Ok(rank) => rank,
+        Err(error) => return problem(StatusCode::BAD_REQUEST, error),
+    };
+    let expires_at: Option<NaiveDateTime> = body.expires_at.map(|at| at.naive_utc());
+
+    let mut client = match data.pool.primary().get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+    let upsert_query = r#"
+        INSERT INTO leaderboard_overrides (canon_id, pinned_rank, reason, expires_at)
+        SELECT $1, $2, $3, $4
+        WHERE EXISTS (SELECT 1 FROM canons WHERE id = $1)
+        ON CONFLICT (canon_id) DO UPDATE
+        SET pinned_rank = EXCLUDED.pinned_rank,
+            reason = EXCLUDED.reason,
+            expires_at = EXCLUDED.expires_at,
+            created_at = now()
+        RETURNING canon_id"#;
+    let upsert = async {
+        let transaction = read_write(&mut client).await?;
+        let row = transaction
+            .query_opt(
+                upsert_query,
+                &[&id, &pinned_rank, &body.reason.trim(), &expires_at],
+            )
+            .await?;
+        transaction.commit().await?;
+        Ok::<_, tokio_postgres::Error>(row)
+    };
+    match timed("put_override", upsert).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return not_found(format!("No row found with id '{id}' in table canons")),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            return database_error(&e);
+        }
+    }
+
+    evict(&data, id);
+    match &pinned_rank {
+        Some(rank) => log::info!(
+            target: "chai_api::audit",
+            "Leaderboard override on {id}: pinned at {rank} until {:?} ({})",
+            body.expires_at, body.reason.trim()
+        ),
+        None => log::info!(
+            target: "chai_api::audit",
+            "Leaderboard override on {id}: excluded until {:?} ({})",
+            body.expires_at, body.reason.trim()
+        ),
+    }
+
+    match leaderboard_overrides(&client, Some(id), data.row_format).await {
+        Ok(mut overrides) if !overrides.is_empty() => HttpResponse::Ok().json(overrides.remove(0)),
+        Ok(_) => not_found(format!("No row found with id '{id}' in table canons")),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+#[utoipa::path(
+    tag = "admin",
+    params(("id" = Uuid, Path, description = "Project id")),
+    security(("apiKey" = [])),
+    responses(
+        (status = 204, description = "Override removed"),
+        (status = 403, description = "Not an admin API key"),
+        (status = 404, description = "The project has no override"),
+    )
+)]
+#[delete("/admin/overrides/{id}")]
+pub async fn delete_override(_: Admin, id: PathUuid, data: web::Data<AppState>) -> impl Responder {
+    let PathUuid(id) = id;
+
+    let mut client = match data.pool.primary().get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+    let remove = async {
+        let transaction = read_write(&mut client).await?;
+        let removed = transaction
+            .execute(
+                "DELETE FROM leaderboard_overrides WHERE canon_id = $1",
+                &[&id],
+            )
+            .await?;
+        transaction.commit().await?;
+        Ok::<_, tokio_postgres::Error>(removed)
+    };
+    match timed("delete_override", remove).await {
+        Ok(0) => not_found(format!("No override for project '{id}'")),
+        Ok(_) => {
+            evict(&data, id);
+            log::info!(target: "chai_api::audit", "Leaderboard override on {id} removed");
+            HttpResponse::NoContent().finish()
+        }
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+/// Drops the project's cached leaderboard entry, which carries its old rank
+fn evict(data: &AppState, id: Uuid) {
+    let target = Target::parse(&format!("project:{id}")).expect("project ids are valid targets");
+    if let Err(e) = data.invalidate(&[target]) {
+        log::error!("Failed to evict project {id} after an override change: {e}");
+    }
+}
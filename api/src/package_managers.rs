@@ -0,0 +1,139 @@
+//! Each canon's package managers, loaded by the `package_managers` task with
+//! one grouped query. Project and leaderboard queries leave `packageManagers`
+//! null and responses are filled in from here instead. Canons the map doesn't
+//! have, which before the first load is all of them, are looked up together.
+
+use chai_core::projects::package_managers_by_canon;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+use tokio_postgres::Client;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+
+const FIELD: &str = "packageManagers";
+
+/// Each canon's package managers, sharing identical lists
+type ByCanon = HashMap<Uuid, Arc<[String]>>;
+
+/// Replaced by the `package_managers` task
+#[derive(Default)]
+pub struct PackageManagers(RwLock<Option<Arc<ByCanon>>>);
+
+impl PackageManagers {
+    fn loaded(&self) -> Arc<ByCanon> {
+        self.0
+            .read()
+            .expect("package manager lock poisoned")
+            .clone()
+            .unwrap_or_default()
+    }
+
+    /// Fills in the `packageManagers` of `projects`, querying for those of
+    /// canons the map doesn't have. Projects without the field are left
+    /// alone, and canons without packages keep a null.
+    pub async fn hydrate(
+        &self,
+        client: &Client,
+        projects: &mut [Value],
+    ) -> Result<(), tokio_postgres::Error> {
+        let loaded = self.loaded();
+        let missing: Vec<Uuid> = projects
+            .iter()
+            .filter_map(project_id)
+            .filter(|id| !loaded.contains_key(id))
+            .collect();
+        let fetched = if missing.is_empty() {
+            ByCanon::new()
+        } else {
+            package_managers_by_canon(client, Some(&missing)).await?
+        };
+        for project in projects {
+            let list = project_id(project).and_then(|id| loaded.get(&id).or(fetched.get(&id)));
+            if let Some(list) = list {
+                project[FIELD] = json!(&**list);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The canon of a project with a `packageManagers` field
+fn project_id(project: &Value) -> Option<Uuid> {
+    project.get(FIELD)?;
+    project["projectId"].as_str()?.parse().ok()
+}
+
+/// One pass of the `package_managers` maintenance task. A failed pass keeps
+/// the previous map.
+pub async fn refresh_package_managers(data: &AppState) {
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection for package managers: {e}");
+            return;
+        }
+    };
+    let started = Instant::now();
+    match package_managers_by_canon(&client, None).await {
+        Ok(managers) => {
+            log::info!(
+                "Loaded the package managers of {} canons in {:?}",
+                managers.len(),
+                started.elapsed()
+            );
+            *data
+                .package_managers
+                .0
+                .write()
+                .expect("package manager lock poisoned") = Some(Arc::new(managers));
+        }
+        Err(e) => log::error!("Failed to load package managers: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chai_core::test_support::{insert_project, test_client};
+
+    #[tokio::test]
+    async fn projects_missing_from_the_map_are_looked_up() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+        let cached = insert_project(&client, "left-pad", "npm", &[]).await;
+        let uncached = insert_project(&client, "requests", "pypi", &[]).await;
+        let cache = PackageManagers::default();
+        let stale: Arc<[String]> = vec!["homebrew".to_string()].into();
+        *cache.0.write().unwrap() = Some(Arc::new(HashMap::from([(cached, stale)])));
+
+        let mut projects = vec![
+            json!({ "projectId": cached, "packageManagers": null }),
+            json!({ "projectId": uncached, "packageManagers": null }),
+            json!({ "projectId": Uuid::new_v4(), "packageManagers": null }),
+            json!({ "projectId": uncached, "name": "requests" }),
+        ];
+        let unknown = projects[2]["projectId"].clone();
+        cache.hydrate(&client, &mut projects).await.unwrap();
+        assert_eq!(
+            projects,
+            [
+                json!({ "projectId": cached, "packageManagers": ["homebrew"] }),
+                json!({ "projectId": uncached, "packageManagers": ["pypi"] }),
+                json!({ "projectId": unknown, "packageManagers": null }),
+                json!({ "projectId": uncached, "name": "requests" }),
+            ]
+        );
+
+        // Before the first load every canon is looked up
+        let mut project = [json!({ "projectId": cached, "packageManagers": null })];
+        PackageManagers::default()
+            .hydrate(&client, &mut project)
+            .await
+            .unwrap();
+        assert_eq!(project[0]["packageManagers"], json!(["npm"]));
+    }
+}
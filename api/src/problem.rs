@@ -0,0 +1,214 @@
+use actix_web::http::header::{HeaderValue, RETRY_AFTER};
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use serde_json::{json, Value};
+use tokio_postgres::error::SqlState;
+
+/// Media type of RFC 9457 problem details
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// How long a caller is asked to wait when no database connection was free
+const NO_CONNECTION_RETRY_SECS: u64 = 1;
+
+/// An RFC 9457 problem details response. `error` repeats `detail` so clients
+/// written against the older `{"error": ...}` bodies keep working.
+pub fn problem(status: StatusCode, detail: impl Into<String>) -> HttpResponse {
+    problem_with(status, detail, json!({}))
+}
+
+/// Like [`problem`], with extension members (an object) merged into the body
+pub fn problem_with(
+    status: StatusCode,
+    detail: impl Into<String>,
+    extensions: Value,
+) -> HttpResponse {
+    let detail = detail.into();
+    let mut body = json!({
+        "type": "about:blank",
+        "title": status.canonical_reason().unwrap_or("Error"),
+        "status": status.as_u16(),
+        "detail": detail,
+        "error": detail,
+    });
+    if let (Value::Object(body), Value::Object(extensions)) = (&mut body, extensions) {
+        body.extend(extensions);
+    }
+    HttpResponse::build(status)
+        .content_type(PROBLEM_JSON)
+        .body(body.to_string())
+}
+
+/// 404 for a single resource that doesn't exist
+pub fn not_found(detail: impl Into<String>) -> HttpResponse {
+    problem(StatusCode::NOT_FOUND, detail)
+}
+
+/// 503 for a request that couldn't check out a database connection, most
+/// often a pool drained by a burst that clears up within a second or two
+pub fn no_connection() -> HttpResponse {
+    let mut response = problem(
+        StatusCode::SERVICE_UNAVAILABLE,
+        "Failed to get database connection",
+    );
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, HeaderValue::from(NO_CONNECTION_RETRY_SECS));
+    response
+}
+
+/// The status and client-facing message for a database error. Failures with a
+/// known cause say what to do about it; anything else is answered with a
+/// generic 500, since its message can name tables and values. Logging `e` is
+/// left to callers, which know what was being attempted.
+pub fn database_problem(e: &tokio_postgres::Error) -> (StatusCode, String) {
+    let Some(code) = e.code() else {
+        return if e.is_closed() {
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "The database connection was lost. Retry shortly.".to_string(),
+            )
+        } else {
+            unexpected()
+        };
+    };
+
+    let (status, detail) = if *code == SqlState::QUERY_CANCELED {
+        (
+            StatusCode::GATEWAY_TIMEOUT,
+            "The query was cancelled for running too long. Narrow the request, for example \
+            with a smaller limit or page, and retry.",
+        )
+    } else if [
+        SqlState::UNDEFINED_COLUMN,
+        SqlState::UNDEFINED_TABLE,
+        SqlState::UNDEFINED_FUNCTION,
+    ]
+    .contains(code)
+    {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "The database schema doesn't match this version of the API, most likely because \
+            a migration hasn't run yet. This is a server problem; please report it.",
+        )
+    } else if *code == SqlState::INSUFFICIENT_PRIVILEGE {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "The API's database role is missing a privilege this request needs. This is a \
+            server problem; please report it.",
+        )
+    } else if *code == SqlState::READ_ONLY_SQL_TRANSACTION {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "The database is only accepting reads right now. Retry the write later.",
+        )
+    } else if [
+        SqlState::ADMIN_SHUTDOWN,
+        SqlState::CANNOT_CONNECT_NOW,
+        SqlState::TOO_MANY_CONNECTIONS,
+    ]
+    .contains(code)
+    {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "The database is temporarily unavailable. Retry shortly.",
+        )
+    } else if [
+        SqlState::T_R_SERIALIZATION_FAILURE,
+        SqlState::T_R_DEADLOCK_DETECTED,
+        SqlState::LOCK_NOT_AVAILABLE,
+    ]
+    .contains(code)
+    {
+        (
+            StatusCode::CONFLICT,
+            "The request conflicted with a concurrent change. Retry it.",
+        )
+    } else if [
+        SqlState::INVALID_TEXT_REPRESENTATION,
+        SqlState::NUMERIC_VALUE_OUT_OF_RANGE,
+        SqlState::DATETIME_FIELD_OVERFLOW,
+    ]
+    .contains(code)
+    {
+        (
+            StatusCode::BAD_REQUEST,
+            "A parameter couldn't be interpreted by the database. Check its type and range.",
+        )
+    } else {
+        return unexpected();
+    };
+    (status, detail.to_string())
+}
+
+/// A database failure without a known cause
+fn unexpected() -> (StatusCode, String) {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Internal server error".to_string(),
+    )
+}
+
+/// A problem response for a failed query, with the SQLSTATE as `code`
+pub fn database_error(e: &tokio_postgres::Error) -> HttpResponse {
+    let (status, detail) = database_problem(e);
+    problem_with(
+        status,
+        detail,
+        json!({ "code": e.code().map(SqlState::code) }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chai_core::test_support::test_client;
+
+    #[tokio::test]
+    async fn database_errors_map_to_distinct_statuses() {
+        let Some(client) = test_client().await else {
+            return;
+        };
+
+        let drift = client
+            .query("SELECT missing_column FROM canons", &[])
+            .await
+            .expect_err("column should not exist");
+        assert_eq!(
+            database_problem(&drift).0,
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert!(database_problem(&drift).1.contains("migration"));
+
+        client
+            .batch_execute("SET statement_timeout = '10ms'")
+            .await
+            .unwrap();
+        let canceled = client
+            .query("SELECT pg_sleep(1)", &[])
+            .await
+            .expect_err("query should time out");
+        assert_eq!(database_problem(&canceled).0, StatusCode::GATEWAY_TIMEOUT);
+        client
+            .batch_execute("RESET statement_timeout")
+            .await
+            .unwrap();
+
+        let cast = client
+            .query("SELECT 'abc'::int", &[])
+            .await
+            .expect_err("cast should fail");
+        assert_eq!(database_problem(&cast).0, StatusCode::BAD_REQUEST);
+
+        let other = client
+            .query("SELECT 1 / 0", &[])
+            .await
+            .expect_err("division should fail");
+        assert_eq!(
+            database_problem(&other),
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal server error".to_string()
+            )
+        );
+    }
+}
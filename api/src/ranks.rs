@@ -0,0 +1,442 @@
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpResponse, Responder};
+use chai_core::projects::canon_exists;
+use chai_core::ranks::{
+    metrics, rank_changes, rank_contributors, rank_percentiles, rank_series, rank_trend, run_ranks,
+    run_split,
+};
+use chai_core::rows::{round_ranks, with_precision};
+use chai_types::models::{
+    Percentiles, ProjectMetrics, RankContributors, RankTrend, RunComparison, TrendPoint,
+};
+use chai_types::requests::{CompareParams, ContributorParams, PercentileParams, RunWindowParams};
+use dashmap::DashMap;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use tokio_postgres::Client;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::extractors::PathUuid;
+use crate::problem::{database_error, no_connection, not_found, problem};
+use crate::runs::select_run;
+
+const DEFAULT_PERCENTILES: &str = "50,90,99";
+const MAX_PERCENTILES: usize = 20;
+const DEFAULT_WINDOW_RUNS: i64 = 5;
+const MAX_WINDOW_RUNS: i64 = 50;
+const DEFAULT_CONTRIBUTORS: i64 = 10;
+const MAX_CONTRIBUTORS: i64 = 100;
+
+/// Rank value at a percentile, keyed by run and percentile in hundredths (so
+/// 99.5 is 9950). Runs never change once written, so entries never expire, and
+/// the key space is bounded at 10,001 percentiles per run. `None` means the run
+/// had no positive ranks.
+pub type PercentileCache = DashMap<(i32, u32), Option<f64>>;
+
+/// Parses `p=50,90,99.9` into hundredths, rejecting anything outside 0-100 or
+/// finer than two decimals
+fn parse_percentiles(raw: &str) -> Result<Vec<u32>, String> {
+    let mut percentiles = Vec::new();
+    for part in raw.split(',').map(str::trim) {
+        let value: f64 = part
+            .parse()
+            .map_err(|_| format!("Invalid percentile '{part}': expected a number"))?;
+        if !(0.0..=100.0).contains(&value) {
+            return Err(format!(
+                "Invalid percentile {part}: must be between 0 and 100"
+            ));
+        }
+        let hundredths = value * 100.0;
+        if (hundredths - hundredths.round()).abs() > 1e-6 {
+            return Err(format!(
+                "Invalid percentile {part}: at most two decimal places are supported"
+            ));
+        }
+        let hundredths = hundredths.round() as u32;
+        if !percentiles.contains(&hundredths) {
+            percentiles.push(hundredths);
+        }
+    }
+    if percentiles.len() > MAX_PERCENTILES {
+        return Err(format!(
+            "Too many percentiles (maximum {MAX_PERCENTILES} allowed)"
+        ));
+    }
+    percentiles.sort_unstable();
+    Ok(percentiles)
+}
+
+/// `9950` as `"99.5"`, the key the percentile is reported under
+fn percentile_label(hundredths: u32) -> String {
+    let label = format!("{}.{:02}", hundredths / 100, hundredths % 100);
+    label
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/// Rank thresholds at the requested percentiles of a run's positive ranks
+#[utoipa::path(
+    tag = "ranks",
+    params(PercentileParams),
+    responses(
+        (status = 200, description = "OK", body = Percentiles),
+        (status = 400, description = "Invalid percentiles"),
+        (status = 404, description = "No such run"),
+    )
+)]
+#[get("/ranks/percentiles")]
+pub async fn get_rank_percentiles(
+    params: web::Query<PercentileParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let percentiles = match parse_percentiles(params.p.as_deref().unwrap_or(DEFAULT_PERCENTILES)) {
+        Ok(percentiles) => percentiles,
+        Err(error) => return problem(StatusCode::BAD_REQUEST, error),
+    };
+
+    // No runs yet means there is nothing to measure
+    let Some(run) = params.run.resolve(*data.latest_run.borrow()) else {
+        return HttpResponse::Ok().json(Percentiles {
+            run: None,
+            percentiles: BTreeMap::new(),
+        });
+    };
+
+    let missing: Vec<u32> = percentiles
+        .iter()
+        .copied()
+        .filter(|p| !data.percentile_cache.contains_key(&(run, *p)))
+        .collect();
+
+    if !missing.is_empty() {
+        let client = match data.pool.get().await {
+            Ok(client) => client,
+            Err(e) => {
+                log::error!("Failed to get database connection: {e}");
+                return no_connection();
+            }
+        };
+
+        let values = match rank_percentiles(&client, run, &missing).await {
+            Ok(Some(values)) => values,
+            Ok(None) => return not_found(format!("Run {run} not found")),
+            Err(e) => {
+                log::error!("Database query error: {e}");
+                return database_error(&e);
+            }
+        };
+        for (p, value) in missing.iter().zip(values) {
+            data.percentile_cache.insert((run, *p), value);
+        }
+    }
+
+    let thresholds = percentiles
+        .iter()
+        .map(|p| {
+            let value = data
+                .percentile_cache
+                .get(&(run, *p))
+                .and_then(|entry| *entry);
+            (percentile_label(*p), value)
+        })
+        .collect();
+
+    HttpResponse::Ok().json(Percentiles {
+        run: Some(run),
+        percentiles: thresholds,
+    })
+}
+
+/// Aggregate rank movement from one run to another, as a sanity check on a
+/// new run
+#[utoipa::path(
+    tag = "ranks",
+    params(CompareParams),
+    responses(
+        (status = 200, description = "OK", body = RunComparison),
+        (status = 404, description = "No such run"),
+    )
+)]
+#[get("/ranks/compare")]
+pub async fn get_run_comparison(
+    params: web::Query<CompareParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+
+    let mut ranks = Vec::with_capacity(2);
+    for run in [params.from, params.to] {
+        match run_ranks(&client, run).await {
+            Ok(Some(run_ranks)) => ranks.push(run_ranks),
+            Ok(None) => return not_found(format!("Run {run} not found")),
+            Err(e) => {
+                log::error!("Database query error: {e}");
+                return database_error(&e);
+            }
+        }
+    }
+
+    let changes = rank_changes(&ranks[0], &ranks[1]);
+    HttpResponse::Ok().json(RunComparison {
+        from: params.from,
+        to: params.to,
+        rose: changes.rose,
+        fell: changes.fell,
+        unchanged: changes.unchanged,
+        new: changes.new,
+        dropped: changes.dropped,
+        mean_absolute_change: changes.mean_absolute_change,
+        kendall_tau: changes.kendall_tau,
+    })
+}
+
+/// How many runs a project history covers
+fn window_runs(params: &RunWindowParams) -> i64 {
+    params
+        .runs
+        .unwrap_or(DEFAULT_WINDOW_RUNS)
+        .clamp(2, MAX_WINDOW_RUNS)
+}
+
+/// 404s unless `id` is a live canon
+async fn check_canon_exists(client: &Client, data: &AppState, id: Uuid) -> Option<HttpResponse> {
+    match canon_exists(client, id, &data.not_deleted("canons", "c")).await {
+        Ok(true) => None,
+        Ok(false) => Some(not_found(format!(
+            "No row found with id '{}' in table canons",
+            id
+        ))),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            Some(database_error(&e))
+        }
+    }
+}
+
+/// A project's rank over the last `runs` runs, with its slope, moving average,
+/// and a rising/falling/stable classification
+#[utoipa::path(
+    tag = "ranks",
+    params(
+        ("id" = Uuid, Path, description = "Project id"),
+        RunWindowParams,
+    ),
+    responses(
+        (status = 200, description = "OK", body = RankTrend),
+    )
+)]
+#[get("/project/{id}/trend")]
+pub async fn get_project_trend(
+    id: PathUuid,
+    params: web::Query<RunWindowParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let PathUuid(id) = id;
+    let runs = window_runs(&params);
+
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+
+    if let Some(response) = check_canon_exists(&client, &data, id).await {
+        return response;
+    }
+
+    // Oldest first, with a null rank for runs the project wasn't ranked in
+    let series = match rank_series(&client, id, runs).await {
+        Ok(series) => series,
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            return database_error(&e);
+        }
+    };
+
+    let trend = rank_trend(
+        &series
+            .iter()
+            .map(|(_, rank)| rank.as_deref().and_then(|r| r.parse().ok()))
+            .collect::<Vec<_>>(),
+    );
+    let precision = data.rank_precision(params.precision);
+    HttpResponse::Ok().json(RankTrend {
+        project_id: id,
+        runs: series.len(),
+        slope: trend.as_ref().map(|t| t.slope),
+        moving_average: trend.as_ref().map(|t| t.moving_average),
+        direction: trend.as_ref().map(|t| t.direction.to_string()),
+        series: series
+            .into_iter()
+            .map(|(run, tea_rank)| with_precision(json!(TrendPoint { run, tea_rank }), precision))
+            .collect(),
+    })
+}
+
+/// A project's rank and dependent count side by side for each of the last
+/// `runs` runs, for charting one against the other
+#[utoipa::path(
+    tag = "ranks",
+    params(
+        ("id" = Uuid, Path, description = "Project id"),
+        RunWindowParams,
+    ),
+    responses(
+        (status = 200, description = "OK", body = ProjectMetrics),
+    )
+)]
+#[get("/project/{id}/metrics")]
+pub async fn get_project_metrics(
+    id: PathUuid,
+    params: web::Query<RunWindowParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let PathUuid(id) = id;
+    let runs = window_runs(&params);
+
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+
+    if let Some(response) = check_canon_exists(&client, &data, id).await {
+        return response;
+    }
+
+    match metrics(&client, id, runs, data.row_format).await {
+        Ok(points) => {
+            let precision = data.rank_precision(params.precision);
+            HttpResponse::Ok().json(ProjectMetrics {
+                project_id: id,
+                points: points
+                    .into_iter()
+                    .map(|point| with_precision(point, precision))
+                    .collect(),
+            })
+        }
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+/// The direct dependents sending the most rank to a project in a run. The
+/// ranker doesn't record attribution, so the flow is re-derived from its model:
+/// a node keeps `split_ratio` of what reaches it and splits the rest evenly
+/// across its dependencies, so a dependent ranked `r` with `n` dependencies
+/// passed on `r / split_ratio * (1 - split_ratio) / n` to each.
+#[utoipa::path(
+    tag = "ranks",
+    params(
+        ("id" = Uuid, Path, description = "Project id"),
+        ContributorParams,
+    ),
+    responses(
+        (status = 200, description = "OK", body = RankContributors),
+        (status = 404, description = "No such project or run"),
+    )
+)]
+#[get("/project/{id}/rank-contributors")]
+pub async fn get_rank_contributors(
+    id: PathUuid,
+    params: web::Query<ContributorParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let PathUuid(id) = id;
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_CONTRIBUTORS)
+        .clamp(1, MAX_CONTRIBUTORS);
+
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+
+    if let Some(response) = check_canon_exists(&client, &data, id).await {
+        return response;
+    }
+
+    // No runs yet means nothing has flowed anywhere
+    let latest = *data.latest_run.borrow();
+    let run = match select_run(&client, params.run, params.as_of, latest).await {
+        Ok(run) => run,
+        Err(response) => return response,
+    };
+    let Some(run) = run else {
+        return HttpResponse::Ok().json(RankContributors::<Value> {
+            project_id: id,
+            run: None,
+            tea_rank: None,
+            contributors: Vec::new(),
+        });
+    };
+
+    let (split_ratio, rank) = match run_split(&client, run, id).await {
+        Ok(Some(split)) => split,
+        Ok(None) => return not_found(format!("Run {run} not found")),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            return database_error(&e);
+        }
+    };
+
+    // A ratio of 0 keeps nothing, and every ranked node would be a leaf
+    if split_ratio <= 0.0 || split_ratio > 1.0 {
+        log::error!("Run {run} has unusable split_ratio {split_ratio}");
+        return problem(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Run {run} has an invalid split ratio"),
+        );
+    }
+
+    let live = data.not_deleted("canons", "c");
+    let current = Some(run) == latest;
+    match rank_contributors(
+        &client,
+        id,
+        run,
+        current,
+        split_ratio,
+        limit,
+        &live,
+        data.row_format,
+    )
+    .await
+    {
+        Ok(contributors) => {
+            let mut response = json!(RankContributors {
+                project_id: id,
+                run: Some(run),
+                tea_rank: rank,
+                contributors,
+            });
+            if let Some(precision) = data.rank_precision(params.precision) {
+                round_ranks(&mut response, precision);
+                round_ranks(&mut response["contributors"], precision);
+            }
+            HttpResponse::Ok().json(response)
+        }
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
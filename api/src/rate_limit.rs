@@ -0,0 +1,241 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+        StatusCode,
+    },
+    middleware::Next,
+    web, Error,
+};
+use chai_types::models::Quota;
+use dashmap::DashMap;
+use serde_json::json;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::app_state::AppState;
+use crate::auth::caller;
+use crate::problem::problem_with;
+
+const LIMIT_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+const REMAINING_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+const RESET_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-reset");
+
+/// Fixed-window request counter keyed by caller.
+pub struct RateLimiter {
+    limit: u64,
+    window: Duration,
+    windows: DashMap<String, Window>,
+}
+
+struct Window {
+    started_at: u64,
+    count: u64,
+}
+
+impl RateLimiter {
+    pub fn new(limit: u64, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            windows: DashMap::new(),
+        }
+    }
+
+    /// Counts a request against `key`, returning whether it is allowed and the
+    /// quota left afterwards.
+    pub fn check(&self, key: &str) -> (bool, Quota) {
+        let now = unix_now();
+        let mut entry = self.windows.entry(key.to_string()).or_insert(Window {
+            started_at: now,
+            count: 0,
+        });
+        if now >= entry.started_at + self.window.as_secs() {
+            entry.started_at = now;
+            entry.count = 0;
+        }
+
+        let allowed = entry.count < self.limit;
+        if allowed {
+            entry.count += 1;
+        }
+        (allowed, self.quota(entry.started_at, entry.count))
+    }
+
+    /// Reports the quota for `key` without counting a request.
+    pub fn peek(&self, key: &str) -> Quota {
+        let now = unix_now();
+        match self.windows.get(key) {
+            Some(entry) if now < entry.started_at + self.window.as_secs() => {
+                self.quota(entry.started_at, entry.count)
+            }
+            _ => self.quota(now, 0),
+        }
+    }
+
+    /// Drops windows that have already reset so idle callers don't accumulate.
+    pub fn sweep(&self) {
+        let now = unix_now();
+        let window = self.window.as_secs();
+        self.windows
+            .retain(|_, entry| now < entry.started_at + window);
+    }
+
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    fn quota(&self, started_at: u64, count: u64) -> Quota {
+        Quota {
+            limit: self.limit,
+            remaining: self.limit.saturating_sub(count),
+            reset: started_at + self.window.as_secs(),
+            window_seconds: self.window.as_secs(),
+        }
+    }
+}
+
+/// Reports `quota` in the `X-RateLimit-*` headers
+fn apply_quota(quota: &Quota, headers: &mut HeaderMap) {
+    headers.insert(LIMIT_HEADER, HeaderValue::from(quota.limit));
+    headers.insert(REMAINING_HEADER, HeaderValue::from(quota.remaining));
+    headers.insert(RESET_HEADER, HeaderValue::from(quota.reset));
+}
+
+pub async fn rate_limit<B: MessageBody + 'static>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let Some(data) = req.app_data::<web::Data<AppState>>().cloned() else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+
+    let (allowed, quota) = data.rate_limiter.check(&caller(req.request()).id());
+    if !allowed {
+        let retry_after = quota.reset.saturating_sub(unix_now());
+        let mut response = problem_with(
+            StatusCode::TOO_MANY_REQUESTS,
+            "Rate limit exceeded",
+            json!({
+                "limit": quota.limit,
+                "reset": quota.reset,
+            }),
+        );
+        response
+            .headers_mut()
+            .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+        apply_quota(&quota, response.headers_mut());
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+
+    let mut res = next.call(req).await?;
+    apply_quota(&quota, res.headers_mut());
+    Ok(res.map_into_left_body())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header::CONTENT_TYPE;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{App, HttpResponse};
+
+    use crate::auth::authenticate;
+    use crate::config::Config;
+    use crate::problem::PROBLEM_JSON;
+    use crate::test_state::{test_state, UNREACHABLE_DATABASE_URL};
+
+    #[test]
+    fn windows_count_per_caller_and_roll_over() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        assert_eq!(limiter.check("a").1.remaining, 1);
+        assert_eq!(limiter.check("a").1.remaining, 0);
+        let (allowed, quota) = limiter.check("a");
+        assert!(!allowed);
+        assert_eq!(quota.remaining, 0);
+        assert_eq!(
+            quota.reset,
+            quota.window_seconds + limiter.windows.get("a").unwrap().started_at
+        );
+
+        // Other callers have their own windows
+        let (allowed, quota) = limiter.check("b");
+        assert!(allowed);
+        assert_eq!(quota.remaining, 1);
+        assert_eq!(limiter.peek("b").remaining, 1);
+
+        // Once the window has passed, the count starts over
+        limiter.windows.get_mut("a").unwrap().started_at -= 60;
+        assert_eq!(limiter.peek("a").remaining, 2);
+        let (allowed, quota) = limiter.check("a");
+        assert!(allowed);
+        assert_eq!(quota.remaining, 1);
+
+        // and swept windows are only those that have passed
+        limiter.windows.get_mut("b").unwrap().started_at -= 60;
+        limiter.sweep();
+        assert!(limiter.windows.contains_key("a"));
+        assert!(!limiter.windows.contains_key("b"));
+    }
+
+    #[actix_web::test]
+    async fn requests_over_the_limit_get_429() {
+        let config = Config {
+            rate_limit_requests: 2,
+            ..Config::from_env()
+        };
+        let app = init_service(
+            App::new()
+                .app_data(test_state(&config, UNREACHABLE_DATABASE_URL))
+                .wrap(from_fn(rate_limit))
+                .wrap(from_fn(authenticate))
+                .route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+        let request = |peer: &str| {
+            TestRequest::get()
+                .uri("/")
+                .peer_addr(peer.parse().unwrap())
+                .to_request()
+        };
+        let header = |response: &ServiceResponse<_>, name: HeaderName| {
+            response
+                .headers()
+                .get(name)
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+
+        for remaining in ["1", "0"] {
+            let response = call_service(&app, request("192.0.2.1:4000")).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(header(&response, LIMIT_HEADER), "2");
+            assert_eq!(header(&response, REMAINING_HEADER), remaining);
+        }
+
+        let response = call_service(&app, request("192.0.2.1:4000")).await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(header(&response, CONTENT_TYPE), PROBLEM_JSON);
+        assert_eq!(header(&response, LIMIT_HEADER), "2");
+        assert_eq!(header(&response, REMAINING_HEADER), "0");
+        let reset: u64 = header(&response, RESET_HEADER).parse().unwrap();
+        let retry_after: u64 = header(&response, RETRY_AFTER).parse().unwrap();
+        assert!(retry_after <= config.rate_limit_window.as_secs());
+        assert!(reset >= unix_now() + retry_after - 1);
+
+        // Another caller still has its whole quota
+        let response = call_service(&app, request("192.0.2.2:4000")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(header(&response, REMAINING_HEADER), "1");
+    }
+}
@@ -0,0 +1,162 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    error::InternalError,
+    http::{header::HeaderValue, StatusCode},
+    middleware::Next,
+    Error,
+};
+use serde_json::json;
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::future::{poll_fn, Future};
+use std::panic::{self, AssertUnwindSafe};
+use std::task::Poll;
+
+use crate::logging::{request_id_of, REQUEST_ID_HEADER};
+use crate::problem::problem_with;
+
+/// What the panic hook saw for a panic raised while a request was being polled
+struct PanicReport {
+    message: String,
+    location: String,
+    backtrace: Backtrace,
+}
+
+thread_local! {
+    /// Set while [`catch_panic`] polls the inner service on this thread
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+    static REPORT: RefCell<Option<PanicReport>> = const { RefCell::new(None) };
+}
+
+/// Installs a panic hook that records panics raised inside requests for
+/// [`catch_panic`] to log, and defers to the previous hook everywhere else.
+pub fn install_panic_hook() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if !CATCHING.with(Cell::get) {
+            return previous(info);
+        }
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "non-string panic payload".to_string());
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}", l.file(), l.line()))
+            .unwrap_or_default();
+        REPORT.with(|report| {
+            *report.borrow_mut() = Some(PanicReport {
+                message,
+                location,
+                backtrace: Backtrace::force_capture(),
+            })
+        });
+    }));
+}
+
+/// Turns a panic anywhere below this middleware into a 500 problem response
+/// and an error log carrying the request id, instead of a dropped connection.
+///
+/// The response is returned as an error because routing needs the only handle
+/// on the request, so none can be kept here to build a `ServiceResponse`. As a
+/// result the access log skips these requests; the error log line replaces it.
+pub async fn catch_panic(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let method = req.method().clone();
+    let path = req.path().to_string();
+    let request_id = request_id_of(req.request()).unwrap_or_default();
+    let mut call = Box::pin(next.call(req));
+
+    let result = poll_fn(|cx| {
+        CATCHING.with(|catching| catching.set(true));
+        let poll = panic::catch_unwind(AssertUnwindSafe(|| call.as_mut().poll(cx)));
+        CATCHING.with(|catching| catching.set(false));
+        match poll {
+            Ok(poll) => poll.map(Ok),
+            Err(_) => Poll::Ready(Err(())),
+        }
+    })
+    .await;
+
+    if let Ok(res) = result {
+        return res;
+    }
+
+    match REPORT.with(|report| report.borrow_mut().take()) {
+        Some(report) => log::error!(
+            "Handler panicked on {method} {path} (request {request_id}) at {}: {}\n{}",
+            report.location,
+            report.message,
+            report.backtrace,
+        ),
+        None => log::error!("Handler panicked on {method} {path} (request {request_id})"),
+    }
+    let mut response = problem_with(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Internal server error",
+        json!({ "requestId": request_id }),
+    );
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    Err(InternalError::from_response("handler panicked", response).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header::CONTENT_TYPE;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{call_service, init_service, read_body, try_call_service, TestRequest};
+    use actix_web::{body, web, App};
+    use serde_json::Value;
+
+    use crate::logging::request_id;
+
+    async fn panics() -> &'static str {
+        panic!("boom")
+    }
+
+    #[actix_web::test]
+    async fn panics_answer_500_and_the_worker_keeps_serving() {
+        install_panic_hook();
+        let app = init_service(
+            App::new()
+                .wrap(from_fn(catch_panic))
+                .wrap(from_fn(request_id))
+                .route("/panic", web::get().to(panics))
+                .route("/ok", web::get().to(|| async { "ok" })),
+        )
+        .await;
+
+        let request = TestRequest::get()
+            .uri("/panic")
+            .insert_header((REQUEST_ID_HEADER, "req-1"));
+        let error = try_call_service(&app, request.to_request())
+            .await
+            .err()
+            .expect("the panic is answered as an error");
+        let response = error.error_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            "application/problem+json"
+        );
+        let body = body::to_bytes(response.into_body()).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["status"], 500);
+        assert_eq!(body["detail"], "Internal server error");
+        assert_eq!(body["requestId"], "req-1");
+        assert!(!body.to_string().contains("boom"));
+
+        // The same worker goes on to serve the next request
+        let response = call_service(&app, TestRequest::get().uri("/ok").to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(read_body(response).await, "ok");
+    }
+}
@@ -0,0 +1,243 @@
+use deadpool_postgres::{Pool, PoolError, Status};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// A probe slower than this marks the region unhealthy
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Carries the primary's WAL position after a write. Reads that send it back
+/// are only served by a region that has replayed at least that far.
+pub const CONSISTENCY_HEADER: &str = "X-Consistency-Token";
+
+pub struct RegionPool {
+    pub region: String,
+    pub pool: Pool,
+    /// Round trip of the last successful probe
+    latency_micros: AtomicU64,
+    healthy: AtomicBool,
+}
+
+impl RegionPool {
+    pub fn new(region: String, pool: Pool) -> Self {
+        Self {
+            region,
+            pool,
+            latency_micros: AtomicU64::new(u64::MAX),
+            healthy: AtomicBool::new(true),
+        }
+    }
+
+    fn latency(&self) -> Option<Duration> {
+        match self.latency_micros.load(Ordering::Relaxed) {
+            u64::MAX => None,
+            micros => Some(Duration::from_micros(micros)),
+        }
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+}
+
+/// The primary database plus any regional replicas from `DATABASE_URLS`.
+/// Reads go to whichever healthy region answered probes fastest and fail over
+/// to the others; writes always go to the primary.
+pub struct Pools {
+    regions: Vec<RegionPool>,
+    /// Index into `regions` that reads currently go to
+    preferred: AtomicUsize,
+}
+
+impl Pools {
+    /// `primary` first, then the replicas. Reads start on the primary until
+    /// the first probe has run.
+    pub fn new(primary: RegionPool, replicas: Vec<RegionPool>) -> Self {
+        Self {
+            regions: std::iter::once(primary).chain(replicas).collect(),
+            preferred: AtomicUsize::new(0),
+        }
+    }
+
+    /// The pool that accepts writes
+    pub fn primary(&self) -> &Pool {
+        &self.regions[0].pool
+    }
+
+    /// A connection from the preferred region, or from the next best healthy
+    /// one if that fails. Regions that fail are marked unhealthy until a probe
+    /// reaches them again.
+    pub async fn get(&self) -> Result<deadpool_postgres::Client, PoolError> {
+        let preferred = self.preferred.load(Ordering::Relaxed);
+        let first = &self.regions[preferred];
+        let mut last_error = match first.pool.get().await {
+            Ok(client) => return Ok(client),
+            Err(e) => {
+                self.mark_unhealthy(preferred, &e);
+                e
+            }
+        };
+
+        for index in self.failover_order(preferred) {
+            match self.regions[index].pool.get().await {
+                Ok(client) => {
+                    self.prefer(index);
+                    return Ok(client);
+                }
+                Err(e) => {
+                    self.mark_unhealthy(index, &e);
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Like [`Pools::get`], but when `token` is set the connection comes from a
+    /// region that has replayed the primary's WAL up to it, falling back to
+    /// the primary when the preferred replica lags behind.
+    pub async fn get_at(
+        &self,
+        token: Option<&str>,
+    ) -> Result<deadpool_postgres::Client, PoolError> {
+        let Some(token) = token else {
+            return self.get().await;
+        };
+        if self.preferred.load(Ordering::Relaxed) != 0 {
+            let client = self.get().await?;
+            // NULL outside recovery, so a region that is not a replica never
+            // counts as caught up
+            let caught_up = client
+                .query_one(
+                    "SELECT pg_last_wal_replay_lsn() >= $1::text::pg_lsn",
+                    &[&token],
+                )
+                .await
+                .map(|row| row.get::<_, Option<bool>>(0) == Some(true))
+                .unwrap_or(false);
+            if caught_up {
+                return Ok(client);
+            }
+        }
+        self.primary().get().await
+    }
+
+    /// Every region but `skip`, healthy ones first, fastest first
+    fn failover_order(&self, skip: usize) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.regions.len()).filter(|i| *i != skip).collect();
+        order.sort_by_key(|i| {
+            let region = &self.regions[*i];
+            (
+                !region.is_healthy(),
+                region.latency().unwrap_or(Duration::MAX),
+            )
+        });
+        order
+    }
+
+    fn mark_unhealthy(&self, index: usize, error: &PoolError) {
+        let region = &self.regions[index];
+        if region.healthy.swap(false, Ordering::Relaxed) {
+            log::warn!("Database region {} is unhealthy: {error}", region.region);
+        }
+    }
+
+    fn prefer(&self, index: usize) {
+        let previous = self.preferred.swap(index, Ordering::Relaxed);
+        if previous != index {
+            log::info!(
+                "Routing reads to database region {} (was {})",
+                self.regions[index].region,
+                self.regions[previous].region
+            );
+        }
+    }
+
+    /// Times a trivial query against every region and routes reads to the
+    /// fastest healthy one. With no healthy region, routing is left alone.
+    pub async fn probe(&self) {
+        for region in &self.regions {
+            let probe = async {
+                let client = region.pool.get().await.map_err(|e| e.to_string())?;
+                let started = Instant::now();
+                client
+                    .query_one("SELECT 1", &[])
+                    .await
+                    .map_err(|e| e.to_string())?;
+                Ok::<_, String>(started.elapsed())
+            };
+            match tokio::time::timeout(PROBE_TIMEOUT, probe).await {
+                Ok(Ok(latency)) => {
+                    region
+                        .latency_micros
+                        .store(latency.as_micros() as u64, Ordering::Relaxed);
+                    if !region.healthy.swap(true, Ordering::Relaxed) {
+                        log::info!("Database region {} is healthy again", region.region);
+                    }
+                }
+                Ok(Err(e)) => {
+                    if region.healthy.swap(false, Ordering::Relaxed) {
+                        log::warn!("Database region {} is unhealthy: {e}", region.region);
+                    }
+                }
+                Err(_) => {
+                    if region.healthy.swap(false, Ordering::Relaxed) {
+                        log::warn!(
+                            "Database region {} is unhealthy: probe timed out",
+                            region.region
+                        );
+                    }
+                }
+            }
+        }
+
+        let fastest = (0..self.regions.len())
+            .filter(|i| self.regions[*i].is_healthy())
+            .min_by_key(|i| self.regions[*i].latency().unwrap_or(Duration::MAX));
+        if let Some(index) = fastest {
+            self.prefer(index);
+        }
+    }
+
+    /// Each region's pool usage, the primary first
+    pub fn statuses(&self) -> impl Iterator<Item = (&str, Status)> {
+        self.regions
+            .iter()
+            .map(|region| (region.region.as_str(), region.pool.status()))
+    }
+
+    /// Routing state and pool usage per region
+    pub fn to_json(&self) -> Value {
+        let preferred = self.preferred.load(Ordering::Relaxed);
+        let regions: Vec<Value> = self
+            .regions
+            .iter()
+            .enumerate()
+            .map(|(i, region)| {
+                let status = region.pool.status();
+                json!({
+                    "region": region.region,
+                    "primary": i == 0,
+                    "preferred": i == preferred,
+                    "healthy": region.is_healthy(),
+                    "latencyMs": region.latency().map(|latency| latency.as_secs_f64() * 1000.0),
+                    "size": status.size,
+                    "maxSize": status.max_size,
+                    "available": status.available,
+                })
+            })
+            .collect();
+        Value::Array(regions)
+    }
+}
+
+/// The token to hand back after a write committed on `client`, which must be
+/// a primary connection
+pub async fn consistency_token(
+    client: &deadpool_postgres::Client,
+) -> Result<String, tokio_postgres::Error> {
+    let row = client
+        .query_one("SELECT pg_current_wal_lsn()::text", &[])
+        .await?;
+    Ok(row.get(0))
+}
@@ -0,0 +1,123 @@
+//! Lists of projects that need attention, for risk and audit teams.
+
+use actix_web::http::StatusCode;
+use actix_web::web::Query;
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use chai_core::dependencies::unknown_package_managers;
+use chai_core::stats::{count_stale_projects, stale_projects, StaleFilter};
+use chai_types::models::StaleProjects;
+use chai_types::requests::{PaginationParams, StaleParams};
+
+use crate::app_state::AppState;
+use crate::auth::caller;
+use crate::problem::{database_error, no_connection, problem};
+use crate::utils::Pagination;
+
+/// Longest time without a release the stale report looks back over: 20 years
+const MAX_STALE_MONTHS: i32 = 240;
+
+/// Highly ranked projects without a release in `months` (24 by default): the
+/// critical but unmaintained ones, a page at a time
+#[utoipa::path(
+    tag = "stats",
+    params(
+        StaleParams,
+        PaginationParams,
+    ),
+    responses(
+        (status = 200, description = "OK", body = StaleProjects),
+        (status = 400, description = "Invalid parameters"),
+    )
+)]
+#[get("/reports/stale")]
+pub async fn get_stale_report(
+    req: HttpRequest,
+    params: Query<StaleParams>,
+    pagination: Query<PaginationParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let months = params.months.unwrap_or(24);
+    if !(1..=MAX_STALE_MONTHS).contains(&months) {
+        return problem(
+            StatusCode::BAD_REQUEST,
+            format!("months must be between 1 and {MAX_STALE_MONTHS}"),
+        );
+    }
+    let ecosystem = params
+        .ecosystem
+        .as_deref()
+        .map(str::trim)
+        .filter(|ecosystem| !ecosystem.is_empty());
+
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+
+    // An unknown ecosystem would just look like one with nothing stale
+    if let Some(ecosystem) = ecosystem {
+        match unknown_package_managers(&client, &[ecosystem]).await {
+            Ok(unknown) if !unknown.is_empty() => {
+                return problem(
+                    StatusCode::BAD_REQUEST,
+                    format!("Unknown ecosystem '{ecosystem}'"),
+                )
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::error!("Database query error: {e}");
+                return database_error(&e);
+            }
+        }
+    }
+
+    let filter = StaleFilter {
+        run: *data.latest_run.borrow(),
+        ecosystem,
+        months,
+    };
+    let live = data.not_deleted("canons", "c");
+    let total_count = match count_stale_projects(&client, &filter, &live).await {
+        Ok(count) => count,
+        Err(e) => {
+            log::error!("Database count query error: {e}");
+            return database_error(&e);
+        }
+    };
+    let pagination = Pagination::new(
+        pagination,
+        total_count,
+        data.api_keys.limits(&caller(&req)).response_limit,
+    );
+
+    match stale_projects(
+        &client,
+        &filter,
+        pagination.limit,
+        pagination.offset,
+        &live,
+        data.row_format,
+    )
+    .await
+    {
+        Ok(projects) => HttpResponse::Ok()
+            .insert_header(pagination.link(&req))
+            .json(StaleProjects {
+                run: filter.run,
+                ecosystem: ecosystem.map(str::to_string),
+                months,
+                total_count,
+                page: pagination.page,
+                limit: pagination.limit,
+                total_pages: pagination.total_pages,
+                projects,
+            }),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
@@ -0,0 +1,95 @@
+//! Path normalization ahead of routing, so near-miss spellings of a route
+//! reach it instead of a 404.
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::uri::{PathAndQuery, Uri},
+    middleware::Next,
+    web, Error,
+};
+
+use crate::app_state::AppState;
+
+/// Rewrites the request path before it is routed: repeated slashes are merged,
+/// a trailing slash is dropped, the leading segment is lowercased, and the
+/// table of `/tables/{table}` is matched case-insensitively against the
+/// catalog. `/Tables/Packages/` is served as `/tables/packages`.
+///
+/// Runs outside the IP filter, authentication, and usage tracking so they all
+/// see the path the router does.
+pub async fn normalize_path(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let normalized = match req.app_data::<web::Data<AppState>>() {
+        Some(data) => normalize(req.path(), &data.tables.borrow().tables),
+        None => normalize(req.path(), &[]),
+    };
+    if let Some(path) = normalized {
+        let head = req.head_mut();
+        let path_and_query = match head.uri.query() {
+            Some(query) => format!("{path}?{query}"),
+            None => path,
+        };
+        let mut parts = head.uri.clone().into_parts();
+        parts.path_and_query = PathAndQuery::try_from(path_and_query).ok();
+        if let Ok(uri) = Uri::from_parts(parts) {
+            req.match_info_mut().get_mut().update(&uri);
+            req.head_mut().uri = uri;
+        }
+    }
+    next.call(req).await
+}
+
+/// The normalized form of `path`, or `None` when it already is
+fn normalize(path: &str, tables: &[String]) -> Option<String> {
+    let mut segments: Vec<String> = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+        .collect();
+    // Every route starts with a fixed, lowercase segment
+    if let Some(first) = segments.first_mut() {
+        *first = first.to_ascii_lowercase();
+    }
+    if segments.first().map(String::as_str) == Some("tables") {
+        if let Some(table) = segments.get_mut(1) {
+            if let Some(known) = tables.iter().find(|t| t.eq_ignore_ascii_case(table)) {
+                table.clone_from(known);
+            }
+        }
+    }
+
+    let normalized = format!("/{}", segments.join("/"));
+    (normalized != path).then_some(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_slashes_and_case() {
+        let tables = vec!["packages".to_string(), "canons".to_string()];
+        let normalize = |path| normalize(path, &tables);
+
+        assert_eq!(normalize("/tables/packages"), None);
+        assert_eq!(normalize("/"), None);
+        assert_eq!(
+            normalize("/Tables/Packages/").as_deref(),
+            Some("/tables/packages")
+        );
+        assert_eq!(
+            normalize("//tables///canons/ID").as_deref(),
+            Some("/tables/canons/ID")
+        );
+        assert_eq!(normalize("/heartbeat/").as_deref(), Some("/heartbeat"));
+        // Only the route prefix and known tables change case
+        assert_eq!(
+            normalize("/Project/search/Serde").as_deref(),
+            Some("/project/search/Serde")
+        );
+        assert_eq!(normalize("/tables/Unknown"), None);
+    }
+}
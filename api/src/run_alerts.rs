@@ -0,0 +1,379 @@
+//! Sanity checks on each new ranking run. Whenever `run_detection` publishes a
+//! run it is compared with the run before it: how many projects are ranked,
+//! their mean rank, and how much of the top 100 changed. Changes past the
+//! `RUN_ALERT_*` thresholds are logged, posted to `RUN_ALERT_WEBHOOK_URL`, and
+//! counted for `/admin/runs/check`. The same watch posts collection digests,
+//! signed with each collection's webhook secret.
+
+use chai_core::leaderboard::previous_run;
+use chai_core::ranks::run_ranks;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use url::Url;
+use uuid::Uuid;
+
+use crate::collections::send_digests;
+use crate::config::{ignore_setting, Config};
+use crate::http_client::HttpClient;
+use crate::regions::Pools;
+use crate::runs::LatestRun;
+
+/// How many of the best ranked projects churn is measured over
+const TOP_PROJECTS: usize = 100;
+/// Unix time a signed webhook body was signed at
+const TIMESTAMP_HEADER: &str = "X-Chai-Timestamp";
+/// One `v1=<hex>` signature per secret, comma separated
+const SIGNATURE_HEADER: &str = "X-Chai-Signature";
+
+/// Largest changes from one run to the next that pass without an alert, as
+/// fractions: `0.2` allows 20% more or fewer ranked projects
+pub struct Thresholds {
+    pub count_change: f64,
+    pub mean_change: f64,
+    pub top_churn: f64,
+}
+
+/// A new run next to the one before it
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunCheck {
+    run: i32,
+    previous_run: Option<i32>,
+    checked_at: DateTime<Utc>,
+    count: usize,
+    previous_count: Option<usize>,
+    mean: Option<f64>,
+    previous_mean: Option<f64>,
+    /// Relative change in `count`
+    count_change: Option<f64>,
+    /// Relative change in `mean`
+    mean_change: Option<f64>,
+    /// Share of the previous top 100 that left it
+    top_churn: Option<f64>,
+    /// One line per threshold crossed
+    alerts: Vec<String>,
+}
+
+struct RunStats {
+    count: usize,
+    mean: Option<f64>,
+    top: HashSet<Uuid>,
+}
+
+impl RunStats {
+    fn of(ranks: &[(Uuid, f64)]) -> Self {
+        let count = ranks.len();
+        let mean =
+            (count > 0).then(|| ranks.iter().map(|(_, rank)| rank).sum::<f64>() / count as f64);
+        let mut sorted: Vec<&(Uuid, f64)> = ranks.iter().collect();
+        sorted.sort_unstable_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        let top = sorted
+            .iter()
+            .take(TOP_PROJECTS)
+            .map(|(id, _)| *id)
+            .collect();
+        Self { count, mean, top }
+    }
+}
+
+/// `|after - before| / |before|`, or `None` when there was nothing before
+fn relative_change(before: f64, after: f64) -> Option<f64> {
+    (before != 0.0).then(|| (after - before).abs() / before.abs())
+}
+
+impl Thresholds {
+    /// Compares `run`'s ranks with `previous`'s; a first run has nothing to
+    /// compare with and never alerts
+    fn check(
+        &self,
+        run: i32,
+        ranks: &[(Uuid, f64)],
+        previous: Option<(i32, &[(Uuid, f64)])>,
+    ) -> RunCheck {
+        let current = RunStats::of(ranks);
+        let before = previous.map(|(run, ranks)| (run, RunStats::of(ranks)));
+        let before = before.as_ref();
+
+        let count_change =
+            before.and_then(|(_, b)| relative_change(b.count as f64, current.count as f64));
+        let mean_change = before.and_then(|(_, b)| relative_change(b.mean?, current.mean?));
+        let top_churn = before
+            .filter(|(_, b)| !b.top.is_empty())
+            .map(|(_, b)| b.top.difference(&current.top).count() as f64 / b.top.len() as f64);
+
+        let mut alerts = Vec::new();
+        if let Some(change) = count_change.filter(|c| *c > self.count_change) {
+            alerts.push(format!(
+                "Ranked projects changed by {:.1}% (limit {:.1}%)",
+                change * 100.0,
+                self.count_change * 100.0
+            ));
+        }
+        if let Some(change) = mean_change.filter(|c| *c > self.mean_change) {
+            alerts.push(format!(
+                "Mean rank changed by {:.1}% (limit {:.1}%)",
+                change * 100.0,
+                self.mean_change * 100.0
+            ));
+        }
+        if let Some(churn) = top_churn.filter(|c| *c > self.top_churn) {
+            alerts.push(format!(
+                "{:.1}% of the top {TOP_PROJECTS} changed (limit {:.1}%)",
+                churn * 100.0,
+                self.top_churn * 100.0
+            ));
+        }
+
+        RunCheck {
+            run,
+            previous_run: before.map(|(run, _)| *run),
+            checked_at: Utc::now(),
+            count: current.count,
+            previous_count: before.map(|(_, b)| b.count),
+            mean: current.mean,
+            previous_mean: before.and_then(|(_, b)| b.mean),
+            count_change,
+            mean_change,
+            top_churn,
+            alerts,
+        }
+    }
+}
+
+pub struct RunAlerts {
+    thresholds: Thresholds,
+    webhook: Option<String>,
+    http: Arc<HttpClient>,
+    last: Mutex<Option<RunCheck>>,
+    checks: AtomicU64,
+    alerts: AtomicU64,
+    webhook_failures: AtomicU64,
+}
+
+impl RunAlerts {
+    pub fn from_config(config: &Config, http: Arc<HttpClient>) -> Self {
+        // An operator's URL is held to the same egress rules as a caller's
+        let webhook = config.run_alert_webhook_url.clone().filter(|url| {
+            match Url::parse(url)
+                .map_err(|e| e.to_string())
+                .and_then(|parsed| http.egress().check(&parsed))
+            {
+                Ok(()) => true,
+                Err(e) => {
+                    ignore_setting(format_args!("Ignoring RUN_ALERT_WEBHOOK_URL: {e}"));
+                    false
+                }
+            }
+        });
+        Self {
+            thresholds: Thresholds {
+                count_change: config.run_alert_count_change,
+                mean_change: config.run_alert_mean_change,
+                top_churn: config.run_alert_top_churn,
+            },
+            webhook,
+            http,
+            last: Mutex::new(None),
+            checks: AtomicU64::new(0),
+            alerts: AtomicU64::new(0),
+            webhook_failures: AtomicU64::new(0),
+        }
+    }
+
+    /// Checks `run` against the run before it, alerting on anything past the
+    /// thresholds
+    pub async fn check(&self, pool: &Pools, run: i32) {
+        let check = match self.compare(pool, run).await {
+            Ok(Some(check)) => check,
+            Ok(None) => return,
+            Err(e) => {
+                log::error!("Failed to check run {run}: {e}");
+                return;
+            }
+        };
+        self.checks.fetch_add(1, Ordering::Relaxed);
+
+        if check.alerts.is_empty() {
+            log::info!(
+                "Run {run} passed its checks against {:?}",
+                check.previous_run
+            );
+        } else {
+            self.alerts.fetch_add(1, Ordering::Relaxed);
+            for alert in &check.alerts {
+                log::warn!("Run {run} looks anomalous: {alert}");
+            }
+            if let Some(url) = &self.webhook {
+                if let Err(e) = post_webhook(&self.http, url, &check, &[]).await {
+                    self.webhook_failures.fetch_add(1, Ordering::Relaxed);
+                    log::error!("Failed to post run {run} alert: {e}");
+                }
+            }
+        }
+        *self.last.lock().unwrap() = Some(check);
+    }
+
+    /// `None` when `run` no longer exists, e.g. after a schema cutover
+    async fn compare(&self, pool: &Pools, run: i32) -> Result<Option<RunCheck>, String> {
+        let client = pool.get().await.map_err(|e| e.to_string())?;
+        let Some(ranks) = run_ranks(&client, run).await.map_err(|e| e.to_string())? else {
+            return Ok(None);
+        };
+        let Some(previous) = previous_run(&client, run)
+            .await
+            .map_err(|e| e.to_string())?
+        else {
+            return Ok(None);
+        };
+        let previous_ranks = match previous {
+            Some(previous) => run_ranks(&client, previous)
+                .await
+                .map_err(|e| e.to_string())?
+                .map(|ranks| (previous, ranks)),
+            None => None,
+        };
+        Ok(Some(
+            self.thresholds.check(
+                run,
+                &ranks,
+                previous_ranks
+                    .as_ref()
+                    .map(|(run, ranks)| (*run, ranks.as_slice())),
+            ),
+        ))
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "checks": self.checks.load(Ordering::Relaxed),
+            "alerts": self.alerts.load(Ordering::Relaxed),
+            "webhookFailures": self.webhook_failures.load(Ordering::Relaxed),
+            "webhook": self.webhook.is_some(),
+            "thresholds": {
+                "countChange": self.thresholds.count_change,
+                "meanChange": self.thresholds.mean_change,
+                "topChurn": self.thresholds.top_churn,
+            },
+            "last": *self.last.lock().unwrap(),
+        })
+    }
+}
+
+/// Hex HMAC-SHA256 signatures of `{timestamp}.{body}`, one per secret
+fn signature(secrets: &[&str], timestamp: i64, body: &[u8]) -> String {
+    secrets
+        .iter()
+        .map(|secret| {
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+                .expect("HMAC takes keys of any length");
+            mac.update(format!("{timestamp}.").as_bytes());
+            mac.update(body);
+            format!("v1={}", hex::encode(mac.finalize().into_bytes()))
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Posts `body` as JSON to `url`, failing on error statuses too. Given
+/// `secrets`, the body is signed with each of them along with the time it was
+/// sent, so receivers can tell it came from here and drop replays.
+pub async fn post_webhook(
+    http: &HttpClient,
+    url: &str,
+    body: &impl Serialize,
+    secrets: &[&str],
+) -> Result<(), String> {
+    let body = serde_json::to_vec(body).map_err(|e| e.to_string())?;
+    let mut request = http.post(url).header(CONTENT_TYPE, "application/json");
+    if !secrets.is_empty() {
+        let timestamp = Utc::now().timestamp();
+        request = request
+            .header(TIMESTAMP_HEADER, timestamp)
+            .header(SIGNATURE_HEADER, signature(secrets, timestamp, &body));
+    }
+    http.send(request.body(body))
+        .await
+        .map_err(|e| e.to_string())?
+        .error_for_status()
+        .map(drop)
+        .map_err(|e| e.to_string())
+}
+
+/// Checks every run `latest` publishes from now on, then posts the digests of
+/// collections with a webhook; the run current at startup was already handled
+/// by whichever process saw it arrive
+pub async fn watch_runs(
+    pool: Arc<Pools>,
+    latest: LatestRun,
+    alerts: Arc<RunAlerts>,
+    http: Arc<HttpClient>,
+) {
+    let mut runs = latest.subscribe();
+    while runs.changed().await.is_ok() {
+        let run = *runs.borrow_and_update();
+        if let Some(run) = run {
+            alerts.check(&pool, run).await;
+            send_digests(&pool, &http, run).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_alert_past_thresholds_only() {
+        let thresholds = Thresholds {
+            count_change: 0.2,
+            mean_change: 0.2,
+            top_churn: 0.5,
+        };
+        let ids: Vec<Uuid> = (1..=4).map(Uuid::from_u128).collect();
+        let previous: Vec<(Uuid, f64)> = ids.iter().map(|id| (*id, 10.0)).collect();
+
+        // First runs have nothing to compare with
+        let first = thresholds.check(1, &previous, None);
+        assert_eq!((first.previous_run, first.count_change), (None, None));
+        assert!(first.alerts.is_empty());
+
+        // One project swapped and ranks a little higher: within every limit
+        let steady = [
+            (ids[0], 11.0),
+            (ids[1], 11.0),
+            (ids[2], 11.0),
+            (Uuid::from_u128(9), 11.0),
+        ];
+        let check = thresholds.check(2, &steady, Some((1, &previous)));
+        assert_eq!(check.count_change, Some(0.0));
+        assert_eq!(check.top_churn, Some(0.25));
+        assert!(check.alerts.is_empty(), "{:?}", check.alerts);
+
+        // Half the projects gone, with mean ranks doubled
+        let broken = [(ids[0], 20.0), (ids[1], 20.0)];
+        let check = thresholds.check(2, &broken, Some((1, &previous)));
+        assert_eq!(check.mean_change, Some(1.0));
+        assert_eq!(check.alerts.len(), 2, "{:?}", check.alerts);
+    }
+
+    #[test]
+    fn signs_with_every_secret() {
+        let body = br#"{"run":1}"#;
+        assert_eq!(
+            signature(&["whsec_new"], 1_700_000_000, body),
+            "v1=140977a49a660e1a6851b0661f642bb1b1ef46c838186fcbee1c7966e437b5ea"
+        );
+        assert_eq!(
+            signature(&["whsec_new", "whsec_old"], 1_700_000_000, body),
+            "v1=140977a49a660e1a6851b0661f642bb1b1ef46c838186fcbee1c7966e437b5ea,\
+            v1=0e01f9b4163686ae27f57a7cabbc172f67d840b687460d78e428fb6e22f14243"
+        );
+    }
+}
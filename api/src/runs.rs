@@ -0,0 +1,144 @@
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use chai_core::leaderboard::run_as_of;
+use chai_core::ranks::rebuild_latest_ranks;
+use chai_types::requests::RunSelector;
+use chrono::NaiveDate;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio_postgres::Client;
+
+use crate::db::read_write;
+use crate::problem::{database_error, not_found, problem};
+use crate::regions::Pools;
+
+/// Latest `tea_rank_runs.run` that isn't quarantined, or `None` before the
+/// first run. Handlers read it with `borrow()`; anything reacting to new runs
+/// can `subscribe()` instead.
+pub type LatestRun = watch::Sender<Option<i32>>;
+
+async fn fetch_latest_run(pool: &Pools) -> Result<Option<i32>, String> {
+    let client = pool.get().await.map_err(|e| e.to_string())?;
+    let row = client
+        .query_one(
+            "SELECT MAX(run) FROM tea_rank_runs WHERE quarantined_at IS NULL",
+            &[],
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(row.get(0))
+}
+
+/// Reads the current run at startup; the `run_detection` maintenance task keeps
+/// it current.
+pub async fn watch_latest_run(pool: &Pools) -> LatestRun {
+    let initial = fetch_latest_run(pool).await.unwrap_or_else(|e| {
+        log::warn!("Failed to read latest run at startup: {e}");
+        None
+    });
+    log::info!("Latest run: {initial:?}");
+    watch::Sender::new(initial)
+}
+
+/// Checks `tea_rank_runs` once, publishing the latest run if it changed
+pub async fn detect_latest_run(pool: &Pools, latest: &LatestRun) {
+    match fetch_latest_run(pool).await {
+        Ok(run) => {
+            latest.send_if_modified(|current| {
+                if *current == run {
+                    return false;
+                }
+                log::info!("Latest run changed from {current:?} to {run:?}");
+                *current = run;
+                true
+            });
+        }
+        Err(e) => log::error!("Failed to poll latest run: {e}"),
+    }
+}
+
+/// Rebuilds `latest_ranks` on the primary unless it already holds ranks from
+/// run `current`, which another instance seeing the same run will have done;
+/// `None` always rebuilds. On failure the table keeps its previous contents.
+pub async fn refresh_latest_ranks(pool: &Pools, current: Option<i32>) {
+    let rebuild = async {
+        let mut client = pool.primary().get().await.map_err(|e| e.to_string())?;
+        let transaction = read_write(&mut client).await.map_err(|e| e.to_string())?;
+        // Readers keep the old rows until commit; other rebuilds wait their turn
+        transaction
+            .batch_execute("LOCK TABLE latest_ranks IN EXCLUSIVE MODE")
+            .await
+            .map_err(|e| e.to_string())?;
+        if current.is_some() {
+            let row = transaction
+                .query_one("SELECT MAX(tea_rank_run) FROM latest_ranks", &[])
+                .await
+                .map_err(|e| e.to_string())?;
+            if row.get::<_, Option<i32>>(0) == current {
+                return Ok(None);
+            }
+        }
+        let ranked = rebuild_latest_ranks(&*transaction)
+            .await
+            .map_err(|e| e.to_string())?;
+        transaction.commit().await.map_err(|e| e.to_string())?;
+        Ok::<_, String>(Some(ranked))
+    };
+    match rebuild.await {
+        Ok(Some(ranked)) => log::info!("Rebuilt latest ranks of {ranked} canons"),
+        Ok(None) => {}
+        Err(e) => log::error!("Failed to rebuild latest ranks: {e}"),
+    }
+}
+
+/// Keeps `latest_ranks` in step with the run `latest` publishes, starting with
+/// the run current at startup
+pub async fn watch_latest_ranks(pool: Arc<Pools>, latest: LatestRun) {
+    let mut runs = latest.subscribe();
+    loop {
+        let run = *runs.borrow_and_update();
+        refresh_latest_ranks(&pool, run).await;
+        if runs.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+/// The run `as_of` asks for: the last one created on or before that day.
+/// `None` when no day was given; a 404 when no run had been created by then.
+pub async fn resolve_as_of(
+    client: &Client,
+    as_of: Option<NaiveDate>,
+) -> Result<Option<i32>, HttpResponse> {
+    let Some(day) = as_of else {
+        return Ok(None);
+    };
+    match run_as_of(client, day).await {
+        Ok(Some(run)) => Ok(Some(run)),
+        Ok(None) => Err(not_found(format!("No run on or before {day}"))),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            Err(database_error(&e))
+        }
+    }
+}
+
+/// The run a `run` selector and an `as_of` day pick between them; `None` when
+/// `run` is `latest` and there is no run yet. Asking for both is a 400.
+pub async fn select_run(
+    client: &Client,
+    run: RunSelector,
+    as_of: Option<NaiveDate>,
+    latest: Option<i32>,
+) -> Result<Option<i32>, HttpResponse> {
+    if as_of.is_none() {
+        return Ok(run.resolve(latest));
+    }
+    if run != RunSelector::Latest {
+        return Err(problem(
+            StatusCode::BAD_REQUEST,
+            "Pass either run or as_of, not both",
+        ));
+    }
+    resolve_as_of(client, as_of).await
+}
@@ -0,0 +1,198 @@
+//! Periodic maintenance run inside the server. Each task is registered once
+//! with [`Scheduler::every`] under a name from [`TASKS`] and a default interval,
+//! which `MAINTENANCE_SCHEDULE=task:secs,...` overrides (`0` turns a task off).
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::MissedTickBehavior;
+
+use crate::config::{ignore_setting, Config};
+
+/// Every task the server may schedule
+pub const TASKS: [&str; 13] = [
+    "rate_limit_sweep",
+    "usage_flush",
+    "region_probe",
+    "run_detection",
+    "verification",
+    "job_cleanup",
+    "cache_sweep",
+    "table_refresh",
+    "leaderboard_warmup",
+    "cache_snapshot",
+    "graph_stats",
+    "ingest_check",
+    "package_managers",
+];
+
+/// Default intervals of the tasks without a setting of their own
+pub const CACHE_SWEEP_INTERVAL: Duration = Duration::from_secs(300);
+pub const TABLE_REFRESH_INTERVAL: Duration = Duration::from_secs(600);
+/// Under the project cache's TTL, so warmed entries are replaced before expiring
+pub const LEADERBOARD_WARMUP_INTERVAL: Duration = Duration::from_secs(1800);
+pub const CACHE_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(600);
+/// The dependency graph changes with the indexers, far less often than ranks
+pub const GRAPH_STATS_INTERVAL: Duration = Duration::from_secs(6 * 3600);
+/// Well under `/status`'s default ingest age limit
+pub const INGEST_CHECK_INTERVAL: Duration = Duration::from_secs(900);
+/// Canons only gain package managers as the indexers run
+pub const PACKAGE_MANAGERS_INTERVAL: Duration = Duration::from_secs(600);
+
+/// One task as `/admin/schedule` reports it
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TaskStatus {
+    name: &'static str,
+    /// `None` when the schedule turns the task off
+    interval_secs: Option<u64>,
+    runs: u64,
+    /// Runs that panicked
+    failures: u64,
+    running: bool,
+    last_started_at: Option<DateTime<Utc>>,
+    last_duration_ms: Option<u64>,
+}
+
+pub struct Scheduler {
+    overrides: HashMap<String, Duration>,
+    tasks: Mutex<Vec<Arc<Mutex<TaskStatus>>>>,
+}
+
+impl Scheduler {
+    pub fn from_config(config: &Config) -> Self {
+        let overrides = config
+            .maintenance_schedule
+            .iter()
+            .filter(|(name, _)| {
+                let known = TASKS.contains(&name.as_str());
+                if !known {
+                    ignore_setting(format_args!(
+                        "Ignoring unknown maintenance task {name:?} in MAINTENANCE_SCHEDULE"
+                    ));
+                }
+                known
+            })
+            .cloned()
+            .collect();
+        Self {
+            overrides,
+            tasks: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Runs `task` on a clone of `state` now and then every `interval`, unless
+    /// the schedule says otherwise. Runs of a task never overlap: one that
+    /// overruns delays the next, and one that panics is logged and counted.
+    pub fn every<S, F, Fut>(&self, name: &'static str, interval: Duration, state: S, task: F)
+    where
+        S: Clone + Send + 'static,
+        F: Fn(S) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        debug_assert!(TASKS.contains(&name), "unlisted maintenance task {name}");
+        let interval = self.overrides.get(name).copied().unwrap_or(interval);
+        let status = Arc::new(Mutex::new(TaskStatus {
+            name,
+            interval_secs: (!interval.is_zero()).then_some(interval.as_secs()),
+            runs: 0,
+            failures: 0,
+            running: false,
+            last_started_at: None,
+            last_duration_ms: None,
+        }));
+        self.tasks.lock().unwrap().push(Arc::clone(&status));
+        if interval.is_zero() {
+            log::info!("Maintenance task {name} is turned off");
+            return;
+        }
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let started = Instant::now();
+                {
+                    let mut status = status.lock().unwrap();
+                    status.running = true;
+                    status.last_started_at = Some(Utc::now());
+                }
+                // Spawned so a panic ends this run rather than the whole schedule
+                let panicked = tokio::spawn(task(state.clone())).await.is_err();
+                let mut status = status.lock().unwrap();
+                status.running = false;
+                status.runs += 1;
+                status.last_duration_ms = Some(started.elapsed().as_millis() as u64);
+                if panicked {
+                    status.failures += 1;
+                    log::error!("Maintenance task {name} panicked");
+                }
+            }
+        });
+    }
+
+    /// Every registered task in registration order, for `/admin/schedule`
+    pub fn to_json(&self) -> Value {
+        let tasks: Vec<Value> = self
+            .tasks
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|status| json!(&*status.lock().unwrap()))
+            .collect();
+        json!({ "tasks": tasks })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn scheduler(overrides: &[(&str, u64)]) -> Scheduler {
+        Scheduler {
+            overrides: overrides
+                .iter()
+                .map(|(name, secs)| (name.to_string(), Duration::from_secs(*secs)))
+                .collect(),
+            tasks: Mutex::new(Vec::new()),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn tasks_run_on_their_interval_and_survive_panics() {
+        let scheduler = scheduler(&[("cache_sweep", 0)]);
+        let runs = Arc::new(AtomicUsize::new(0));
+        scheduler.every(
+            "usage_flush",
+            Duration::from_secs(10),
+            Arc::clone(&runs),
+            |runs| async move {
+                // The second run fails; later ones must still happen
+                if runs.fetch_add(1, Ordering::SeqCst) == 1 {
+                    panic!("flush failed");
+                }
+            },
+        );
+        scheduler.every("cache_sweep", Duration::from_secs(1), (), |_| async {
+            unreachable!("turned off")
+        });
+
+        // Immediately, then at 10s and 20s
+        tokio::time::sleep(Duration::from_secs(25)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+
+        let report = scheduler.to_json();
+        assert_eq!(report["tasks"][0]["name"], "usage_flush");
+        assert_eq!(report["tasks"][0]["intervalSecs"], 10);
+        assert_eq!(report["tasks"][0]["runs"], 3);
+        assert_eq!(report["tasks"][0]["failures"], 1);
+        assert_eq!(report["tasks"][1]["intervalSecs"], Value::Null);
+        assert_eq!(report["tasks"][1]["runs"], 0);
+    }
+}
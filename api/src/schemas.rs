@@ -0,0 +1,170 @@
+//! Blue/green schema targets. `DB_SCHEMAS=blue:chai_blue,green:chai_green` names
+//! the schemas a rebuilt dataset may be loaded into, and every pooled session's
+//! `search_path` points at the active one. A cutover retires sessions opened
+//! before it at their next checkout, so requests already running finish on the
+//! old schema and later ones start on the new.
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::sync::RwLock;
+use std::time::Instant;
+
+use crate::config::{ignore_setting, Config};
+
+/// Where unqualified tables resolve without any targets configured
+const DEFAULT_SCHEMA: &str = "public";
+
+struct Active {
+    /// Index into `targets`
+    target: usize,
+    /// Instant and time of the last cutover; sessions opened before it are stale
+    since: Option<(Instant, DateTime<Utc>)>,
+}
+
+#[derive(Default)]
+pub struct Schemas {
+    /// `(name, schema)` pairs, in configuration order
+    targets: Vec<(String, String)>,
+    active: RwLock<Option<Active>>,
+    /// Held for the length of a cutover, so two can't interleave
+    pub cutover: tokio::sync::Mutex<()>,
+}
+
+impl Schemas {
+    pub fn from_config(config: &Config) -> Self {
+        let targets = config.db_schemas.clone();
+        let target = match &config.db_active_schema {
+            Some(name) => targets.iter().position(|(n, _)| n == name).or_else(|| {
+                ignore_setting(format_args!("Ignoring unknown DB_ACTIVE_SCHEMA {name:?}"));
+                None
+            }),
+            None => None,
+        };
+        let active = (!targets.is_empty()).then(|| Active {
+            target: target.unwrap_or(0),
+            since: None,
+        });
+        Self {
+            targets,
+            active: RwLock::new(active),
+            cutover: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// The schema unqualified tables currently resolve to
+    pub fn schema(&self) -> String {
+        self.schema_of(self.active.read().unwrap().as_ref())
+            .to_string()
+    }
+
+    /// The schema configured as `name`
+    pub fn target(&self, name: &str) -> Option<&str> {
+        self.targets
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, schema)| schema.as_str())
+    }
+
+    /// Names of the configured targets
+    pub fn names(&self) -> Vec<&str> {
+        self.targets.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Points new sessions at target `name` and marks every open one stale
+    pub fn activate(&self, name: &str) {
+        let Some(target) = self.targets.iter().position(|(n, _)| n == name) else {
+            return;
+        };
+        *self.active.write().unwrap() = Some(Active {
+            target,
+            since: Some((Instant::now(), Utc::now())),
+        });
+    }
+
+    /// `SET search_path` for a new session, or `None` without targets
+    pub fn search_path_sql(&self) -> Option<String> {
+        self.active.read().unwrap().as_ref().map(|active| {
+            let schema = &self.targets[active.target].1;
+            format!("SET search_path TO \"{}\"", schema.replace('"', "\"\""))
+        })
+    }
+
+    /// Whether a session opened at `created` predates the last cutover
+    pub fn is_stale(&self, created: Instant) -> bool {
+        self.active
+            .read()
+            .unwrap()
+            .as_ref()
+            .and_then(|active| active.since)
+            .is_some_and(|(since, _)| created < since)
+    }
+
+    /// The active target and every configured one, for `/admin/schema`
+    pub fn to_json(&self) -> Value {
+        let active = self.active.read().unwrap();
+        let (name, since) = match &*active {
+            Some(active) => (
+                Some(self.targets[active.target].0.as_str()),
+                active.since.map(|(_, at)| at),
+            ),
+            None => (None, None),
+        };
+        json!({
+            "active": name,
+            "schema": self.schema_of(active.as_ref()),
+            "switchedAt": since,
+            "targets": self
+                .targets
+                .iter()
+                .map(|(name, schema)| json!({ "name": name, "schema": schema }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    fn schema_of(&self, active: Option<&Active>) -> &str {
+        active.map_or(DEFAULT_SCHEMA, |active| &self.targets[active.target].1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schemas() -> Schemas {
+        let targets = vec![
+            ("blue".to_string(), "chai_blue".to_string()),
+            ("green".to_string(), "chai \"green\"".to_string()),
+        ];
+        Schemas {
+            targets,
+            active: RwLock::new(Some(Active {
+                target: 0,
+                since: None,
+            })),
+            cutover: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    #[test]
+    fn cutover_repoints_new_sessions_and_retires_old_ones() {
+        let schemas = schemas();
+        let opened = Instant::now();
+        assert_eq!(
+            schemas.search_path_sql().as_deref(),
+            Some("SET search_path TO \"chai_blue\"")
+        );
+        assert!(!schemas.is_stale(opened));
+
+        schemas.activate("green");
+        assert_eq!(schemas.schema(), "chai \"green\"");
+        assert_eq!(
+            schemas.search_path_sql().as_deref(),
+            Some("SET search_path TO \"chai \"\"green\"\"\"")
+        );
+        assert!(schemas.is_stale(opened));
+        assert!(!schemas.is_stale(Instant::now()));
+
+        assert_eq!(Schemas::default().schema(), "public");
+        assert_eq!(Schemas::default().search_path_sql(), None);
+    }
+}
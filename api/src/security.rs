@@ -0,0 +1,138 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        header::{
+            HeaderValue, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, STRICT_TRANSPORT_SECURITY,
+            TRANSFER_ENCODING, X_CONTENT_TYPE_OPTIONS,
+        },
+        Method, StatusCode,
+    },
+    middleware::Next,
+    web, Error,
+};
+
+use crate::app_state::AppState;
+use crate::problem::problem;
+
+const HSTS: &str = "max-age=31536000; includeSubDomains";
+
+/// Adds baseline security headers, leaving any `Cache-Control` set by the
+/// handler untouched.
+pub async fn security_headers(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let is_https = req.connection_info().scheme() == "https";
+    let cache_control = req
+        .app_data::<web::Data<AppState>>()
+        .and_then(|data| HeaderValue::from_str(&data.default_cache_control).ok());
+
+    let mut res = next.call(req).await?;
+    let headers = res.headers_mut();
+    headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    if let Some(cache_control) = cache_control {
+        if !headers.contains_key(CACHE_CONTROL) {
+            headers.insert(CACHE_CONTROL, cache_control);
+        }
+    }
+    if is_https {
+        headers.insert(STRICT_TRANSPORT_SECURITY, HeaderValue::from_static(HSTS));
+    }
+    Ok(res)
+}
+
+/// Rejects request bodies that aren't JSON with 415 before they reach a handler.
+/// Bodyless requests, such as a `POST` that only takes query parameters, pass.
+pub async fn require_json<B: MessageBody + 'static>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let takes_body = matches!(*req.method(), Method::POST | Method::PUT | Method::PATCH);
+    if !takes_body || !has_body(&req) || is_json(&req) {
+        return Ok(next.call(req).await?.map_into_left_body());
+    }
+
+    let response = problem(
+        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        "Request body must be JSON (Content-Type: application/json)",
+    );
+    Ok(req.into_response(response).map_into_right_body())
+}
+
+/// Whether the request sends a body, going by its framing headers
+fn has_body(req: &ServiceRequest) -> bool {
+    let headers = req.headers();
+    let length = headers
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok());
+    let chunked = headers
+        .get(TRANSFER_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.to_ascii_lowercase().contains("chunked"));
+    chunked || length.is_some_and(|length| length > 0)
+}
+
+fn is_json(req: &ServiceRequest) -> bool {
+    req.headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(|mime| {
+            let mime = mime.trim().to_ascii_lowercase();
+            mime == "application/json" || mime.ends_with("+json")
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::{App, HttpResponse};
+
+    #[actix_web::test]
+    async fn only_bodies_must_be_json() {
+        let app = init_service(
+            App::new()
+                .wrap(from_fn(require_json))
+                .route("/", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let bodyless = TestRequest::post().uri("/").to_request();
+        assert_eq!(call_service(&app, bodyless).await.status(), StatusCode::OK);
+        let empty = TestRequest::post()
+            .uri("/")
+            .insert_header((CONTENT_LENGTH, "0"))
+            .insert_header((CONTENT_TYPE, "text/plain"))
+            .to_request();
+        assert_eq!(call_service(&app, empty).await.status(), StatusCode::OK);
+
+        let json = TestRequest::post()
+            .uri("/")
+            .set_payload(r#"{"projectIds":[]}"#)
+            .insert_header((CONTENT_TYPE, "application/json; charset=utf-8"))
+            .to_request();
+        assert_eq!(call_service(&app, json).await.status(), StatusCode::OK);
+        let text = TestRequest::post()
+            .uri("/")
+            .set_payload("projectIds=")
+            .insert_header((CONTENT_TYPE, "application/x-www-form-urlencoded"))
+            .to_request();
+        assert_eq!(
+            call_service(&app, text).await.status(),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+        let chunked = TestRequest::post()
+            .uri("/")
+            .insert_header((TRANSFER_ENCODING, "chunked"))
+            .to_request();
+        assert_eq!(
+            call_service(&app, chunked).await.status(),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+    }
+}
@@ -0,0 +1,260 @@
+//! Catalog-wide statistics.
+
+use actix_web::http::header::{HeaderValue, RETRY_AFTER};
+use actix_web::http::StatusCode;
+use actix_web::{get, web, HttpResponse, Responder};
+use chai_core::graph::{graph_stats, top_degree, Degree};
+use chai_core::stats::{cross_ecosystem_projects, ecosystem_combinations, source_hosts};
+use chai_types::format::Int8;
+use chai_types::models::{CrossEcosystem, GraphStats, SourceHosts, TopDegree};
+use chai_types::requests::{CrossEcosystemParams, TopDegreeParams};
+use dashmap::DashMap;
+use serde_json::Value;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+use crate::app_state::AppState;
+use crate::problem::{database_error, no_connection, problem};
+
+/// Most projects one cross-ecosystem request lists
+const MAX_PROJECTS: i64 = 1000;
+/// Most projects one top dependents or dependencies request lists, and how
+/// many of each are cached
+const MAX_DEGREE_PROJECTS: i64 = 1000;
+/// Suggested wait before asking again for graph statistics not computed yet
+const GRAPH_RETRY_SECS: u64 = 60;
+
+/// The best connected projects of each direction, `MAX_DEGREE_PROJECTS` of
+/// them with ranks from the run they are keyed by. Only the latest run's are
+/// kept.
+pub type DegreeCache = DashMap<(Option<i32>, Degree), Arc<Vec<Value>>>;
+
+/// The last `GET /stats/graph` report, replaced by the `graph_stats` task
+pub type GraphCache = RwLock<Option<GraphStats>>;
+
+/// Projects published to several package managers, e.g. both Homebrew and
+/// Debian: how many share each combination, and the best ranked of them
+#[utoipa::path(
+    tag = "stats",
+    params(CrossEcosystemParams),
+    responses(
+        (status = 200, description = "OK", body = CrossEcosystem),
+    )
+)]
+#[get("/stats/cross-ecosystem")]
+pub async fn get_cross_ecosystem(
+    params: web::Query<CrossEcosystemParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let limit = params.limit.unwrap_or(100).clamp(1, MAX_PROJECTS);
+    let managers: Vec<&str> = params
+        .package_managers
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|manager| !manager.is_empty())
+        .collect();
+
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+
+    let run = *data.latest_run.borrow();
+    let live = data.not_deleted("canons", "c");
+    let combinations = match ecosystem_combinations(&client, &live, data.row_format).await {
+        Ok(combinations) => combinations,
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            return database_error(&e);
+        }
+    };
+    let total = combinations
+        .iter()
+        .map(|combination| combination.count.0)
+        .sum();
+
+    match cross_ecosystem_projects(&client, run, &managers, limit, &live, data.row_format).await {
+        Ok(projects) => HttpResponse::Ok().json(CrossEcosystem {
+            run,
+            total: Int8(total, data.row_format),
+            combinations,
+            projects,
+        }),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+/// Projects by where their source is hosted, e.g. github.com, and how much of
+/// the rank each host holds
+#[utoipa::path(
+    tag = "stats",
+    responses(
+        (status = 200, description = "OK", body = SourceHosts),
+    )
+)]
+#[get("/stats/source-hosts")]
+pub async fn get_source_hosts(data: web::Data<AppState>) -> impl Responder {
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+
+    let run = *data.latest_run.borrow();
+    let live = data.not_deleted("canons", "c");
+    match source_hosts(&client, run, &live, data.row_format).await {
+        Ok(hosts) => HttpResponse::Ok().json(hosts),
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            database_error(&e)
+        }
+    }
+}
+
+/// Projects most depended on directly, e.g. to set next to their rank
+#[utoipa::path(
+    tag = "stats",
+    params(TopDegreeParams),
+    responses(
+        (status = 200, description = "OK", body = TopDegree),
+    )
+)]
+#[get("/stats/top-dependents")]
+pub async fn get_top_dependents(
+    params: web::Query<TopDegreeParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    top_degree_response(Degree::Dependents, &params, &data).await
+}
+
+/// Projects with the most direct dependencies
+#[utoipa::path(
+    tag = "stats",
+    params(TopDegreeParams),
+    responses(
+        (status = 200, description = "OK", body = TopDegree),
+    )
+)]
+#[get("/stats/top-dependencies")]
+pub async fn get_top_dependencies(
+    params: web::Query<TopDegreeParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    top_degree_response(Degree::Dependencies, &params, &data).await
+}
+
+async fn top_degree_response(
+    degree: Degree,
+    params: &TopDegreeParams,
+    data: &AppState,
+) -> HttpResponse {
+    let limit = params.limit.unwrap_or(100).clamp(1, MAX_DEGREE_PROJECTS);
+    let run = *data.latest_run.borrow();
+
+    let cached = data
+        .degree_cache
+        .get(&(run, degree))
+        .map(|entry| Arc::clone(&entry));
+    let projects = match cached {
+        Some(projects) => projects,
+        None => {
+            let client = match data.pool.get().await {
+                Ok(client) => client,
+                Err(e) => {
+                    log::error!("Failed to get database connection: {e}");
+                    return no_connection();
+                }
+            };
+            let live = data.not_deleted("canons", "c");
+            let projects = match top_degree(
+                &client,
+                degree,
+                run,
+                MAX_DEGREE_PROJECTS,
+                &live,
+                data.row_format,
+            )
+            .await
+            {
+                Ok(projects) => Arc::new(projects),
+                Err(e) => {
+                    log::error!("Database query error: {e}");
+                    return database_error(&e);
+                }
+            };
+            // A new run makes the other runs' entries stale
+            data.degree_cache
+                .retain(|(cached_run, _), _| *cached_run == run);
+            data.degree_cache
+                .insert((run, degree), Arc::clone(&projects));
+            projects
+        }
+    };
+
+    HttpResponse::Ok().json(TopDegree {
+        run,
+        projects: projects.iter().take(limit as usize).collect::<Vec<_>>(),
+    })
+}
+
+/// One pass of the `graph_stats` maintenance task. A failed pass keeps the
+/// previous report.
+pub async fn refresh_graph_stats(data: &AppState) {
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection for graph statistics: {e}");
+            return;
+        }
+    };
+    let started = Instant::now();
+    let live = data.not_deleted("canons", "c");
+    match graph_stats(&client, &live).await {
+        Ok(stats) => {
+            log::info!(
+                "Computed graph statistics over {} edges in {:?}",
+                stats.edges,
+                started.elapsed()
+            );
+            *data.graph_stats.write().expect("graph cache lock poisoned") = Some(stats);
+        }
+        Err(e) => log::error!("Failed to compute graph statistics: {e}"),
+    }
+}
+
+/// Edge count, dependency cycles, the longest chain and the most connected
+/// projects of the dependency graph, as the `graph_stats` task last computed
+/// them
+#[utoipa::path(
+    tag = "stats",
+    responses(
+        (status = 200, description = "OK", body = GraphStats),
+        (status = 503, description = "Not computed yet"),
+    )
+)]
+#[get("/stats/graph")]
+pub async fn get_graph_stats(data: web::Data<AppState>) -> impl Responder {
+    match &*data.graph_stats.read().expect("graph cache lock poisoned") {
+        Some(stats) => HttpResponse::Ok().json(stats),
+        None => {
+            let mut response = problem(
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Graph statistics have not been computed yet",
+            );
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(GRAPH_RETRY_SECS));
+            response
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! One public summary of the API's health and how current its data is, for
+//! embedding in a status page.
+
+use actix_web::{get, web, HttpResponse, Responder};
+use chai_core::db::timed;
+use chai_core::leaderboard::run_created_at;
+use chai_core::stats::ecosystem_ingests;
+use chai_types::format::Timestamp;
+use chai_types::models::{EcosystemFreshness, Health, Status};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::app_state::AppState;
+
+/// Each ecosystem's last ingest as the `ingest_check` task last read it
+pub struct Ingests {
+    checked_at: DateTime<Utc>,
+    ecosystems: Vec<(String, Option<NaiveDateTime>)>,
+}
+
+/// Replaced by the `ingest_check` task
+pub type IngestCache = RwLock<Option<Ingests>>;
+
+/// Ages past which `/status` turns yellow
+pub struct StatusLimits {
+    pub run_max_age: Duration,
+    pub ingest_max_age: Duration,
+}
+
+/// One pass of the `ingest_check` maintenance task. A failed pass keeps the
+/// previous result.
+pub async fn refresh_ingests(data: &AppState) {
+    let client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection for the ingest check: {e}");
+            return;
+        }
+    };
+    match ecosystem_ingests(&client).await {
+        Ok(ecosystems) => {
+            *data.ingests.write().expect("ingest cache lock poisoned") = Some(Ingests {
+                checked_at: Utc::now(),
+                ecosystems,
+            });
+        }
+        Err(e) => log::error!("Failed to check ecosystem ingests: {e}"),
+    }
+}
+
+/// Hours from `at` (UTC) to `now`, to a tenth of an hour
+fn age_hours(at: NaiveDateTime, now: DateTime<Utc>) -> f64 {
+    ((now.naive_utc() - at).num_seconds() as f64 / 360.0).round() / 10.0
+}
+
+/// Green when `at` is within `max_age` of `now`, yellow when it is older or
+/// unknown
+fn freshness(at: Option<NaiveDateTime>, max_age: Duration, now: DateTime<Utc>) -> Health {
+    match at {
+        Some(at) if (now.naive_utc() - at).num_seconds() <= max_age.as_secs() as i64 => {
+            Health::Green
+        }
+        _ => Health::Yellow,
+    }
+}
+
+/// The API's overall status: `red` when the database can't be reached, `yellow`
+/// when the latest run or an ecosystem's last ingest is older than its limit,
+/// `green` otherwise. Answers `503` when red, so uptime checks can use it too.
+#[utoipa::path(
+    tag = "health",
+    responses(
+        (status = 200, description = "OK", body = Status),
+        (status = 503, description = "The database is unreachable", body = Status),
+    )
+)]
+#[get("/status")]
+pub async fn get_status(data: web::Data<AppState>) -> impl Responder {
+    let now = Utc::now();
+    let limits = &data.status_limits;
+    let run = *data.latest_run.borrow();
+
+    let (database, created_at) = match data.pool.get().await {
+        Ok(client) => match run {
+            Some(run) => match run_created_at(&client, run).await {
+                Ok(created_at) => (Health::Green, created_at),
+                Err(e) => {
+                    log::error!("Database query error: {e}");
+                    (Health::Red, None)
+                }
+            },
+            None => match timed("status", client.query_one("SELECT 1", &[])).await {
+                Ok(_) => (Health::Green, None),
+                Err(e) => {
+                    log::error!("Database query error: {e}");
+                    (Health::Red, None)
+                }
+            },
+        },
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            (Health::Red, None)
+        }
+    };
+    let run_status = freshness(created_at, limits.run_max_age, now);
+
+    let (ecosystems_checked_at, ecosystems) =
+        match &*data.ingests.read().expect("ingest cache lock poisoned") {
+            Some(ingests) => (
+                Some(ingests.checked_at),
+                ingests
+                    .ecosystems
+                    .iter()
+                    .map(|(ecosystem, last_ingest)| EcosystemFreshness {
+                        ecosystem: ecosystem.clone(),
+                        last_ingest: Timestamp(*last_ingest, data.row_format),
+                        age_hours: last_ingest.map(|at| age_hours(at, now)),
+                        status: freshness(*last_ingest, limits.ingest_max_age, now),
+                    })
+                    .collect(),
+            ),
+            None => (None, Vec::new()),
+        };
+
+    let checked = if ecosystems_checked_at.is_some() {
+        Health::Green
+    } else {
+        Health::Yellow
+    };
+    let status = ecosystems
+        .iter()
+        .map(|ecosystem| ecosystem.status)
+        .chain([database, run_status, checked])
+        .max()
+        .unwrap_or(Health::Green);
+
+    let body = Status {
+        status,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        database,
+        run,
+        run_created_at: Timestamp(created_at, data.row_format),
+        run_age_hours: created_at.map(|at| age_hours(at, now)),
+        run_status,
+        ecosystems_checked_at,
+        ecosystems,
+    };
+    match status {
+        Health::Red => HttpResponse::ServiceUnavailable().json(body),
+        _ => HttpResponse::Ok().json(body),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+
+    #[test]
+    fn freshness_turns_yellow_past_the_limit() {
+        let now = Utc::now();
+        let limit = Duration::from_secs(48 * 3600);
+        let ago = |hours| Some(now.naive_utc() - TimeDelta::hours(hours));
+
+        assert_eq!(freshness(ago(1), limit, now), Health::Green);
+        assert_eq!(freshness(ago(48), limit, now), Health::Green);
+        assert_eq!(freshness(ago(49), limit, now), Health::Yellow);
+        assert_eq!(freshness(None, limit, now), Health::Yellow);
+        assert_eq!(
+            [Health::Green, Health::Red, Health::Yellow].iter().max(),
+            Some(&Health::Red)
+        );
+    }
+}
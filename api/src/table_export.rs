@@ -0,0 +1,366 @@
+//! Whole-table dumps for `chai-api export`. Rows are read through a portal in
+//! batches and written as they arrive, so tables larger than memory export in
+//! constant space.
+
+use arrow_array::{
+    ArrayRef, BooleanArray, Date32Array, Float32Array, Float64Array, Int16Array, Int32Array,
+    Int64Array, RecordBatch, StringArray, TimestampMicrosecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use chai_core::rows::{column_to_json, renders_natively, row_to_json};
+use chai_types::format::RowFormat;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use clap::ValueEnum;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use serde_json::Value;
+use std::io::{self, Write};
+use std::sync::Arc;
+use tokio_postgres::types::Type;
+use tokio_postgres::{Client, Column, Portal, Row, Statement, Transaction};
+
+use crate::app_state::AppState;
+use crate::csv_stream::record;
+
+/// Rows fetched from the portal, and written, at a time
+const BATCH_ROWS: i32 = 8192;
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum DumpFormat {
+    /// One JSON object per line, as `/tables/{table}` renders rows
+    Json,
+    Csv,
+    /// Snappy-compressed, with native columns for numbers, booleans and times
+    Parquet,
+}
+
+/// Writes every row of `table` to `out` as `format`, skipping soft-deleted rows
+/// unless `include_deleted`. Returns the number of rows written.
+pub async fn dump_table(
+    data: &AppState,
+    table: &str,
+    include_deleted: bool,
+    format: DumpFormat,
+    out: Box<dyn Write + Send>,
+) -> Result<u64, String> {
+    let catalog = data.tables.borrow().clone();
+    if !catalog.tables.iter().any(|t| t == table) {
+        return Err(format!(
+            "Table '{table}' not found; valid tables: {}",
+            catalog.tables.join(", ")
+        ));
+    }
+    let live = if include_deleted {
+        "TRUE".to_string()
+    } else {
+        data.not_deleted(table, "t")
+    };
+
+    let mut client = data
+        .pool
+        .primary()
+        .get()
+        .await
+        .map_err(|e| format!("Failed to get database connection: {e}"))?;
+    write_rows(&mut client, table, &live, format, data.row_format, out).await
+}
+
+/// Streams the rows of `table` (aliased `t`) matching `filter` into `out`
+async fn write_rows(
+    client: &mut Client,
+    table: &str,
+    filter: &str,
+    format: DumpFormat,
+    row_format: RowFormat,
+    out: Box<dyn Write + Send>,
+) -> Result<u64, String> {
+    let transaction = client
+        .transaction()
+        .await
+        .map_err(|e| format!("Failed to start a transaction: {e}"))?;
+    let (statement, portal) = bind_rows(&transaction, table, filter)
+        .await
+        .map_err(|e| format!("Failed to query {table}: {e}"))?;
+
+    let mut writer = DumpWriter::new(format, statement.columns(), row_format, out)
+        .map_err(|e| format!("Failed to start the {table} export: {e}"))?;
+    let mut written = 0;
+    loop {
+        let rows = transaction
+            .query_portal(&portal, BATCH_ROWS)
+            .await
+            .map_err(|e| format!("Failed to read {table}: {e}"))?;
+        if rows.is_empty() {
+            break;
+        }
+        writer
+            .write(&rows)
+            .map_err(|e| format!("Failed to write the {table} export: {e}"))?;
+        written += rows.len() as u64;
+    }
+    writer
+        .finish()
+        .map_err(|e| format!("Failed to finish the {table} export: {e}"))?;
+    Ok(written)
+}
+
+/// A portal over the rows of `table` (aliased `t`) matching `filter`, and the
+/// statement it was bound from
+pub async fn bind_rows(
+    transaction: &Transaction<'_>,
+    table: &str,
+    filter: &str,
+) -> Result<(Statement, Portal), tokio_postgres::Error> {
+    // Only catalog tables get through, so the name needs no quoting. Columns
+    // the row renderer can't read are cast to text rather than lost as nulls.
+    let columns = transaction
+        .prepare(&format!("SELECT * FROM {table} t WHERE FALSE"))
+        .await?;
+    let select = columns
+        .columns()
+        .iter()
+        .map(|column| {
+            let name = column.name().replace('"', "\"\"");
+            if renders_natively(column.type_()) {
+                format!("t.\"{name}\"")
+            } else {
+                format!("t.\"{name}\"::text AS \"{name}\"")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let statement = transaction
+        .prepare(&format!("SELECT {select} FROM {table} t WHERE {filter}"))
+        .await?;
+    let portal = transaction.bind(&statement, &[]).await?;
+    Ok((statement, portal))
+}
+
+enum DumpWriter {
+    Json(Box<dyn Write + Send>, RowFormat),
+    Csv(csv::Writer<Box<dyn Write + Send>>, RowFormat),
+    /// With the Postgres type of each column
+    Parquet(ArrowWriter<Box<dyn Write + Send>>, Arc<Schema>, Vec<Type>),
+}
+
+impl DumpWriter {
+    fn new(
+        format: DumpFormat,
+        columns: &[Column],
+        row_format: RowFormat,
+        out: Box<dyn Write + Send>,
+    ) -> io::Result<Self> {
+        Ok(match format {
+            DumpFormat::Json => DumpWriter::Json(out, row_format),
+            DumpFormat::Csv => {
+                let mut writer = csv::Writer::from_writer(out);
+                writer.write_record(columns.iter().map(Column::name))?;
+                DumpWriter::Csv(writer, row_format)
+            }
+            DumpFormat::Parquet => {
+                let schema = Arc::new(Schema::new(
+                    columns
+                        .iter()
+                        .map(|column| Field::new(column.name(), data_type(column.type_()), true))
+                        .collect::<Vec<_>>(),
+                ));
+                let properties = WriterProperties::builder()
+                    .set_compression(Compression::SNAPPY)
+                    .build();
+                let writer = ArrowWriter::try_new(out, Arc::clone(&schema), Some(properties))
+                    .map_err(io::Error::other)?;
+                let types = columns
+                    .iter()
+                    .map(|column| column.type_().clone())
+                    .collect();
+                DumpWriter::Parquet(writer, schema, types)
+            }
+        })
+    }
+
+    fn write(&mut self, rows: &[Row]) -> io::Result<()> {
+        match self {
+            DumpWriter::Json(out, format) => {
+                for row in rows {
+                    serde_json::to_writer(&mut *out, &row_to_json(row, *format))?;
+                    out.write_all(b"\n")?;
+                }
+            }
+            DumpWriter::Csv(writer, format) => {
+                for row in rows {
+                    writer.write_record(record(row, *format))?;
+                }
+            }
+            DumpWriter::Parquet(writer, schema, types) => {
+                let columns = types
+                    .iter()
+                    .enumerate()
+                    .map(|(i, ty)| column_array(rows, i, ty))
+                    .collect();
+                let batch =
+                    RecordBatch::try_new(Arc::clone(schema), columns).map_err(io::Error::other)?;
+                writer.write(&batch).map_err(io::Error::other)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> io::Result<()> {
+        match self {
+            DumpWriter::Json(mut out, _) => out.flush(),
+            DumpWriter::Csv(mut writer, _) => writer.flush(),
+            DumpWriter::Parquet(writer, ..) => {
+                writer.close().map_err(io::Error::other)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The Parquet type of a Postgres column; anything without a native
+/// counterpart is written as text
+fn data_type(ty: &Type) -> DataType {
+    match *ty {
+        Type::INT2 => DataType::Int16,
+        Type::INT4 => DataType::Int32,
+        Type::INT8 => DataType::Int64,
+        Type::FLOAT4 => DataType::Float32,
+        Type::FLOAT8 => DataType::Float64,
+        Type::BOOL => DataType::Boolean,
+        Type::TIMESTAMP => DataType::Timestamp(TimeUnit::Microsecond, None),
+        Type::TIMESTAMPTZ => DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+        Type::DATE => DataType::Date32,
+        _ => DataType::Utf8,
+    }
+}
+
+/// Column `i` of `rows`, of Postgres type `ty`, typed per [`data_type`]
+fn column_array(rows: &[Row], i: usize, ty: &Type) -> ArrayRef {
+    fn values<'a, T: tokio_postgres::types::FromSql<'a>>(
+        rows: &'a [Row],
+        i: usize,
+    ) -> impl Iterator<Item = Option<T>> + 'a {
+        rows.iter()
+            .map(move |row| row.try_get::<_, Option<T>>(i).ok().flatten())
+    }
+
+    match *ty {
+        Type::INT2 => Arc::new(Int16Array::from_iter(values::<i16>(rows, i))),
+        Type::INT4 => Arc::new(Int32Array::from_iter(values::<i32>(rows, i))),
+        Type::INT8 => Arc::new(Int64Array::from_iter(values::<i64>(rows, i))),
+        Type::FLOAT4 => Arc::new(Float32Array::from_iter(values::<f32>(rows, i))),
+        Type::FLOAT8 => Arc::new(Float64Array::from_iter(values::<f64>(rows, i))),
+        Type::BOOL => Arc::new(BooleanArray::from_iter(values::<bool>(rows, i))),
+        Type::TIMESTAMP => Arc::new(TimestampMicrosecondArray::from_iter(
+            values::<NaiveDateTime>(rows, i).map(|t| t.map(|t| t.and_utc().timestamp_micros())),
+        )),
+        Type::TIMESTAMPTZ => Arc::new(
+            TimestampMicrosecondArray::from_iter(
+                values::<DateTime<Utc>>(rows, i).map(|t| t.map(|t| t.timestamp_micros())),
+            )
+            .with_timezone("UTC"),
+        ),
+        Type::DATE => Arc::new(Date32Array::from_iter(
+            values::<NaiveDate>(rows, i)
+                .map(|d| d.map(|d| (d - DateTime::UNIX_EPOCH.date_naive()).num_days() as i32)),
+        )),
+        _ => Arc::new(StringArray::from_iter(
+            rows.iter()
+                .map(|row| text(column_to_json(row, i, RowFormat::default()))),
+        )),
+    }
+}
+
+/// A rendered value as a text cell; strings are written without quotes
+fn text(value: Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s),
+        other => Some(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::Array;
+    use chai_core::test_support::test_client;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::fs::File;
+
+    #[tokio::test]
+    async fn parquet_keeps_native_types_and_nulls() {
+        let Some(mut client) = test_client().await else {
+            return;
+        };
+        client
+            .batch_execute(
+                r#"
+                CREATE TEMP TABLE dump_sample (
+                    n integer, big bigint, flag boolean, at timestamp, name text, rank numeric
+                );
+                INSERT INTO dump_sample VALUES
+                    (1, 9007199254740993, true, '2024-01-02 03:04:05', 'tokio', 12.50),
+                    (2, NULL, NULL, NULL, NULL, NULL);"#,
+            )
+            .await
+            .unwrap();
+        let path = std::env::temp_dir().join(format!("chai-dump-{}.parquet", std::process::id()));
+        let out = Box::new(File::create(&path).unwrap());
+        let written = write_rows(
+            &mut client,
+            "dump_sample",
+            "TRUE",
+            DumpFormat::Parquet,
+            RowFormat::default(),
+            out,
+        )
+        .await
+        .unwrap();
+        assert_eq!(written, 2);
+
+        let batch = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let types: Vec<&DataType> = batch
+            .schema_ref()
+            .fields()
+            .iter()
+            .map(|field| field.data_type())
+            .collect();
+        assert_eq!(
+            types,
+            [
+                &DataType::Int32,
+                &DataType::Int64,
+                &DataType::Boolean,
+                &DataType::Timestamp(TimeUnit::Microsecond, None),
+                &DataType::Utf8,
+                &DataType::Utf8,
+            ]
+        );
+        let column = |name: &str| batch.column_by_name(name).unwrap();
+        let big = column("big").as_any().downcast_ref::<Int64Array>().unwrap();
+        assert_eq!(big.value(0), 9007199254740993);
+        assert!(big.is_null(1));
+        let at = column("at")
+            .as_any()
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .unwrap();
+        assert_eq!(at.value(0), 1_704_164_645_000_000);
+        // Numerics keep their exact digits as text
+        let rank = column("rank")
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(rank.value(0), "12.50");
+        assert!(rank.is_null(1));
+    }
+}
@@ -0,0 +1,262 @@
+//! Whole tables as newline-delimited JSON, for mirrors that would otherwise
+//! page through them. Rows are read through a portal a batch at a time, and
+//! the next batch only once the client has taken the one before, so a slow
+//! client holds up the reads rather than having rows pile up in memory.
+
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::http::StatusCode;
+use actix_web::web::Bytes;
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
+use chai_core::rows::row_to_json;
+use chai_types::format::RowFormat;
+use chai_types::requests::SoftDeleteParams;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+use tokio_postgres::Client;
+
+use crate::app_state::AppState;
+use crate::auth::caller;
+use crate::export::require_exports;
+use crate::handlers::{check_include_deleted, check_table_exists, live_rows};
+use crate::problem::{database_error, no_connection, problem};
+use crate::table_export::bind_rows;
+use crate::usage::TableRead;
+
+/// Rows fetched from the portal, and sent as one chunk, at a time
+const BATCH_ROWS: i32 = 1000;
+/// Chunks read ahead of the client
+const READ_AHEAD: usize = 2;
+
+type Chunk = Result<Bytes, tokio_postgres::Error>;
+
+/// Chunks of rows as they are read, starting with one read before the
+/// response so a table that can't be read fails with a status
+pub struct NdjsonBody {
+    first: Option<Bytes>,
+    rest: mpsc::Receiver<Chunk>,
+}
+
+impl MessageBody for NdjsonBody {
+    type Error = tokio_postgres::Error;
+
+    fn size(&self) -> BodySize {
+        BodySize::Stream
+    }
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        let this = self.get_mut();
+        match this.first.take() {
+            Some(first) => Poll::Ready(Some(Ok(first))),
+            None => this.rest.poll_recv(cx),
+        }
+    }
+}
+
+/// Every row of a table, one JSON object per line as `/tables/{table}`
+/// renders them, in no particular order. Only `TABLE_STREAMS_MAX` streams may
+/// be open at once.
+#[utoipa::path(
+    tag = "tables",
+    params(
+        ("table" = String, Path, description = "Table name"),
+        SoftDeleteParams,
+    ),
+    responses(
+        (
+            status = 200,
+            description = "One row per line",
+            content((String = "application/x-ndjson"))
+        ),
+        (status = 403, description = "Exports aren't available on the caller's tier"),
+        (status = 404, description = "No such table"),
+        (status = 503, description = "Too many streams open"),
+    )
+)]
+#[get("/tables/{table}/stream")]
+pub async fn stream_table(
+    req: HttpRequest,
+    path: web::Path<String>,
+    deleted: web::Query<SoftDeleteParams>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    // A whole table is as much of an export as a whole run's leaderboard
+    if let Some(response) = require_exports(&req, &data) {
+        return response;
+    }
+    let table = path.into_inner();
+    if let Some(response) = check_table_exists(&table, &data.tables.borrow().tables) {
+        return response;
+    }
+    if let Some(response) = check_include_deleted(&req, &deleted) {
+        return response;
+    }
+    // Held until the last row is sent or the client goes away
+    let Ok(permit) = Arc::clone(&data.table_streams).try_acquire_owned() else {
+        return problem(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "As many table streams as the server allows are already open. Retry shortly.",
+        );
+    };
+    // A stream reads every page at once
+    data.usage
+        .record_table_read(&table, &caller(&req).id(), TableRead::Page);
+    let live = live_rows(&deleted, &data, &table);
+
+    let mut client = match data.pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+    let (chunks, mut rest) = mpsc::channel(READ_AHEAD);
+    let row_format = data.row_format;
+    actix_web::rt::spawn(async move {
+        let _permit = permit;
+        if let Err(e) = send_rows(&mut client, &table, &live, row_format, &chunks).await {
+            log::error!("Failed to stream {table}: {e}");
+            let _ = chunks.send(Err(e)).await;
+        }
+    });
+
+    let first = match rest.recv().await {
+        Some(Ok(first)) => Some(first),
+        Some(Err(e)) => return database_error(&e),
+        None => None,
+    };
+    HttpResponse::Ok()
+        .content_type("application/x-ndjson")
+        .body(NdjsonBody { first, rest })
+}
+
+/// Sends the rows of `table` (aliased `t`) matching `filter` to `chunks` a
+/// batch at a time, until they run out or the receiver is dropped
+async fn send_rows(
+    client: &mut Client,
+    table: &str,
+    filter: &str,
+    format: RowFormat,
+    chunks: &mpsc::Sender<Chunk>,
+) -> Result<(), tokio_postgres::Error> {
+    let transaction = client.transaction().await?;
+    let (_, portal) = bind_rows(&transaction, table, filter).await?;
+    loop {
+        let rows = transaction.query_portal(&portal, BATCH_ROWS).await?;
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let mut chunk = Vec::new();
+        for row in &rows {
+            chunk.extend_from_slice(row_to_json(row, format).to_string().as_bytes());
+            chunk.push(b'\n');
+        }
+        if chunks.send(Ok(Bytes::from(chunk))).await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::App;
+    use chai_core::test_support::test_client;
+    use serde_json::{json, Value};
+
+    use crate::config::Config;
+    use crate::test_state::{test_state, UNREACHABLE_DATABASE_URL};
+
+    #[actix_web::test]
+    async fn streams_need_exports() {
+        let config = Config {
+            tier_exports: vec![("free".to_string(), false)],
+            ..Config::from_env()
+        };
+        let app = init_service(
+            App::new()
+                .app_data(test_state(&config, UNREACHABLE_DATABASE_URL))
+                .service(stream_table),
+        )
+        .await;
+        let response = call_service(
+            &app,
+            TestRequest::get().uri("/tables/canons/stream").to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn rows_are_sent_a_batch_at_a_time() {
+        let Some(mut client) = test_client().await else {
+            return;
+        };
+        client
+            .batch_execute(
+                "CREATE TEMP TABLE stream_sample AS
+                SELECT n, 'p' || n AS name FROM generate_series(1, 2500) n",
+            )
+            .await
+            .unwrap();
+
+        let (chunks, mut received) = mpsc::channel(READ_AHEAD);
+        let send = async {
+            let sent = send_rows(
+                &mut client,
+                "stream_sample",
+                "t.n % 2 = 1",
+                RowFormat::default(),
+                &chunks,
+            )
+            .await;
+            drop(chunks);
+            sent
+        };
+        let receive = async {
+            let mut lines = Vec::new();
+            while let Some(chunk) = received.recv().await {
+                let chunk = chunk.unwrap();
+                assert!(chunk.ends_with(b"\n"));
+                lines.push(chunk.split(|b| *b == b'\n').count() - 1);
+            }
+            lines
+        };
+        let (sent, lines) = tokio::join!(send, receive);
+        sent.unwrap();
+        assert_eq!(lines, [1000, 250]);
+
+        // The client going away ends the stream without an error
+        let (chunks, received) = mpsc::channel(READ_AHEAD);
+        drop(received);
+        send_rows(
+            &mut client,
+            "stream_sample",
+            "TRUE",
+            RowFormat::default(),
+            &chunks,
+        )
+        .await
+        .unwrap();
+
+        let (chunks, rest) = mpsc::channel(READ_AHEAD);
+        chunks.send(Ok(Bytes::from("{\"n\":3}\n"))).await.unwrap();
+        drop(chunks);
+        let body = NdjsonBody {
+            first: Some(Bytes::from("{\"n\":1}\n")),
+            rest,
+        };
+        let body = actix_web::body::to_bytes(body).await.unwrap();
+        let rows: Vec<Value> = body
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        assert_eq!(rows, [json!({ "n": 1 }), json!({ "n": 3 })]);
+    }
+}
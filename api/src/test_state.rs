@@ -0,0 +1,46 @@
+//! State for tests that drive handlers and middleware through actix's test
+//! service, without connecting at startup or starting maintenance tasks.
+
+use actix_web::web;
+use chai_core::db::Catalog;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+use crate::app_state::AppState;
+use crate::config::Config;
+use crate::db;
+use crate::regions::{Pools, RegionPool};
+use crate::{assemble_state, Settings};
+
+/// For state whose requests never reach the database, since pools only connect
+/// when a connection is first taken
+pub const UNREACHABLE_DATABASE_URL: &str = "postgresql://chai@127.0.0.1:1/chai";
+
+/// State for `config` around a pool to `database_url`, opened on first use.
+/// The pool holds a single writable connection, so fixture tables created on
+/// it are what every handler reads. There is no latest run and no table.
+pub fn test_state(config: &Config, database_url: &str) -> web::Data<AppState> {
+    let settings = Settings::from_config(config).expect("Invalid test settings");
+    let recycling = settings.recycling(config);
+    let mut pool_config = db::pool_config(database_url, false);
+    if let Some(pool) = pool_config.pool.as_mut() {
+        pool.max_size = 1;
+    }
+    let pool = db::build_pool(&pool_config, &recycling);
+    let catalog = Catalog {
+        tables: Vec::new(),
+        columns: HashMap::new(),
+    };
+    assemble_state(
+        config,
+        settings,
+        Arc::new(Pools::new(
+            RegionPool::new(config.database_region.clone(), pool),
+            Vec::new(),
+        )),
+        catalog,
+        watch::Sender::new(None),
+        recycling,
+    )
+}
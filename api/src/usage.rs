@@ -0,0 +1,282 @@
+use actix_web::{
+    body::{BodySize, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::CONTENT_LENGTH,
+    middleware::Next,
+    web, Error,
+};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+use crate::app_state::AppState;
+use crate::auth::caller;
+
+const TOP_ROUTES: usize = 5;
+
+/// In-process aggregator of request counts and bytes served per caller, and
+/// of request and response sizes per route.
+pub struct UsageTracker {
+    callers: DashMap<String, CallerUsage>,
+    pending: DashMap<String, CallerUsage>,
+    routes: DashMap<String, RouteSizes>,
+    tables: DashMap<String, TableReads>,
+    /// Responses larger than this are logged as oversized
+    oversized_bytes: Option<u64>,
+}
+
+#[derive(Clone, Default)]
+struct CallerUsage {
+    requests: u64,
+    bytes: u64,
+    routes: HashMap<String, u64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageSummary {
+    pub caller: String,
+    pub requests: u64,
+    pub bytes_served: u64,
+    pub top_routes: Vec<RouteCount>,
+}
+
+#[derive(Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RouteSizes {
+    pub route: String,
+    pub requests: u64,
+    pub request_bytes: u64,
+    pub max_request_bytes: u64,
+    pub response_bytes: u64,
+    pub max_response_bytes: u64,
+    /// Responses over `RESPONSE_SIZE_WARN_BYTES`
+    pub oversized: u64,
+}
+
+/// Which generic table endpoint a read went through
+pub enum TableRead {
+    /// `/tables/{table}`
+    Page,
+    /// `/tables/{table}/{id}`
+    Row,
+}
+
+#[derive(Default)]
+struct TableReads {
+    pages: u64,
+    rows: u64,
+    callers: HashSet<String>,
+    last_read_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TableUsage {
+    pub table: String,
+    pub page_reads: u64,
+    pub row_reads: u64,
+    /// Distinct callers that read the table
+    pub callers: usize,
+    pub last_read_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct RouteCount {
+    pub route: String,
+    pub requests: u64,
+}
+
+impl CallerUsage {
+    fn record(&mut self, route: &str, bytes: u64) {
+        self.requests += 1;
+        self.bytes += bytes;
+        *self.routes.entry(route.to_string()).or_default() += 1;
+    }
+
+    fn summarize(&self, caller: &str) -> UsageSummary {
+        let mut routes: Vec<RouteCount> = self
+            .routes
+            .iter()
+            .map(|(route, requests)| RouteCount {
+                route: route.clone(),
+                requests: *requests,
+            })
+            .collect();
+        routes.sort_by(|a, b| b.requests.cmp(&a.requests).then(a.route.cmp(&b.route)));
+        routes.truncate(TOP_ROUTES);
+
+        UsageSummary {
+            caller: caller.to_string(),
+            requests: self.requests,
+            bytes_served: self.bytes,
+            top_routes: routes,
+        }
+    }
+}
+
+impl UsageTracker {
+    pub fn new(oversized_bytes: Option<u64>) -> Self {
+        Self {
+            callers: DashMap::new(),
+            pending: DashMap::new(),
+            routes: DashMap::new(),
+            tables: DashMap::new(),
+            oversized_bytes,
+        }
+    }
+
+    pub fn record(&self, caller: &str, route: &str, bytes: u64) {
+        self.callers
+            .entry(caller.to_string())
+            .or_default()
+            .record(route, bytes);
+        self.pending
+            .entry(caller.to_string())
+            .or_default()
+            .record(route, bytes);
+    }
+
+    /// Totals since startup, busiest callers first.
+    pub fn summaries(&self) -> Vec<UsageSummary> {
+        let mut summaries: Vec<UsageSummary> = self
+            .callers
+            .iter()
+            .map(|entry| entry.value().summarize(entry.key()))
+            .collect();
+        summaries.sort_by_key(|s| std::cmp::Reverse(s.requests));
+        summaries
+    }
+
+    /// Adds one request to `route`'s size totals. Returns whether the response
+    /// was oversized.
+    pub fn record_sizes(&self, route: &str, request_bytes: u64, response_bytes: u64) -> bool {
+        let oversized = self
+            .oversized_bytes
+            .is_some_and(|limit| response_bytes > limit);
+        let mut sizes = self
+            .routes
+            .entry(route.to_string())
+            .or_insert_with(|| RouteSizes {
+                route: route.to_string(),
+                ..RouteSizes::default()
+            });
+        sizes.requests += 1;
+        sizes.request_bytes += request_bytes;
+        sizes.max_request_bytes = sizes.max_request_bytes.max(request_bytes);
+        sizes.response_bytes += response_bytes;
+        sizes.max_response_bytes = sizes.max_response_bytes.max(response_bytes);
+        if oversized {
+            sizes.oversized += 1;
+        }
+        oversized
+    }
+
+    /// Size totals since startup, most bytes served first.
+    pub fn route_sizes(&self) -> Vec<RouteSizes> {
+        let mut routes: Vec<RouteSizes> = self
+            .routes
+            .iter()
+            .map(|entry| entry.value().clone())
+            .collect();
+        routes.sort_by_key(|r| std::cmp::Reverse(r.response_bytes));
+        routes
+    }
+
+    pub fn record_table_read(&self, table: &str, caller: &str, read: TableRead) {
+        let mut reads = self.tables.entry(table.to_string()).or_default();
+        match read {
+            TableRead::Page => reads.pages += 1,
+            TableRead::Row => reads.rows += 1,
+        }
+        if !reads.callers.contains(caller) {
+            reads.callers.insert(caller.to_string());
+        }
+        reads.last_read_at = Some(Utc::now());
+    }
+
+    /// Reads since startup of every table in `tables`, including unread ones,
+    /// most read first.
+    pub fn table_usage(&self, tables: &[String]) -> Vec<TableUsage> {
+        let mut usage: Vec<TableUsage> = tables
+            .iter()
+            .map(|table| match self.tables.get(table) {
+                Some(reads) => TableUsage {
+                    table: table.clone(),
+                    page_reads: reads.pages,
+                    row_reads: reads.rows,
+                    callers: reads.callers.len(),
+                    last_read_at: reads.last_read_at,
+                },
+                None => TableUsage {
+                    table: table.clone(),
+                    page_reads: 0,
+                    row_reads: 0,
+                    callers: 0,
+                    last_read_at: None,
+                },
+            })
+            .collect();
+        usage.sort_by_key(|t| std::cmp::Reverse(t.page_reads + t.row_reads));
+        usage
+    }
+
+    /// Emits the usage accumulated since the previous flush to the log and
+    /// resets the interval counters.
+    pub fn flush(&self) {
+        let callers: Vec<String> = self.pending.iter().map(|e| e.key().clone()).collect();
+        for caller in callers {
+            if let Some((caller, usage)) = self.pending.remove(&caller) {
+                let summary = usage.summarize(&caller);
+                log::info!(
+                    target: "chai_api::usage",
+                    "caller={} requests={} bytes={} top_route={}",
+                    summary.caller,
+                    summary.requests,
+                    summary.bytes_served,
+                    summary
+                        .top_routes
+                        .first()
+                        .map(|r| r.route.as_str())
+                        .unwrap_or("-"),
+                );
+            }
+        }
+    }
+}
+
+pub async fn track_usage(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let data = req.app_data::<web::Data<AppState>>().cloned();
+    let request_bytes = req
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0);
+    let res = next.call(req).await?;
+
+    if let Some(data) = data {
+        let route = res
+            .request()
+            .match_pattern()
+            .unwrap_or_else(|| "unmatched".to_string());
+        let bytes = match res.response().body().size() {
+            BodySize::Sized(n) => n,
+            _ => 0,
+        };
+        let caller = caller(res.request()).id();
+        data.usage.record(&caller, &route, bytes);
+        if data.usage.record_sizes(&route, request_bytes, bytes) {
+            log::warn!(
+                target: "chai_api::usage",
+                "Oversized response: route={route} caller={caller} bytes={bytes}; \
+                suggest paginating (page/limit) or a narrower query",
+            );
+        }
+    }
+    Ok(res)
+}
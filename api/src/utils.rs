@@ -1,75 +1,10 @@
+use actix_web::http::header::{HeaderName, LINK};
 use actix_web::web::Query;
-use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
-use dashmap::DashMap;
-use serde_json::{json, Value};
-use std::sync::Arc;
-use tokio_postgres::{types::Type, Row};
+use actix_web::HttpRequest;
+use chai_types::requests::PaginationParams;
+use tokio_postgres::types::{ToSql, Type};
 use uuid::Uuid;
 
-use crate::{app_state::ProjectCacheEntry, handlers::PaginationParams};
-
-pub fn get_column_names(rows: &[Row]) -> Vec<String> {
-    if let Some(row) = rows.first() {
-        row.columns()
-            .iter()
-            .map(|col| col.name().to_string())
-            .collect()
-    } else {
-        vec![]
-    }
-}
-
-pub fn convert_optional_to_json<T, E>(result: Result<Option<T>, E>) -> Value
-where
-    T: serde::Serialize,
-{
-    match result {
-        Ok(Some(val)) => json!(val),
-        _ => Value::Null,
-    }
-}
-
-pub fn rows_to_json(rows: &[Row]) -> Vec<Value> {
-    rows.iter()
-        .map(|row| {
-            let mut map = serde_json::Map::new();
-            for (i, column) in row.columns().iter().enumerate() {
-                let value: Value = match *column.type_() {
-                    Type::INT2 => convert_optional_to_json(row.try_get::<_, Option<i16>>(i)),
-                    Type::INT4 => convert_optional_to_json(row.try_get::<_, Option<i32>>(i)),
-                    Type::INT8 => convert_optional_to_json(row.try_get::<_, Option<i64>>(i)),
-                    Type::FLOAT4 => convert_optional_to_json(row.try_get::<_, Option<f32>>(i)),
-                    Type::FLOAT8 => convert_optional_to_json(row.try_get::<_, Option<f64>>(i)),
-                    Type::BOOL => convert_optional_to_json(row.try_get::<_, Option<bool>>(i)),
-                    Type::VARCHAR | Type::TEXT | Type::BPCHAR => {
-                        convert_optional_to_json(row.try_get::<_, Option<String>>(i))
-                    }
-                    Type::TIMESTAMP => {
-                        convert_optional_to_json(row.try_get::<_, Option<NaiveDateTime>>(i))
-                    }
-                    Type::TIMESTAMPTZ => {
-                        convert_optional_to_json(row.try_get::<_, Option<DateTime<Utc>>>(i))
-                    }
-                    Type::DATE => convert_optional_to_json(row.try_get::<_, Option<NaiveDate>>(i)),
-                    Type::JSON | Type::JSONB => {
-                        convert_optional_to_json(row.try_get::<_, Option<serde_json::Value>>(i))
-                    }
-                    Type::UUID => convert_optional_to_json(row.try_get::<_, Option<Uuid>>(i)),
-                    Type::TEXT_ARRAY | Type::VARCHAR_ARRAY => {
-                        convert_optional_to_json(row.try_get::<_, Option<Vec<String>>>(i))
-                    }
-                    _ => {
-                        // For unsupported types, try to convert to string
-                        convert_optional_to_json(row.try_get::<_, Option<String>>(i))
-                    }
-                };
-                map.insert(column.name().to_string(), value);
-            }
-            Value::Object(map)
-        })
-        .collect()
-}
-
 pub struct Pagination {
     pub page: i64,
     pub limit: i64,
@@ -78,11 +13,14 @@ pub struct Pagination {
 }
 
 impl Pagination {
-    pub fn new(query: Query<PaginationParams>, total_count: i64) -> Self {
-        let limit = query.limit.unwrap_or(200).clamp(1, 1000);
+    /// Page `?page=` of `?limit=` rows, at most `max_limit`: the caller's tier
+    /// `response_limit`
+    pub fn new(query: Query<PaginationParams>, total_count: i64, max_limit: i64) -> Self {
+        let limit = query.limit.unwrap_or(200).clamp(1, max_limit);
         let total_pages = (total_count as f64 / limit as f64).ceil() as i64;
 
-        let page = query.page.unwrap_or(1).clamp(1, total_pages);
+        // An empty table still has a (blank) first page
+        let page = query.page.unwrap_or(1).clamp(1, total_pages.max(1));
 
         let offset = (page - 1) * limit;
         Self {
@@ -92,25 +30,174 @@ impl Pagination {
             total_pages,
         }
     }
+
+    /// Pagination by `?cursor=` rather than page number: every page starts at
+    /// the cursor, so `page` is 0
+    pub fn keyset(query: Query<PaginationParams>, total_count: i64, max_limit: i64) -> Self {
+        let limit = query.limit.unwrap_or(200).clamp(1, max_limit);
+        Self {
+            page: 0,
+            limit,
+            offset: 0,
+            total_pages: (total_count as f64 / limit as f64).ceil() as i64,
+        }
+    }
+
+    /// RFC 8288 `Link` to the page after `req` starting at `cursor`, keeping
+    /// its other query parameters
+    pub fn next_link(&self, req: &HttpRequest, cursor: &str) -> (HeaderName, String) {
+        let kept: Vec<(String, String)> =
+            url::form_urlencoded::parse(req.query_string().as_bytes())
+                .filter(|(key, _)| key != "cursor" && key != "limit")
+                .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                .collect();
+        let query = url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(&kept)
+            .append_pair("cursor", cursor)
+            .append_pair("limit", &self.limit.to_string())
+            .finish();
+        (LINK, format!("<{}?{query}>; rel=\"next\"", req.path()))
+    }
+
+    /// RFC 8288 `Link` to the first, previous, next and last pages of `req`,
+    /// keeping its other query parameters
+    pub fn link(&self, req: &HttpRequest) -> (HeaderName, String) {
+        let last = self.total_pages.max(1);
+        let mut pages = vec![("first", 1)];
+        if self.page > 1 {
+            pages.push(("prev", self.page - 1));
+        }
+        if self.page < last {
+            pages.push(("next", self.page + 1));
+        }
+        pages.push(("last", last));
+
+        let kept: Vec<(String, String)> =
+            url::form_urlencoded::parse(req.query_string().as_bytes())
+                .filter(|(key, _)| key != "page" && key != "limit")
+                .map(|(key, value)| (key.into_owned(), value.into_owned()))
+                .collect();
+        let links: Vec<String> = pages
+            .into_iter()
+            .map(|(rel, page)| {
+                let query = url::form_urlencoded::Serializer::new(String::new())
+                    .extend_pairs(&kept)
+                    .append_pair("page", &page.to_string())
+                    .append_pair("limit", &self.limit.to_string())
+                    .finish();
+                format!("<{}?{query}>; rel=\"{rel}\"", req.path())
+            })
+            .collect();
+        (LINK, links.join(", "))
+    }
 }
 
-// Helper function to get cached projects and return missing ones
-pub fn get_cached_projects(
-    cache: Arc<DashMap<Uuid, ProjectCacheEntry>>,
-    project_ids: &[Uuid],
-) -> (Vec<Arc<Value>>, Vec<Uuid>) {
-    let mut cached_projects = Vec::new();
-    let mut missing_ids = Vec::new();
-
-    for &project_id in project_ids {
-        if let Some(entry) = cache.get(&project_id) {
-            if !entry.is_expired() {
-                cached_projects.push(entry.data.clone());
-                continue;
-            }
+/// Where a page of a table ordered by `id` starts, from `?cursor=`. Cursors
+/// are the hex of the last id of the previous page, so they stay opaque to
+/// clients.
+#[derive(Debug, PartialEq)]
+pub enum Keyset {
+    First,
+    After(String),
+}
+
+impl Keyset {
+    /// `None` when `query` pages by number, or an error for a cursor this API
+    /// didn't issue
+    pub fn from_query(query: &PaginationParams) -> Result<Option<Self>, String> {
+        match query.cursor.as_deref().map(str::trim) {
+            None => Ok(None),
+            Some("") => Ok(Some(Self::First)),
+            Some(cursor) => hex::decode(cursor)
+                .ok()
+                .and_then(|bytes| String::from_utf8(bytes).ok())
+                .map(|id| Some(Self::After(id)))
+                .ok_or_else(|| format!("Invalid cursor '{cursor}'")),
         }
-        missing_ids.push(project_id);
     }
 
-    (cached_projects, missing_ids)
+    /// The cursor of the page after the row with id `id`
+    pub fn cursor(id: &str) -> String {
+        hex::encode(id)
+    }
+
+    /// The id to start after as a parameter of type `ty`, the type of the
+    /// table's `id`, or `None` when it doesn't parse as one
+    pub fn bind(id: &str, ty: &Type) -> Option<Box<dyn ToSql + Sync + Send>> {
+        bind_as(id, ty)
+    }
+}
+
+/// `value` from a query string as a parameter of type `ty`, or `None` when it
+/// doesn't parse as one or `ty` isn't a UUID, integer, boolean, or text type
+pub fn bind_as(value: &str, ty: &Type) -> Option<Box<dyn ToSql + Sync + Send>> {
+    match *ty {
+        Type::UUID => Some(Box::new(Uuid::parse_str(value).ok()?)),
+        Type::INT2 => Some(Box::new(value.parse::<i16>().ok()?)),
+        Type::INT4 => Some(Box::new(value.parse::<i32>().ok()?)),
+        Type::INT8 => Some(Box::new(value.parse::<i64>().ok()?)),
+        Type::BOOL => Some(Box::new(value.parse::<bool>().ok()?)),
+        Type::TEXT | Type::VARCHAR => Some(Box::new(value.to_string())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn pagination_links_keep_other_parameters() {
+        let req = TestRequest::get()
+            .uri("/tables/canons?sort=name%3Adesc&page=2&limit=10")
+            .to_http_request();
+        let query = Query::<PaginationParams>::from_query(req.query_string()).unwrap();
+        let (_, link) = Pagination::new(query, 35, 1000).link(&req);
+        assert_eq!(
+            link,
+            "</tables/canons?sort=name%3Adesc&page=1&limit=10>; rel=\"first\", \
+             </tables/canons?sort=name%3Adesc&page=1&limit=10>; rel=\"prev\", \
+             </tables/canons?sort=name%3Adesc&page=3&limit=10>; rel=\"next\", \
+             </tables/canons?sort=name%3Adesc&page=4&limit=10>; rel=\"last\""
+        );
+
+        let req = TestRequest::get().uri("/tables").to_http_request();
+        let query = Query::<PaginationParams>::from_query("").unwrap();
+        let (_, link) = Pagination::new(query, 0, 1000).link(&req);
+        assert_eq!(
+            link,
+            "</tables?page=1&limit=200>; rel=\"first\", </tables?page=1&limit=200>; rel=\"last\""
+        );
+    }
+
+    #[test]
+    fn cursors_round_trip_and_bind_to_the_id_type() {
+        let query = |cursor: &str| PaginationParams {
+            cursor: Some(cursor.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(Keyset::from_query(&PaginationParams::default()), Ok(None));
+        assert_eq!(Keyset::from_query(&query("")), Ok(Some(Keyset::First)));
+        let cursor = Keyset::cursor("42");
+        assert_eq!(
+            Keyset::from_query(&query(&cursor)),
+            Ok(Some(Keyset::After("42".to_string())))
+        );
+        assert!(Keyset::from_query(&query("not hex")).is_err());
+
+        assert!(Keyset::bind("42", &Type::INT4).is_some());
+        assert!(Keyset::bind("42", &Type::UUID).is_none());
+        assert!(Keyset::bind("42", &Type::BOOL).is_none());
+
+        let req = TestRequest::get()
+            .uri("/tables/packages?cursor=&limit=2&include_deleted=false")
+            .to_http_request();
+        let query = Query::<PaginationParams>::from_query(req.query_string()).unwrap();
+        let (_, link) = Pagination::keyset(query, 10, 1000).next_link(&req, &cursor);
+        assert_eq!(
+            link,
+            "</tables/packages?include_deleted=false&cursor=3432&limit=2>; rel=\"next\""
+        );
+    }
 }
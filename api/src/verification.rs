@@ -0,0 +1,274 @@
+use actix_web::http::StatusCode;
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use chai_core::db::timed;
+use chai_types::format::Timestamp;
+use chai_types::models::{VerificationStatus, VerificationToken, VerifiedMaintainer};
+use chrono::NaiveDateTime;
+use deadpool_postgres::Pool;
+use url::Url;
+use uuid::Uuid;
+
+use crate::app_state::AppState;
+use crate::auth::key_name;
+use crate::db::read_write;
+use crate::extractors::{ConsistencyToken, PathUuid};
+use crate::http_client::HttpClient;
+use crate::problem::{database_error, no_connection, not_found, problem};
+use crate::regions::{consistency_token, CONSISTENCY_HEADER};
+
+/// File a maintainer commits to the root of the project's repository
+const VERIFICATION_FILE: &str = ".chai-verification";
+/// Pending tokens older than this are reissued on the next request
+const TOKEN_TTL_DAYS: i32 = 7;
+
+#[utoipa::path(
+    tag = "maintainers",
+    params(("id" = Uuid, Path, description = "Project id")),
+    responses(
+        (status = 200, description = "OK", body = VerificationToken),
+        (status = 401, description = "Requires an API key"),
+        (status = 404, description = "No such project"),
+    )
+)]
+#[post("/project/{id}/verification")]
+pub async fn request_verification(
+    req: HttpRequest,
+    id: PathUuid,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let PathUuid(id) = id;
+    let Some(maintainer) = key_name(&req) else {
+        return problem(
+            StatusCode::UNAUTHORIZED,
+            "Requesting a verification token requires an API key",
+        );
+    };
+
+    let mut client = match data.pool.primary().get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            return no_connection();
+        }
+    };
+
+    let token = format!("chai-verify-{}", Uuid::new_v4().simple());
+    let issue_query = r#"
+        INSERT INTO maintainer_verifications (canon_id, maintainer, token)
+        SELECT $1, $2, $3
+        WHERE EXISTS (SELECT 1 FROM canons WHERE id = $1)
+        ON CONFLICT (canon_id, maintainer) DO UPDATE
+        SET token = EXCLUDED.token, created_at = now(), last_checked_at = NULL
+        WHERE maintainer_verifications.verified_at IS NULL
+            AND maintainer_verifications.created_at < now() - make_interval(days => $4)
+        RETURNING token, verified_at"#;
+    let existing_query = r#"
+        SELECT token, verified_at
+        FROM maintainer_verifications
+        WHERE canon_id = $1 AND maintainer = $2"#;
+
+    let issue = async {
+        let transaction = read_write(&mut client).await?;
+        let row = transaction
+            .query_opt(issue_query, &[&id, &maintainer, &token, &TOKEN_TTL_DAYS])
+            .await?;
+        transaction.commit().await?;
+        Ok::<_, tokio_postgres::Error>(row)
+    };
+    let issued = timed("issue_verification", issue).await;
+
+    let row = match issued {
+        Ok(Some(row)) => Some(row),
+        Ok(None) => match timed(
+            "existing_verification",
+            client.query_opt(existing_query, &[&id, &maintainer]),
+        )
+        .await
+        {
+            Ok(row) => row,
+            Err(e) => {
+                log::error!("Database query error: {e}");
+                return database_error(&e);
+            }
+        },
+        Err(e) => {
+            log::error!("Database query error: {e}");
+            return database_error(&e);
+        }
+    };
+
+    let Some(row) = row else {
+        return not_found(format!("No row found with id '{}' in table canons", id));
+    };
+    let token: String = row.get("token");
+    let verified_at: Option<NaiveDateTime> = row.get("verified_at");
+
+    let mut response = HttpResponse::Ok();
+    match consistency_token(&client).await {
+        Ok(position) => {
+            response.insert_header((CONSISTENCY_HEADER, position));
+        }
+        Err(e) => log::warn!("Failed to read WAL position: {e}"),
+    }
+    response.json(VerificationToken {
+        project_id: id,
+        maintainer,
+        token,
+        status: if verified_at.is_some() {
+            "verified"
+        } else {
+            "pending"
+        }
+        .to_string(),
+        verified_at: Timestamp(verified_at, data.row_format),
+        instructions: format!(
+            "Commit a file named `{VERIFICATION_FILE}` containing the token to the root of the \
+            project's source repository. It is checked periodically for {TOKEN_TTL_DAYS} days."
+        ),
+    })
+}
+
+#[utoipa::path(
+    tag = "maintainers",
+    params(("id" = Uuid, Path, description = "Project id")),
+    responses(
+        (status = 200, description = "OK", body = VerificationStatus),
+    )
+)]
+#[get("/project/{id}/verification")]
+pub async fn get_verification(
+    id: PathUuid,
+    consistency: ConsistencyToken,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let PathUuid(id) = id;
+    let ConsistencyToken(consistency) = consistency;
+
+    let query = r#"
+        SELECT maintainer, verified_at
+        FROM maintainer_verifications
+        WHERE canon_id = $1 AND verified_at IS NOT NULL
+        ORDER BY verified_at"#;
+
+    match data.pool.get_at(consistency.as_deref()).await {
+        Ok(client) => match timed("verification_status", client.query(query, &[&id])).await {
+            Ok(rows) => {
+                let maintainers: Vec<_> = rows
+                    .iter()
+                    .map(|row| VerifiedMaintainer {
+                        maintainer: row.get("maintainer"),
+                        verified_at: Timestamp(row.get("verified_at"), data.row_format),
+                    })
+                    .collect();
+                HttpResponse::Ok().json(VerificationStatus {
+                    project_id: id,
+                    verified: !maintainers.is_empty(),
+                    verified_maintainers: maintainers,
+                })
+            }
+            Err(e) => {
+                log::error!("Database query error: {e}");
+                database_error(&e)
+            }
+        },
+        Err(e) => {
+            log::error!("Failed to get database connection: {e}");
+            no_connection()
+        }
+    }
+}
+
+/// Checks every pending token against its project's source repository, once
+pub async fn verify_pending(pool: &Pool, http: &HttpClient) {
+    let mut client = match pool.get().await {
+        Ok(client) => client,
+        Err(e) => {
+            log::error!("Verifier failed to get database connection: {e}");
+            return;
+        }
+    };
+
+    let pending_query = r#"
+        SELECT mv.id, mv.token, src.url
+        FROM maintainer_verifications mv
+        JOIN LATERAL (
+            SELECT u_source.url
+            FROM canon_packages cp
+            JOIN package_urls pu ON pu.package_id = cp.package_id
+            JOIN urls u_source ON pu.url_id = u_source.id
+            JOIN url_types ut ON ut.id = u_source.url_type_id
+            WHERE cp.canon_id = mv.canon_id AND ut.name = 'source'
+            ORDER BY u_source.url
+            LIMIT 1
+        ) src ON TRUE
+        WHERE mv.verified_at IS NULL
+            AND mv.created_at > now() - make_interval(days => $1)"#;
+    let rows = match client.query(pending_query, &[&TOKEN_TTL_DAYS]).await {
+        Ok(rows) => rows,
+        Err(e) => {
+            log::error!("Verifier failed to fetch pending verifications: {e}");
+            return;
+        }
+    };
+
+    for row in rows {
+        let id: Uuid = row.get("id");
+        let token: String = row.get("token");
+        let source: String = row.get("url");
+
+        let Some(file_url) = verification_file_url(&source) else {
+            log::debug!("No supported repository host for {source}, skipping {id}");
+            continue;
+        };
+        let verified = match http.send(http.get(&file_url)).await {
+            Ok(response) if response.status().is_success() => response
+                .text()
+                .await
+                .is_ok_and(|body| body.contains(&token)),
+            Ok(_) => false,
+            Err(e) => {
+                log::warn!("Failed to fetch {file_url}: {e}");
+                false
+            }
+        };
+
+        let update = r#"
+            UPDATE maintainer_verifications
+            SET last_checked_at = now(),
+                verified_at = CASE WHEN $2 THEN now() ELSE verified_at END
+            WHERE id = $1"#;
+        let recorded = async {
+            let transaction = read_write(&mut client).await?;
+            transaction.execute(update, &[&id, &verified]).await?;
+            transaction.commit().await
+        };
+        if let Err(e) = recorded.await {
+            log::error!("Verifier failed to record check for {id}: {e}");
+        } else if verified {
+            log::info!("Verified maintainer claim {id} via {file_url}");
+        }
+    }
+}
+
+// Raw URL of the verification file for GitHub and GitLab repositories
+fn verification_file_url(source: &str) -> Option<String> {
+    let source = if source.contains("://") {
+        source.to_string()
+    } else {
+        format!("https://{source}")
+    };
+    let url = Url::parse(&source).ok()?;
+    let mut segments = url.path_segments()?.filter(|s| !s.is_empty());
+    let owner = segments.next()?;
+    let repo = segments.next()?.trim_end_matches(".git");
+
+    match url.host_str()? {
+        "github.com" | "www.github.com" => Some(format!(
+            "https://raw.githubusercontent.com/{owner}/{repo}/HEAD/{VERIFICATION_FILE}"
+        )),
+        "gitlab.com" => Some(format!(
+            "https://gitlab.com/{owner}/{repo}/-/raw/HEAD/{VERIFICATION_FILE}"
+        )),
+        _ => None,
+    }
+}
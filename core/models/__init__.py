@@ -14,7 +14,7 @@ from sqlalchemy import (
     UniqueConstraint,
     func,
 )
-from sqlalchemy.dialects.postgresql import UUID
+from sqlalchemy.dialects.postgresql import ARRAY, UUID
 from sqlalchemy.orm import Mapped, declarative_base, relationship
 
 naming_convention = {
@@ -532,6 +532,8 @@ class TeaRankRun(Base):
     created_at = Column(
         DateTime, nullable=False, default=func.now(), server_default=func.now()
     )
+    # Set by the API's admin quarantine; hides the run until cleared
+    quarantined_at = Column(DateTime, nullable=True)
 
 
 class TeaRank(Base):
@@ -550,3 +552,114 @@ class TeaRank(Base):
     created_at = Column(
         DateTime, nullable=False, default=func.now(), server_default=func.now()
     )
+
+
+# Stable public URL names, assigned once by a trigger on canons
+class CanonSlug(Base):
+    __tablename__ = "canon_slugs"
+    slug = Column(String, primary_key=True)
+    canon_id = Column(
+        UUID(as_uuid=True),
+        ForeignKey("canons.id", ondelete="CASCADE"),
+        nullable=False,
+        unique=True,
+    )
+    created_at = Column(
+        DateTime, nullable=False, default=func.now(), server_default=func.now()
+    )
+
+
+# Ids of canons merged away, pointing at the canon that absorbed them
+class CanonRedirect(Base):
+    __tablename__ = "canon_redirects"
+    old_canon_id = Column(UUID(as_uuid=True), primary_key=True)
+    canon_id = Column(
+        UUID(as_uuid=True), ForeignKey("canons.id"), nullable=False, index=True
+    )
+    created_at = Column(
+        DateTime, nullable=False, default=func.now(), server_default=func.now()
+    )
+
+
+class MaintainerVerification(Base):
+    __tablename__ = "maintainer_verifications"
+    __table_args__ = (
+        UniqueConstraint("canon_id", "maintainer", name="uq_canon_maintainer"),
+    )
+    id = Column(
+        UUID(as_uuid=True),
+        primary_key=True,
+        default=func.uuid_generate_v4(),
+        server_default=func.uuid_generate_v4(),
+    )
+    canon_id = Column(
+        UUID(as_uuid=True),
+        ForeignKey("canons.id", ondelete="CASCADE"),
+        nullable=False,
+    )
+    maintainer = Column(String, nullable=False, index=True)
+    token = Column(String, nullable=False, unique=True)
+    verified_at = Column(DateTime, nullable=True)
+    last_checked_at = Column(DateTime, nullable=True)
+    created_at = Column(
+        DateTime, nullable=False, default=func.now(), server_default=func.now()
+    )
+
+
+# Excludes a canon from the leaderboard (pinned_rank null) or pins its rank
+class LeaderboardOverride(Base):
+    __tablename__ = "leaderboard_overrides"
+    canon_id = Column(
+        UUID(as_uuid=True),
+        ForeignKey("canons.id", ondelete="CASCADE"),
+        primary_key=True,
+    )
+    pinned_rank = Column(String, nullable=True)
+    reason = Column(String, nullable=False)
+    expires_at = Column(DateTime, nullable=True)
+    created_at = Column(
+        DateTime, nullable=False, default=func.now(), server_default=func.now()
+    )
+
+
+# Named lists of canons kept by an API key, owner being the key's name
+class Collection(Base):
+    __tablename__ = "collections"
+    __table_args__ = (
+        UniqueConstraint("owner", "name", name="uq_collection_owner_name"),
+    )
+    id = Column(
+        UUID(as_uuid=True),
+        primary_key=True,
+        default=func.uuid_generate_v4(),
+        server_default=func.uuid_generate_v4(),
+    )
+    owner = Column(String, nullable=False)
+    name = Column(String, nullable=False)
+    canon_ids = Column(ARRAY(UUID(as_uuid=True)), nullable=False)
+    webhook_url = Column(String, nullable=True)
+    webhook_secret = Column(String, nullable=True)
+    webhook_previous_secret = Column(String, nullable=True)
+    webhook_secret_rotated_at = Column(DateTime, nullable=True)
+    created_at = Column(
+        DateTime, nullable=False, default=func.now(), server_default=func.now()
+    )
+    updated_at = Column(
+        DateTime, nullable=False, default=func.now(), server_default=func.now()
+    )
+
+
+# Each canon's newest rank outside quarantined runs, rebuilt by the API
+class LatestRank(Base):
+    __tablename__ = "latest_ranks"
+    canon_id = Column(
+        UUID(as_uuid=True),
+        ForeignKey("canons.id", ondelete="CASCADE"),
+        primary_key=True,
+    )
+    tea_rank_run = Column(Integer, nullable=False, index=True)
+    rank = Column(String, nullable=False)
+    created_at = Column(DateTime, nullable=False)
+    positive_tea_rank_run = Column(Integer, nullable=True)
+    positive_rank = Column(String, nullable=True)
+    positive_created_at = Column(DateTime, nullable=True)